@@ -70,7 +70,8 @@ fn main_loop() -> SarektResult<()> {
     &RECT_INDICES,
   )?;
   let rect_uniform = DefaultForwardShaderLayout::default();
-  let rect_uniform_buffer = renderer.load_uniform_buffer(rect_uniform)?;
+  // Checks DefaultForwardShaderLayout against its std140 block.
+  let rect_uniform_buffer = renderer.load_checked_uniform_buffer(rect_uniform)?;
   let rect: DrawableObject = DrawableObject::builder(&renderer)
     .vertex_buffer(&rect_vertex_buffer)
     .index_buffer(&rect_index_buffer)
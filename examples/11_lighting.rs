@@ -0,0 +1,303 @@
+use log::{info, warn, Level};
+use sarekt::{
+  self,
+  error::{SarektError, SarektResult},
+  renderer::{
+    buffers_and_images::{BufferType, IndexBufferElemSize},
+    config::Config,
+    drawable_object::DrawableObject,
+    lighting::{DirectionalLight, Material, PointLight, ShadingModel, SpotLight},
+    vertex_bindings::{DefaultLitShaderLayout, DefaultLitShaderVertex},
+    Drawer, Renderer, VulkanRenderer,
+  },
+};
+use std::{error::Error, f32, sync::Arc, time::Instant};
+use ultraviolet as uv;
+use winit::{
+  dpi::{LogicalSize, PhysicalSize},
+  event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+  event_loop::{ControlFlow, EventLoop},
+  platform::desktop::EventLoopExtDesktop,
+  window::{WindowBuilder, WindowId},
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+fn main() -> Result<(), Box<dyn Error>> {
+  simple_logger::init_with_level(Level::Info)?;
+  main_loop()?;
+  Ok(())
+}
+
+/// Takes full control of the executing thread and runs the event loop for it.
+fn main_loop() -> Result<(), Box<dyn Error>> {
+  info!("Running main loop...");
+
+  let args: Vec<String> = std::env::args().collect();
+  let shading_model = if args.contains(&"phong".to_owned()) {
+    ShadingModel::Phong
+  } else {
+    ShadingModel::BlinnPhong
+  };
+  let show_fps = args.contains(&"fps".to_owned());
+  info!("Shading model: {:?}", shading_model);
+  info!("Show FPS: {}", show_fps);
+
+  let mut ar = WIDTH as f32 / HEIGHT as f32;
+
+  // Build Window.
+  let mut event_loop = EventLoop::new();
+  let window = Arc::new(
+    WindowBuilder::new()
+      .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+      .build(&event_loop)
+      .unwrap(),
+  );
+
+  // Build Renderer.
+  let config = Config::builder()
+    .requested_width(WIDTH)
+    .requested_height(HEIGHT)
+    .build()
+    .unwrap();
+  let mut renderer = VulkanRenderer::new(window.clone(), config).unwrap();
+
+  // Create Vertex Resources.  The floor is the same cube flattened, the
+  // uniforms are passed with every draw so neither needs a uniform buffer.
+  let (cube_vertices, cube_indices) = cube();
+  let cube_vertex_buffer = renderer.load_buffer(BufferType::Vertex, &cube_vertices)?;
+  let cube_index_buffer = renderer.load_buffer(
+    BufferType::Index(IndexBufferElemSize::UInt16),
+    &cube_indices,
+  )?;
+  let cube: DrawableObject<VulkanRenderer, DefaultLitShaderLayout> =
+    DrawableObject::builder(&renderer)
+      .vertex_buffer(&cube_vertex_buffer)
+      .index_buffer(&cube_index_buffer)
+      .build()?;
+
+  let cube_material = Material::new(
+    uv::Vec3::new(0.8f32, 0.25f32, 0.2f32),
+    uv::Vec3::new(1f32, 1f32, 1f32),
+    64f32,
+  );
+  let floor_material = Material::new(
+    uv::Vec3::new(0.6f32, 0.6f32, 0.6f32),
+    uv::Vec3::new(0.1f32, 0.1f32, 0.1f32),
+    8f32,
+  );
+  let floor_model = uv::Mat4::from_translation(uv::Vec3::new(0f32, -1f32, 0f32))
+    * uv::Mat4::from_nonuniform_scale(uv::Vec4::new(8f32, 0.1f32, 8f32, 1f32));
+
+  let start_time = Instant::now();
+  let mut last_frame_time = start_time;
+  let mut frame_number = 0;
+  let mut fps_average = 0f32;
+
+  // Run the loop.
+  event_loop.run_return(move |event, _, control_flow| {
+    // By default continuously run this event loop, even if the OS hasn't
+    // distributed an event, that way we will draw as fast as possible.
+    *control_flow = ControlFlow::Poll;
+
+    match event {
+      Event::MainEventsCleared => {
+        // All the main events to process are done we can do "work" now (game
+        // engine state update etc.)
+        let now = Instant::now();
+        let time_since_start_secs = ((now - start_time).as_millis() as f32) / 1000f32;
+
+        if show_fps {
+          let time_since_last_frame_secs = ((now - last_frame_time).as_nanos() as f32) / 1e9f32;
+          let fps = 1f32 / time_since_last_frame_secs;
+          if frame_number == 0 {
+            fps_average = 0f32;
+          } else {
+            fps_average =
+              ((frame_number as f32 * fps_average) + fps) / (frame_number as f32 + 1f32);
+          }
+          frame_number += 1;
+
+          info!("Frame Period: {}", time_since_last_frame_secs);
+          info!("FPS: {}", fps);
+          info!("FPS averaged: {}", fps_average);
+          last_frame_time = now;
+        }
+
+        let rotation =
+          (std::f32::consts::PI * time_since_start_secs / 4f32) % (2f32 * std::f32::consts::PI);
+        let cube_model = uv::Mat4::from_rotation_y(rotation) * uv::Mat4::from_rotation_x(0.5f32);
+
+        let camera_position = uv::Vec3::new(0f32, 2f32, 4f32);
+        let view = uv::Mat4::look_at(camera_position, uv::Vec3::zero(), uv::Vec3::unit_y());
+        // TODO BACKENDS this proj should be conditional on backend.
+        let projection =
+          uv::projection::rh_yup::perspective_vk(std::f32::consts::PI / 3f32, ar, 0.1f32, 20f32);
+
+        let lights = Lights::at_time(time_since_start_secs);
+        for &(model, material) in
+          [(cube_model, cube_material), (floor_model, floor_material)].iter()
+        {
+          let mut uniform =
+            DefaultLitShaderLayout::new(model, view, projection, camera_position, material);
+          uniform.set_shading_model(shading_model);
+          lights.apply(&mut uniform).unwrap();
+          renderer.draw_with_uniform(&cube, &uniform).unwrap();
+        }
+
+        // At the end of work request redraw.
+        window.request_redraw();
+      }
+
+      Event::RedrawRequested(_) => {
+        // Redraw requested, this is called after MainEventsCleared.
+        renderer.frame().unwrap_or_else(|err| {
+          match err {
+            SarektError::SwapchainOutOfDate | SarektError::SuboptimalSwapchain => {
+              // Handle window resize etc.
+              warn!("Tried to render without processing window resize event!");
+
+              let PhysicalSize { width, height } = window.inner_size();
+              renderer
+                .recreate_swapchain(width, height)
+                .expect("Error recreating swapchain");
+            }
+            e => panic!("Frame had an unrecoverable error! {}", e),
+          }
+        });
+      }
+
+      Event::WindowEvent { window_id, event } => {
+        main_loop_window_event(&event, &window_id, control_flow, &mut renderer, &mut ar)
+          .expect("Error processing window event.");
+      }
+
+      Event::LoopDestroyed => {
+        // Explicitly call exit so resources are cleaned up.
+        std::process::exit(0);
+      }
+      _ => (),
+    }
+  });
+
+  Ok(())
+}
+
+/// Handles all winit window specific events.
+fn main_loop_window_event(
+  event: &WindowEvent, _id: &WindowId, control_flow: &mut winit::event_loop::ControlFlow,
+  renderer: &mut VulkanRenderer, ar: &mut f32,
+) -> SarektResult<()> {
+  match event {
+    WindowEvent::CloseRequested => {
+      // When the window system requests a close, signal to winit that we'd like to
+      // close the window.
+      info!("Exiting due to close request event from window system...");
+      *control_flow = ControlFlow::Exit;
+    }
+
+    WindowEvent::KeyboardInput { input, .. } => {
+      // When the keyboard input is a press on the escape key, exit and print the
+      // line.
+      if let (Some(VirtualKeyCode::Escape), ElementState::Pressed) =
+        (input.virtual_keycode, input.state)
+      {
+        info!("Exiting due to escape press...");
+        *control_flow = ControlFlow::Exit
+      }
+    }
+
+    WindowEvent::Resized(size) => {
+      // If the size is 0, minimization or something like that happened so I
+      // toggle drawing.
+      info!("Window resized, recreating renderer swapchain...");
+      let enabled = !(size.height == 0 && size.width == 0);
+      if enabled {
+        *ar = size.width as f32 / size.height as f32;
+      }
+      renderer.set_rendering_enabled(enabled);
+      return renderer.recreate_swapchain(size.width, size.height);
+    }
+    _ => (),
+  }
+
+  Ok(())
+}
+
+/// A dim sun, a blue lamp circling the cube and a warm spot light from above.
+struct Lights {
+  directional: DirectionalLight,
+  point: PointLight,
+  spot: SpotLight,
+}
+impl Lights {
+  fn at_time(time_since_start_secs: f32) -> Self {
+    let lamp_angle = time_since_start_secs % (2f32 * std::f32::consts::PI);
+    Self {
+      directional: DirectionalLight::new(
+        uv::Vec3::new(-1f32, -2f32, -1f32),
+        uv::Vec3::new(1f32, 0.95f32, 0.9f32),
+        0.4f32,
+      ),
+      point: PointLight::new(
+        uv::Vec3::new(2f32 * lamp_angle.cos(), 0.5f32, 2f32 * lamp_angle.sin()),
+        uv::Vec3::new(0.3f32, 0.5f32, 1f32),
+        2f32,
+        6f32,
+      ),
+      spot: SpotLight::new(
+        uv::Vec3::new(0f32, 4f32, 0f32),
+        uv::Vec3::new(0f32, -1f32, 0f32),
+        uv::Vec3::new(1f32, 0.8f32, 0.5f32),
+        3f32,
+        10f32,
+        /* inner_cone_angle= */ 0.3f32,
+        /* outer_cone_angle= */ 0.45f32,
+      ),
+    }
+  }
+
+  fn apply(&self, uniform: &mut DefaultLitShaderLayout) -> SarektResult<()> {
+    uniform.set_directional_lights(&[self.directional])?;
+    uniform.set_point_lights(&[self.point])?;
+    uniform.set_spot_lights(&[self.spot])
+  }
+}
+
+/// A unit cube centered on the origin, four vertices per face so each face has
+/// its own normal.  Faces wind counter clockwise seen from outside.
+fn cube() -> (Vec<DefaultLitShaderVertex>, Vec<u16>) {
+  let faces = [
+    (uv::Vec3::unit_x(), uv::Vec3::unit_y()),
+    (-uv::Vec3::unit_x(), uv::Vec3::unit_y()),
+    (uv::Vec3::unit_y(), uv::Vec3::unit_z()),
+    (-uv::Vec3::unit_y(), uv::Vec3::unit_z()),
+    (uv::Vec3::unit_z(), uv::Vec3::unit_x()),
+    (-uv::Vec3::unit_z(), uv::Vec3::unit_x()),
+  ];
+
+  let mut vertices = Vec::new();
+  let mut indices = Vec::new();
+  for &(normal, u) in faces.iter() {
+    // u cross v is the normal.
+    let v = normal.cross(u);
+    let first_index = vertices.len() as u16;
+    let corners = [(-1f32, -1f32), (1f32, -1f32), (1f32, 1f32), (-1f32, 1f32)];
+    for &(s, t) in corners.iter() {
+      let position = (normal + u * s + v * t) * 0.5f32;
+      vertices.push(DefaultLitShaderVertex::new_with_texture(
+        position.as_array(),
+        normal.as_array(),
+        &[(s + 1f32) / 2f32, (t + 1f32) / 2f32],
+      ));
+    }
+    indices.extend(
+      [0u16, 1u16, 2u16, 2u16, 3u16, 0u16]
+        .iter()
+        .map(|i| first_index + i),
+    );
+  }
+
+  (vertices, indices)
+}
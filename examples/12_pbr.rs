@@ -0,0 +1,573 @@
+use log::{info, warn, Level};
+use sarekt::{
+  self,
+  error::{SarektError, SarektResult},
+  image_data::{
+    cubemap::Cubemap, environment::EnvironmentMaps, hdr::HdrImage, ImageData, ImageDataFormat,
+  },
+  renderer::{
+    buffers_and_images::{
+      BufferType, IndexBufferElemSize, MagnificationMinificationFilter, TextureAddressMode,
+    },
+    config::{Config, RenderingPath},
+    drawable_object::DrawableObject,
+    lighting::{DirectionalLight, PointLight, SceneLights},
+    pbr::{generate_tangents, PbrMaterial, PbrTextureSlot},
+    vertex_bindings::{DefaultPbrShaderLayout, DefaultPbrShaderVertex},
+    Drawer, Renderer, VulkanRenderer,
+  },
+};
+use std::{error::Error, f32, sync::Arc, time::Instant};
+use ultraviolet as uv;
+use winit::{
+  dpi::{LogicalSize, PhysicalSize},
+  event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+  event_loop::{ControlFlow, EventLoop},
+  platform::desktop::EventLoopExtDesktop,
+  window::{WindowBuilder, WindowId},
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+// The sphere grid shown without a model, metalness increases to the right and
+// roughness upwards.
+const GRID_SIZE: usize = 5;
+const GRID_SPACING: f32 = 1.1f32;
+const ENVIRONMENT_FACE_SIZE: u32 = 512;
+// Lamps circling the scene with the deferred renderer.
+const DEFERRED_LAMP_COUNT: usize = 64;
+
+fn main() -> Result<(), Box<dyn Error>> {
+  simple_logger::init_with_level(Level::Info)?;
+  main_loop()?;
+  Ok(())
+}
+
+/// Takes full control of the executing thread and runs the event loop for it.
+///
+/// Pass the path to a .glb model to draw it with its material instead of the
+/// sphere grid, and the path to an equirectangular .hdr panorama to light the
+/// scene with.  Pass "deferred" to use the deferred renderer, and light the
+/// scene with a ring of lamps as well.
+fn main_loop() -> Result<(), Box<dyn Error>> {
+  info!("Running main loop...");
+
+  let args: Vec<String> = std::env::args().collect();
+  let model_path = args.iter().find(|arg| arg.ends_with(".glb"));
+  let environment_path = args.iter().find(|arg| arg.ends_with(".hdr"));
+  let show_fps = args.contains(&"fps".to_owned());
+  let deferred = args.contains(&"deferred".to_owned());
+  info!("Model: {:?}", model_path);
+  info!("Environment: {:?}", environment_path);
+  info!("Show FPS: {}", show_fps);
+  info!("Deferred: {}", deferred);
+
+  let mut ar = WIDTH as f32 / HEIGHT as f32;
+
+  // Build Window.
+  let mut event_loop = EventLoop::new();
+  let window = Arc::new(
+    WindowBuilder::new()
+      .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+      .build(&event_loop)
+      .unwrap(),
+  );
+
+  // Build Renderer.
+  let config = Config::builder()
+    .requested_width(WIDTH)
+    .requested_height(HEIGHT)
+    .rendering_path(if deferred {
+      RenderingPath::Deferred
+    } else {
+      RenderingPath::Forward
+    })
+    .build()
+    .unwrap();
+  let mut renderer = VulkanRenderer::new(window.clone(), config).unwrap();
+
+  // Generate the image based lighting maps, this takes a while in debug
+  // builds.
+  if let Some(environment_path) = environment_path {
+    info!("Generating environment maps from {}...", environment_path);
+    let panorama = HdrImage::open_radiance(environment_path)?;
+    let environment = Cubemap::from_equirectangular(panorama, ENVIRONMENT_FACE_SIZE)?;
+    renderer.set_image_based_lighting(Some(EnvironmentMaps::new(environment)?))?;
+  }
+
+  // Create Vertex Resources and the textures of the material.
+  let (vertices, indices, material, textures) = match model_path {
+    Some(model_path) => load_glb_model(model_path)?,
+    None => {
+      let (vertices, indices) = sphere(0.5f32, 32, 64)?;
+      (vertices, indices, PbrMaterial::default(), Vec::new())
+    }
+  };
+  let vertex_buffer = renderer.load_buffer(BufferType::Vertex, &vertices)?;
+  let index_buffer =
+    renderer.load_buffer(BufferType::Index(IndexBufferElemSize::UInt32), &indices)?;
+  let texture_handles = textures
+    .into_iter()
+    .map(|(slot, texture)| {
+      let mip_levels = mip_levels_for(texture.dimensions());
+      let handle = renderer.load_image_with_staging_initialization(
+        texture,
+        MagnificationMinificationFilter::Linear,
+        MagnificationMinificationFilter::Linear,
+        TextureAddressMode::Repeat,
+        TextureAddressMode::Repeat,
+        TextureAddressMode::Repeat,
+        mip_levels,
+      )?;
+      Ok((slot, handle))
+    })
+    .collect::<SarektResult<Vec<_>>>()?;
+
+  let mut object_builder = DrawableObject::builder(&renderer)
+    .vertex_buffer(&vertex_buffer)
+    .index_buffer(&index_buffer);
+  for (slot, handle) in texture_handles.iter() {
+    object_builder = object_builder.texture_image_at(slot.index(), handle);
+  }
+  let object: DrawableObject<VulkanRenderer, DefaultPbrShaderLayout> = object_builder.build()?;
+
+  // A model is drawn once as it is, otherwise a grid of spheres.
+  let draws: Vec<(uv::Mat4, PbrMaterial)> = if model_path.is_some() {
+    vec![(uv::Mat4::identity(), material)]
+  } else {
+    sphere_grid()
+  };
+  let camera_distance = if model_path.is_some() {
+    3f32
+  } else {
+    GRID_SIZE as f32 * GRID_SPACING * 1.4f32
+  };
+
+  let start_time = Instant::now();
+  let mut last_frame_time = start_time;
+  let mut frame_number = 0;
+  let mut fps_average = 0f32;
+
+  // Run the loop.
+  event_loop.run_return(move |event, _, control_flow| {
+    // By default continuously run this event loop, even if the OS hasn't
+    // distributed an event, that way we will draw as fast as possible.
+    *control_flow = ControlFlow::Poll;
+
+    match event {
+      Event::MainEventsCleared => {
+        // All the main events to process are done we can do "work" now (game
+        // engine state update etc.)
+        let now = Instant::now();
+        let time_since_start_secs = ((now - start_time).as_millis() as f32) / 1000f32;
+
+        if show_fps {
+          let time_since_last_frame_secs = ((now - last_frame_time).as_nanos() as f32) / 1e9f32;
+          let fps = 1f32 / time_since_last_frame_secs;
+          if frame_number == 0 {
+            fps_average = 0f32;
+          } else {
+            fps_average =
+              ((frame_number as f32 * fps_average) + fps) / (frame_number as f32 + 1f32);
+          }
+          frame_number += 1;
+
+          info!("Frame Period: {}", time_since_last_frame_secs);
+          info!("FPS: {}", fps);
+          info!("FPS averaged: {}", fps_average);
+          last_frame_time = now;
+        }
+
+        // Slowly swing the camera from side to side.
+        let camera_angle = 0.4f32 * (time_since_start_secs / 4f32).sin();
+        let camera_position = uv::Vec3::new(
+          camera_distance * camera_angle.sin(),
+          0f32,
+          camera_distance * camera_angle.cos(),
+        );
+        let view = uv::Mat4::look_at(camera_position, uv::Vec3::zero(), uv::Vec3::unit_y());
+        // TODO BACKENDS this proj should be conditional on backend.
+        let projection =
+          uv::projection::rh_yup::perspective_vk(std::f32::consts::PI / 3f32, ar, 0.1f32, 100f32);
+
+        // The deferred renderer lights the scene with its own lights instead of
+        // the ones in the uniforms.
+        if deferred {
+          renderer.set_view_projection(view, projection);
+          renderer
+            .set_scene_lights(deferred_lights(time_since_start_secs, camera_distance))
+            .unwrap();
+        }
+
+        for &(model, material) in draws.iter() {
+          let mut uniform =
+            DefaultPbrShaderLayout::new(model, view, projection, camera_position, material);
+          apply_lights(&mut uniform).unwrap();
+          renderer.draw_with_uniform(&object, &uniform).unwrap();
+        }
+
+        // At the end of work request redraw.
+        window.request_redraw();
+      }
+
+      Event::RedrawRequested(_) => {
+        // Redraw requested, this is called after MainEventsCleared.
+        renderer.frame().unwrap_or_else(|err| {
+          match err {
+            SarektError::SwapchainOutOfDate | SarektError::SuboptimalSwapchain => {
+              // Handle window resize etc.
+              warn!("Tried to render without processing window resize event!");
+
+              let PhysicalSize { width, height } = window.inner_size();
+              renderer
+                .recreate_swapchain(width, height)
+                .expect("Error recreating swapchain");
+            }
+            e => panic!("Frame had an unrecoverable error! {}", e),
+          }
+        });
+      }
+
+      Event::WindowEvent { window_id, event } => {
+        main_loop_window_event(&event, &window_id, control_flow, &mut renderer, &mut ar)
+          .expect("Error processing window event.");
+      }
+
+      Event::LoopDestroyed => {
+        // Explicitly call exit so resources are cleaned up.
+        std::process::exit(0);
+      }
+      _ => (),
+    }
+  });
+
+  Ok(())
+}
+
+/// Handles all winit window specific events.
+fn main_loop_window_event(
+  event: &WindowEvent, _id: &WindowId, control_flow: &mut winit::event_loop::ControlFlow,
+  renderer: &mut VulkanRenderer, ar: &mut f32,
+) -> SarektResult<()> {
+  match event {
+    WindowEvent::CloseRequested => {
+      // When the window system requests a close, signal to winit that we'd like to
+      // close the window.
+      info!("Exiting due to close request event from window system...");
+      *control_flow = ControlFlow::Exit;
+    }
+
+    WindowEvent::KeyboardInput { input, .. } => {
+      // When the keyboard input is a press on the escape key, exit and print the
+      // line.
+      if let (Some(VirtualKeyCode::Escape), ElementState::Pressed) =
+        (input.virtual_keycode, input.state)
+      {
+        info!("Exiting due to escape press...");
+        *control_flow = ControlFlow::Exit
+      }
+    }
+
+    WindowEvent::Resized(size) => {
+      // If the size is 0, minimization or something like that happened so I
+      // toggle drawing.
+      info!("Window resized, recreating renderer swapchain...");
+      let enabled = !(size.height == 0 && size.width == 0);
+      if enabled {
+        *ar = size.width as f32 / size.height as f32;
+      }
+      renderer.set_rendering_enabled(enabled);
+      return renderer.recreate_swapchain(size.width, size.height);
+    }
+    _ => (),
+  }
+
+  Ok(())
+}
+
+/// A warm sun and two colored lamps either side of the scene.
+fn apply_lights(uniform: &mut DefaultPbrShaderLayout) -> SarektResult<()> {
+  uniform.set_directional_lights(&[DirectionalLight::new(
+    uv::Vec3::new(-1f32, -1f32, -1f32),
+    uv::Vec3::new(1f32, 0.95f32, 0.85f32),
+    2f32,
+  )])?;
+  uniform.set_point_lights(&[
+    PointLight::new(
+      uv::Vec3::new(-4f32, 2f32, 3f32),
+      uv::Vec3::new(0.4f32, 0.6f32, 1f32),
+      40f32,
+      15f32,
+    ),
+    PointLight::new(
+      uv::Vec3::new(4f32, -2f32, 3f32),
+      uv::Vec3::new(1f32, 0.5f32, 0.3f32),
+      40f32,
+      15f32,
+    ),
+  ])
+}
+
+/// The sun and a ring of lamps of every color circling the scene `radius`
+/// away, for the deferred renderer.
+fn deferred_lights(time_since_start_secs: f32, radius: f32) -> SceneLights {
+  let point_lights = (0..DEFERRED_LAMP_COUNT)
+    .map(|lamp| {
+      let fraction = lamp as f32 / DEFERRED_LAMP_COUNT as f32;
+      let angle = 2f32 * f32::consts::PI * fraction + time_since_start_secs / 2f32;
+      let position = uv::Vec3::new(
+        0.6f32 * radius * angle.cos(),
+        0.4f32 * radius * (3f32 * angle).sin(),
+        0.3f32 * radius * angle.sin() + 1f32,
+      );
+      // Around the hue circle.
+      let hue = 2f32 * f32::consts::PI * fraction;
+      let color = uv::Vec3::new(
+        0.5f32 + 0.5f32 * hue.cos(),
+        0.5f32 + 0.5f32 * (hue - 2f32 * f32::consts::PI / 3f32).cos(),
+        0.5f32 + 0.5f32 * (hue + 2f32 * f32::consts::PI / 3f32).cos(),
+      );
+      PointLight::new(position, color, 4f32, 2.5f32)
+    })
+    .collect();
+
+  SceneLights {
+    point_lights,
+    directional_lights: vec![DirectionalLight::new(
+      uv::Vec3::new(-1f32, -1f32, -1f32),
+      uv::Vec3::new(1f32, 0.95f32, 0.85f32),
+      0.5f32,
+    )],
+    ..SceneLights::default()
+  }
+}
+
+/// Model matrices and materials of the grid of spheres, gold metal on the
+/// right and red plastic on the left, rough at the top and smooth at the
+/// bottom.
+fn sphere_grid() -> Vec<(uv::Mat4, PbrMaterial)> {
+  let mut draws = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+  let half_extent = (GRID_SIZE - 1) as f32 * GRID_SPACING / 2f32;
+  for row in 0..GRID_SIZE {
+    for column in 0..GRID_SIZE {
+      let metallic = column as f32 / (GRID_SIZE - 1) as f32;
+      let roughness = row as f32 / (GRID_SIZE - 1) as f32;
+      let base_color = uv::Vec4::new(1f32, 0.05f32, 0.05f32, 1f32) * (1f32 - metallic)
+        + uv::Vec4::new(1f32, 0.77f32, 0.34f32, 1f32) * metallic;
+      let position = uv::Vec3::new(
+        column as f32 * GRID_SPACING - half_extent,
+        row as f32 * GRID_SPACING - half_extent,
+        0f32,
+      );
+      draws.push((
+        uv::Mat4::from_translation(position),
+        PbrMaterial::new(base_color, metallic, roughness),
+      ));
+    }
+  }
+
+  draws
+}
+
+/// A UV sphere centered on the origin, with texture coordinates wrapping
+/// around it once.  Faces wind counter clockwise seen from outside.
+fn sphere(
+  radius: f32, stacks: u32, sectors: u32,
+) -> SarektResult<(Vec<DefaultPbrShaderVertex>, Vec<u32>)> {
+  let mut vertices = Vec::new();
+  for stack in 0..=stacks {
+    let theta = std::f32::consts::PI * stack as f32 / stacks as f32;
+    for sector in 0..=sectors {
+      let phi = 2f32 * std::f32::consts::PI * sector as f32 / sectors as f32;
+      let normal = uv::Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        -theta.sin() * phi.sin(),
+      );
+      let position = normal * radius;
+      vertices.push(DefaultPbrShaderVertex::without_tangent(
+        position.as_array(),
+        normal.as_array(),
+        &[sector as f32 / sectors as f32, stack as f32 / stacks as f32],
+      ));
+    }
+  }
+
+  let mut indices = Vec::new();
+  let row_length = sectors + 1;
+  for stack in 0..stacks {
+    for sector in 0..sectors {
+      let top_left = stack * row_length + sector;
+      let bottom_left = top_left + row_length;
+      indices.extend_from_slice(&[
+        top_left,
+        bottom_left,
+        bottom_left + 1,
+        top_left,
+        bottom_left + 1,
+        top_left + 1,
+      ]);
+    }
+  }
+
+  generate_tangents(&mut vertices, &indices)?;
+  Ok((vertices, indices))
+}
+
+/// Loads the first primitive of the first mesh with its material and textures.
+fn load_glb_model(
+  gltf_file_path: &str,
+) -> SarektResult<(
+  Vec<DefaultPbrShaderVertex>,
+  Vec<u32>,
+  PbrMaterial,
+  Vec<(PbrTextureSlot, GltfTexture)>,
+)> {
+  let (document, buffers, images) = gltf::import(gltf_file_path).unwrap();
+  let primitive = document
+    .meshes()
+    .next()
+    .and_then(|mesh| mesh.primitives().next())
+    .expect("The model has no meshes");
+  info!("Loaded model {}", gltf_file_path);
+
+  let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+  let positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+  let normals: Vec<[f32; 3]> = reader
+    .read_normals()
+    .expect("The model has no normals")
+    .collect();
+  let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+    Some(tex_coords) => tex_coords.into_f32().collect(),
+    None => vec![[0f32, 0f32]; positions.len()],
+  };
+  let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| t.collect());
+  let indices: Vec<u32> = match reader.read_indices() {
+    Some(indices) => indices.into_u32().collect(),
+    None => (0..positions.len() as u32).collect(),
+  };
+
+  let mut vertices: Vec<DefaultPbrShaderVertex> = (0..positions.len())
+    .map(|i| match &tangents {
+      Some(tangents) => {
+        DefaultPbrShaderVertex::new(&positions[i], &normals[i], &tangents[i], &tex_coords[i])
+      }
+      None => DefaultPbrShaderVertex::without_tangent(&positions[i], &normals[i], &tex_coords[i]),
+    })
+    .collect();
+  if tangents.is_none() {
+    info!("Model has no tangents, generating them...");
+    generate_tangents(&mut vertices, &indices)?;
+  }
+
+  // Color textures are sRGB and the rest are linear data.
+  let gltf_material = primitive.material();
+  let pbr = gltf_material.pbr_metallic_roughness();
+  let mut material = PbrMaterial::new(
+    uv::Vec4::from(pbr.base_color_factor()),
+    pbr.metallic_factor(),
+    pbr.roughness_factor(),
+  )
+  .with_emissive(uv::Vec3::from(gltf_material.emissive_factor()));
+  let mut texture_sources = Vec::new();
+  if let Some(info) = pbr.base_color_texture() {
+    texture_sources.push((PbrTextureSlot::BaseColor, info.texture(), true));
+  }
+  if let Some(info) = pbr.metallic_roughness_texture() {
+    texture_sources.push((PbrTextureSlot::MetallicRoughness, info.texture(), false));
+  }
+  if let Some(normal) = gltf_material.normal_texture() {
+    material.normal_scale = normal.scale();
+    texture_sources.push((PbrTextureSlot::Normal, normal.texture(), false));
+  }
+  if let Some(occlusion) = gltf_material.occlusion_texture() {
+    material.occlusion_strength = occlusion.strength();
+    texture_sources.push((PbrTextureSlot::Occlusion, occlusion.texture(), false));
+  }
+  if let Some(info) = gltf_material.emissive_texture() {
+    texture_sources.push((PbrTextureSlot::Emissive, info.texture(), true));
+  }
+
+  let mut textures = Vec::with_capacity(texture_sources.len());
+  for (slot, texture, srgb) in texture_sources {
+    material = material.with_texture(slot);
+    textures.push((
+      slot,
+      GltfTexture::new(&images[texture.source().index()], srgb),
+    ));
+  }
+
+  info!(
+    "Vertices/indices in model: {}, {}, textures: {}",
+    vertices.len(),
+    indices.len(),
+    textures.len()
+  );
+  Ok((vertices, indices, material, textures))
+}
+
+/// Enough mip levels to go down to 1x1.
+fn mip_levels_for(dimensions: (u32, u32)) -> u32 {
+  32 - dimensions.0.max(dimensions.1).leading_zeros()
+}
+
+/// A glTF image expanded to RGBA, in an sRGB format for color textures and a
+/// linear one for data such as normals.
+struct GltfTexture {
+  pixels: Vec<u8>,
+  dimensions: (u32, u32),
+  format: ImageDataFormat,
+}
+impl GltfTexture {
+  fn new(data: &gltf::image::Data, srgb: bool) -> Self {
+    use gltf::image::Format;
+
+    let channels = match data.format {
+      Format::R8 => 1,
+      Format::R8G8 => 2,
+      Format::R8G8B8 | Format::B8G8R8 => 3,
+      Format::R8G8B8A8 | Format::B8G8R8A8 => 4,
+      format => panic!("Unsupported glTF image format {:?}", format),
+    };
+    let mut pixels = Vec::with_capacity(data.pixels.len() / channels * 4);
+    for t in data.pixels.chunks(channels) {
+      let rgba = match data.format {
+        Format::R8 => [t[0], 0, 0, 255],
+        Format::R8G8 => [t[0], t[1], 0, 255],
+        Format::R8G8B8 => [t[0], t[1], t[2], 255],
+        Format::B8G8R8 => [t[2], t[1], t[0], 255],
+        Format::B8G8R8A8 => [t[2], t[1], t[0], t[3]],
+        _ => [t[0], t[1], t[2], t[3]],
+      };
+      pixels.extend_from_slice(&rgba);
+    }
+
+    Self {
+      pixels,
+      dimensions: (data.width, data.height),
+      format: if srgb {
+        ImageDataFormat::R8G8B8A8Srgb
+      } else {
+        ImageDataFormat::R8G8B8A8Unorm
+      },
+    }
+  }
+}
+impl ImageData for GltfTexture {
+  fn into_bytes(self) -> Vec<u8> {
+    self.pixels
+  }
+
+  fn into_rgba8(self) -> Self {
+    self
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    self.dimensions
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
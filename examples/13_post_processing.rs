@@ -0,0 +1,440 @@
+use log::{info, warn, Level};
+use sarekt::{
+  self,
+  error::{SarektError, SarektResult},
+  image_data::{ImageData, ImageDataFormat},
+  renderer::{
+    buffers_and_images::{
+      BufferType, IndexBufferElemSize, MagnificationMinificationFilter, TextureAddressMode,
+    },
+    config::{AntiAliasingMode, Config, SurfaceFormatPreference},
+    drawable_object::DrawableObject,
+    lighting::{DirectionalLight, PointLight},
+    pbr::{generate_tangents, PbrMaterial},
+    post_process::{PostProcessPass, TonemapOperator},
+    vertex_bindings::{DefaultPbrShaderLayout, DefaultPbrShaderVertex},
+    Drawer, Renderer, VulkanRenderer,
+  },
+};
+use std::{error::Error, f32, sync::Arc, time::Instant};
+use ultraviolet as uv;
+use winit::{
+  dpi::{LogicalSize, PhysicalSize},
+  event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+  event_loop::{ControlFlow, EventLoop},
+  platform::desktop::EventLoopExtDesktop,
+  window::{WindowBuilder, WindowId},
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+const GRID_SIZE: usize = 5;
+const GRID_SPACING: f32 = 1.1f32;
+const LUT_SIZE: u32 = 16;
+
+fn main() -> Result<(), Box<dyn Error>> {
+  simple_logger::init_with_level(Level::Info)?;
+  main_loop()?;
+  Ok(())
+}
+
+/// Takes full control of the executing thread and runs the event loop for it.
+///
+/// By default the frame is bloomed, tonemapped with ACES, anti-aliased with
+/// FXAA and vignetted.  Pass "reinhard" to tonemap with Reinhard instead,
+/// "grading" to add a warm color grade, or "none" to see the HDR scene simply
+/// clamped.  "hdr10" presents to an HDR10 swapchain if the display supports
+/// it, best seen with "none" since tonemapping is for SDR displays.
+fn main_loop() -> Result<(), Box<dyn Error>> {
+  info!("Running main loop...");
+
+  let args: Vec<String> = std::env::args().collect();
+  let reinhard = args.contains(&"reinhard".to_owned());
+  let grading = args.contains(&"grading".to_owned());
+  let no_post_processing = args.contains(&"none".to_owned());
+  let hdr10 = args.contains(&"hdr10".to_owned());
+  let ssaa = args.contains(&"ssaa".to_owned());
+  let taa = args.contains(&"taa".to_owned());
+  let show_fps = args.contains(&"fps".to_owned());
+  info!("Reinhard: {}", reinhard);
+  info!("Color grading: {}", grading);
+  info!("No post processing: {}", no_post_processing);
+  info!("HDR10: {}", hdr10);
+  info!("SSAA: {}", ssaa);
+  info!("TAA: {}", taa);
+  info!("Show FPS: {}", show_fps);
+
+  let mut ar = WIDTH as f32 / HEIGHT as f32;
+
+  // Build Window.
+  let mut event_loop = EventLoop::new();
+  let window = Arc::new(
+    WindowBuilder::new()
+      .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+      .build(&event_loop)
+      .unwrap(),
+  );
+
+  // Build Renderer, drawing into an HDR target.
+  let config = Config::builder()
+    .requested_width(WIDTH)
+    .requested_height(HEIGHT)
+    .post_processing(true)
+    .surface_format(if hdr10 {
+      SurfaceFormatPreference::Hdr10
+    } else {
+      SurfaceFormatPreference::default()
+    })
+    .anti_aliasing(if ssaa {
+      AntiAliasingMode::Supersampling { scale: 2f32 }
+    } else if taa {
+      AntiAliasingMode::Temporal
+    } else {
+      AntiAliasingMode::default()
+    })
+    .build()
+    .unwrap();
+  let mut renderer = VulkanRenderer::new(window.clone(), config).unwrap();
+
+  // Build the post process chain, bloom works on HDR colors so it goes before
+  // the tonemapping, the rest after.
+  let mut passes = Vec::new();
+  if !no_post_processing {
+    passes.push(PostProcessPass::Bloom {
+      threshold: 1f32,
+      intensity: 0.6f32,
+    });
+    passes.push(PostProcessPass::Tonemap {
+      operator: if reinhard {
+        TonemapOperator::Reinhard
+      } else {
+        TonemapOperator::Aces
+      },
+      exposure: 1f32,
+    });
+    if grading {
+      let lut = renderer.load_image_with_staging_initialization(
+        ColorGradingLut::warm(LUT_SIZE),
+        MagnificationMinificationFilter::Linear,
+        MagnificationMinificationFilter::Linear,
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+        1,
+      )?;
+      passes.push(PostProcessPass::ColorGrading { lut });
+    }
+    if !ssaa && !taa {
+      passes.push(PostProcessPass::Fxaa);
+    }
+    passes.push(PostProcessPass::Vignette {
+      intensity: 0.5f32,
+      radius: 0.4f32,
+    });
+  }
+  renderer.set_post_process_chain(passes)?;
+
+  // Create Vertex Resources.
+  let (vertices, indices) = sphere(0.5f32, 32, 64)?;
+  let vertex_buffer = renderer.load_buffer(BufferType::Vertex, &vertices)?;
+  let index_buffer =
+    renderer.load_buffer(BufferType::Index(IndexBufferElemSize::UInt32), &indices)?;
+  let object: DrawableObject<VulkanRenderer, DefaultPbrShaderLayout> =
+    DrawableObject::builder(&renderer)
+      .vertex_buffer(&vertex_buffer)
+      .index_buffer(&index_buffer)
+      .build()?;
+
+  let draws = sphere_grid();
+  let camera_distance = GRID_SIZE as f32 * GRID_SPACING * 1.4f32;
+
+  let start_time = Instant::now();
+  let mut last_frame_time = start_time;
+  let mut frame_number = 0;
+  let mut fps_average = 0f32;
+
+  // Run the loop.
+  event_loop.run_return(move |event, _, control_flow| {
+    // By default continuously run this event loop, even if the OS hasn't
+    // distributed an event, that way we will draw as fast as possible.
+    *control_flow = ControlFlow::Poll;
+
+    match event {
+      Event::MainEventsCleared => {
+        // All the main events to process are done we can do "work" now (game
+        // engine state update etc.)
+        let now = Instant::now();
+        let time_since_start_secs = ((now - start_time).as_millis() as f32) / 1000f32;
+
+        if show_fps {
+          let time_since_last_frame_secs = ((now - last_frame_time).as_nanos() as f32) / 1e9f32;
+          let fps = 1f32 / time_since_last_frame_secs;
+          if frame_number == 0 {
+            fps_average = 0f32;
+          } else {
+            fps_average =
+              ((frame_number as f32 * fps_average) + fps) / (frame_number as f32 + 1f32);
+          }
+          frame_number += 1;
+
+          info!("Frame Period: {}", time_since_last_frame_secs);
+          info!("FPS: {}", fps);
+          info!("FPS averaged: {}", fps_average);
+          last_frame_time = now;
+        }
+
+        // Slowly swing the camera from side to side.
+        let camera_angle = 0.4f32 * (time_since_start_secs / 4f32).sin();
+        let camera_position = uv::Vec3::new(
+          camera_distance * camera_angle.sin(),
+          0f32,
+          camera_distance * camera_angle.cos(),
+        );
+        let view = uv::Mat4::look_at(camera_position, uv::Vec3::zero(), uv::Vec3::unit_y());
+        // TODO BACKENDS this proj should be conditional on backend.
+        let projection =
+          uv::projection::rh_yup::perspective_vk(std::f32::consts::PI / 3f32, ar, 0.1f32, 100f32);
+        // Only changes anything with TAA.
+        renderer.set_view_projection(view, projection);
+        let projection = renderer.jitter_projection(projection);
+
+        for &(model, material) in draws.iter() {
+          let mut uniform =
+            DefaultPbrShaderLayout::new(model, view, projection, camera_position, material);
+          apply_lights(&mut uniform).unwrap();
+          renderer.draw_with_uniform(&object, &uniform).unwrap();
+        }
+
+        // At the end of work request redraw.
+        window.request_redraw();
+      }
+
+      Event::RedrawRequested(_) => {
+        // Redraw requested, this is called after MainEventsCleared.
+        renderer.frame().unwrap_or_else(|err| {
+          match err {
+            SarektError::SwapchainOutOfDate | SarektError::SuboptimalSwapchain => {
+              // Handle window resize etc.
+              warn!("Tried to render without processing window resize event!");
+
+              let PhysicalSize { width, height } = window.inner_size();
+              renderer
+                .recreate_swapchain(width, height)
+                .expect("Error recreating swapchain");
+            }
+            e => panic!("Frame had an unrecoverable error! {}", e),
+          }
+        });
+      }
+
+      Event::WindowEvent { window_id, event } => {
+        main_loop_window_event(&event, &window_id, control_flow, &mut renderer, &mut ar)
+          .expect("Error processing window event.");
+      }
+
+      Event::LoopDestroyed => {
+        // Explicitly call exit so resources are cleaned up.
+        std::process::exit(0);
+      }
+      _ => (),
+    }
+  });
+
+  Ok(())
+}
+
+/// Handles all winit window specific events.
+fn main_loop_window_event(
+  event: &WindowEvent, _id: &WindowId, control_flow: &mut winit::event_loop::ControlFlow,
+  renderer: &mut VulkanRenderer, ar: &mut f32,
+) -> SarektResult<()> {
+  match event {
+    WindowEvent::CloseRequested => {
+      // When the window system requests a close, signal to winit that we'd like to
+      // close the window.
+      info!("Exiting due to close request event from window system...");
+      *control_flow = ControlFlow::Exit;
+    }
+
+    WindowEvent::KeyboardInput { input, .. } => {
+      // When the keyboard input is a press on the escape key, exit and print the
+      // line.
+      if let (Some(VirtualKeyCode::Escape), ElementState::Pressed) =
+        (input.virtual_keycode, input.state)
+      {
+        info!("Exiting due to escape press...");
+        *control_flow = ControlFlow::Exit
+      }
+    }
+
+    WindowEvent::Resized(size) => {
+      // If the size is 0, minimization or something like that happened so I
+      // toggle drawing.
+      info!("Window resized, recreating renderer swapchain...");
+      let enabled = !(size.height == 0 && size.width == 0);
+      if enabled {
+        *ar = size.width as f32 / size.height as f32;
+      }
+      renderer.set_rendering_enabled(enabled);
+      return renderer.recreate_swapchain(size.width, size.height);
+    }
+    _ => (),
+  }
+
+  Ok(())
+}
+
+/// A sun and two lamps bright enough to push highlights well past 1.
+fn apply_lights(uniform: &mut DefaultPbrShaderLayout) -> SarektResult<()> {
+  uniform.set_directional_lights(&[DirectionalLight::new(
+    uv::Vec3::new(-1f32, -1f32, -1f32),
+    uv::Vec3::new(1f32, 0.95f32, 0.85f32),
+    4f32,
+  )])?;
+  uniform.set_point_lights(&[
+    PointLight::new(
+      uv::Vec3::new(-4f32, 2f32, 3f32),
+      uv::Vec3::new(0.4f32, 0.6f32, 1f32),
+      80f32,
+      15f32,
+    ),
+    PointLight::new(
+      uv::Vec3::new(4f32, -2f32, 3f32),
+      uv::Vec3::new(1f32, 0.5f32, 0.3f32),
+      80f32,
+      15f32,
+    ),
+  ])
+}
+
+/// Model matrices and materials of a grid of spheres, with glowing ones along
+/// the diagonal for the bloom to pick up.
+fn sphere_grid() -> Vec<(uv::Mat4, PbrMaterial)> {
+  let glow_colors = [
+    uv::Vec3::new(8f32, 1f32, 0.5f32),
+    uv::Vec3::new(1f32, 8f32, 2f32),
+    uv::Vec3::new(1f32, 2f32, 8f32),
+  ];
+
+  let mut draws = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+  let half_extent = (GRID_SIZE - 1) as f32 * GRID_SPACING / 2f32;
+  for row in 0..GRID_SIZE {
+    for column in 0..GRID_SIZE {
+      let metallic = column as f32 / (GRID_SIZE - 1) as f32;
+      let roughness = 0.2f32 + 0.6f32 * row as f32 / (GRID_SIZE - 1) as f32;
+      let mut material = PbrMaterial::new(
+        uv::Vec4::new(0.8f32, 0.8f32, 0.8f32, 1f32),
+        metallic,
+        roughness,
+      );
+      if row == column {
+        material = material.with_emissive(glow_colors[row % glow_colors.len()]);
+      }
+      let position = uv::Vec3::new(
+        column as f32 * GRID_SPACING - half_extent,
+        row as f32 * GRID_SPACING - half_extent,
+        0f32,
+      );
+      draws.push((uv::Mat4::from_translation(position), material));
+    }
+  }
+
+  draws
+}
+
+/// A UV sphere centered on the origin, with texture coordinates wrapping
+/// around it once.  Faces wind counter clockwise seen from outside.
+fn sphere(
+  radius: f32, stacks: u32, sectors: u32,
+) -> SarektResult<(Vec<DefaultPbrShaderVertex>, Vec<u32>)> {
+  let mut vertices = Vec::new();
+  for stack in 0..=stacks {
+    let theta = std::f32::consts::PI * stack as f32 / stacks as f32;
+    for sector in 0..=sectors {
+      let phi = 2f32 * std::f32::consts::PI * sector as f32 / sectors as f32;
+      let normal = uv::Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        -theta.sin() * phi.sin(),
+      );
+      let position = normal * radius;
+      vertices.push(DefaultPbrShaderVertex::without_tangent(
+        position.as_array(),
+        normal.as_array(),
+        &[sector as f32 / sectors as f32, stack as f32 / stacks as f32],
+      ));
+    }
+  }
+
+  let mut indices = Vec::new();
+  let row_length = sectors + 1;
+  for stack in 0..stacks {
+    for sector in 0..sectors {
+      let top_left = stack * row_length + sector;
+      let bottom_left = top_left + row_length;
+      indices.extend_from_slice(&[
+        top_left,
+        bottom_left,
+        bottom_left + 1,
+        top_left,
+        bottom_left + 1,
+        top_left + 1,
+      ]);
+    }
+  }
+
+  generate_tangents(&mut vertices, &indices)?;
+  Ok((vertices, indices))
+}
+
+/// A color grading lookup table, `size` slices of `size`x`size` side by side.
+struct ColorGradingLut {
+  pixels: Vec<u8>,
+  size: u32,
+}
+impl ColorGradingLut {
+  /// Lifts the reds, lowers the blues and adds a little contrast.
+  fn warm(size: u32) -> Self {
+    let mut pixels = Vec::with_capacity((size * size * size * 4) as usize);
+    let max = (size - 1) as f32;
+    // Smoothstep S curve.
+    let contrast = |c: f32| {
+      let c = c.min(1f32).max(0f32);
+      c * c * (3f32 - 2f32 * c)
+    };
+    for green in 0..size {
+      for blue in 0..size {
+        for red in 0..size {
+          let color = uv::Vec3::new(red as f32 / max, green as f32 / max, blue as f32 / max);
+          let warm = color * uv::Vec3::new(1.1f32, 1f32, 0.85f32);
+          pixels.extend_from_slice(&[
+            (contrast(warm.x) * 255f32) as u8,
+            (contrast(warm.y) * 255f32) as u8,
+            (contrast(warm.z) * 255f32) as u8,
+            255u8,
+          ]);
+        }
+      }
+    }
+
+    Self { pixels, size }
+  }
+}
+impl ImageData for ColorGradingLut {
+  fn into_bytes(self) -> Vec<u8> {
+    self.pixels
+  }
+
+  fn into_rgba8(self) -> Self {
+    self
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    (self.size * self.size, self.size)
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(ImageDataFormat::R8G8B8A8Unorm)
+  }
+}
@@ -0,0 +1,293 @@
+//! Derive macros for Sarekt's unsafe `VertexBindings` and
+//! `DescriptorLayoutInfo` traits, re-exported from
+//! `sarekt::renderer::vertex_bindings`.
+//!
+//! Vertex attribute formats come from each field's type through
+//! `VertexAttributeFormat` and the uniform block layout through `GlslField`,
+//! so a field of a type with no GPU equivalent fails to compile instead of
+//! being bound with the wrong format.
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Copy, Clone, VertexBindings)]
+//! struct Vertex {
+//!   position: uv::Vec3,
+//!   #[location(2)]
+//!   uv: uv::Vec2,
+//! }
+//!
+//! #[repr(C)]
+//! #[derive(Copy, Clone, DescriptorLayout)]
+//! #[binding(0)]
+//! #[texture_binding(1)]
+//! struct Uniforms {
+//!   mvp: uv::Mat4,
+//!   tint: uv::Vec4,
+//! }
+//! ```
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+  parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Field, Fields, Lit,
+  Meta, NestedMeta, Result,
+};
+
+/// Implements `VertexBindings` for a `#[repr(C)]` struct as a single per
+/// vertex binding 0.  Each field is an attribute whose location is its index
+/// unless given with `#[location(n)]`.
+#[proc_macro_derive(VertexBindings, attributes(location))]
+pub fn derive_vertex_bindings(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  vertex_bindings(&input)
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
+}
+
+/// Implements `DescriptorLayoutInfo` for a `#[repr(C)]` uniform struct.
+///
+/// * `#[binding(n, ...)]` the dynamic uniform buffer bindings the struct is
+///   bound to, in the vertex and fragment stages.  Defaults to 0.
+/// * `#[texture_binding(n, ...)]` combined image sampler bindings in the
+///   fragment stage.
+/// * `#[layout(std430)]` if the block isn't std140.
+///
+/// The uniform block layout is described from the field types, so
+/// `Renderer::load_checked_uniform_buffer` can check it.
+#[proc_macro_derive(DescriptorLayout, attributes(binding, texture_binding, layout))]
+pub fn derive_descriptor_layout(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  descriptor_layout(&input)
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
+}
+
+fn vertex_bindings(input: &DeriveInput) -> Result<TokenStream2> {
+  let name = &input.ident;
+  let fields = repr_c_fields(input)?;
+
+  let mut locations = Vec::with_capacity(fields.len());
+  let mut attributes = Vec::with_capacity(fields.len());
+  for (index, field) in fields.iter().enumerate() {
+    let location = match find_attr(&field.attrs, "location") {
+      Some(attr) => {
+        let values = u32_list(attr)?;
+        if values.len() != 1 {
+          return Err(Error::new(attr.span(), "expected #[location(n)]"));
+        }
+        values[0]
+      }
+      None => index as u32,
+    };
+    if locations.contains(&location) {
+      return Err(Error::new(
+        field.span(),
+        format!("location {} is used more than once", location),
+      ));
+    }
+    locations.push(location);
+
+    let ident = field.ident.as_ref().unwrap();
+    let ty = &field.ty;
+    attributes.push(quote! {
+      ::sarekt::derive_support::vk::VertexInputAttributeDescription::builder()
+        .binding(0)
+        .location(#location)
+        .format(<#ty as ::sarekt::renderer::VertexAttributeFormat>::FORMAT)
+        .offset(::sarekt::__memoffset::offset_of!(#name, #ident) as u32)
+        .build()
+    });
+  }
+
+  Ok(quote! {
+    unsafe impl ::sarekt::renderer::vertex_bindings::VertexBindings for #name {
+      type BVA = ::sarekt::derive_support::vk::VertexInputAttributeDescription;
+      type BVB = ::sarekt::derive_support::vk::VertexInputBindingDescription;
+
+      fn get_binding_description() -> Self::BVB {
+        ::sarekt::derive_support::vk::VertexInputBindingDescription::builder()
+          .binding(0)
+          .stride(::std::mem::size_of::<Self>() as u32)
+          .input_rate(::sarekt::derive_support::vk::VertexInputRate::VERTEX)
+          .build()
+      }
+
+      fn get_attribute_descriptions() -> ::std::vec::Vec<Self::BVA> {
+        vec![#(#attributes),*]
+      }
+    }
+  })
+}
+
+fn descriptor_layout(input: &DeriveInput) -> Result<TokenStream2> {
+  let name = &input.ident;
+  let fields = repr_c_fields(input)?;
+
+  let uniform_bindings = match find_attr(&input.attrs, "binding") {
+    Some(attr) => u32_list(attr)?,
+    None => vec![0],
+  };
+  let texture_bindings = match find_attr(&input.attrs, "texture_binding") {
+    Some(attr) => u32_list(attr)?,
+    None => Vec::new(),
+  };
+  let mut all_bindings = Vec::with_capacity(uniform_bindings.len() + texture_bindings.len());
+  for &binding in uniform_bindings.iter().chain(texture_bindings.iter()) {
+    if all_bindings.contains(&binding) {
+      return Err(Error::new(
+        input.span(),
+        format!("binding {} is used more than once", binding),
+      ));
+    }
+    all_bindings.push(binding);
+  }
+
+  let rules = match find_attr(&input.attrs, "layout") {
+    Some(attr) => match attr.parse_meta()? {
+      Meta::List(list) if list.nested.len() == 1 => match &list.nested[0] {
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("std140") => quote!(Std140),
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("std430") => quote!(Std430),
+        other => return Err(Error::new(other.span(), "expected std140 or std430")),
+      },
+      meta => {
+        return Err(Error::new(
+          meta.span(),
+          "expected #[layout(std140 | std430)]",
+        ))
+      }
+    },
+    None => quote!(Std140),
+  };
+
+  let vk = quote!(::sarekt::derive_support::vk);
+  let uniform_layout_bindings = uniform_bindings.iter().map(|binding| {
+    quote! {
+      #vk::DescriptorSetLayoutBinding::builder()
+        .binding(#binding)
+        .descriptor_type(#vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(#vk::ShaderStageFlags::VERTEX | #vk::ShaderStageFlags::FRAGMENT)
+        .build()
+    }
+  });
+  let texture_layout_bindings = texture_bindings.iter().map(|binding| {
+    quote! {
+      #vk::DescriptorSetLayoutBinding::builder()
+        .binding(#binding)
+        .descriptor_type(#vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(#vk::ShaderStageFlags::FRAGMENT)
+        .build()
+    }
+  });
+  let binding_count = all_bindings.len();
+
+  let uniform_fields = fields.iter().map(|field| {
+    let ident = field.ident.as_ref().unwrap();
+    let ty = &field.ty;
+    quote! {
+      ::sarekt::uniform_field!(
+        #name,
+        #ident,
+        <#ty as ::sarekt::renderer::uniform_layout::GlslField>::glsl_type()
+      )
+    }
+  });
+
+  Ok(quote! {
+    unsafe impl ::sarekt::renderer::vertex_bindings::DescriptorLayoutInfo for #name {
+      type BackendDescriptorSetLayoutBindings = [#vk::DescriptorSetLayoutBinding; #binding_count];
+
+      fn get_descriptor_set_layout_bindings() -> Self::BackendDescriptorSetLayoutBindings {
+        [#(#uniform_layout_bindings,)* #(#texture_layout_bindings,)*]
+      }
+
+      fn get_bind_uniform_info(
+      ) -> ::sarekt::error::SarektResult<::sarekt::renderer::vertex_bindings::BindUniformInfo> {
+        Ok(::sarekt::renderer::vertex_bindings::BindUniformInfo {
+          bindings: vec![#(#uniform_bindings),*],
+          offset: 0u64,
+          range: ::std::mem::size_of::<Self>() as u64,
+        })
+      }
+
+      fn get_bind_texture_info(
+      ) -> ::sarekt::error::SarektResult<::sarekt::renderer::vertex_bindings::BindTextureInfo> {
+        Ok(::sarekt::renderer::vertex_bindings::BindTextureInfo {
+          bindings: vec![#(#texture_bindings),*],
+        })
+      }
+
+      fn get_uniform_block_layout(
+      ) -> ::std::option::Option<::sarekt::renderer::uniform_layout::UniformBlockLayout> {
+        Some(::sarekt::renderer::uniform_layout::UniformBlockLayout::new::<Self>(
+          ::sarekt::renderer::uniform_layout::LayoutRules::#rules,
+          vec![#(#uniform_fields),*],
+        ))
+      }
+    }
+  })
+}
+
+/// The named fields of a non generic `#[repr(C)]` struct, the only kind whose
+/// offsets can be relied on.
+fn repr_c_fields(input: &DeriveInput) -> Result<Vec<&Field>> {
+  if !input.generics.params.is_empty() {
+    return Err(Error::new(
+      input.generics.span(),
+      "generic structs are not supported",
+    ));
+  }
+
+  let is_repr_c = input
+    .attrs
+    .iter()
+    .filter(|attr| attr.path.is_ident("repr"))
+    .filter_map(|attr| attr.parse_meta().ok())
+    .any(|meta| match meta {
+      Meta::List(list) => list.nested.iter().any(|nested| match nested {
+        NestedMeta::Meta(Meta::Path(path)) => path.is_ident("C"),
+        _ => false,
+      }),
+      _ => false,
+    });
+  if !is_repr_c {
+    return Err(Error::new(
+      input.ident.span(),
+      "the struct must be #[repr(C)] so its field offsets are stable",
+    ));
+  }
+
+  match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => Ok(fields.named.iter().collect()),
+      _ => Err(Error::new(
+        input.ident.span(),
+        "only structs with named fields are supported",
+      )),
+    },
+    _ => Err(Error::new(input.ident.span(), "only structs are supported")),
+  }
+}
+
+fn find_attr<'a>(attrs: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+  attrs.iter().find(|attr| attr.path.is_ident(name))
+}
+
+/// Parses `#[name(1, 2, ...)]`.
+fn u32_list(attr: &Attribute) -> Result<Vec<u32>> {
+  let list = match attr.parse_meta()? {
+    Meta::List(list) => list,
+    meta => return Err(Error::new(meta.span(), "expected a list of integers")),
+  };
+  list
+    .nested
+    .iter()
+    .map(|nested| match nested {
+      NestedMeta::Lit(Lit::Int(int)) => int.base10_parse::<u32>(),
+      other => Err(Error::new(other.span(), "expected an integer")),
+    })
+    .collect()
+}
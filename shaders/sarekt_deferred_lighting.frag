@@ -0,0 +1,184 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Shades the G-buffer with the scene lights, added onto the scene color.  The
+// BRDF is the same as sarekt_forward_pbr's.
+
+const int MAX_DEFERRED_POINT_LIGHTS = 256;
+const int MAX_DEFERRED_DIRECTIONAL_LIGHTS = 4;
+const int MAX_DEFERRED_SPOT_LIGHTS = 128;
+
+// Values of LightVolume.kind.
+const uint DIRECTIONAL_LIGHTS = 0;
+const uint POINT_LIGHTS = 1;
+const uint SPOT_LIGHTS = 2;
+
+const float PI = 3.14159265359;
+
+// Reflectance at normal incidence of dielectrics.
+const vec3 DIELECTRIC_F0 = vec3(0.04);
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+layout(binding = 0) uniform DeferredLightingUniform {
+  mat4 viewProjection;
+  mat4 inverseViewProjection;
+  vec3 cameraPosition;
+  uint directionalLightCount;
+  vec2 inverseTargetSize;
+  DirectionalLight directionalLights[MAX_DEFERRED_DIRECTIONAL_LIGHTS];
+} lighting;
+layout(binding = 1) uniform DeferredPointLights {
+  PointLight lights[MAX_DEFERRED_POINT_LIGHTS];
+} pointLights;
+layout(binding = 2) uniform DeferredSpotLights {
+  SpotLight lights[MAX_DEFERRED_SPOT_LIGHTS];
+} spotLights;
+
+// The G-buffer, written by the previous subpass.
+layout(input_attachment_index = 0, binding = 3) uniform subpassInput albedoInput;
+layout(input_attachment_index = 1, binding = 4) uniform subpassInput normalInput;
+layout(input_attachment_index = 2, binding = 5) uniform subpassInput materialInput;
+layout(input_attachment_index = 3, binding = 6) uniform subpassInput depthInput;
+
+layout(push_constant) uniform LightVolume {
+  uint kind;
+} volume;
+
+layout(location = 0) flat in uint lightIndex;
+
+layout(location = 0) out vec4 outColor;
+
+// Smoothly reaches zero at the light's range.
+float rangeAttenuation(float lightDistance, float range) {
+  float ratio = lightDistance / range;
+  float falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+  return falloff * falloff / (lightDistance * lightDistance + 1.0);
+}
+
+// GGX/Trowbridge-Reitz normal distribution.
+float distributionGgx(float nDotH, float alpha) {
+  float alphaSquared = alpha * alpha;
+  float denominator = nDotH * nDotH * (alphaSquared - 1.0) + 1.0;
+  return alphaSquared / (PI * denominator * denominator);
+}
+
+// Smith geometry term with Schlick-GGX for each direction, k for direct
+// lighting.
+float geometrySmith(float nDotV, float nDotL, float roughness) {
+  float r = roughness + 1.0;
+  float k = r * r / 8.0;
+  float view = nDotV / (nDotV * (1.0 - k) + k);
+  float light = nDotL / (nDotL * (1.0 - k) + k);
+  return view * light;
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+  return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance reflected radiance of light arriving along toLight.
+vec3 shade(
+  vec3 toLight, vec3 radiance, vec3 normal, vec3 toCamera, vec3 baseColor, float metallic,
+  float roughness, vec3 f0) {
+  float nDotL = max(dot(normal, toLight), 0.0);
+  if (nDotL <= 0.0) {
+    return vec3(0.0);
+  }
+
+  vec3 halfway = normalize(toLight + toCamera);
+  float nDotV = max(dot(normal, toCamera), 1e-4);
+  float nDotH = max(dot(normal, halfway), 0.0);
+
+  float d = distributionGgx(nDotH, roughness * roughness);
+  float g = geometrySmith(nDotV, nDotL, roughness);
+  vec3 f = fresnelSchlick(max(dot(halfway, toCamera), 0.0), f0);
+  vec3 specular = d * g * f / (4.0 * nDotV * nDotL);
+
+  // Metals have no diffuse reflection.
+  vec3 diffuse = (1.0 - f) * (1.0 - metallic) * baseColor / PI;
+
+  return (diffuse + specular) * radiance * nDotL;
+}
+
+void main() {
+  // Nothing was drawn here, leave it to the skybox.
+  float depth = subpassLoad(depthInput).r;
+  if (depth >= 1.0) {
+    discard;
+  }
+
+  // Back from the pixel and its depth to where the surface is.
+  vec2 ndc = gl_FragCoord.xy * lighting.inverseTargetSize * 2.0 - 1.0;
+  vec4 worldPosition = lighting.inverseViewProjection * vec4(ndc, depth, 1.0);
+  worldPosition /= worldPosition.w;
+
+  vec3 baseColor = subpassLoad(albedoInput).rgb;
+  vec3 normal = normalize(subpassLoad(normalInput).xyz);
+  vec2 metallicRoughness = subpassLoad(materialInput).rg;
+  float metallic = metallicRoughness.r;
+  float roughness = metallicRoughness.g;
+
+  vec3 toCamera = normalize(lighting.cameraPosition - worldPosition.xyz);
+  vec3 f0 = mix(DIELECTRIC_F0, baseColor, metallic);
+
+  vec3 light = vec3(0.0);
+
+  if (volume.kind == DIRECTIONAL_LIGHTS) {
+    for (uint i = 0; i < lighting.directionalLightCount; ++i) {
+      DirectionalLight directional = lighting.directionalLights[i];
+      light += shade(
+        normalize(-directional.direction),
+        directional.color * directional.intensity,
+        normal, toCamera, baseColor, metallic, roughness, f0);
+    }
+  } else if (volume.kind == POINT_LIGHTS) {
+    PointLight point = pointLights.lights[lightIndex];
+    vec3 toLight = point.position - worldPosition.xyz;
+    float lightDistance = length(toLight);
+    float attenuation = point.intensity * rangeAttenuation(lightDistance, point.range);
+    light = shade(
+      toLight / lightDistance,
+      point.color * attenuation,
+      normal, toCamera, baseColor, metallic, roughness, f0);
+  } else {
+    SpotLight spot = spotLights.lights[lightIndex];
+    vec3 toLight = spot.position - worldPosition.xyz;
+    float lightDistance = length(toLight);
+    vec3 toLightDirection = toLight / lightDistance;
+    float cone = smoothstep(
+      spot.outerConeCos,
+      spot.innerConeCos,
+      dot(-toLightDirection, normalize(spot.direction)));
+    float attenuation = spot.intensity * cone * rangeAttenuation(lightDistance, spot.range);
+    light = shade(
+      toLightDirection,
+      spot.color * attenuation,
+      normal, toCamera, baseColor, metallic, roughness, f0);
+  }
+
+  // Added onto what is there.
+  outColor = vec4(light, 0.0);
+}
@@ -0,0 +1,97 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_DEFERRED_POINT_LIGHTS = 256;
+const int MAX_DEFERRED_DIRECTIONAL_LIGHTS = 4;
+const int MAX_DEFERRED_SPOT_LIGHTS = 128;
+
+// Values of LightVolume.kind.
+const uint DIRECTIONAL_LIGHTS = 0;
+const uint POINT_LIGHTS = 1;
+const uint SPOT_LIGHTS = 2;
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+layout(binding = 0) uniform DeferredLightingUniform {
+  mat4 viewProjection;
+  mat4 inverseViewProjection;
+  vec3 cameraPosition;
+  uint directionalLightCount;
+  vec2 inverseTargetSize;
+  DirectionalLight directionalLights[MAX_DEFERRED_DIRECTIONAL_LIGHTS];
+} lighting;
+layout(binding = 1) uniform DeferredPointLights {
+  PointLight lights[MAX_DEFERRED_POINT_LIGHTS];
+} pointLights;
+layout(binding = 2) uniform DeferredSpotLights {
+  SpotLight lights[MAX_DEFERRED_SPOT_LIGHTS];
+} spotLights;
+
+layout(push_constant) uniform LightVolume {
+  uint kind;
+} volume;
+
+layout(location = 0) flat out uint lightIndex;
+
+// Corners of a cube from -1 to 1, x, y and z are bits 0, 1 and 2 of the
+// index.
+vec3 cubeCorner(int corner) {
+  return vec3(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1) * 2.0 - 1.0;
+}
+
+// Two triangles per face, counter clockwise seen from outside like the
+// objects' faces.
+const int CUBE_INDICES[36] = int[](
+  1, 3, 7, 1, 7, 5,
+  0, 4, 6, 0, 6, 2,
+  6, 7, 3, 6, 3, 2,
+  0, 1, 5, 0, 5, 4,
+  4, 5, 7, 4, 7, 6,
+  0, 2, 3, 0, 3, 1);
+
+void main() {
+  lightIndex = uint(gl_InstanceIndex);
+
+  if (volume.kind == DIRECTIONAL_LIGHTS) {
+    // One triangle that covers the whole screen, every pixel can be lit.
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    return;
+  }
+
+  // A cube around the light reaching its range, only the pixels it covers
+  // can be lit.  Its back faces are drawn so it works with the camera inside.
+  vec3 center;
+  float range;
+  if (volume.kind == POINT_LIGHTS) {
+    center = pointLights.lights[gl_InstanceIndex].position;
+    range = pointLights.lights[gl_InstanceIndex].range;
+  } else {
+    center = spotLights.lights[gl_InstanceIndex].position;
+    range = spotLights.lights[gl_InstanceIndex].range;
+  }
+  vec3 corner = cubeCorner(CUBE_INDICES[gl_VertexIndex]);
+  gl_Position = lighting.viewProjection * vec4(center + corner * range, 1.0);
+}
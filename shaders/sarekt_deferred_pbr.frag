@@ -0,0 +1,171 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Writes the surface into the G-buffer for the deferred lighting subpass,
+// along with the light that doesn't depend on the scene lights (the
+// environment and emission).  Reads the same uniform and textures as
+// sarekt_forward_pbr, but not its lights.
+
+const int MAX_POINT_LIGHTS = 4;
+const int MAX_DIRECTIONAL_LIGHTS = 2;
+const int MAX_SPOT_LIGHTS = 4;
+
+// Bits of PbrMaterial.textureMask, in PbrTextureSlot order.
+const uint BASE_COLOR_TEXTURE = 1;
+const uint METALLIC_ROUGHNESS_TEXTURE = 2;
+const uint NORMAL_TEXTURE = 4;
+const uint OCCLUSION_TEXTURE = 8;
+const uint EMISSIVE_TEXTURE = 16;
+
+// Reflectance at normal incidence of dielectrics.
+const vec3 DIELECTRIC_F0 = vec3(0.04);
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+struct PbrMaterial {
+  vec4 baseColorFactor;
+  vec3 emissiveFactor;
+  float metallicFactor;
+  float roughnessFactor;
+  float normalScale;
+  float occlusionStrength;
+  uint textureMask;
+};
+
+layout(binding = 0) uniform DefaultPbrShaderUniform {
+  mat4 model;
+  mat4 view;
+  mat4 projection;
+  vec3 cameraPosition;
+  float environmentIntensity;
+  PointLight pointLights[MAX_POINT_LIGHTS];
+  DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+  SpotLight spotLights[MAX_SPOT_LIGHTS];
+  PbrMaterial material;
+  uint pointLightCount;
+  uint directionalLightCount;
+  uint spotLightCount;
+} ubo;
+
+layout(binding = 1) uniform sampler2D baseColorSampler;
+layout(binding = 2) uniform sampler2D metallicRoughnessSampler;
+layout(binding = 3) uniform sampler2D normalSampler;
+layout(binding = 4) uniform sampler2D occlusionSampler;
+layout(binding = 5) uniform sampler2D emissiveSampler;
+// Image based lighting, bound by the renderer.
+layout(binding = 6) uniform samplerCube irradianceSampler;
+layout(binding = 7) uniform samplerCube prefilteredSampler;
+layout(binding = 8) uniform sampler2D brdfLutSampler;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec3 fragWorldPosition;
+layout(location = 2) in vec3 fragWorldNormal;
+layout(location = 3) in vec4 fragWorldTangent;
+
+// The scene color, which the lighting subpass adds the lights onto.
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outAlbedo;
+// World space normal.
+layout(location = 2) out vec4 outNormal;
+// Metalness in red and roughness in green.
+layout(location = 3) out vec4 outMaterial;
+
+bool hasTexture(uint slot) {
+  return (ubo.material.textureMask & slot) != 0;
+}
+
+// Fresnel for the environment, rough surfaces reflect less at grazing angles.
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 f0, float roughness) {
+  return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+vec3 surfaceNormal() {
+  vec3 normal = normalize(fragWorldNormal);
+  if (!gl_FrontFacing) {
+    normal = -normal;
+  }
+  if (!hasTexture(NORMAL_TEXTURE)) {
+    return normal;
+  }
+
+  vec3 tangentNormal = texture(normalSampler, fragTexCoord).xyz * 2.0 - 1.0;
+  tangentNormal.xy *= ubo.material.normalScale;
+
+  // Gram-Schmidt in case interpolation skewed the tangent.
+  vec3 tangent = normalize(fragWorldTangent.xyz - normal * dot(normal, fragWorldTangent.xyz));
+  vec3 bitangent = cross(normal, tangent) * fragWorldTangent.w;
+  return normalize(mat3(tangent, bitangent, normal) * tangentNormal);
+}
+
+void main() {
+  vec4 baseColor = ubo.material.baseColorFactor;
+  if (hasTexture(BASE_COLOR_TEXTURE)) {
+    baseColor *= texture(baseColorSampler, fragTexCoord);
+  }
+
+  float metallic = ubo.material.metallicFactor;
+  float roughness = ubo.material.roughnessFactor;
+  if (hasTexture(METALLIC_ROUGHNESS_TEXTURE)) {
+    vec4 metallicRoughness = texture(metallicRoughnessSampler, fragTexCoord);
+    roughness *= metallicRoughness.g;
+    metallic *= metallicRoughness.b;
+  }
+  metallic = clamp(metallic, 0.0, 1.0);
+  // Perfectly smooth surfaces make the GGX highlight infinitely small.
+  roughness = clamp(roughness, 0.04, 1.0);
+
+  vec3 normal = surfaceNormal();
+  vec3 toCamera = normalize(ubo.cameraPosition - fragWorldPosition);
+  vec3 f0 = mix(DIELECTRIC_F0, baseColor.rgb, metallic);
+
+  // Image based lighting with the split sum approximation, the maps are black
+  // when there is no environment.
+  float nDotV = max(dot(normal, toCamera), 1e-4);
+  vec3 f = fresnelSchlickRoughness(nDotV, f0, roughness);
+  vec3 diffuseIbl = (1.0 - f) * (1.0 - metallic) * baseColor.rgb
+    * texture(irradianceSampler, normal).rgb;
+  float maxLod = float(textureQueryLevels(prefilteredSampler) - 1);
+  vec3 prefiltered =
+    textureLod(prefilteredSampler, reflect(-toCamera, normal), roughness * maxLod).rgb;
+  vec2 brdf = texture(brdfLutSampler, vec2(nDotV, roughness)).rg;
+  vec3 specularIbl = prefiltered * (f * brdf.x + brdf.y);
+  vec3 light = (diffuseIbl + specularIbl) * ubo.environmentIntensity;
+
+  if (hasTexture(OCCLUSION_TEXTURE)) {
+    float occlusion = texture(occlusionSampler, fragTexCoord).r;
+    light *= 1.0 + ubo.material.occlusionStrength * (occlusion - 1.0);
+  }
+
+  vec3 emissive = ubo.material.emissiveFactor;
+  if (hasTexture(EMISSIVE_TEXTURE)) {
+    emissive *= texture(emissiveSampler, fragTexCoord).rgb;
+  }
+  light += emissive;
+
+  outColor = vec4(light, 1.0);
+  outAlbedo = vec4(baseColor.rgb, 1.0);
+  outNormal = vec4(normal, 0.0);
+  outMaterial = vec4(metallic, roughness, 0.0, 0.0);
+}
@@ -0,0 +1,136 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_POINT_LIGHTS = 4;
+const int MAX_DIRECTIONAL_LIGHTS = 2;
+const int MAX_SPOT_LIGHTS = 4;
+
+const uint SHADING_MODEL_PHONG = 0;
+const uint SHADING_MODEL_BLINN_PHONG = 1;
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+struct Material {
+  vec3 diffuse;
+  float shininess;
+  vec3 specular;
+  float ambient;
+};
+
+layout(binding = 0) uniform DefaultLitShaderUniform {
+  mat4 model;
+  mat4 view;
+  mat4 projection;
+  vec3 cameraPosition;
+  uint shadingModel;
+  PointLight pointLights[MAX_POINT_LIGHTS];
+  DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+  SpotLight spotLights[MAX_SPOT_LIGHTS];
+  Material material;
+  uint pointLightCount;
+  uint directionalLightCount;
+  uint spotLightCount;
+  int enableColorMixing;
+  int enableTextureMixing;
+} ubo;
+
+layout(binding = 1) uniform sampler2D texSampler;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragWorldPosition;
+layout(location = 3) in vec3 fragWorldNormal;
+
+layout(location = 0) out vec4 outColor;
+
+// Smoothly reaches zero at the light's range.
+float rangeAttenuation(float lightDistance, float range) {
+  float ratio = lightDistance / range;
+  float falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+  return falloff * falloff / (lightDistance * lightDistance + 1.0);
+}
+
+// Diffuse and specular light from a light arriving along toLight.
+vec3 shade(vec3 toLight, vec3 lightColor, vec3 normal, vec3 toCamera, vec3 diffuseColor) {
+  float diffuse = max(dot(normal, toLight), 0.0);
+
+  float specular = 0.0;
+  if (diffuse > 0.0) {
+    if (ubo.shadingModel == SHADING_MODEL_PHONG) {
+      vec3 reflected = reflect(-toLight, normal);
+      specular = pow(max(dot(reflected, toCamera), 0.0), ubo.material.shininess);
+    } else {
+      vec3 halfway = normalize(toLight + toCamera);
+      specular = pow(max(dot(normal, halfway), 0.0), ubo.material.shininess);
+    }
+  }
+
+  return lightColor * (diffuse * diffuseColor + specular * ubo.material.specular);
+}
+
+void main() {
+  vec4 colorFromTexture;
+  if (ubo.enableTextureMixing == 1) {
+    colorFromTexture = texture(texSampler, fragTexCoord);
+  } else {
+    colorFromTexture = vec4(1.0);
+  }
+  vec3 diffuseColor = ubo.material.diffuse * fragColor * colorFromTexture.rgb;
+
+  vec3 normal = normalize(fragWorldNormal);
+  vec3 toCamera = normalize(ubo.cameraPosition - fragWorldPosition);
+
+  vec3 light = ubo.material.ambient * diffuseColor;
+
+  for (uint i = 0; i < ubo.directionalLightCount; ++i) {
+    DirectionalLight directional = ubo.directionalLights[i];
+    light += directional.intensity
+      * shade(normalize(-directional.direction), directional.color, normal, toCamera, diffuseColor);
+  }
+
+  for (uint i = 0; i < ubo.pointLightCount; ++i) {
+    PointLight point = ubo.pointLights[i];
+    vec3 toLight = point.position - fragWorldPosition;
+    float lightDistance = length(toLight);
+    float attenuation = point.intensity * rangeAttenuation(lightDistance, point.range);
+    light += attenuation * shade(toLight / lightDistance, point.color, normal, toCamera, diffuseColor);
+  }
+
+  for (uint i = 0; i < ubo.spotLightCount; ++i) {
+    SpotLight spot = ubo.spotLights[i];
+    vec3 toLight = spot.position - fragWorldPosition;
+    float lightDistance = length(toLight);
+    vec3 toLightDirection = toLight / lightDistance;
+    float cone = smoothstep(
+      spot.outerConeCos,
+      spot.innerConeCos,
+      dot(-toLightDirection, normalize(spot.direction)));
+    float attenuation = spot.intensity * cone * rangeAttenuation(lightDistance, spot.range);
+    light += attenuation * shade(toLightDirection, spot.color, normal, toCamera, diffuseColor);
+  }
+
+  // Alpha is from the texture alone.
+  outColor = vec4(light, colorFromTexture.a);
+}
@@ -0,0 +1,84 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_POINT_LIGHTS = 4;
+const int MAX_DIRECTIONAL_LIGHTS = 2;
+const int MAX_SPOT_LIGHTS = 4;
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+struct Material {
+  vec3 diffuse;
+  float shininess;
+  vec3 specular;
+  float ambient;
+};
+
+layout(binding = 0) uniform DefaultLitShaderUniform {
+  mat4 model;
+  mat4 view;
+  mat4 projection;
+  vec3 cameraPosition;
+  uint shadingModel;
+  PointLight pointLights[MAX_POINT_LIGHTS];
+  DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+  SpotLight spotLights[MAX_SPOT_LIGHTS];
+  Material material;
+  uint pointLightCount;
+  uint directionalLightCount;
+  uint spotLightCount;
+  int enableColorMixing;
+  int enableTextureMixing;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec3 inColor;
+layout(location = 3) in vec2 inTexCoord;
+
+// Set per pipeline, only used when drawing points.
+layout(constant_id = 0) const float POINT_SIZE = 1.0;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragWorldPosition;
+layout(location = 3) out vec3 fragWorldNormal;
+
+void main() {
+  vec4 worldPosition = ubo.model * vec4(inPosition, 1.0);
+  gl_Position = ubo.projection * ubo.view * worldPosition;
+  gl_PointSize = POINT_SIZE;
+
+  if (ubo.enableColorMixing != 0) {
+    fragColor = inColor;
+  } else {
+    fragColor = vec3(1.0);
+  }
+
+  fragTexCoord = inTexCoord;
+  fragWorldPosition = worldPosition.xyz;
+  // Keeps normals perpendicular to surfaces under non uniform scaling.
+  fragWorldNormal = mat3(transpose(inverse(ubo.model))) * inNormal;
+}
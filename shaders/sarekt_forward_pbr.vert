@@ -0,0 +1,81 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_POINT_LIGHTS = 4;
+const int MAX_DIRECTIONAL_LIGHTS = 2;
+const int MAX_SPOT_LIGHTS = 4;
+
+struct PointLight {
+  vec3 position;
+  float intensity;
+  vec3 color;
+  float range;
+};
+
+struct DirectionalLight {
+  vec3 direction;
+  float intensity;
+  vec3 color;
+  float padding;
+};
+
+struct SpotLight {
+  vec3 position;
+  float intensity;
+  vec3 direction;
+  float range;
+  vec3 color;
+  float innerConeCos;
+  float outerConeCos;
+};
+
+struct PbrMaterial {
+  vec4 baseColorFactor;
+  vec3 emissiveFactor;
+  float metallicFactor;
+  float roughnessFactor;
+  float normalScale;
+  float occlusionStrength;
+  uint textureMask;
+};
+
+layout(binding = 0) uniform DefaultPbrShaderUniform {
+  mat4 model;
+  mat4 view;
+  mat4 projection;
+  vec3 cameraPosition;
+  float environmentIntensity;
+  PointLight pointLights[MAX_POINT_LIGHTS];
+  DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+  SpotLight spotLights[MAX_SPOT_LIGHTS];
+  PbrMaterial material;
+  uint pointLightCount;
+  uint directionalLightCount;
+  uint spotLightCount;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec4 inTangent;
+layout(location = 3) in vec2 inTexCoord;
+
+// Set per pipeline, only used when drawing points.
+layout(constant_id = 0) const float POINT_SIZE = 1.0;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec3 fragWorldPosition;
+layout(location = 2) out vec3 fragWorldNormal;
+layout(location = 3) out vec4 fragWorldTangent;
+
+void main() {
+  vec4 worldPosition = ubo.model * vec4(inPosition, 1.0);
+  gl_Position = ubo.projection * ubo.view * worldPosition;
+  gl_PointSize = POINT_SIZE;
+
+  fragTexCoord = inTexCoord;
+  fragWorldPosition = worldPosition.xyz;
+  // Keeps normals perpendicular to surfaces under non uniform scaling,
+  // tangents lie in the surface so they transform like positions.
+  fragWorldNormal = mat3(transpose(inverse(ubo.model))) * inNormal;
+  fragWorldTangent = vec4(mat3(ubo.model) * inTangent.xyz, inTangent.w);
+}
@@ -0,0 +1,90 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const int MAX_SHADOW_CASCADES = 4;
+// How much light is taken away in full shadow.
+const float SHADOW_DARKNESS = 0.6;
+
+layout(binding = 0) uniform DefaultShadowedShaderUniform {
+  mat4 mvp;
+  mat4 model;
+  int enableColorMixing;
+  int enableTextureMixing;
+} ubo;
+
+layout(binding = 1) uniform sampler2D texSampler;
+
+// A 2x2 atlas with one tile per cascade, sampled with a comparison sampler.
+layout(binding = 2) uniform sampler2DShadow shadowMap;
+
+layout(binding = 3) uniform ShadowUniform {
+  mat4 lightViewProjections[MAX_SHADOW_CASCADES];
+  // View depth each cascade ends at.
+  vec4 cascadeSplits;
+  mat4 cameraView;
+  // Zero when there is no shadow casting light.
+  int cascadeCount;
+  // Size of a texel of the whole atlas in uv.
+  float texelSize;
+} shadow;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragWorldPosition;
+
+layout(location = 0) out vec4 outColor;
+
+// 1 where fully lit, 0 where fully in shadow.
+float shadowVisibility() {
+  if (shadow.cascadeCount == 0) {
+    return 1.0;
+  }
+
+  float viewDepth = -(shadow.cameraView * vec4(fragWorldPosition, 1.0)).z;
+  int cascade = shadow.cascadeCount - 1;
+  for (int i = 0; i < shadow.cascadeCount; ++i) {
+    if (viewDepth < shadow.cascadeSplits[i]) {
+      cascade = i;
+      break;
+    }
+  }
+
+  vec4 lightClip = shadow.lightViewProjections[cascade] * vec4(fragWorldPosition, 1.0);
+  vec3 lightNdc = lightClip.xyz / lightClip.w;
+  if (any(greaterThan(abs(lightNdc.xy), vec2(1.0))) || lightNdc.z < 0.0 || lightNdc.z > 1.0) {
+    // Outside what the light's shadow map covers.
+    return 1.0;
+  }
+
+  vec2 tileOffset = vec2(cascade % 2, cascade / 2) * 0.5;
+  vec2 tileUv = tileOffset + (lightNdc.xy * 0.5 + 0.5) * 0.5;
+  // Keep the filter taps inside this cascade's tile.
+  vec2 minUv = tileOffset + vec2(shadow.texelSize * 0.5);
+  vec2 maxUv = tileOffset + vec2(0.5 - shadow.texelSize * 0.5);
+
+  // 3x3 percentage closer filtering, each tap is itself bilinearly filtered.
+  float visibility = 0.0;
+  for (int x = -1; x <= 1; ++x) {
+    for (int y = -1; y <= 1; ++y) {
+      vec2 uv = clamp(tileUv + vec2(x, y) * shadow.texelSize, minUv, maxUv);
+      visibility += texture(shadowMap, vec3(uv, lightNdc.z));
+    }
+  }
+  return visibility / 9.0;
+}
+
+void main() {
+  vec3 colorFromFragColor = fragColor;
+
+  vec4 colorFromTexture;
+  if (ubo.enableTextureMixing == 1) {
+    colorFromTexture = texture(texSampler, fragTexCoord);
+  } else {
+    colorFromTexture = vec4(1.0);
+  }
+
+  float lighting = 1.0 - SHADOW_DARKNESS * (1.0 - shadowVisibility());
+
+  // Alpha is from the texture alone.
+  outColor = vec4(colorFromFragColor * colorFromTexture.rgb * lighting, colorFromTexture.a);
+}
@@ -0,0 +1,34 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform DefaultShadowedShaderUniform {
+  mat4 mvp;
+  mat4 model;
+  int enableColorMixing;
+  int enableTextureMixing;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+// Set per pipeline, only used when drawing points.
+layout(constant_id = 0) const float POINT_SIZE = 1.0;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragWorldPosition;
+
+void main() {
+  gl_Position = ubo.mvp * vec4(inPosition, 1.0);
+  gl_PointSize = POINT_SIZE;
+
+  if (ubo.enableColorMixing != 0) {
+    fragColor = inColor;
+  } else {
+    fragColor = vec3(1.0);
+  }
+
+  fragTexCoord = inTexCoord;
+  fragWorldPosition = (ubo.model * vec4(inPosition, 1.0)).xyz;
+}
@@ -0,0 +1,22 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D bloom;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the intensity.  The blurred highlights are half
+// resolution, linear filtering scales them up.
+
+void main() {
+  vec3 color = texture(source, uv).rgb + texture(bloom, uv).rgb * pc.parameters[0].x;
+  outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the threshold.  Drawn at half resolution, so four bilinear
+// taps average a 4x4 block of the source.
+
+void main() {
+  vec2 t = pc.texelSize;
+  vec3 color = 0.25 * (texture(source, uv + vec2(-t.x, -t.y)).rgb
+                     + texture(source, uv + vec2(t.x, -t.y)).rgb
+                     + texture(source, uv + vec2(-t.x, t.y)).rgb
+                     + texture(source, uv + vec2(t.x, t.y)).rgb);
+
+  // Keep only what is past the threshold, scaling the color rather than
+  // subtracting from it so hues don't shift.
+  float brightness = max(color.r, max(color.g, color.b));
+  float contribution = max(brightness - pc.parameters[0].x, 0.0) / max(brightness, 0.0001);
+  outColor = vec4(color * contribution, 1.0);
+}
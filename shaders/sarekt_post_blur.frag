@@ -0,0 +1,27 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].xy is the direction to blur in, 1, 0 or 0, 1.  A 9 tap gaussian.
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+  vec2 offset = pc.parameters[0].xy * pc.texelSize;
+  vec3 color = texture(source, uv).rgb * weights[0];
+  for (int i = 1; i < 5; i++) {
+    color += texture(source, uv + offset * float(i)).rgb * weights[i];
+    color += texture(source, uv - offset * float(i)).rgb * weights[i];
+  }
+  outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,35 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D lut;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// The lookup table is N slices of NxN side by side, red along each slice, green
+// down it, and blue picking the slice.
+
+void main() {
+  vec3 color = clamp(texture(source, uv).rgb, 0.0, 1.0);
+  float size = float(textureSize(lut, 0).y);
+  vec2 lutSize = vec2(size * size, size);
+
+  // Blue is interpolated between two slices by hand, linear filtering does red
+  // and green.  Texel centers are half a texel in so it doesn't bleed into the
+  // neighbouring slice.
+  float slice = color.b * (size - 1.0);
+  float slice0 = floor(slice);
+  float slice1 = min(slice0 + 1.0, size - 1.0);
+  vec2 inSlice = color.rg * (size - 1.0) + 0.5;
+  vec3 color0 = texture(lut, (vec2(slice0 * size, 0.0) + inSlice) / lutSize).rgb;
+  vec3 color1 = texture(lut, (vec2(slice1 * size, 0.0) + inSlice) / lutSize).rgb;
+
+  outColor = vec4(mix(color0, color1, slice - slice0), 1.0);
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  outColor = vec4(texture(source, uv).rgb, 1.0);
+}
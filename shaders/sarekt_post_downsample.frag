@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the supersampling scale.
+
+void main() {
+  // Averages a grid of taps spread over the scale x scale texels behind this
+  // pixel, which land on texel centers for whole scales.
+  float scale = pc.parameters[0].x;
+  int taps = int(ceil(scale));
+  vec2 footprint = pc.texelSize * scale;
+  vec2 corner = uv - footprint * 0.5;
+
+  vec3 sum = vec3(0.0);
+  for (int y = 0; y < taps; y++) {
+    for (int x = 0; x < taps; x++) {
+      sum += texture(source, corner + footprint * (vec2(x, y) + 0.5) / float(taps)).rgb;
+    }
+  }
+  outColor = vec4(sum / float(taps * taps), 1.0);
+}
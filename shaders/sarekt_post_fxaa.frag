@@ -0,0 +1,54 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// Timothy Lottes' FXAA, the simple version without the edge search.
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+
+// The colors are linear, the square root brings luma close to what is seen.
+float luma(vec3 color) {
+  return dot(sqrt(max(color, vec3(0.0))), vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+  vec2 t = pc.texelSize;
+  float lumaNW = luma(texture(source, uv + vec2(-t.x, -t.y)).rgb);
+  float lumaNE = luma(texture(source, uv + vec2(t.x, -t.y)).rgb);
+  float lumaSW = luma(texture(source, uv + vec2(-t.x, t.y)).rgb);
+  float lumaSE = luma(texture(source, uv + vec2(t.x, t.y)).rgb);
+  vec3 colorM = texture(source, uv).rgb;
+  float lumaM = luma(colorM);
+
+  float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+  float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+  // Blur along the edge, perpendicular to the luma gradient.
+  vec2 direction = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+                        (lumaNW + lumaSW) - (lumaNE + lumaSE));
+  float directionReduce =
+      max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * FXAA_REDUCE_MUL), FXAA_REDUCE_MIN);
+  float inverseDirectionMin = 1.0 / (min(abs(direction.x), abs(direction.y)) + directionReduce);
+  direction = clamp(direction * inverseDirectionMin, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * t;
+
+  vec3 colorA = 0.5 * (texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb
+                     + texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+  vec3 colorB = colorA * 0.5 + 0.25 * (texture(source, uv - direction * 0.5).rgb
+                                     + texture(source, uv + direction * 0.5).rgb);
+
+  // The wider blur crossed another edge, fall back to the narrow one.
+  float lumaB = luma(colorB);
+  outColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB, 1.0);
+}
@@ -0,0 +1,38 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the luminance in nits that 1.0 is displayed at.
+
+// Linear BT.709 to linear BT.2020, from ITU-R BT.2087 (column major).
+const mat3 BT709_TO_BT2020 = mat3(
+  0.6274, 0.0691, 0.0164,
+  0.3293, 0.9195, 0.0880,
+  0.0433, 0.0114, 0.8956);
+
+// SMPTE ST 2084 inverse EOTF, from linear with 1.0 at 10000 nits.
+vec3 pqEncode(vec3 color) {
+  const float m1 = 0.1593017578125;
+  const float m2 = 78.84375;
+  const float c1 = 0.8359375;
+  const float c2 = 18.8515625;
+  const float c3 = 18.6875;
+  vec3 lm1 = pow(clamp(color, 0.0, 1.0), vec3(m1));
+  return pow((c1 + c2 * lm1) / (1.0 + c3 * lm1), vec3(m2));
+}
+
+void main() {
+  vec3 color = BT709_TO_BT2020 * max(texture(source, uv).rgb, vec3(0.0));
+  outColor = vec4(pqEncode(color * pc.parameters[0].x / 10000.0), 1.0);
+}
@@ -0,0 +1,11 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 uv;
+
+void main() {
+  // One triangle that covers the whole screen, no vertex buffer needed.  uv is
+  // 0..1 across the screen, with 0, 0 at the top left like the images.
+  uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+  gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,45 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+// The previous frame's output.
+layout(binding = 1) uniform sampler2D history;
+layout(binding = 2) uniform sampler2D velocity;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is 1 when the history holds the previous frame, and
+// parameters[0].y the weight of the current frame.
+
+void main() {
+  vec3 current = texture(source, uv).rgb;
+
+  // The history is clamped to the colors around the pixel this frame, so what
+  // was hidden or has moved on its own doesn't leave a trail.
+  vec3 low = current;
+  vec3 high = current;
+  for (int y = -1; y <= 1; y++) {
+    for (int x = -1; x <= 1; x++) {
+      vec3 neighbour = texture(source, uv + vec2(x, y) * pc.texelSize).rgb;
+      low = min(low, neighbour);
+      high = max(high, neighbour);
+    }
+  }
+
+  vec2 previousUv = uv - texture(velocity, uv).rg;
+  bool offscreen = any(lessThan(previousUv, vec2(0.0))) || any(greaterThan(previousUv, vec2(1.0)));
+  if (pc.parameters[0].x < 0.5 || offscreen) {
+    outColor = vec4(current, 1.0);
+    return;
+  }
+
+  vec3 previous = clamp(texture(history, previousUv).rgb, low, high);
+  outColor = vec4(mix(previous, current, pc.parameters[0].y), 1.0);
+}
@@ -0,0 +1,37 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the exposure, parameters[0].y the operator, 0 for Reinhard
+// and 1 for ACES.
+
+vec3 reinhard(vec3 color) {
+  return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 color) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+  vec3 color = texture(source, uv).rgb * pc.parameters[0].x;
+  color = pc.parameters[0].y < 0.5 ? reinhard(color) : aces(color);
+  outColor = vec4(color, 1.0);
+}
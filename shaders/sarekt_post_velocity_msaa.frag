@@ -0,0 +1,25 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Same as sarekt_post_velocity.frag for a multisampled depth buffer, using the
+// depth of its first sample.
+layout(binding = 0) uniform sampler2DMS depth;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+  // From this frame's clip space to the previous frame's.
+  mat4 reprojection;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  float sampleDepth = texelFetch(depth, ivec2(uv * vec2(textureSize(depth))), 0).r;
+  vec4 previous = pc.reprojection * vec4(uv * 2.0 - 1.0, sampleDepth, 1.0);
+  vec2 previousUv = previous.xy / previous.w * 0.5 + 0.5;
+  outColor = vec4(uv - previousUv, 0.0, 0.0);
+}
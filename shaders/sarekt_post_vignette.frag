@@ -0,0 +1,25 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform sampler2D source;
+layout(binding = 1) uniform sampler2D scene;
+
+layout(push_constant) uniform PostProcessConstants {
+  vec4 parameters[2];
+  vec2 texelSize;
+} pc;
+
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 outColor;
+
+// parameters[0].x is the intensity, parameters[0].y the radius darkening starts
+// at.
+
+void main() {
+  // 0 in the center, 1 in the corners.
+  float fromCenter = length(uv - 0.5) * sqrt(2.0);
+  float radius = min(pc.parameters[0].y, 0.999);
+  float darken = pc.parameters[0].x * smoothstep(radius, 1.0, fromCenter);
+  outColor = vec4(texture(source, uv).rgb * (1.0 - darken), 1.0);
+}
@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// The light's view projection for the cascade being drawn times the model.
+layout(push_constant) uniform ShadowCasterPushConstants {
+  mat4 lightMvp;
+} pc;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+  // Depth only, there is no fragment shader.
+  gl_Position = pc.lightMvp * vec4(inPosition, 1.0);
+}
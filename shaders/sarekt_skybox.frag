@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform samplerCube skybox;
+
+layout(location = 0) in vec3 direction;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  outColor = vec4(texture(skybox, direction).rgb, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Projection * view with the translation removed, so the sky never gets closer.
+layout(push_constant) uniform SkyboxPushConstants {
+  mat4 viewProjection;
+} pc;
+
+layout(location = 0) out vec3 direction;
+
+void main() {
+  // One triangle that covers the whole screen, no vertex buffer needed.
+  vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+
+  // Sit on the far plane, with the depth buffer cleared to 1.0 and a LESS_OR_EQUAL
+  // test only pixels nothing else was drawn to pass.
+  gl_Position = vec4(ndc, 1.0, 1.0);
+
+  vec4 world = inverse(pc.viewProjection) * vec4(ndc, 1.0, 1.0);
+  direction = world.xyz / world.w;
+}
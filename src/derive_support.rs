@@ -0,0 +1,3 @@
+//! Paths used by the code generated by sarekt_derive, which can't assume the
+//! application depends on ash itself.
+pub use ash::vk;
@@ -1,7 +1,7 @@
-use crate::error::SarektError::CStrError;
+use crate::{error::SarektError::CStrError, image_data::ImageDataFormat};
 
 use ash::vk;
-use std::{error::Error, ffi::NulError, fmt};
+use std::{error::Error, ffi::NulError, fmt, io};
 
 pub type SarektResult<T> = Result<T, SarektError>;
 
@@ -20,13 +20,36 @@ pub enum SarektError {
   IncorrectBufferType,
   IncorrectResourceType,
   UnsupportedImageFormat,
+  NoCpuDecoder(ImageDataFormat),
   UnknownResource,
   NoSuitableMemoryHeap,
   NoSuitableDepthBufferFormat,
+  NoSuitableSceneColorFormat,
+  UnsupportedAntiAliasing(&'static str),
   VulkanMemoryAllocatorError(vk_mem::error::Error),
   IllegalMipmapCount,
   FormatDoesNotSupportMipmapping(String),
   UnsupportedMsaa(&'static str),
+  InvalidMipChain(String),
+  InvalidImageContainer(String),
+  InvalidCubemap(String),
+  InvalidEnvironmentMap(String),
+  InvalidImageLayers(String),
+  InvalidImageUpdate(String),
+  InvalidBufferUpdate(String),
+  TransientUniformMemoryExhausted(u64),
+  NoUniformBuffer,
+  UniformLayoutMismatch(String),
+  UnsupportedVertexFormat(String),
+  UnsupportedPrimitiveState(String),
+  UnsupportedRenderTextureFormat(ImageDataFormat),
+  InvalidShadowLight(String),
+  TooManyLights(String),
+  InvalidMesh(String),
+  PostProcessingDisabled,
+  DeferredRenderingDisabled,
+  InvalidPostProcessPass(String),
+  IoError(io::Error),
 }
 
 impl From<vk::Result> for SarektError {
@@ -48,6 +71,11 @@ impl From<vk_mem::error::Error> for SarektError {
     SarektError::VulkanMemoryAllocatorError(e)
   }
 }
+impl From<io::Error> for SarektError {
+  fn from(e: io::Error) -> SarektError {
+    SarektError::IoError(e)
+  }
+}
 impl From<NulError> for SarektError {
   fn from(e: NulError) -> SarektError {
     CStrError(e)
@@ -90,6 +118,12 @@ impl fmt::Display for SarektError {
       SarektError::UnsupportedImageFormat => {
         write!(f, "Image format of ImageData is not supported")
       }
+      SarektError::NoCpuDecoder(format) => write!(
+        f,
+        "Device can't sample {:?} images and they can't be decoded on the CPU, ship the texture \
+         in another format as well",
+        format
+      ),
       SarektError::NoSuitableMemoryHeap => write!(
         f,
         "Could not find memory heap that was suitable for the device allocation."
@@ -97,6 +131,10 @@ impl fmt::Display for SarektError {
       SarektError::NoSuitableDepthBufferFormat => {
         write!(f, "Could not select a format for the depth buffer")
       }
+      SarektError::NoSuitableSceneColorFormat => {
+        write!(f, "Could not select a format for the scene color target")
+      }
+      SarektError::UnsupportedAntiAliasing(s) => write!(f, "Unsupported anti-aliasing: {}", s),
       SarektError::VulkanMemoryAllocatorError(e) => {
         write!(f, "Vulkan memory allocator error: {}", e)
       }
@@ -115,6 +153,52 @@ impl fmt::Display for SarektError {
         write!(f, "Format not supported for mipmapping: {}", s)
       }
       SarektError::UnsupportedMsaa(s) => write!(f, "Unsupported MSAA: {}", s),
+      SarektError::InvalidMipChain(s) => write!(f, "Invalid mip chain: {}", s),
+      SarektError::InvalidImageContainer(s) => write!(f, "Could not parse image container: {}", s),
+      SarektError::InvalidCubemap(s) => write!(f, "Invalid cubemap: {}", s),
+      SarektError::InvalidEnvironmentMap(s) => write!(f, "Invalid environment map: {}", s),
+      SarektError::InvalidImageLayers(s) => write!(f, "Invalid image layers: {}", s),
+      SarektError::InvalidImageUpdate(s) => write!(f, "Invalid image update: {}", s),
+      SarektError::InvalidBufferUpdate(s) => write!(f, "Invalid buffer update: {}", s),
+      SarektError::TransientUniformMemoryExhausted(size) => write!(
+        f,
+        "Ran out of transient uniform memory this frame, increase \
+         Config::transient_uniform_buffer_size (currently {} bytes)",
+        size
+      ),
+      SarektError::NoUniformBuffer => write!(
+        f,
+        "Drawable object has no uniform buffer, draw it with draw_with_uniform instead"
+      ),
+      SarektError::UnsupportedVertexFormat(s) => write!(
+        f,
+        "Device can't read these vertex attribute formats from vertex buffers: {}",
+        s
+      ),
+      SarektError::UnsupportedPrimitiveState(s) => {
+        write!(f, "Unsupported primitive state: {}", s)
+      }
+      SarektError::UnsupportedRenderTextureFormat(format) => write!(
+        f,
+        "Device can't both render to and sample images of format {:?}",
+        format
+      ),
+      SarektError::InvalidShadowLight(s) => write!(f, "Invalid shadow light: {}", s),
+      SarektError::TooManyLights(s) => write!(f, "Too many lights: {}", s),
+      SarektError::InvalidMesh(s) => write!(f, "Invalid mesh: {}", s),
+      SarektError::PostProcessingDisabled => write!(
+        f,
+        "Post processing was not enabled in the config the renderer was created with"
+      ),
+      SarektError::DeferredRenderingDisabled => write!(
+        f,
+        "The renderer was not created with the deferred rendering path"
+      ),
+      SarektError::InvalidPostProcessPass(s) => write!(f, "Invalid post process pass: {}", s),
+      SarektError::UniformLayoutMismatch(s) => {
+        write!(f, "Uniform struct does not match its shader block: {}", s)
+      }
+      SarektError::IoError(e) => write!(f, "IO error: {}", e),
     }
   }
 }
@@ -0,0 +1,129 @@
+//! IEEE 754 half precision conversions, for half float images and vertex
+//! attributes.
+
+/// Converts to IEEE 754 half precision, rounding to nearest with ties to even.
+pub(crate) fn f32_to_f16_bits(value: f32) -> u16 {
+  let bits = value.to_bits();
+  let sign = ((bits >> 16) & 0x8000) as u16;
+  let exponent = ((bits >> 23) & 0xFF) as i32;
+  let mantissa = bits & 0x7F_FFFF;
+
+  if exponent == 0xFF {
+    // Infinity stays infinity, NaN stays NaN.
+    return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+  }
+
+  let half_exponent = exponent - 127 + 15;
+  if half_exponent >= 0x1F {
+    return sign | 0x7C00;
+  }
+
+  if half_exponent <= 0 {
+    // Subnormal half, or zero if too small.
+    if half_exponent < -10 {
+      return sign;
+    }
+    let mantissa = mantissa | 0x80_0000;
+    let shift = (14 - half_exponent) as u32;
+    let half = sign | (mantissa >> shift) as u16;
+    return half + round_up(mantissa, shift, half) as u16;
+  }
+
+  // A carry out of the mantissa correctly bumps the exponent, up to infinity.
+  let half = sign | ((half_exponent as u16) << 10) | (mantissa >> 13) as u16;
+  half + round_up(mantissa, 13, half) as u16
+}
+
+/// Whether dropping the low `shift` bits of mantissa to get the truncated half
+/// should round up, ties going to the even half.
+fn round_up(mantissa: u32, shift: u32, truncated: u16) -> bool {
+  let round_bit = (mantissa >> (shift - 1)) & 1 == 1;
+  let sticky = mantissa & ((1 << (shift - 1)) - 1) != 0;
+  round_bit && (sticky || truncated & 1 == 1)
+}
+
+/// Converts from IEEE 754 half precision.
+pub(crate) fn f16_bits_to_f32(half: u16) -> f32 {
+  let sign = ((half & 0x8000) as u32) << 16;
+  let exponent = ((half >> 10) & 0x1F) as u32;
+  let mantissa = (half & 0x3FF) as u32;
+
+  let bits = match exponent {
+    0 if mantissa == 0 => sign,
+    // Subnormal, scale by 2^-24.
+    0 => {
+      let magnitude = mantissa as f32 / (1 << 24) as f32;
+      return if sign != 0 { -magnitude } else { magnitude };
+    }
+    0x1F => sign | 0x7F80_0000 | (mantissa << 13),
+    _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+  };
+  f32::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn f32_to_f16_normals() {
+    assert_eq!(f32_to_f16_bits(1.0), 0x3C00);
+    assert_eq!(f32_to_f16_bits(-2.0), 0xC000);
+    assert_eq!(f32_to_f16_bits(0.0), 0x0000);
+    assert_eq!(f32_to_f16_bits(-0.0), 0x8000);
+    assert_eq!(f32_to_f16_bits(65504.0), 0x7BFF);
+    // Smallest normal.
+    assert_eq!(f32_to_f16_bits(2f32.powi(-14)), 0x0400);
+  }
+
+  #[test]
+  fn f32_to_f16_subnormals() {
+    assert_eq!(f32_to_f16_bits(2f32.powi(-24)), 0x0001);
+    assert_eq!(f32_to_f16_bits(-2f32.powi(-24)), 0x8001);
+    assert_eq!(f32_to_f16_bits(1023.0 * 2f32.powi(-24)), 0x03FF);
+    // Halfway to the smallest subnormal is a tie that goes to even (zero),
+    // anything past it rounds up.
+    assert_eq!(f32_to_f16_bits(2f32.powi(-25)), 0x0000);
+    assert_eq!(f32_to_f16_bits(1.5 * 2f32.powi(-25)), 0x0001);
+    assert_eq!(f32_to_f16_bits(2f32.powi(-26)), 0x0000);
+    // Rounding the largest subnormal up carries into the smallest normal.
+    assert_eq!(f32_to_f16_bits(1023.75 * 2f32.powi(-24)), 0x0400);
+  }
+
+  #[test]
+  fn f32_to_f16_infinities_and_nan() {
+    assert_eq!(f32_to_f16_bits(f32::INFINITY), 0x7C00);
+    assert_eq!(f32_to_f16_bits(f32::NEG_INFINITY), 0xFC00);
+    assert_eq!(f32_to_f16_bits(1e6), 0x7C00);
+    assert_eq!(f32_to_f16_bits(-1e6), 0xFC00);
+    // Past the halfway point between the largest half and 2^16.
+    assert_eq!(f32_to_f16_bits(65520.0), 0x7C00);
+
+    let nan = f32_to_f16_bits(f32::NAN);
+    assert_eq!(nan & 0x7C00, 0x7C00);
+    assert_ne!(nan & 0x3FF, 0);
+    assert!(f16_bits_to_f32(nan).is_nan());
+  }
+
+  #[test]
+  fn f32_to_f16_rounds_to_nearest_even() {
+    let ulp = 2f32.powi(-10);
+    assert_eq!(f32_to_f16_bits(1.0 + ulp * 0.25), 0x3C00);
+    assert_eq!(f32_to_f16_bits(1.0 + ulp * 0.75), 0x3C01);
+    // Ties go to the even mantissa.
+    assert_eq!(f32_to_f16_bits(1.0 + ulp * 0.5), 0x3C00);
+    assert_eq!(f32_to_f16_bits(1.0 + ulp * 1.5), 0x3C02);
+    assert_eq!(f32_to_f16_bits(1.0 + ulp * 0.5 + 2f32.powi(-20)), 0x3C01);
+  }
+
+  #[test]
+  fn f16_round_trips() {
+    for half in 0..=u16::MAX {
+      let value = f16_bits_to_f32(half);
+      if value.is_nan() {
+        continue;
+      }
+      assert_eq!(f32_to_f16_bits(value), half, "{:#06x} -> {}", half, value);
+    }
+  }
+}
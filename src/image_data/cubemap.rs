@@ -0,0 +1,271 @@
+//! Cube images, built either out of six separate faces or projected from a
+//! single equirectangular (latitude/longitude) panorama such as the HDR
+//! environment maps commonly distributed for skyboxes.
+//!
+//! Faces are always in Vulkan's layer order: +X, -X, +Y, -Y, +Z, -Z.
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::{mip_level_dimensions, ImageData, ImageDataFormat},
+};
+use std::f32::consts::PI;
+
+/// The trait used for loading cube images into Sarekt, see
+/// [Cubemap](struct.Cubemap.html) for the provided implementation.
+pub trait CubemapImageData {
+  /// Returns the bytes of each face in +X, -X, +Y, -Y, +Z, -Z order.
+  fn into_face_bytes(self) -> [Vec<u8>; 6];
+
+  /// Width (and height, faces are square) of each face.
+  fn face_size(&self) -> u32;
+
+  /// Underlying image format, shared by all the faces.
+  fn format(&self) -> SarektResult<ImageDataFormat>;
+}
+
+/// The trait used for loading cube images whose mip chain was generated ahead
+/// of time, such as the prefiltered maps made by
+/// [environment](../environment/index.html).  See
+/// [MipmappedCubemap](struct.MipmappedCubemap.html) for the provided
+/// implementation.
+pub trait MipmappedCubemapImageData {
+  /// Returns the bytes of each level, largest first, with the level's six
+  /// faces back to back in +X, -X, +Y, -Y, +Z, -Z order.
+  fn into_level_bytes(self) -> Vec<Vec<u8>>;
+
+  /// Width (and height) of each face of the largest level.
+  fn face_size(&self) -> u32;
+
+  /// Number of levels in the mip chain.
+  fn mip_levels(&self) -> u32;
+
+  /// Underlying image format, shared by all the faces and levels.
+  fn format(&self) -> SarektResult<ImageDataFormat>;
+}
+
+/// Six square faces of the same size and format held in memory.
+pub struct Cubemap {
+  format: ImageDataFormat,
+  face_size: u32,
+  faces: [Vec<u8>; 6],
+}
+impl Cubemap {
+  /// Creates a cubemap out of the raw bytes of each face, checking that every
+  /// face is the size its format and dimensions call for.
+  pub fn new(format: ImageDataFormat, face_size: u32, faces: [Vec<u8>; 6]) -> SarektResult<Self> {
+    if face_size == 0 {
+      return Err(SarektError::InvalidCubemap(
+        "faces must not be empty".to_owned(),
+      ));
+    }
+
+    let expected_size = format.level_size((face_size, face_size));
+    for (face, bytes) in faces.iter().enumerate() {
+      if bytes.len() != expected_size {
+        return Err(SarektError::InvalidCubemap(format!(
+          "face {} of {:?} cubemap with size {} should be {} bytes but was {}",
+          face,
+          format,
+          face_size,
+          expected_size,
+          bytes.len()
+        )));
+      }
+    }
+
+    Ok(Self {
+      format,
+      face_size,
+      faces,
+    })
+  }
+
+  /// Builds a cubemap out of six images in +X, -X, +Y, -Y, +Z, -Z order.  They
+  /// must be square and all share a size and format.
+  pub fn from_faces<I: ImageData>(faces: [I; 6]) -> SarektResult<Self> {
+    let format = faces[0].format()?;
+    let (face_size, _) = faces[0].dimensions();
+    for face in faces.iter() {
+      if face.dimensions() != (face_size, face_size) || face.format()? != format {
+        return Err(SarektError::InvalidCubemap(format!(
+          "all faces must be square {:?} images of size {}",
+          format, face_size
+        )));
+      }
+    }
+
+    let [px, nx, py, ny, pz, nz] = faces;
+    Self::new(
+      format,
+      face_size,
+      [
+        px.into_bytes(),
+        nx.into_bytes(),
+        py.into_bytes(),
+        ny.into_bytes(),
+        pz.into_bytes(),
+        nz.into_bytes(),
+      ],
+    )
+  }
+
+  /// Projects an equirectangular panorama onto the faces of a cube with the
+  /// given face size, using nearest sampling.  A face size of a quarter of the
+  /// panorama's width keeps roughly the same texel density.
+  ///
+  /// Works for any uncompressed format, so HDR panoramas stay HDR.
+  pub fn from_equirectangular<I: ImageData>(image: I, face_size: u32) -> SarektResult<Self> {
+    let format = image.format()?;
+    if format.is_compressed() {
+      return Err(SarektError::InvalidCubemap(format!(
+        "can't project block compressed {:?} panorama",
+        format
+      )));
+    }
+
+    let (width, height) = image.dimensions();
+    let texel_size = format.texel_block_size();
+    let pixels = image.into_bytes();
+
+    let mut faces: [Vec<u8>; 6] = Default::default();
+    for (face_index, face) in faces.iter_mut().enumerate() {
+      face.reserve(face_size as usize * face_size as usize * texel_size);
+      for y in 0..face_size {
+        for x in 0..face_size {
+          let s = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+          let t = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+          let (dx, dy, dz) = face_direction(face_index, s, t);
+
+          // Longitude around y, latitude down from +y.
+          let length = (dx * dx + dy * dy + dz * dz).sqrt();
+          let u = 0.5 + dz.atan2(dx) / (2.0 * PI);
+          let v = (dy / length).acos() / PI;
+          let src_x = ((u * width as f32) as u32).min(width - 1);
+          let src_y = ((v * height as f32) as u32).min(height - 1);
+
+          let offset = (src_y as usize * width as usize + src_x as usize) * texel_size;
+          face.extend_from_slice(&pixels[offset..offset + texel_size]);
+        }
+      }
+    }
+
+    Self::new(format, face_size, faces)
+  }
+}
+impl CubemapImageData for Cubemap {
+  fn into_face_bytes(self) -> [Vec<u8>; 6] {
+    self.faces
+  }
+
+  fn face_size(&self) -> u32 {
+    self.face_size
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
+
+/// The six faces of every level of a pre-generated mip chain held in memory,
+/// level 0 first.
+pub struct MipmappedCubemap {
+  format: ImageDataFormat,
+  face_size: u32,
+  levels: Vec<[Vec<u8>; 6]>,
+}
+impl MipmappedCubemap {
+  /// Creates a cubemap out of the raw bytes of each face of each level,
+  /// checking that every face is the size its level calls for.
+  pub fn new(
+    format: ImageDataFormat, face_size: u32, levels: Vec<[Vec<u8>; 6]>,
+  ) -> SarektResult<Self> {
+    if face_size == 0 {
+      return Err(SarektError::InvalidCubemap(
+        "faces must not be empty".to_owned(),
+      ));
+    }
+    if levels.is_empty() {
+      return Err(SarektError::IllegalMipmapCount);
+    }
+
+    for (level, faces) in levels.iter().enumerate() {
+      let level_dimensions = mip_level_dimensions((face_size, face_size), level as u32);
+      let expected_size = format.level_size(level_dimensions);
+      for (face, bytes) in faces.iter().enumerate() {
+        if bytes.len() != expected_size {
+          return Err(SarektError::InvalidCubemap(format!(
+            "face {} of level {} of {:?} cubemap with size {} should be {} bytes but was {}",
+            face,
+            level,
+            format,
+            face_size,
+            expected_size,
+            bytes.len()
+          )));
+        }
+      }
+    }
+
+    Ok(Self {
+      format,
+      face_size,
+      levels,
+    })
+  }
+}
+impl MipmappedCubemapImageData for MipmappedCubemap {
+  fn into_level_bytes(self) -> Vec<Vec<u8>> {
+    self
+      .levels
+      .into_iter()
+      .map(|faces| faces.concat())
+      .collect()
+  }
+
+  fn face_size(&self) -> u32 {
+    self.face_size
+  }
+
+  fn mip_levels(&self) -> u32 {
+    self.levels.len() as u32
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
+
+/// Direction (not normalized) through the texel at (s, t) in [-1, 1] of the
+/// given face, following the cube map face selection table in the Vulkan spec.
+pub(crate) fn face_direction(face_index: usize, s: f32, t: f32) -> (f32, f32, f32) {
+  match face_index {
+    0 => (1.0, -t, -s),
+    1 => (-1.0, -t, s),
+    2 => (s, 1.0, t),
+    3 => (s, -1.0, -t),
+    4 => (s, -t, 1.0),
+    _ => (-s, -t, -1.0),
+  }
+}
+
+/// The face a direction points into and its (s, t) coordinates in [-1, 1] on
+/// that face, the inverse of `face_direction`.
+pub(crate) fn direction_face(x: f32, y: f32, z: f32) -> (usize, f32, f32) {
+  let (abs_x, abs_y, abs_z) = (x.abs(), y.abs(), z.abs());
+  if abs_x >= abs_y && abs_x >= abs_z {
+    if x > 0.0 {
+      (0, -z / abs_x, -y / abs_x)
+    } else {
+      (1, z / abs_x, -y / abs_x)
+    }
+  } else if abs_y >= abs_z {
+    if y > 0.0 {
+      (2, x / abs_y, z / abs_y)
+    } else {
+      (3, x / abs_y, -z / abs_y)
+    }
+  } else if z > 0.0 {
+    (4, x / abs_z, -y / abs_z)
+  } else {
+    (5, -x / abs_z, -y / abs_z)
+  }
+}
@@ -0,0 +1,256 @@
+//! Loader for DirectDraw Surface (DDS) containers, including those with the
+//! DX10 extended header.
+//!
+//! Legacy headers carry no color space information, so images without a DX10
+//! header are loaded as UNORM.  Their DXT1-5 and ATI1/ATI2 four character codes
+//! are mapped to the matching BC formats.
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::{
+    mip_level_dimensions, read_slice, read_u32_le, validate_container_header, ImageDataFormat,
+    MipChain,
+  },
+};
+use std::path::Path;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DX10_FOURCC: &[u8; 4] = b"DX10";
+/// Magic plus the 124 byte DDS_HEADER.
+const HEADER_END: usize = 128;
+/// Size of the DDS_HEADER_DXT10 following the header when present.
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+/// Reads a DDS file from disk, see `load_dds`.
+pub fn open_dds<P: AsRef<Path>>(path: P) -> SarektResult<MipChain> {
+  let bytes = std::fs::read(path)?;
+  load_dds(&bytes)
+}
+
+/// Parses an in memory DDS file into a mip chain that can be passed to
+/// `load_mipmapped_image_with_staging_initialization`.
+///
+/// The file must contain a single 2D image (no cubemap faces, array layers or
+/// volume slices).
+pub fn load_dds(bytes: &[u8]) -> SarektResult<MipChain> {
+  if bytes.get(0..4) != Some(&DDS_MAGIC[..]) {
+    return Err(SarektError::InvalidImageContainer(
+      "missing DDS magic".to_owned(),
+    ));
+  }
+
+  let flags = read_u32_le(bytes, 8)?;
+  let height = read_u32_le(bytes, 12)?;
+  let width = read_u32_le(bytes, 16)?;
+  let mip_map_count = read_u32_le(bytes, 28)?;
+  let caps2 = read_u32_le(bytes, 112)?;
+
+  if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+    return Err(SarektError::InvalidImageContainer(
+      "only single 2D images are supported".to_owned(),
+    ));
+  }
+
+  let (format, data_offset) = if read_slice(bytes, 84, 4)? == &DX10_FOURCC[..] {
+    let dxgi_format = read_u32_le(bytes, HEADER_END)?;
+    let array_size = read_u32_le(bytes, HEADER_END + 12)?;
+    if array_size > 1 {
+      return Err(SarektError::InvalidImageContainer(
+        "only single 2D images are supported".to_owned(),
+      ));
+    }
+
+    (
+      dxgi_format_to_format(dxgi_format)?,
+      HEADER_END + DX10_HEADER_SIZE,
+    )
+  } else {
+    (legacy_pixel_format_to_format(bytes)?, HEADER_END)
+  };
+
+  let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+    mip_map_count.max(1)
+  } else {
+    1
+  };
+
+  validate_container_header((width, height), level_count)?;
+
+  // Levels are tightly packed one after the other, largest first.
+  let mut levels = Vec::with_capacity(level_count as usize);
+  let mut offset = data_offset;
+  for level in 0..level_count {
+    let level_dimensions = mip_level_dimensions((width, height), level);
+    let level_size = format
+      .checked_level_size(level_dimensions)
+      .ok_or_else(|| level_overflow(level))?;
+    levels.push(read_slice(bytes, offset, level_size)?.to_vec());
+    offset = offset
+      .checked_add(level_size)
+      .ok_or_else(|| level_overflow(level))?;
+  }
+
+  MipChain::new(format, (width, height), levels)
+}
+
+fn level_overflow(level: u32) -> SarektError {
+  SarektError::InvalidImageContainer(format!("size of mip level {} overflows", level))
+}
+
+fn dxgi_format_to_format(dxgi_format: u32) -> SarektResult<ImageDataFormat> {
+  match dxgi_format {
+    2 => Ok(ImageDataFormat::R32G32B32A32Sfloat),
+    10 => Ok(ImageDataFormat::R16G16B16A16Sfloat),
+    11 => Ok(ImageDataFormat::R16G16B16A16Unorm),
+    28 => Ok(ImageDataFormat::R8G8B8A8Unorm),
+    29 => Ok(ImageDataFormat::R8G8B8A8Srgb),
+    87 => Ok(ImageDataFormat::B8G8R8A8Unorm),
+    91 => Ok(ImageDataFormat::B8G8R8A8Srgb),
+    34 => Ok(ImageDataFormat::R16G16Sfloat),
+    35 => Ok(ImageDataFormat::R16G16Unorm),
+    49 => Ok(ImageDataFormat::R8G8Unorm),
+    51 => Ok(ImageDataFormat::R8G8Snorm),
+    56 => Ok(ImageDataFormat::R16Unorm),
+    61 => Ok(ImageDataFormat::R8Unorm),
+    63 => Ok(ImageDataFormat::R8Snorm),
+    71 => Ok(ImageDataFormat::Bc1RgbaUnorm),
+    72 => Ok(ImageDataFormat::Bc1RgbaSrgb),
+    74 => Ok(ImageDataFormat::Bc2Unorm),
+    75 => Ok(ImageDataFormat::Bc2Srgb),
+    77 => Ok(ImageDataFormat::Bc3Unorm),
+    78 => Ok(ImageDataFormat::Bc3Srgb),
+    80 => Ok(ImageDataFormat::Bc4Unorm),
+    81 => Ok(ImageDataFormat::Bc4Snorm),
+    83 => Ok(ImageDataFormat::Bc5Unorm),
+    84 => Ok(ImageDataFormat::Bc5Snorm),
+    95 => Ok(ImageDataFormat::Bc6hUfloat),
+    96 => Ok(ImageDataFormat::Bc6hSfloat),
+    98 => Ok(ImageDataFormat::Bc7Unorm),
+    99 => Ok(ImageDataFormat::Bc7Srgb),
+    _ => Err(SarektError::InvalidImageContainer(format!(
+      "unsupported DXGI format {}",
+      dxgi_format
+    ))),
+  }
+}
+
+/// Maps the DDS_PIXELFORMAT of a file without a DX10 header.
+fn legacy_pixel_format_to_format(bytes: &[u8]) -> SarektResult<ImageDataFormat> {
+  let pixel_format_flags = read_u32_le(bytes, 80)?;
+  if pixel_format_flags & DDPF_FOURCC != 0 {
+    let four_cc = read_slice(bytes, 84, 4)?;
+    return match four_cc {
+      b"DXT1" => Ok(ImageDataFormat::Bc1RgbaUnorm),
+      // DXT2 and DXT4 are premultiplied alpha, which is up to the shader.
+      b"DXT2" | b"DXT3" => Ok(ImageDataFormat::Bc2Unorm),
+      b"DXT4" | b"DXT5" => Ok(ImageDataFormat::Bc3Unorm),
+      b"ATI1" | b"BC4U" => Ok(ImageDataFormat::Bc4Unorm),
+      b"BC4S" => Ok(ImageDataFormat::Bc4Snorm),
+      b"ATI2" | b"BC5U" => Ok(ImageDataFormat::Bc5Unorm),
+      b"BC5S" => Ok(ImageDataFormat::Bc5Snorm),
+      _ => Err(SarektError::InvalidImageContainer(format!(
+        "unsupported four character code {:?}",
+        String::from_utf8_lossy(four_cc)
+      ))),
+    };
+  }
+  if pixel_format_flags & DDPF_RGB == 0 {
+    return Err(SarektError::InvalidImageContainer(
+      "only RGB(A) pixel formats are supported".to_owned(),
+    ));
+  }
+
+  let bit_count = read_u32_le(bytes, 88)?;
+  let red_mask = read_u32_le(bytes, 92)?;
+  match (bit_count, red_mask) {
+    (32, 0x0000_00FF) => Ok(ImageDataFormat::R8G8B8A8Unorm),
+    (32, 0x00FF_0000) => Ok(ImageDataFormat::B8G8R8A8Unorm),
+    (24, 0x0000_00FF) => Ok(ImageDataFormat::R8G8B8Unorm),
+    (24, 0x00FF_0000) => Ok(ImageDataFormat::B8G8R8Unorm),
+    _ => Err(SarektError::InvalidImageContainer(format!(
+      "unsupported {} bit pixel format with red mask {:#x}",
+      bit_count, red_mask
+    ))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::image_data::MipmappedImageData;
+
+  /// Builds a legacy DXT1 header for an image with the given dimensions.
+  fn dxt1_header(width: u32, height: u32, mip_map_count: u32) -> Vec<u8> {
+    let mut bytes = vec![0u8; HEADER_END];
+    bytes[0..4].copy_from_slice(DDS_MAGIC);
+    bytes[8..12].copy_from_slice(&DDSD_MIPMAPCOUNT.to_le_bytes());
+    bytes[12..16].copy_from_slice(&height.to_le_bytes());
+    bytes[16..20].copy_from_slice(&width.to_le_bytes());
+    bytes[28..32].copy_from_slice(&mip_map_count.to_le_bytes());
+    bytes[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+    bytes[84..88].copy_from_slice(b"DXT1");
+    bytes
+  }
+
+  fn assert_invalid_container(result: SarektResult<MipChain>) {
+    match result {
+      Err(SarektError::InvalidImageContainer(_)) => {}
+      Err(e) => panic!("expected InvalidImageContainer, got {:?}", e),
+      Ok(_) => panic!("expected InvalidImageContainer, got a mip chain"),
+    }
+  }
+
+  #[test]
+  fn loads_full_dxt1_mip_chain() {
+    // 8x8, 4x4, 2x2 and 1x1 levels, each one at least a single 8 byte block.
+    let mut bytes = dxt1_header(8, 8, 4);
+    let level_sizes = [32, 8, 8, 8];
+    for (level, &size) in level_sizes.iter().enumerate() {
+      bytes.extend(vec![level as u8; size]);
+    }
+
+    let chain = load_dds(&bytes).unwrap();
+    assert_eq!(chain.format().unwrap(), ImageDataFormat::Bc1RgbaUnorm);
+    assert_eq!(MipmappedImageData::dimensions(&chain), (8, 8));
+    assert_eq!(chain.mip_levels(), 4);
+    for (level, bytes) in chain.into_level_bytes().into_iter().enumerate() {
+      assert_eq!(bytes, vec![level as u8; level_sizes[level]]);
+    }
+  }
+
+  #[test]
+  fn rejects_truncated_header() {
+    let bytes = dxt1_header(8, 8, 1);
+    assert_invalid_container(load_dds(&bytes[..100]));
+  }
+
+  #[test]
+  fn rejects_truncated_level_data() {
+    let mut bytes = dxt1_header(8, 8, 1);
+    bytes.extend(vec![0u8; 31]);
+    assert_invalid_container(load_dds(&bytes));
+  }
+
+  #[test]
+  fn rejects_more_levels_than_dimensions_allow() {
+    let mut bytes = dxt1_header(1, 1, 0xFFFF_FFFF);
+    bytes.extend(vec![0u8; 8 * 40]);
+    assert_invalid_container(load_dds(&bytes));
+
+    let mut bytes = dxt1_header(1, 1, 2);
+    bytes.extend(vec![0u8; 16]);
+    assert_invalid_container(load_dds(&bytes));
+  }
+
+  #[test]
+  fn rejects_zero_and_huge_dimensions() {
+    assert_invalid_container(load_dds(&dxt1_header(0, 8, 1)));
+    assert_invalid_container(load_dds(&dxt1_header(8, 0, 1)));
+    assert_invalid_container(load_dds(&dxt1_header(u32::MAX, u32::MAX, 1)));
+  }
+}
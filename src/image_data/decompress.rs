@@ -0,0 +1,1000 @@
+//! CPU decoding of block compressed formats into rgba8, used as a fallback when
+//! the device can't sample them (for example BC on most mobile GPUs, or ETC2
+//! on most desktop ones).  Signed BC4 and BC5 decode to r8 and r8g8 SNORM
+//! instead so their values keep their sign.
+//!
+//! BC1-BC5, BC7, ETC2 and EAC are supported.  BC6H (HDR) and ASTC are out of
+//! scope and fail with `SarektError::NoCpuDecoder`, if you need those on
+//! devices without support ship a second copy of the texture in another
+//! format.
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::ImageDataFormat::{self, *},
+};
+use std::convert::TryInto;
+
+/// The decoded texels of one 4x4 block, row major.
+type Texels = [[u8; 4]; 16];
+
+/// Decodes one level of a block compressed image into tightly packed texels,
+/// in the format returned by
+/// [decompressed_format](../enum.ImageDataFormat.html#method.
+/// decompressed_format).
+pub fn decompress_level(
+  format: ImageDataFormat, dimensions: (u32, u32), bytes: &[u8],
+) -> SarektResult<Vec<u8>> {
+  let decode_block: fn(&[u8], &mut Texels) = match format {
+    Bc1RgbUnorm | Bc1RgbSrgb => decode_bc1_rgb,
+    Bc1RgbaUnorm | Bc1RgbaSrgb => decode_bc1_rgba,
+    Bc2Unorm | Bc2Srgb => decode_bc2,
+    Bc3Unorm | Bc3Srgb => decode_bc3,
+    Bc4Unorm => decode_bc4_unorm,
+    Bc4Snorm => decode_bc4_snorm,
+    Bc5Unorm => decode_bc5_unorm,
+    Bc5Snorm => decode_bc5_snorm,
+    Bc7Unorm | Bc7Srgb => decode_bc7,
+    Etc2R8G8B8Unorm | Etc2R8G8B8Srgb => decode_etc2_rgb,
+    Etc2R8G8B8A1Unorm | Etc2R8G8B8A1Srgb => decode_etc2_rgb_a1,
+    Etc2R8G8B8A8Unorm | Etc2R8G8B8A8Srgb => decode_etc2_rgba,
+    EacR11Unorm => decode_eac_r11,
+    EacR11G11Unorm => decode_eac_r11g11,
+    _ if format.is_compressed() => return Err(SarektError::NoCpuDecoder(format)),
+    _ => return Err(SarektError::UnsupportedImageFormat),
+  };
+
+  let expected_size = format.level_size(dimensions);
+  if bytes.len() != expected_size {
+    return Err(SarektError::InvalidMipChain(format!(
+      "{:?} level with dimensions {:?} should be {} bytes but was {}",
+      format,
+      dimensions,
+      expected_size,
+      bytes.len()
+    )));
+  }
+
+  let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+  let blocks_wide = (width + 3) / 4;
+  // Decoders always fill all four channels, only the ones the decompressed
+  // format has are kept.
+  let texel_size = format.decompressed_format().texel_block_size();
+  let mut decoded = vec![0u8; width * height * texel_size];
+  let mut texels = [[0u8; 4]; 16];
+  for (block_index, block) in bytes.chunks_exact(format.texel_block_size()).enumerate() {
+    decode_block(block, &mut texels);
+
+    // Blocks on the right and bottom edges may hang off the image.
+    let block_x = block_index % blocks_wide * 4;
+    let block_y = block_index / blocks_wide * 4;
+    for y in 0..4.min(height - block_y) {
+      for x in 0..4.min(width - block_x) {
+        let offset = ((block_y + y) * width + block_x + x) * texel_size;
+        decoded[offset..offset + texel_size].copy_from_slice(&texels[y * 4 + x][..texel_size]);
+      }
+    }
+  }
+
+  Ok(decoded)
+}
+
+fn clamp_u8(value: i32) -> u8 {
+  value.max(0).min(255) as u8
+}
+
+// ================================================================================
+//  BC1-BC5
+// ================================================================================
+fn expand_565(color: u16) -> [u32; 3] {
+  let r = u32::from(color >> 11) & 0x1F;
+  let g = u32::from(color >> 5) & 0x3F;
+  let b = u32::from(color) & 0x1F;
+  [
+    (r << 3) | (r >> 2),
+    (g << 2) | (g >> 4),
+    (b << 3) | (b >> 2),
+  ]
+}
+
+/// Decodes the 8 byte color block shared by BC1-BC3.  BC2 and BC3 always use
+/// four colors, BC1 switches to three and transparent black when the endpoints
+/// are in descending order.
+fn decode_bc1_color_block(
+  block: &[u8], texels: &mut Texels, allow_three_color: bool, transparent_black: bool,
+) {
+  let color0 = u16::from_le_bytes([block[0], block[1]]);
+  let color1 = u16::from_le_bytes([block[2], block[3]]);
+  let (endpoint0, endpoint1) = (expand_565(color0), expand_565(color1));
+  let three_color = allow_three_color && color0 <= color1;
+
+  let mut palette = [[0u8, 0, 0, 255]; 4];
+  for channel in 0..3 {
+    let (e0, e1) = (endpoint0[channel], endpoint1[channel]);
+    palette[0][channel] = e0 as u8;
+    palette[1][channel] = e1 as u8;
+    if three_color {
+      palette[2][channel] = ((e0 + e1) / 2) as u8;
+    } else {
+      palette[2][channel] = ((2 * e0 + e1) / 3) as u8;
+      palette[3][channel] = ((e0 + 2 * e1) / 3) as u8;
+    }
+  }
+  if three_color && transparent_black {
+    palette[3][3] = 0;
+  }
+
+  let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+  for (i, texel) in texels.iter_mut().enumerate() {
+    *texel = palette[((indices >> (2 * i)) & 3) as usize];
+  }
+}
+
+/// Decodes an 8 byte BC4 style block (also the alpha of BC3 and the channels
+/// of BC5) into one byte per texel, either unsigned normalized or two's
+/// complement signed normalized (-1.0 being -127) to match the UNORM and SNORM
+/// formats.
+fn decode_bc4_channel(block: &[u8], signed: bool) -> [u8; 16] {
+  // Work in [0, 1] so snorm and unorm share the interpolation.
+  let (endpoint0, endpoint1, descending) = if signed {
+    let (e0, e1) = ((block[0] as i8).max(-127), (block[1] as i8).max(-127));
+    (
+      (f32::from(e0) / 127.0 + 1.0) / 2.0,
+      (f32::from(e1) / 127.0 + 1.0) / 2.0,
+      e0 > e1,
+    )
+  } else {
+    (
+      f32::from(block[0]) / 255.0,
+      f32::from(block[1]) / 255.0,
+      block[0] > block[1],
+    )
+  };
+
+  let mut palette = [0f32; 8];
+  palette[0] = endpoint0;
+  palette[1] = endpoint1;
+  if descending {
+    for i in 1..7 {
+      palette[i + 1] = ((7 - i) as f32 * endpoint0 + i as f32 * endpoint1) / 7.0;
+    }
+  } else {
+    for i in 1..5 {
+      palette[i + 1] = ((5 - i) as f32 * endpoint0 + i as f32 * endpoint1) / 5.0;
+    }
+    palette[6] = 0.0;
+    palette[7] = 1.0;
+  }
+
+  let mut index_bytes = [0u8; 8];
+  index_bytes[..6].copy_from_slice(&block[2..8]);
+  let indices = u64::from_le_bytes(index_bytes);
+
+  let mut values = [0u8; 16];
+  for (i, value) in values.iter_mut().enumerate() {
+    let normalized = palette[((indices >> (3 * i)) & 7) as usize];
+    *value = if signed {
+      ((normalized * 2.0 - 1.0) * 127.0).round() as i8 as u8
+    } else {
+      (normalized * 255.0).round() as u8
+    };
+  }
+  values
+}
+
+fn decode_bc1_rgb(block: &[u8], texels: &mut Texels) {
+  decode_bc1_color_block(block, texels, true, false);
+}
+
+fn decode_bc1_rgba(block: &[u8], texels: &mut Texels) {
+  decode_bc1_color_block(block, texels, true, true);
+}
+
+fn decode_bc2(block: &[u8], texels: &mut Texels) {
+  decode_bc1_color_block(&block[8..], texels, false, false);
+  let alphas = u64::from_le_bytes(block[..8].try_into().unwrap());
+  for (i, texel) in texels.iter_mut().enumerate() {
+    texel[3] = ((alphas >> (4 * i)) & 0xF) as u8 * 17;
+  }
+}
+
+fn decode_bc3(block: &[u8], texels: &mut Texels) {
+  decode_bc1_color_block(&block[8..], texels, false, false);
+  let alphas = decode_bc4_channel(&block[..8], false);
+  for (texel, &alpha) in texels.iter_mut().zip(alphas.iter()) {
+    texel[3] = alpha;
+  }
+}
+
+fn decode_bc4(block: &[u8], texels: &mut Texels, signed: bool) {
+  let reds = decode_bc4_channel(block, signed);
+  for (texel, &red) in texels.iter_mut().zip(reds.iter()) {
+    *texel = [red, 0, 0, 255];
+  }
+}
+
+fn decode_bc4_unorm(block: &[u8], texels: &mut Texels) {
+  decode_bc4(block, texels, false);
+}
+
+fn decode_bc4_snorm(block: &[u8], texels: &mut Texels) {
+  decode_bc4(block, texels, true);
+}
+
+fn decode_bc5(block: &[u8], texels: &mut Texels, signed: bool) {
+  let reds = decode_bc4_channel(&block[..8], signed);
+  let greens = decode_bc4_channel(&block[8..], signed);
+  for (i, texel) in texels.iter_mut().enumerate() {
+    *texel = [reds[i], greens[i], 0, 255];
+  }
+}
+
+fn decode_bc5_unorm(block: &[u8], texels: &mut Texels) {
+  decode_bc5(block, texels, false);
+}
+
+fn decode_bc5_snorm(block: &[u8], texels: &mut Texels) {
+  decode_bc5(block, texels, true);
+}
+
+// ================================================================================
+//  BC7
+// ================================================================================
+/// Layout of one of the 8 BC7 modes.
+struct Bc7Mode {
+  subsets: usize,
+  partition_bits: u32,
+  rotation_bits: u32,
+  index_selection_bits: u32,
+  color_bits: u32,
+  alpha_bits: u32,
+  /// One p-bit per endpoint.
+  endpoint_p_bits: bool,
+  /// One p-bit per subset, shared by both its endpoints.
+  shared_p_bits: bool,
+  index_bits: u32,
+  secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+  Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+  Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+  Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+  Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+  Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+  Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+  Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+  Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Subset of each texel for the 64 two subset BC7 partitions, bit n is texel n.
+const BC7_PARTITIONS_2: [u16; 64] = [
+  0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+  0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+  0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+  0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+  0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+  0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subset of each texel for the 64 three subset BC7 partitions.
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+  [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+  [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+  [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+  [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+  [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+  [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+  [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+  [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+  [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+  [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+  [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+  [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+  [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+  [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+  [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+  [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+  [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+  [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+  [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+  [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+  [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+  [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+  [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+  [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+  [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+  [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+  [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+  [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+  [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+  [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+  [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+  [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+  [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+  [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+  [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+  [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+  [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+  [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+  [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+  [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+  [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+  [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+  [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+  [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+  [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+  [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+  [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+  [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+  [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+  [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+  [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+  [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of the two subset partitions.
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [usize; 64] = [
+  15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+  15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+  15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+  6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the three subset
+/// partitions.
+#[rustfmt::skip]
+const BC7_ANCHORS_3: [[usize; 64]; 2] = [
+  [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+  ],
+  [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+  ],
+];
+
+/// Reads the fields of a BC7 block from least to most significant bit.
+struct BitReader {
+  bits: u128,
+  position: u32,
+}
+impl BitReader {
+  fn read(&mut self, count: u32) -> u32 {
+    let value = (self.bits >> self.position) & ((1u128 << count) - 1);
+    self.position += count;
+    value as u32
+  }
+}
+
+/// Expands an endpoint channel of the given precision to 8 bits by replicating
+/// its high bits.
+fn bc7_expand(value: u32, bits: u32) -> u32 {
+  (value << (8 - bits)) | (value >> (2 * bits - 8))
+}
+
+fn bc7_interpolate(endpoint0: u32, endpoint1: u32, index: u32, index_bits: u32) -> u8 {
+  let weight = match index_bits {
+    2 => BC7_WEIGHTS_2[index as usize],
+    3 => BC7_WEIGHTS_3[index as usize],
+    _ => BC7_WEIGHTS_4[index as usize],
+  };
+  (((64 - weight) * endpoint0 + weight * endpoint1 + 32) >> 6) as u8
+}
+
+fn decode_bc7(block: &[u8], texels: &mut Texels) {
+  let bits = u128::from_le_bytes(block.try_into().unwrap());
+  let mode_index = bits.trailing_zeros();
+  if mode_index >= 8 {
+    // Reserved mode, decodes to transparent black.
+    *texels = [[0; 4]; 16];
+    return;
+  }
+  let mode = &BC7_MODES[mode_index as usize];
+  let mut reader = BitReader {
+    bits,
+    position: mode_index + 1,
+  };
+
+  let partition = reader.read(mode.partition_bits) as usize;
+  let rotation = reader.read(mode.rotation_bits);
+  let index_selection = reader.read(mode.index_selection_bits);
+
+  // Channels are stored planar, all the reds first, then greens, etc.
+  let endpoint_count = mode.subsets * 2;
+  let mut endpoints = [[0u32; 4]; 6];
+  for channel in 0..3 {
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+      endpoint[channel] = reader.read(mode.color_bits);
+    }
+  }
+  for endpoint in endpoints.iter_mut().take(endpoint_count) {
+    endpoint[3] = reader.read(mode.alpha_bits);
+  }
+
+  let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+  if mode.endpoint_p_bits || mode.shared_p_bits {
+    let endpoints_per_p_bit = if mode.shared_p_bits { 2 } else { 1 };
+    for endpoint_group in endpoints[..endpoint_count].chunks_mut(endpoints_per_p_bit) {
+      let p_bit = reader.read(1);
+      for endpoint in endpoint_group.iter_mut() {
+        for channel in endpoint.iter_mut() {
+          *channel = (*channel << 1) | p_bit;
+        }
+      }
+    }
+    color_bits += 1;
+    if alpha_bits > 0 {
+      alpha_bits += 1;
+    }
+  }
+
+  for endpoint in endpoints.iter_mut().take(endpoint_count) {
+    for channel in endpoint.iter_mut().take(3) {
+      *channel = bc7_expand(*channel, color_bits);
+    }
+    endpoint[3] = if alpha_bits > 0 {
+      bc7_expand(endpoint[3], alpha_bits)
+    } else {
+      255
+    };
+  }
+
+  let subset_of = |texel: usize| match mode.subsets {
+    1 => 0,
+    2 => ((BC7_PARTITIONS_2[partition] >> texel) & 1) as usize,
+    _ => BC7_PARTITIONS_3[partition][texel] as usize,
+  };
+  // Anchor texels drop the high bit of their index, it is always 0.
+  let is_anchor = |texel: usize| {
+    texel == 0
+      || match mode.subsets {
+        1 => false,
+        2 => texel == BC7_ANCHORS_2[partition],
+        _ => texel == BC7_ANCHORS_3[0][partition] || texel == BC7_ANCHORS_3[1][partition],
+      }
+  };
+
+  let mut primary_indices = [0u32; 16];
+  for (texel, index) in primary_indices.iter_mut().enumerate() {
+    *index = reader.read(mode.index_bits - is_anchor(texel) as u32);
+  }
+  let mut secondary_indices = [0u32; 16];
+  if mode.secondary_index_bits > 0 {
+    for (texel, index) in secondary_indices.iter_mut().enumerate() {
+      *index = reader.read(mode.secondary_index_bits - (texel == 0) as u32);
+    }
+  }
+
+  for (i, texel) in texels.iter_mut().enumerate() {
+    let subset = subset_of(i);
+    let (endpoint0, endpoint1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+    // With two index sets the index selection bit picks which one is color.
+    let primary = (primary_indices[i], mode.index_bits);
+    let secondary = (secondary_indices[i], mode.secondary_index_bits);
+    let ((color_index, color_index_bits), (alpha_index, alpha_index_bits)) =
+      if mode.secondary_index_bits == 0 {
+        (primary, primary)
+      } else if index_selection == 0 {
+        (primary, secondary)
+      } else {
+        (secondary, primary)
+      };
+
+    for channel in 0..3 {
+      texel[channel] = bc7_interpolate(
+        endpoint0[channel],
+        endpoint1[channel],
+        color_index,
+        color_index_bits,
+      );
+    }
+    texel[3] = bc7_interpolate(endpoint0[3], endpoint1[3], alpha_index, alpha_index_bits);
+
+    match rotation {
+      1 => texel.swap(0, 3),
+      2 => texel.swap(1, 3),
+      3 => texel.swap(2, 3),
+      _ => (),
+    }
+  }
+}
+
+// ================================================================================
+//  ETC2 and EAC
+// ================================================================================
+/// Intensity modifiers of ETC1/ETC2 individual and differential blocks, by
+/// codeword table and pixel index.
+const ETC_MODIFIERS: [[i32; 4]; 8] = [
+  [2, 8, -2, -8],
+  [5, 17, -5, -17],
+  [9, 29, -9, -29],
+  [13, 42, -13, -42],
+  [18, 60, -18, -60],
+  [24, 80, -24, -80],
+  [33, 106, -33, -106],
+  [47, 183, -47, -183],
+];
+
+/// Distances used by the ETC2 T and H modes.
+const ETC2_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Modifiers of EAC blocks, by table and pixel index.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+  [-3, -6, -9, -15, 2, 5, 8, 14],
+  [-3, -7, -10, -13, 2, 6, 9, 12],
+  [-2, -5, -8, -13, 1, 4, 7, 12],
+  [-2, -4, -6, -13, 1, 3, 5, 12],
+  [-3, -6, -8, -12, 2, 5, 7, 11],
+  [-3, -7, -9, -11, 2, 6, 8, 10],
+  [-4, -7, -8, -11, 3, 6, 7, 10],
+  [-3, -5, -8, -11, 2, 4, 7, 10],
+  [-2, -6, -8, -10, 1, 5, 7, 9],
+  [-2, -5, -8, -10, 1, 4, 7, 9],
+  [-2, -4, -8, -10, 1, 3, 7, 9],
+  [-2, -5, -7, -10, 1, 4, 6, 9],
+  [-3, -4, -7, -10, 2, 3, 6, 9],
+  [-1, -2, -3, -10, 0, 1, 2, 9],
+  [-4, -6, -8, -9, 3, 5, 7, 8],
+  [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn extend_4(value: u64) -> i32 {
+  let value = (value & 0xF) as i32;
+  (value << 4) | value
+}
+
+fn extend_5(value: i32) -> i32 {
+  (value << 3) | (value >> 2)
+}
+
+fn extend_6(value: u64) -> i32 {
+  let value = (value & 0x3F) as i32;
+  (value << 2) | (value >> 4)
+}
+
+fn extend_7(value: u64) -> i32 {
+  let value = (value & 0x7F) as i32;
+  (value << 1) | (value >> 6)
+}
+
+fn offset_color(color: [i32; 3], offset: i32) -> [u8; 4] {
+  [
+    clamp_u8(color[0] + offset),
+    clamp_u8(color[1] + offset),
+    clamp_u8(color[2] + offset),
+    255,
+  ]
+}
+
+/// ETC pixel indices are stored column major, with the most significant bits
+/// of all 16 pixels followed by the least significant bits.
+fn etc_pixel_index(bits: u64, x: usize, y: usize) -> usize {
+  let i = x * 4 + y;
+  let msb = (bits >> (16 + i)) & 1;
+  let lsb = (bits >> i) & 1;
+  ((msb << 1) | lsb) as usize
+}
+
+/// Decodes an 8 byte ETC2 color block.  With punchthrough alpha (RGB A1) the
+/// differential bit instead says whether the block is opaque, and individual
+/// mode is unavailable.
+fn decode_etc2_color_block(block: &[u8], texels: &mut Texels, punchthrough: bool) {
+  let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+  let (differential, opaque) = if punchthrough {
+    (true, (bits >> 33) & 1 == 1)
+  } else {
+    ((bits >> 33) & 1 == 1, true)
+  };
+
+  if !differential {
+    let base_colors = [
+      [
+        extend_4(bits >> 60),
+        extend_4(bits >> 52),
+        extend_4(bits >> 44),
+      ],
+      [
+        extend_4(bits >> 56),
+        extend_4(bits >> 48),
+        extend_4(bits >> 40),
+      ],
+    ];
+    decode_etc_subblocks(bits, texels, base_colors, opaque);
+    return;
+  }
+
+  // Sign extend the 3 bit deltas.
+  let delta = |shift: u32| (((bits >> shift) & 7) as i32) << 29 >> 29;
+  let red = ((bits >> 59) & 0x1F) as i32;
+  let green = ((bits >> 51) & 0x1F) as i32;
+  let blue = ((bits >> 43) & 0x1F) as i32;
+  let (red2, green2, blue2) = (red + delta(56), green + delta(48), blue + delta(40));
+
+  // ETC2 hides its extra modes in deltas that would overflow.
+  let overflows = |value: i32| value < 0 || value > 31;
+  if overflows(red2) {
+    decode_etc2_t_mode(bits, texels, opaque);
+  } else if overflows(green2) {
+    decode_etc2_h_mode(bits, texels, opaque);
+  } else if overflows(blue2) {
+    decode_etc2_planar_mode(bits, texels);
+  } else {
+    let base_colors = [
+      [extend_5(red), extend_5(green), extend_5(blue)],
+      [extend_5(red2), extend_5(green2), extend_5(blue2)],
+    ];
+    decode_etc_subblocks(bits, texels, base_colors, opaque);
+  }
+}
+
+/// Individual and differential modes, two 2x4 (or 4x2 if flipped) subblocks
+/// each with a base color and modifier table.
+fn decode_etc_subblocks(bits: u64, texels: &mut Texels, base_colors: [[i32; 3]; 2], opaque: bool) {
+  let flip = (bits >> 32) & 1 == 1;
+  let tables = [((bits >> 37) & 7) as usize, ((bits >> 34) & 7) as usize];
+  for y in 0..4 {
+    for x in 0..4 {
+      let subblock = if flip { y >= 2 } else { x >= 2 } as usize;
+      let index = etc_pixel_index(bits, x, y);
+      texels[y * 4 + x] = match index {
+        2 if !opaque => [0; 4],
+        0 if !opaque => offset_color(base_colors[subblock], 0),
+        _ => offset_color(
+          base_colors[subblock],
+          ETC_MODIFIERS[tables[subblock]][index],
+        ),
+      };
+    }
+  }
+}
+
+/// Writes each texel's paint color, index 2 being transparent for non opaque
+/// punchthrough blocks.
+fn write_etc2_paint_colors(
+  bits: u64, texels: &mut Texels, paint_colors: [[u8; 4]; 4], opaque: bool,
+) {
+  for y in 0..4 {
+    for x in 0..4 {
+      let index = etc_pixel_index(bits, x, y);
+      texels[y * 4 + x] = if !opaque && index == 2 {
+        [0; 4]
+      } else {
+        paint_colors[index]
+      };
+    }
+  }
+}
+
+fn decode_etc2_t_mode(bits: u64, texels: &mut Texels, opaque: bool) {
+  let color0 = [
+    extend_4(((bits >> 57) & 0xC) | ((bits >> 56) & 0x3)),
+    extend_4(bits >> 52),
+    extend_4(bits >> 48),
+  ];
+  let color1 = [
+    extend_4(bits >> 44),
+    extend_4(bits >> 40),
+    extend_4(bits >> 36),
+  ];
+  let distance = ETC2_DISTANCES[(((bits >> 33) & 0x6) | ((bits >> 32) & 1)) as usize];
+
+  let paint_colors = [
+    offset_color(color0, 0),
+    offset_color(color1, distance),
+    offset_color(color1, 0),
+    offset_color(color1, -distance),
+  ];
+  write_etc2_paint_colors(bits, texels, paint_colors, opaque);
+}
+
+fn decode_etc2_h_mode(bits: u64, texels: &mut Texels, opaque: bool) {
+  let red0 = (bits >> 59) & 0xF;
+  let green0 = ((bits >> 55) & 0xE) | ((bits >> 52) & 1);
+  let blue0 = ((bits >> 48) & 0x8) | ((bits >> 47) & 0x7);
+  let (red1, green1, blue1) = ((bits >> 43) & 0xF, (bits >> 39) & 0xF, (bits >> 35) & 0xF);
+
+  // The low bit of the distance index is whether the first color is larger.
+  let ordering = (red0 << 8 | green0 << 4 | blue0) >= (red1 << 8 | green1 << 4 | blue1);
+  let distance_index = ((bits >> 32) & 0x4) | ((bits >> 31) & 0x2) | ordering as u64;
+  let distance = ETC2_DISTANCES[distance_index as usize];
+
+  let color0 = [extend_4(red0), extend_4(green0), extend_4(blue0)];
+  let color1 = [extend_4(red1), extend_4(green1), extend_4(blue1)];
+  let paint_colors = [
+    offset_color(color0, distance),
+    offset_color(color0, -distance),
+    offset_color(color1, distance),
+    offset_color(color1, -distance),
+  ];
+  write_etc2_paint_colors(bits, texels, paint_colors, opaque);
+}
+
+/// Planar mode blends three colors at the origin, horizontal and vertical
+/// corners.  Always opaque.
+fn decode_etc2_planar_mode(bits: u64, texels: &mut Texels) {
+  let origin = [
+    extend_6(bits >> 57),
+    extend_7(((bits >> 50) & 0x40) | ((bits >> 49) & 0x3F)),
+    extend_6(((bits >> 43) & 0x20) | ((bits >> 40) & 0x18) | ((bits >> 39) & 0x7)),
+  ];
+  let horizontal = [
+    extend_6(((bits >> 33) & 0x3E) | ((bits >> 32) & 1)),
+    extend_7(bits >> 25),
+    extend_6(bits >> 19),
+  ];
+  let vertical = [extend_6(bits >> 13), extend_7(bits >> 6), extend_6(bits)];
+
+  for y in 0..4 {
+    for x in 0..4 {
+      let texel = &mut texels[y * 4 + x];
+      for channel in 0..3 {
+        let value = (x as i32 * (horizontal[channel] - origin[channel])
+          + y as i32 * (vertical[channel] - origin[channel])
+          + 4 * origin[channel]
+          + 2)
+          >> 2;
+        texel[channel] = clamp_u8(value);
+      }
+      texel[3] = 255;
+    }
+  }
+}
+
+/// Returns the base codeword, multiplier and per texel (row major) modifiers of
+/// an 8 byte EAC block.
+fn decode_eac_block(block: &[u8]) -> (i32, i32, [i32; 16]) {
+  let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+  let base = (bits >> 56) as i32;
+  let multiplier = ((bits >> 52) & 0xF) as i32;
+  let table = &EAC_MODIFIERS[((bits >> 48) & 0xF) as usize];
+
+  let mut modifiers = [0i32; 16];
+  for y in 0..4 {
+    for x in 0..4 {
+      // Column major like ETC, 3 bits each from the most significant end.
+      let i = x * 4 + y;
+      modifiers[y * 4 + x] = table[((bits >> (45 - 3 * i)) & 7) as usize];
+    }
+  }
+  (base, multiplier, modifiers)
+}
+
+/// Decodes an 11 bit EAC channel, scaled down to 8 bits.
+fn decode_eac_r11_channel(block: &[u8]) -> [u8; 16] {
+  let (base, multiplier, modifiers) = decode_eac_block(block);
+  let mut values = [0u8; 16];
+  for (value, &modifier) in values.iter_mut().zip(modifiers.iter()) {
+    let scaled_modifier = if multiplier == 0 {
+      modifier
+    } else {
+      modifier * multiplier * 8
+    };
+    let value_11 = (base * 8 + 4 + scaled_modifier).max(0).min(2047);
+    *value = ((value_11 * 255 + 1023) / 2047) as u8;
+  }
+  values
+}
+
+fn decode_etc2_rgb(block: &[u8], texels: &mut Texels) {
+  decode_etc2_color_block(block, texels, false);
+}
+
+fn decode_etc2_rgb_a1(block: &[u8], texels: &mut Texels) {
+  decode_etc2_color_block(block, texels, true);
+}
+
+fn decode_etc2_rgba(block: &[u8], texels: &mut Texels) {
+  decode_etc2_color_block(&block[8..], texels, false);
+  let (base, multiplier, modifiers) = decode_eac_block(&block[..8]);
+  for (texel, &modifier) in texels.iter_mut().zip(modifiers.iter()) {
+    texel[3] = clamp_u8(base + modifier * multiplier);
+  }
+}
+
+fn decode_eac_r11(block: &[u8], texels: &mut Texels) {
+  let reds = decode_eac_r11_channel(block);
+  for (texel, &red) in texels.iter_mut().zip(reds.iter()) {
+    *texel = [red, 0, 0, 255];
+  }
+}
+
+fn decode_eac_r11g11(block: &[u8], texels: &mut Texels) {
+  let reds = decode_eac_r11_channel(&block[..8]);
+  let greens = decode_eac_r11_channel(&block[8..]);
+  for (i, texel) in texels.iter_mut().enumerate() {
+    *texel = [reds[i], greens[i], 0, 255];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Packs the 2 bit BC1 (or 3 bit BC4) indices of each texel, texel 0 first.
+  fn pack_indices(indices: &[u64], bits: u32) -> u64 {
+    indices
+      .iter()
+      .enumerate()
+      .fold(0, |packed, (i, &index)| packed | index << (bits * i as u32))
+  }
+
+  fn bc1_block(color0: u16, color1: u16, indices: &[u64]) -> Vec<u8> {
+    let mut block = Vec::new();
+    block.extend_from_slice(&color0.to_le_bytes());
+    block.extend_from_slice(&color1.to_le_bytes());
+    block.extend_from_slice(&(pack_indices(indices, 2) as u32).to_le_bytes());
+    block
+  }
+
+  fn bc4_block(endpoint0: u8, endpoint1: u8, indices: &[u64]) -> Vec<u8> {
+    let mut block = vec![endpoint0, endpoint1];
+    block.extend_from_slice(&pack_indices(indices, 3).to_le_bytes()[..6]);
+    block
+  }
+
+  #[test]
+  fn bc1_four_color_block() {
+    // Pure red and pure blue endpoints, texels 0-3 use each palette entry.
+    let block = bc1_block(0xF800, 0x001F, &[0, 1, 2, 3]);
+    let rgba = decompress_level(Bc1RgbaUnorm, (4, 4), &block).unwrap();
+
+    assert_eq!(rgba.len(), 64);
+    assert_eq!(&rgba[0..4], &[255, 0, 0, 255]);
+    assert_eq!(&rgba[4..8], &[0, 0, 255, 255]);
+    assert_eq!(&rgba[8..12], &[170, 0, 85, 255]);
+    assert_eq!(&rgba[12..16], &[85, 0, 170, 255]);
+    // Every other texel is index 0.
+    assert_eq!(&rgba[60..64], &[255, 0, 0, 255]);
+  }
+
+  #[test]
+  fn bc1_three_color_block_has_transparent_black() {
+    let block = bc1_block(0x001F, 0xF800, &[0, 1, 2, 3]);
+
+    let rgba = decompress_level(Bc1RgbaUnorm, (4, 4), &block).unwrap();
+    assert_eq!(&rgba[8..12], &[127, 0, 127, 255]);
+    assert_eq!(&rgba[12..16], &[0, 0, 0, 0]);
+
+    // Without alpha the fourth color is opaque black.
+    let rgba = decompress_level(Bc1RgbUnorm, (4, 4), &block).unwrap();
+    assert_eq!(&rgba[12..16], &[0, 0, 0, 255]);
+  }
+
+  #[test]
+  fn bc4_unorm_block() {
+    let block = bc4_block(255, 0, &[0, 1, 2, 7]);
+    let rgba = decompress_level(Bc4Unorm, (4, 4), &block).unwrap();
+
+    assert_eq!(rgba.len(), 64);
+    assert_eq!(&rgba[0..4], &[255, 0, 0, 255]);
+    assert_eq!(&rgba[4..8], &[0, 0, 0, 255]);
+    // 6/7 and 1/7 of the way between the endpoints.
+    assert_eq!(&rgba[8..12], &[219, 0, 0, 255]);
+    assert_eq!(&rgba[12..16], &[36, 0, 0, 255]);
+  }
+
+  #[test]
+  fn bc4_snorm_block_keeps_sign() {
+    // 127 and -127, descending so there are 8 interpolated values.
+    let block = bc4_block(127, -127i8 as u8, &[0, 1, 2, 7]);
+    let red = decompress_level(Bc4Snorm, (4, 4), &block).unwrap();
+
+    assert_eq!(Bc4Snorm.decompressed_format(), R8Snorm);
+    assert_eq!(red.len(), 16);
+    let red: Vec<i8> = red.into_iter().map(|r| r as i8).collect();
+    assert_eq!(&red[0..4], &[127, -127, 91, -91]);
+
+    // Ascending endpoints use 6 values plus -1 and 1, -128 clamps to -127.
+    let block = bc4_block(-128i8 as u8, 0, &[0, 1, 6, 7]);
+    let red = decompress_level(Bc4Snorm, (4, 4), &block).unwrap();
+    let red: Vec<i8> = red.into_iter().map(|r| r as i8).collect();
+    assert_eq!(&red[0..4], &[-127, 0, -127, 127]);
+  }
+
+  #[test]
+  fn bc5_snorm_block_keeps_sign() {
+    let mut block = bc4_block(-127i8 as u8, 127, &[0, 1]);
+    block.extend(bc4_block(127, -127i8 as u8, &[0, 1]));
+    let red_green = decompress_level(Bc5Snorm, (4, 4), &block).unwrap();
+
+    assert_eq!(Bc5Snorm.decompressed_format(), R8G8Snorm);
+    assert_eq!(red_green.len(), 32);
+    let red_green: Vec<i8> = red_green.into_iter().map(|c| c as i8).collect();
+    assert_eq!(&red_green[0..4], &[-127, 127, 127, -127]);
+  }
+
+  #[test]
+  fn bc7_mode_6_block() {
+    // Mode 6 is a single subset with 7 bit RGBA endpoints, one p-bit each and
+    // 4 bit indices.  Endpoint 0 is black, endpoint 1 white, and texel n uses
+    // index n.
+    let mut bits = 1u128 << 6;
+    let mut position = 7;
+    let mut write = |value: u128, count: u32| {
+      bits |= value << position;
+      position += count;
+    };
+    for _ in 0..4 {
+      write(0, 7);
+      write(0x7F, 7);
+    }
+    write(0, 1);
+    write(1, 1);
+    write(0, 3);
+    for index in 1..16 {
+      write(index, 4);
+    }
+
+    let rgba = decompress_level(Bc7Unorm, (4, 4), &bits.to_le_bytes()).unwrap();
+    assert_eq!(&rgba[0..4], &[0, 0, 0, 0]);
+    // Weight 34 of 64.
+    assert_eq!(&rgba[32..36], &[135, 135, 135, 135]);
+    assert_eq!(&rgba[60..64], &[255, 255, 255, 255]);
+  }
+
+  #[test]
+  fn bc7_reserved_mode_is_transparent_black() {
+    let rgba = decompress_level(Bc7Unorm, (4, 4), &[0u8; 16]).unwrap();
+    assert!(rgba.iter().all(|&c| c == 0));
+  }
+
+  #[test]
+  fn etc2_individual_mode_block() {
+    // Black base colors with table 0, every texel uses the +2 modifier.
+    let rgba = decompress_level(Etc2R8G8B8Unorm, (4, 4), &[0u8; 8]).unwrap();
+    for texel in rgba.chunks_exact(4) {
+      assert_eq!(texel, &[2, 2, 2, 255]);
+    }
+  }
+
+  #[test]
+  fn partial_edge_blocks_are_cropped() {
+    let block = bc1_block(0xF800, 0x001F, &[0, 1, 2, 3]);
+    let rgba = decompress_level(Bc1RgbaUnorm, (2, 1), &block).unwrap();
+    assert_eq!(rgba, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+  }
+
+  #[test]
+  fn rejects_wrong_level_size() {
+    match decompress_level(Bc1RgbaUnorm, (8, 8), &[0u8; 8]) {
+      Err(SarektError::InvalidMipChain(_)) => {}
+      r => panic!("expected InvalidMipChain, got {:?}", r),
+    }
+  }
+
+  #[test]
+  fn bc6h_and_astc_have_no_cpu_decoder() {
+    for &format in [Bc6hUfloat, Bc6hSfloat, Astc4x4Unorm, Astc12x12Srgb].iter() {
+      assert!(!format.has_cpu_decoder());
+      assert_eq!(format.decompressed_format(), format);
+      match decompress_level(format, (4, 4), &[0u8; 16]) {
+        Err(SarektError::NoCpuDecoder(f)) => assert_eq!(f, format),
+        r => panic!("expected NoCpuDecoder, got {:?}", r),
+      }
+    }
+  }
+}
@@ -0,0 +1,656 @@
+//! Image based lighting for the PBR shaders, generated on the CPU from an
+//! environment cubemap when it is loaded.
+//!
+//! This is the split sum approximation from Karis, "Real Shading in Unreal
+//! Engine 4":
+//! * The irradiance map is the cosine weighted average of the environment
+//!   around each normal, for the diffuse term.
+//! * The prefiltered map is the environment convolved with the GGX lobe, each
+//!   mip level for a rougher surface, for the specular term.
+//! * The BRDF lookup table is the scale and bias to the surface's reflectance
+//!   at normal incidence, by view angle and roughness.  It doesn't depend on
+//!   the environment.
+//!
+//! The environment is decoded to linear floats, so HDR environments stay HDR
+//! and 8 bit sRGB ones are linearized first.  The results are
+//! R16G16B16A16Sfloat.  With the default sizes generation takes a fraction of a
+//! second with optimizations, but several seconds in a debug build.
+//!
+//! ```ignore
+//! let panorama = HdrImage::open_radiance("textures/studio.hdr")?;
+//! let environment = Cubemap::from_equirectangular(panorama, 512)?;
+//! renderer.set_image_based_lighting(Some(EnvironmentMaps::new(environment)?))?;
+//! ```
+use crate::{
+  error::{SarektError, SarektResult},
+  half::{f16_bits_to_f32, f32_to_f16_bits},
+  image_data::{
+    cubemap::{direction_face, face_direction, Cubemap, CubemapImageData, MipmappedCubemap},
+    hdr::HdrImage,
+    ImageDataFormat,
+  },
+};
+use std::f32::consts::PI;
+use ultraviolet as uv;
+
+/// Face size of the irradiance map `EnvironmentMaps::new` makes, irradiance
+/// has no high frequencies so it can be tiny.
+pub const DEFAULT_IRRADIANCE_SIZE: u32 = 32;
+/// Face size of the top level of the prefiltered map `EnvironmentMaps::new`
+/// makes.
+pub const DEFAULT_PREFILTERED_SIZE: u32 = 128;
+/// Mip levels of the prefiltered map `EnvironmentMaps::new` makes, from
+/// roughness 0 in the top level to 1 in the last.
+pub const DEFAULT_PREFILTERED_LEVELS: u32 = 6;
+/// Width and height of the BRDF lookup table the renderer generates.
+pub const DEFAULT_BRDF_LUT_SIZE: u32 = 128;
+
+const PREFILTER_SAMPLE_COUNT: u32 = 64;
+const BRDF_SAMPLE_COUNT: u32 = 256;
+// The irradiance integral visits every texel of a downsampled copy of the
+// environment no larger than this.
+const IRRADIANCE_SOURCE_SIZE: u32 = 16;
+
+/// The irradiance and prefiltered cubemaps of an environment, see the module
+/// documentation.
+pub struct EnvironmentMaps {
+  irradiance: Cubemap,
+  prefiltered: MipmappedCubemap,
+}
+impl EnvironmentMaps {
+  /// Generates the maps with the default sizes.
+  pub fn new(environment: impl CubemapImageData) -> SarektResult<Self> {
+    Self::with_sizes(
+      environment,
+      DEFAULT_IRRADIANCE_SIZE,
+      DEFAULT_PREFILTERED_SIZE,
+      DEFAULT_PREFILTERED_LEVELS,
+    )
+  }
+
+  /// Generates the maps with the given face sizes and number of prefiltered
+  /// levels, which must fit in the prefiltered size's mip chain.
+  pub fn with_sizes(
+    environment: impl CubemapImageData, irradiance_size: u32, prefiltered_size: u32,
+    prefiltered_levels: u32,
+  ) -> SarektResult<Self> {
+    if irradiance_size == 0 || prefiltered_size == 0 {
+      return Err(SarektError::InvalidEnvironmentMap(
+        "map sizes must not be zero".to_owned(),
+      ));
+    }
+    let max_levels = 32 - prefiltered_size.leading_zeros();
+    if prefiltered_levels == 0 || prefiltered_levels > max_levels {
+      return Err(SarektError::InvalidEnvironmentMap(format!(
+        "{} prefiltered levels, a size of {} allows 1 to {}",
+        prefiltered_levels, prefiltered_size, max_levels
+      )));
+    }
+
+    let source_chain = FloatCube::decode(environment)?.mip_chain();
+    let irradiance = irradiance_map(&source_chain, irradiance_size);
+    let prefiltered = (0..prefiltered_levels)
+      .map(|level| {
+        let size = (prefiltered_size >> level).max(1);
+        let roughness = if prefiltered_levels == 1 {
+          0f32
+        } else {
+          level as f32 / (prefiltered_levels - 1) as f32
+        };
+        prefiltered_level(&source_chain, size, roughness).into_face_bytes()
+      })
+      .collect();
+
+    Ok(Self {
+      irradiance: Cubemap::new(
+        ImageDataFormat::R16G16B16A16Sfloat,
+        irradiance_size,
+        irradiance.into_face_bytes(),
+      )?,
+      prefiltered: MipmappedCubemap::new(
+        ImageDataFormat::R16G16B16A16Sfloat,
+        prefiltered_size,
+        prefiltered,
+      )?,
+    })
+  }
+
+  /// The irradiance map and the prefiltered map.
+  pub fn into_parts(self) -> (Cubemap, MipmappedCubemap) {
+    (self.irradiance, self.prefiltered)
+  }
+}
+
+/// Generates the BRDF lookup table.  Its x axis is the cosine between the
+/// normal and view direction and its y axis is the roughness, the red channel
+/// is the scale and the green channel the bias to apply to the reflectance at
+/// normal incidence.
+pub fn brdf_lut(size: u32) -> SarektResult<HdrImage> {
+  if size == 0 {
+    return Err(SarektError::InvalidEnvironmentMap(
+      "BRDF lookup table size must not be zero".to_owned(),
+    ));
+  }
+
+  let mut texels = Vec::with_capacity(size as usize * size as usize);
+  for y in 0..size {
+    let roughness = (y as f32 + 0.5) / size as f32;
+    for x in 0..size {
+      let n_dot_v = (x as f32 + 0.5) / size as f32;
+      let (scale, bias) = integrate_brdf(n_dot_v, roughness);
+      texels.push([scale, bias, 0f32, 1f32]);
+    }
+  }
+
+  Ok(HdrImage::from_rgba32f((size, size), texels)?.into_rgba16f())
+}
+
+/// Linear RGB faces of a cube.
+struct FloatCube {
+  size: u32,
+  faces: [Vec<uv::Vec3>; 6],
+}
+impl FloatCube {
+  fn decode(environment: impl CubemapImageData) -> SarektResult<Self> {
+    let size = environment.face_size();
+    let format = environment.format()?;
+    let mut faces: [Vec<uv::Vec3>; 6] = Default::default();
+    for (face, bytes) in faces.iter_mut().zip(environment.into_face_bytes().iter()) {
+      *face = decode_texels(format, bytes)?;
+    }
+
+    Ok(Self { size, faces })
+  }
+
+  /// Evaluates `radiance` in the direction of every texel.
+  fn from_fn(size: u32, radiance: impl Fn(uv::Vec3) -> uv::Vec3) -> Self {
+    let mut faces: [Vec<uv::Vec3>; 6] = Default::default();
+    for (face_index, face) in faces.iter_mut().enumerate() {
+      face.reserve(size as usize * size as usize);
+      for y in 0..size {
+        for x in 0..size {
+          face.push(radiance(texel_direction(face_index, x, y, size)));
+        }
+      }
+    }
+
+    Self { size, faces }
+  }
+
+  /// Box filtered levels down to 1x1, this level first.
+  fn mip_chain(self) -> Vec<Self> {
+    let mut chain = vec![self];
+    while chain.last().unwrap().size > 1 {
+      let next = chain.last().unwrap().downsampled();
+      chain.push(next);
+    }
+    chain
+  }
+
+  fn downsampled(&self) -> Self {
+    let size = (self.size / 2).max(1);
+    let mut faces: [Vec<uv::Vec3>; 6] = Default::default();
+    for (face, source) in faces.iter_mut().zip(self.faces.iter()) {
+      face.reserve(size as usize * size as usize);
+      for y in 0..size {
+        for x in 0..size {
+          let texel = |dx: u32, dy: u32| {
+            let source_x = (2 * x + dx).min(self.size - 1);
+            let source_y = (2 * y + dy).min(self.size - 1);
+            source[(source_y * self.size + source_x) as usize]
+          };
+          face.push((texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1)) * 0.25);
+        }
+      }
+    }
+
+    Self { size, faces }
+  }
+
+  /// Bilinearly filtered radiance in `direction`, clamped to the edges of the
+  /// face.
+  fn sample(&self, direction: uv::Vec3) -> uv::Vec3 {
+    let (face, s, t) = direction_face(direction.x, direction.y, direction.z);
+    let texels = &self.faces[face];
+    let max = (self.size - 1) as f32;
+    let x = ((s + 1.0) * 0.5 * self.size as f32 - 0.5).max(0.0).min(max);
+    let y = ((t + 1.0) * 0.5 * self.size as f32 - 0.5).max(0.0).min(max);
+    let (x0, y0) = (x as u32, y as u32);
+    let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let texel = |x: u32, y: u32| texels[(y * self.size + x) as usize];
+    let top = lerp(texel(x0, y0), texel(x1, y0), fx);
+    let bottom = lerp(texel(x0, y1), texel(x1, y1), fx);
+    lerp(top, bottom, fy)
+  }
+
+  fn into_face_bytes(self) -> [Vec<u8>; 6] {
+    let mut faces: [Vec<u8>; 6] = Default::default();
+    for (bytes, texels) in faces.iter_mut().zip(self.faces.iter()) {
+      bytes.reserve(texels.len() * 8);
+      for texel in texels.iter() {
+        for &channel in [texel.x, texel.y, texel.z, 1f32].iter() {
+          bytes.extend_from_slice(&f32_to_f16_bits(channel).to_ne_bytes());
+        }
+      }
+    }
+    faces
+  }
+}
+
+/// Trilinearly filtered radiance in `direction` at a fractional level of the
+/// chain.
+fn sample_chain(chain: &[FloatCube], direction: uv::Vec3, lod: f32) -> uv::Vec3 {
+  let lod = lod.max(0.0).min((chain.len() - 1) as f32);
+  let level = lod as usize;
+  let fraction = lod - level as f32;
+  if fraction == 0.0 || level + 1 >= chain.len() {
+    return chain[level].sample(direction);
+  }
+
+  lerp(
+    chain[level].sample(direction),
+    chain[level + 1].sample(direction),
+    fraction,
+  )
+}
+
+fn irradiance_map(chain: &[FloatCube], size: u32) -> FloatCube {
+  // Every texel of a small level weighted by the solid angle it covers.
+  let source = chain
+    .iter()
+    .find(|level| level.size <= IRRADIANCE_SOURCE_SIZE)
+    .unwrap_or_else(|| chain.last().unwrap());
+  let mut weighted_texels = Vec::with_capacity(6 * (source.size * source.size) as usize);
+  for (face_index, face) in source.faces.iter().enumerate() {
+    for y in 0..source.size {
+      for x in 0..source.size {
+        let (s, t) = texel_coordinates(x, y, source.size);
+        let solid_angle = (2.0 / source.size as f32).powi(2) / (1.0 + s * s + t * t).powf(1.5);
+        weighted_texels.push((
+          texel_direction(face_index, x, y, source.size),
+          face[(y * source.size + x) as usize] * solid_angle,
+        ));
+      }
+    }
+  }
+
+  FloatCube::from_fn(size, |normal| {
+    let irradiance = weighted_texels
+      .iter()
+      .fold(uv::Vec3::zero(), |sum, &(direction, radiance)| {
+        sum + radiance * normal.dot(direction).max(0.0)
+      });
+    irradiance / PI
+  })
+}
+
+fn prefiltered_level(chain: &[FloatCube], size: u32, roughness: f32) -> FloatCube {
+  if roughness == 0.0 {
+    // A mirror, just resample at the matching resolution.
+    let lod = (chain[0].size as f32 / size as f32).log2();
+    return FloatCube::from_fn(size, |direction| sample_chain(chain, direction, lod));
+  }
+
+  let alpha = roughness * roughness;
+  let source_texel_solid_angle = 4.0 * PI / (6.0 * (chain[0].size * chain[0].size) as f32);
+  FloatCube::from_fn(size, |normal| {
+    // The view and reflection directions are taken to be the normal.
+    let (tangent, bitangent) = tangent_frame(normal);
+    let mut sum = uv::Vec3::zero();
+    let mut total_weight = 0f32;
+    for i in 0..PREFILTER_SAMPLE_COUNT {
+      let h = importance_sample_ggx(i, PREFILTER_SAMPLE_COUNT, alpha);
+      let half_vector = tangent * h.x + bitangent * h.y + normal * h.z;
+      let light = half_vector * (2.0 * normal.dot(half_vector)) - normal;
+      let n_dot_l = normal.dot(light);
+      if n_dot_l <= 0.0 {
+        continue;
+      }
+
+      // Samples that are less likely cover more of the sphere, so read them from
+      // a blurrier level to avoid aliasing.  With the view along the normal
+      // the pdf is D / 4.
+      let pdf = ggx_distribution(h.z, alpha) / 4.0;
+      let sample_solid_angle = 1.0 / (PREFILTER_SAMPLE_COUNT as f32 * pdf + 1e-4);
+      let lod = 0.5 * (sample_solid_angle / source_texel_solid_angle).log2() + 1.0;
+
+      sum += sample_chain(chain, light, lod) * n_dot_l;
+      total_weight += n_dot_l;
+    }
+    sum / total_weight.max(1e-4)
+  })
+}
+
+fn integrate_brdf(n_dot_v: f32, roughness: f32) -> (f32, f32) {
+  // In tangent space with the normal along z.
+  let view = uv::Vec3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+  let alpha = roughness * roughness;
+  // k for image based lighting, direct lighting uses (roughness + 1)^2 / 8.
+  let k = alpha / 2.0;
+  let geometry = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+
+  let (mut scale, mut bias) = (0f32, 0f32);
+  for i in 0..BRDF_SAMPLE_COUNT {
+    let half_vector = importance_sample_ggx(i, BRDF_SAMPLE_COUNT, alpha);
+    let v_dot_h = view.dot(half_vector);
+    let light = half_vector * (2.0 * v_dot_h) - view;
+    let n_dot_l = light.z;
+    if n_dot_l <= 0.0 {
+      continue;
+    }
+
+    let (n_dot_h, v_dot_h) = (half_vector.z.max(0.0), v_dot_h.max(0.0));
+    let visibility =
+      geometry(n_dot_v) * geometry(n_dot_l) * v_dot_h / (n_dot_h * n_dot_v).max(1e-6);
+    let fresnel = (1.0 - v_dot_h).powi(5);
+    scale += (1.0 - fresnel) * visibility;
+    bias += fresnel * visibility;
+  }
+
+  (
+    scale / BRDF_SAMPLE_COUNT as f32,
+    bias / BRDF_SAMPLE_COUNT as f32,
+  )
+}
+
+/// Half vector of the i'th of `count` low discrepancy samples of the GGX
+/// distribution, in tangent space with the normal along z.
+fn importance_sample_ggx(i: u32, count: u32, alpha: f32) -> uv::Vec3 {
+  // Hammersley sequence.
+  let u = i as f32 / count as f32;
+  let v = i.reverse_bits() as f32 / 4_294_967_296f32;
+
+  let phi = 2.0 * PI * u;
+  let cos_theta = ((1.0 - v) / (1.0 + (alpha * alpha - 1.0) * v)).sqrt();
+  let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+  uv::Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+  let alpha_squared = alpha * alpha;
+  let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+  alpha_squared / (PI * denominator * denominator)
+}
+
+fn tangent_frame(normal: uv::Vec3) -> (uv::Vec3, uv::Vec3) {
+  let up = if normal.z.abs() < 0.999 {
+    uv::Vec3::unit_z()
+  } else {
+    uv::Vec3::unit_x()
+  };
+  let tangent = up.cross(normal).normalized();
+  (tangent, normal.cross(tangent))
+}
+
+/// (s, t) in [-1, 1] of the center of texel (x, y).
+fn texel_coordinates(x: u32, y: u32, size: u32) -> (f32, f32) {
+  (
+    2.0 * (x as f32 + 0.5) / size as f32 - 1.0,
+    2.0 * (y as f32 + 0.5) / size as f32 - 1.0,
+  )
+}
+
+fn texel_direction(face_index: usize, x: u32, y: u32, size: u32) -> uv::Vec3 {
+  let (s, t) = texel_coordinates(x, y, size);
+  let (x, y, z) = face_direction(face_index, s, t);
+  uv::Vec3::new(x, y, z).normalized()
+}
+
+fn lerp(a: uv::Vec3, b: uv::Vec3, t: f32) -> uv::Vec3 {
+  a + (b - a) * t
+}
+
+/// Decodes the RGB channels of every texel to linear floats.
+fn decode_texels(format: ImageDataFormat, bytes: &[u8]) -> SarektResult<Vec<uv::Vec3>> {
+  use ImageDataFormat::*;
+
+  let (texel_size, decode): (usize, fn(&[u8]) -> uv::Vec3) = match format {
+    R8G8B8Srgb => (3, |t| srgb_texel(t[0], t[1], t[2])),
+    R8G8B8A8Srgb => (4, |t| srgb_texel(t[0], t[1], t[2])),
+    B8G8R8Srgb => (3, |t| srgb_texel(t[2], t[1], t[0])),
+    B8G8R8A8Srgb => (4, |t| srgb_texel(t[2], t[1], t[0])),
+    R8G8B8Unorm => (3, |t| unorm8_texel(t[0], t[1], t[2])),
+    R8G8B8A8Unorm => (4, |t| unorm8_texel(t[0], t[1], t[2])),
+    B8G8R8Unorm => (3, |t| unorm8_texel(t[2], t[1], t[0])),
+    B8G8R8A8Unorm => (4, |t| unorm8_texel(t[2], t[1], t[0])),
+    R16G16B16Unorm => (6, unorm16_texel),
+    R16G16B16A16Unorm => (8, unorm16_texel),
+    R16G16B16A16Sfloat => (8, |t| {
+      let channel = |i: usize| f16_bits_to_f32(u16::from_ne_bytes([t[2 * i], t[2 * i + 1]]));
+      uv::Vec3::new(channel(0), channel(1), channel(2))
+    }),
+    R32G32B32A32Sfloat => (16, |t| {
+      let channel =
+        |i: usize| f32::from_ne_bytes([t[4 * i], t[4 * i + 1], t[4 * i + 2], t[4 * i + 3]]);
+      uv::Vec3::new(channel(0), channel(1), channel(2))
+    }),
+    _ => {
+      return Err(SarektError::InvalidEnvironmentMap(format!(
+        "can't generate image based lighting from a {:?} environment",
+        format
+      )))
+    }
+  };
+
+  Ok(bytes.chunks_exact(texel_size).map(decode).collect())
+}
+
+fn srgb_texel(r: u8, g: u8, b: u8) -> uv::Vec3 {
+  let linear = |value: u8| {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+      value / 12.92
+    } else {
+      ((value + 0.055) / 1.055).powf(2.4)
+    }
+  };
+  uv::Vec3::new(linear(r), linear(g), linear(b))
+}
+
+fn unorm8_texel(r: u8, g: u8, b: u8) -> uv::Vec3 {
+  uv::Vec3::new(r as f32, g as f32, b as f32) / 255.0
+}
+
+fn unorm16_texel(texel: &[u8]) -> uv::Vec3 {
+  let channel = |i: usize| u16::from_ne_bytes([texel[2 * i], texel[2 * i + 1]]) as f32 / 65535.0;
+  uv::Vec3::new(channel(0), channel(1), channel(2))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::image_data::{cubemap::MipmappedCubemapImageData, ImageData};
+
+  fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+      (actual - expected).abs() <= tolerance,
+      "{} is not within {} of {}",
+      actual,
+      tolerance,
+      expected
+    );
+  }
+
+  // Radiance of an overcast sky, brightest straight up and black below the
+  // horizon.
+  fn sky(direction: uv::Vec3) -> uv::Vec3 {
+    uv::Vec3::broadcast(direction.y.max(0.0))
+  }
+
+  #[test]
+  fn smooth_brdf_is_exactly_fresnel() {
+    // With no roughness every sample is the mirror direction and the geometry
+    // term is 1, so the integral is Schlick's approximation itself.
+    for &n_dot_v in [0.05f32, 0.25, 0.5, 0.75, 1.0].iter() {
+      let fresnel = (1.0 - n_dot_v).powi(5);
+      let (scale, bias) = integrate_brdf(n_dot_v, 0.0);
+      assert_close(scale, 1.0 - fresnel, 1e-5);
+      assert_close(bias, fresnel, 1e-5);
+    }
+  }
+
+  #[test]
+  fn rough_brdf_loses_energy() {
+    // A smooth surface reflects everything.
+    let mut previous_total = 1.0;
+    for &roughness in [0.25f32, 0.5, 0.75, 1.0].iter() {
+      let (scale, bias) = integrate_brdf(0.5, roughness);
+      assert!(scale >= 0.0 && bias >= 0.0);
+      assert!(scale + bias < previous_total);
+      previous_total = scale + bias;
+    }
+  }
+
+  #[test]
+  fn brdf_lut_texels() {
+    assert!(brdf_lut(0).is_err());
+
+    let size = 8;
+    let lut = brdf_lut(size).unwrap();
+    assert_eq!(lut.dimensions(), (size, size));
+    assert_eq!(lut.format().unwrap(), ImageDataFormat::R16G16B16A16Sfloat);
+
+    let bytes = lut.into_bytes();
+    let texel = |x: u32, y: u32| {
+      let offset = 8 * (y * size + x) as usize;
+      let channel = |i: usize| {
+        f16_bits_to_f32(u16::from_ne_bytes([
+          bytes[offset + 2 * i],
+          bytes[offset + 2 * i + 1],
+        ]))
+      };
+      (channel(0), channel(1), channel(3))
+    };
+    // x is the cosine and y the roughness at texel centers, half float
+    // precision.
+    for y in 0..size {
+      for x in 0..size {
+        let n_dot_v = (x as f32 + 0.5) / size as f32;
+        let roughness = (y as f32 + 0.5) / size as f32;
+        let (expected_scale, expected_bias) = integrate_brdf(n_dot_v, roughness);
+        let (scale, bias, alpha) = texel(x, y);
+        assert_close(scale, expected_scale, 1e-3);
+        assert_close(bias, expected_bias, 1e-3);
+        assert_eq!(alpha, 1.0);
+      }
+    }
+    // Smooth surfaces seen head on reflect only what Fresnel says, F0.
+    let (scale, bias, _) = texel(size - 1, 0);
+    assert!(scale > 0.99 && bias < 0.01);
+  }
+
+  #[test]
+  fn irradiance_of_a_uniform_environment_is_its_radiance() {
+    let chain = FloatCube::from_fn(16, |_| uv::Vec3::new(0.5, 1.0, 2.0)).mip_chain();
+    let irradiance = irradiance_map(&chain, 4);
+    for face in irradiance.faces.iter() {
+      for texel in face.iter() {
+        assert_close(texel.x, 0.5, 0.01);
+        assert_close(texel.y, 1.0, 0.02);
+        assert_close(texel.z, 2.0, 0.04);
+      }
+    }
+  }
+
+  #[test]
+  fn irradiance_of_the_sky() {
+    // The cosine weighted integral of cos(theta) over the upper hemisphere is
+    // 2 pi / 3, divided by pi.
+    let chain = FloatCube::from_fn(16, sky).mip_chain();
+    let irradiance = irradiance_map(&chain, 8);
+    assert_close(irradiance.sample(uv::Vec3::unit_y()).x, 2.0 / 3.0, 0.02);
+    // Texels next to straight down see a sliver of sky.
+    assert_close(irradiance.sample(-uv::Vec3::unit_y()).x, 0.0, 1e-3);
+    // Looking at the horizon sees half the sky at a grazing angle.
+    let horizon = irradiance.sample(uv::Vec3::unit_x()).x;
+    assert!(horizon > 0.0 && horizon < 2.0 / 3.0);
+  }
+
+  #[test]
+  fn smooth_prefiltered_level_is_the_environment() {
+    let chain = FloatCube::from_fn(16, sky).mip_chain();
+    let level = prefiltered_level(&chain, 16, 0.0);
+    for (face, source) in level.faces.iter().zip(chain[0].faces.iter()) {
+      for (texel, source_texel) in face.iter().zip(source.iter()) {
+        assert_close(texel.x, source_texel.x, 1e-5);
+      }
+    }
+  }
+
+  #[test]
+  fn rough_prefiltered_levels_blur() {
+    let chain = FloatCube::from_fn(16, sky).mip_chain();
+    let up = |roughness: f32| {
+      prefiltered_level(&chain, 4, roughness)
+        .sample(uv::Vec3::unit_y())
+        .x
+    };
+    let below_horizon = |roughness: f32| {
+      prefiltered_level(&chain, 4, roughness)
+        .sample(-uv::Vec3::unit_y())
+        .x
+    };
+
+    let (smooth, rough) = (up(0.25), up(1.0));
+    assert!(smooth > rough && rough > 0.0);
+    assert_close(below_horizon(0.25), 0.0, 0.01);
+
+    // Blurring a uniform environment leaves it unchanged.
+    let uniform_chain = FloatCube::from_fn(16, |_| uv::Vec3::one()).mip_chain();
+    for &roughness in [0.5f32, 1.0].iter() {
+      for face in prefiltered_level(&uniform_chain, 4, roughness).faces.iter() {
+        for texel in face.iter() {
+          assert_close(texel.x, 1.0, 1e-3);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn environment_maps_sizes() {
+    let face = vec![0u8; 4 * 4 * 16];
+    let environment = || {
+      Cubemap::new(
+        ImageDataFormat::R32G32B32A32Sfloat,
+        4,
+        [
+          face.clone(),
+          face.clone(),
+          face.clone(),
+          face.clone(),
+          face.clone(),
+          face.clone(),
+        ],
+      )
+      .unwrap()
+    };
+
+    let (irradiance, prefiltered) = EnvironmentMaps::with_sizes(environment(), 2, 8, 4)
+      .unwrap()
+      .into_parts();
+    assert_eq!(irradiance.face_size(), 2);
+    assert_eq!(prefiltered.face_size(), 8);
+    assert_eq!(prefiltered.mip_levels(), 4);
+
+    assert!(EnvironmentMaps::with_sizes(environment(), 2, 8, 5).is_err());
+    assert!(EnvironmentMaps::with_sizes(environment(), 0, 8, 1).is_err());
+  }
+
+  #[test]
+  fn decodes_16_bit_channels_but_not_packed_texels() {
+    let texel: Vec<u8> = [0u16, 32768, 65535]
+      .iter()
+      .flat_map(|c| c.to_ne_bytes().to_vec())
+      .collect();
+    let decoded = decode_texels(ImageDataFormat::R16G16B16Unorm, &texel).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_close(decoded[0].x, 0.0, 1e-6);
+    assert_close(decoded[0].y, 0.5, 1e-4);
+    assert_close(decoded[0].z, 1.0, 1e-6);
+
+    assert!(decode_texels(ImageDataFormat::RGB16Unorm, &[0; 6]).is_err());
+    assert!(decode_texels(ImageDataFormat::RGBA16Unorm, &[0; 8]).is_err());
+  }
+}
@@ -0,0 +1,161 @@
+//! Floating point images, for HDR environment maps, light probes and other
+//! data that doesn't fit in [0, 1].
+//!
+//! Radiance `.hdr` files are decoded with the image crate.  OpenEXR is not
+//! loaded by Sarekt, the version of the image crate it depends on can't decode
+//! it.  EXR files must be decoded by the application (for example with the
+//! [exr](https://crates.io/crates/exr) crate) and passed to
+//! [HdrImage::from_rgba32f](struct.HdrImage.html#method.from_rgba32f).
+use crate::{
+  error::{SarektError, SarektResult},
+  half::{f16_bits_to_f32, f32_to_f16_bits},
+  image_data::{ImageData, ImageDataFormat},
+};
+use safe_transmute::to_bytes::transmute_to_bytes;
+use std::{
+  fs::File,
+  io::{BufRead, BufReader},
+  path::Path,
+};
+
+/// A floating point RGBA image held in memory, either 32 or 16 bits per
+/// channel.
+pub struct HdrImage {
+  format: ImageDataFormat,
+  dimensions: (u32, u32),
+  bytes: Vec<u8>,
+}
+impl HdrImage {
+  /// Creates an R32G32B32A32Sfloat image out of (width * height) RGBA texels.
+  pub fn from_rgba32f(dimensions: (u32, u32), texels: Vec<[f32; 4]>) -> SarektResult<Self> {
+    let expected_len = dimensions.0 as usize * dimensions.1 as usize;
+    if texels.len() != expected_len {
+      return Err(SarektError::InvalidImageContainer(format!(
+        "HDR image with dimensions {:?} should have {} texels but had {}",
+        dimensions,
+        expected_len,
+        texels.len()
+      )));
+    }
+
+    let floats: Vec<f32> = texels
+      .into_iter()
+      .flat_map(|texel| texel.to_vec())
+      .collect();
+    Ok(Self {
+      format: ImageDataFormat::R32G32B32A32Sfloat,
+      dimensions,
+      // A copy, the vector's allocation can't be reused with u8's alignment.
+      bytes: transmute_to_bytes(&floats).to_vec(),
+    })
+  }
+
+  /// Decodes a Radiance RGBE (`.hdr`) image, with alpha set to 1.
+  pub fn load_radiance<R: BufRead>(reader: R) -> SarektResult<Self> {
+    let decoder = image::codecs::hdr::HdrDecoder::new(reader)
+      .map_err(|err| SarektError::InvalidImageContainer(err.to_string()))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+      .read_image_hdr()
+      .map_err(|err| SarektError::InvalidImageContainer(err.to_string()))?;
+
+    let texels = pixels
+      .into_iter()
+      .map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+      .collect();
+    Self::from_rgba32f((metadata.width, metadata.height), texels)
+  }
+
+  /// Opens and decodes a Radiance RGBE (`.hdr`) file.
+  pub fn open_radiance<P: AsRef<Path>>(path: P) -> SarektResult<Self> {
+    let file = File::open(path)?;
+    Self::load_radiance(BufReader::new(file))
+  }
+
+  /// Converts to R16G16B16A16Sfloat, halving the memory used.  Values outside
+  /// of half float range become infinity.
+  pub fn into_rgba16f(self) -> Self {
+    if self.format != ImageDataFormat::R32G32B32A32Sfloat {
+      return self;
+    }
+
+    let halves: Vec<u16> = self
+      .bytes
+      .chunks_exact(4)
+      .map(|b| f32_to_f16_bits(f32::from_ne_bytes([b[0], b[1], b[2], b[3]])))
+      .collect();
+    Self {
+      format: ImageDataFormat::R16G16B16A16Sfloat,
+      dimensions: self.dimensions,
+      bytes: transmute_to_bytes(&halves).to_vec(),
+    }
+  }
+}
+impl ImageData for HdrImage {
+  fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  /// Clamps to [0, 1] and stores as linear R8G8B8A8Unorm, losing the HDR
+  /// range.
+  fn into_rgba8(self) -> Self {
+    let to_unorm = |value: f32| (value.max(0.0).min(1.0) * 255.0 + 0.5) as u8;
+    let bytes = match self.format {
+      ImageDataFormat::R32G32B32A32Sfloat => self
+        .bytes
+        .chunks_exact(4)
+        .map(|b| to_unorm(f32::from_ne_bytes([b[0], b[1], b[2], b[3]])))
+        .collect(),
+      ImageDataFormat::R16G16B16A16Sfloat => self
+        .bytes
+        .chunks_exact(2)
+        .map(|b| to_unorm(f16_bits_to_f32(u16::from_ne_bytes([b[0], b[1]]))))
+        .collect(),
+      _ => return self,
+    };
+
+    Self {
+      format: ImageDataFormat::R8G8B8A8Unorm,
+      dimensions: self.dimensions,
+      bytes,
+    }
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    self.dimensions
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rgba32f_converts_to_rgba16f() {
+    assert!(HdrImage::from_rgba32f((2, 1), vec![[0f32; 4]]).is_err());
+
+    let image = HdrImage::from_rgba32f(
+      (2, 1),
+      vec![[1.0, -2.0, 0.5, 1.0], [0.0, 65504.0, 1e6, 0.0]],
+    )
+    .unwrap();
+    assert_eq!(image.format().unwrap(), ImageDataFormat::R32G32B32A32Sfloat);
+    assert_eq!(image.dimensions(), (2, 1));
+
+    let image = image.into_rgba16f();
+    assert_eq!(image.format().unwrap(), ImageDataFormat::R16G16B16A16Sfloat);
+    let halves: Vec<u16> = image
+      .into_bytes()
+      .chunks_exact(2)
+      .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+      .collect();
+    assert_eq!(
+      halves,
+      [0x3C00, 0xC000, 0x3800, 0x3C00, 0x0000, 0x7BFF, 0x7C00, 0x0000]
+    );
+  }
+}
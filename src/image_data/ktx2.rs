@@ -0,0 +1,173 @@
+//! Loader for [KTX2](https://github.khronos.org/KTX-Specification/) containers.
+//!
+//! Only the pieces needed to get at pre-generated mip chains are parsed, the
+//! data format descriptor and key/value data are skipped.  Supercompressed
+//! (Basis Universal, zstd, etc) files are not supported, transcode them in the
+//! asset pipeline instead.
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::{
+    read_slice, read_u32_le, read_u64_le, validate_container_header, ImageDataFormat, MipChain,
+  },
+};
+use ash::vk;
+use std::{convert::TryInto, path::Path};
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+  0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+/// Identifier, 9 u32 header fields, 4 u32 and 2 u64 index fields.
+const LEVEL_INDEX_OFFSET: usize = 80;
+/// byteOffset, byteLength and uncompressedByteLength, all u64.
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// Reads a KTX2 file from disk, see `load_ktx2`.
+pub fn open_ktx2<P: AsRef<Path>>(path: P) -> SarektResult<MipChain> {
+  let bytes = std::fs::read(path)?;
+  load_ktx2(&bytes)
+}
+
+/// Parses an in memory KTX2 file into a mip chain that can be passed to
+/// `load_mipmapped_image_with_staging_initialization`.
+///
+/// The file must contain a single 2D image (no array layers, cube faces or
+/// depth).
+pub fn load_ktx2(bytes: &[u8]) -> SarektResult<MipChain> {
+  if bytes.get(0..KTX2_IDENTIFIER.len()) != Some(&KTX2_IDENTIFIER[..]) {
+    return Err(SarektError::InvalidImageContainer(
+      "missing KTX2 identifier".to_owned(),
+    ));
+  }
+
+  let vk_format = read_u32_le(bytes, 12)?;
+  let width = read_u32_le(bytes, 20)?;
+  let height = read_u32_le(bytes, 24)?;
+  let depth = read_u32_le(bytes, 28)?;
+  let layer_count = read_u32_le(bytes, 32)?;
+  let face_count = read_u32_le(bytes, 36)?;
+  let level_count = read_u32_le(bytes, 40)?;
+  let supercompression_scheme = read_u32_le(bytes, 44)?;
+
+  if supercompression_scheme != 0 {
+    return Err(SarektError::InvalidImageContainer(format!(
+      "supercompression scheme {} is not supported",
+      supercompression_scheme
+    )));
+  }
+  if depth > 1 || layer_count > 1 || face_count != 1 {
+    return Err(SarektError::InvalidImageContainer(format!(
+      "only single 2D images are supported (depth {}, layers {}, faces {})",
+      depth, layer_count, face_count
+    )));
+  }
+
+  let format = ktx2_format(vk_format)?;
+
+  // A level count of 0 asks the loader to generate mips, the file still only
+  // contains level 0.
+  let level_count = level_count.max(1);
+  validate_container_header((width, height), level_count)?;
+
+  let level_count = level_count as usize;
+  let mut levels = Vec::with_capacity(level_count);
+  for level in 0..level_count {
+    let entry_offset = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_ENTRY_SIZE;
+    let byte_offset = to_usize(read_u64_le(bytes, entry_offset)?)?;
+    let byte_length = to_usize(read_u64_le(bytes, entry_offset + 8)?)?;
+    levels.push(read_slice(bytes, byte_offset, byte_length)?.to_vec());
+  }
+
+  MipChain::new(format, (width, height), levels)
+}
+
+/// Level index entries are u64s, which don't fit in a usize on 32 bit targets.
+fn to_usize(value: u64) -> SarektResult<usize> {
+  value.try_into().map_err(|_| {
+    SarektError::InvalidImageContainer(format!("level index entry {} is out of range", value))
+  })
+}
+
+/// KTX2 stores the VkFormat directly, so use the same mapping as the Vulkan
+/// backend.
+fn ktx2_format(vk_format: u32) -> SarektResult<ImageDataFormat> {
+  if vk_format == vk::Format::UNDEFINED.as_raw() as u32 {
+    return Err(SarektError::InvalidImageContainer(
+      "VK_FORMAT_UNDEFINED (Basis Universal) is not supported".to_owned(),
+    ));
+  }
+
+  vk::Format::from_raw(vk_format as i32).try_into()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::image_data::MipmappedImageData;
+
+  /// Builds an R8G8B8A8_UNORM file with the given level index entries, the
+  /// level data is appended by the caller.
+  fn rgba8_header(width: u32, height: u32, level_index: &[(u64, u64)]) -> Vec<u8> {
+    let mut bytes = vec![0u8; LEVEL_INDEX_OFFSET];
+    bytes[0..12].copy_from_slice(&KTX2_IDENTIFIER);
+    bytes[12..16].copy_from_slice(&(vk::Format::R8G8B8A8_UNORM.as_raw() as u32).to_le_bytes());
+    bytes[16..20].copy_from_slice(&1u32.to_le_bytes());
+    bytes[20..24].copy_from_slice(&width.to_le_bytes());
+    bytes[24..28].copy_from_slice(&height.to_le_bytes());
+    bytes[36..40].copy_from_slice(&1u32.to_le_bytes());
+    bytes[40..44].copy_from_slice(&(level_index.len() as u32).to_le_bytes());
+    for &(byte_offset, byte_length) in level_index {
+      bytes.extend_from_slice(&byte_offset.to_le_bytes());
+      bytes.extend_from_slice(&byte_length.to_le_bytes());
+      bytes.extend_from_slice(&byte_length.to_le_bytes());
+    }
+    bytes
+  }
+
+  fn assert_invalid_container(result: SarektResult<MipChain>) {
+    match result {
+      Err(SarektError::InvalidImageContainer(_)) => {}
+      Err(e) => panic!("expected InvalidImageContainer, got {:?}", e),
+      Ok(_) => panic!("expected InvalidImageContainer, got a mip chain"),
+    }
+  }
+
+  #[test]
+  fn loads_full_rgba8_mip_chain() {
+    let data_start = (LEVEL_INDEX_OFFSET + 2 * LEVEL_INDEX_ENTRY_SIZE) as u64;
+    // KTX2 stores the smallest level first.
+    let mut bytes = rgba8_header(2, 2, &[(data_start + 4, 16), (data_start, 4)]);
+    bytes.extend(vec![1u8; 4]);
+    bytes.extend(vec![0u8; 16]);
+
+    let chain = load_ktx2(&bytes).unwrap();
+    assert_eq!(chain.format().unwrap(), ImageDataFormat::R8G8B8A8Unorm);
+    assert_eq!(MipmappedImageData::dimensions(&chain), (2, 2));
+    assert_eq!(chain.into_level_bytes(), vec![vec![0u8; 16], vec![1u8; 4]]);
+  }
+
+  #[test]
+  fn rejects_truncated_level_index() {
+    let data_start = (LEVEL_INDEX_OFFSET + LEVEL_INDEX_ENTRY_SIZE) as u64;
+    let bytes = rgba8_header(1, 1, &[(data_start, 4)]);
+    assert_invalid_container(load_ktx2(&bytes[..LEVEL_INDEX_OFFSET + 4]));
+  }
+
+  #[test]
+  fn rejects_level_past_end_of_file() {
+    let bytes = rgba8_header(1, 1, &[(u64::MAX - 2, 4)]);
+    assert_invalid_container(load_ktx2(&bytes));
+  }
+
+  #[test]
+  fn rejects_more_levels_than_dimensions_allow() {
+    let mut bytes = rgba8_header(1, 1, &[(0, 4)]);
+    bytes[40..44].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    assert_invalid_container(load_ktx2(&bytes));
+  }
+
+  #[test]
+  fn rejects_zero_and_huge_dimensions() {
+    assert_invalid_container(load_ktx2(&rgba8_header(0, 1, &[(0, 4)])));
+    assert_invalid_container(load_ktx2(&rgba8_header(u32::MAX, 1, &[(0, 4)])));
+  }
+}
@@ -0,0 +1,145 @@
+//! Images made of several 2D layers of the same size and format.  The same
+//! data is used for 2D array textures, where each layer is sampled separately
+//! (terrain splat layers, sprite sheets), and for 3D textures, where the
+//! layers are the depth slices (volumetric data, color grading LUTs).
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::{ImageData, ImageDataFormat},
+};
+
+/// The trait used for loading array and 3D textures into Sarekt, see
+/// [LayeredImage](struct.LayeredImage.html) for the provided implementation.
+pub trait LayeredImageData {
+  /// Returns the bytes of each layer, or each depth slice from front to back.
+  fn into_layer_bytes(self) -> Vec<Vec<u8>>;
+
+  /// Returns (width, height) of each layer.
+  fn dimensions(&self) -> (u32, u32);
+
+  /// Number of layers, or the depth of a 3D texture.
+  fn layer_count(&self) -> u32;
+
+  /// Underlying image format, shared by all the layers.
+  fn format(&self) -> SarektResult<ImageDataFormat>;
+}
+
+/// Layers of the same size and format held in memory.
+pub struct LayeredImage {
+  format: ImageDataFormat,
+  dimensions: (u32, u32),
+  layers: Vec<Vec<u8>>,
+}
+impl LayeredImage {
+  /// Creates a layered image out of the raw bytes of each layer, checking that
+  /// every layer is the size its format and dimensions call for.
+  pub fn new(
+    format: ImageDataFormat, dimensions: (u32, u32), layers: Vec<Vec<u8>>,
+  ) -> SarektResult<Self> {
+    if layers.is_empty() {
+      return Err(SarektError::InvalidImageLayers(
+        "there must be at least one layer".to_owned(),
+      ));
+    }
+
+    let expected_size = format.level_size(dimensions);
+    for (layer, bytes) in layers.iter().enumerate() {
+      if bytes.len() != expected_size {
+        return Err(SarektError::InvalidImageLayers(format!(
+          "layer {} of {:?} image with dimensions {:?} should be {} bytes but was {}",
+          layer,
+          format,
+          dimensions,
+          expected_size,
+          bytes.len()
+        )));
+      }
+    }
+
+    Ok(Self {
+      format,
+      dimensions,
+      layers,
+    })
+  }
+
+  /// Builds a layered image out of separate images, which must all share a
+  /// size and format.
+  pub fn from_images<I: ImageData>(images: Vec<I>) -> SarektResult<Self> {
+    let first = images.first().ok_or_else(|| {
+      SarektError::InvalidImageLayers("there must be at least one layer".to_owned())
+    })?;
+    let format = first.format()?;
+    let dimensions = first.dimensions();
+
+    let mut layers = Vec::with_capacity(images.len());
+    for image in images.into_iter() {
+      if image.dimensions() != dimensions || image.format()? != format {
+        return Err(SarektError::InvalidImageLayers(format!(
+          "all layers must be {:?} images with dimensions {:?}",
+          format, dimensions
+        )));
+      }
+      layers.push(image.into_bytes());
+    }
+
+    Self::new(format, dimensions, layers)
+  }
+
+  /// Splits a single image made of equally sized tiles, such as a sprite sheet
+  /// or a LUT laid out as a strip of slices, into one layer per tile.  Tiles
+  /// are taken left to right, then top to bottom.
+  pub fn from_tiles<I: ImageData>(image: I, tile_dimensions: (u32, u32)) -> SarektResult<Self> {
+    let format = image.format()?;
+    let (width, height) = image.dimensions();
+    let (tile_width, tile_height) = tile_dimensions;
+    if format.is_compressed()
+      || tile_width == 0
+      || tile_height == 0
+      || width % tile_width != 0
+      || height % tile_height != 0
+    {
+      return Err(SarektError::InvalidImageLayers(format!(
+        "can't split {:?} image with dimensions {:?} into {:?} tiles",
+        format,
+        (width, height),
+        tile_dimensions
+      )));
+    }
+
+    let texel_size = format.texel_block_size();
+    let row_len = tile_width as usize * texel_size;
+    let pixels = image.into_bytes();
+
+    let mut layers = Vec::new();
+    for tile_y in 0..height / tile_height {
+      for tile_x in 0..width / tile_width {
+        let mut layer = Vec::with_capacity(row_len * tile_height as usize);
+        for y in 0..tile_height {
+          let row = (tile_y * tile_height + y) as usize;
+          let offset = (row * width as usize + (tile_x * tile_width) as usize) * texel_size;
+          layer.extend_from_slice(&pixels[offset..offset + row_len]);
+        }
+        layers.push(layer);
+      }
+    }
+
+    Self::new(format, tile_dimensions, layers)
+  }
+}
+impl LayeredImageData for LayeredImage {
+  fn into_layer_bytes(self) -> Vec<Vec<u8>> {
+    self.layers
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    self.dimensions
+  }
+
+  fn layer_count(&self) -> u32 {
+    self.layers.len() as u32
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
@@ -0,0 +1,479 @@
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::ImageDataFormat::*,
+};
+use safe_transmute::to_bytes::transmute_to_bytes;
+
+pub mod cubemap;
+pub mod dds;
+pub mod decompress;
+pub mod environment;
+pub mod hdr;
+pub mod ktx2;
+pub mod layered;
+
+/// The trait used for loading images into Sarekt.  An implementation is
+/// provided for the rust [image](https://crates.io/crates/image) crate.  Feel free to create one in your own project for other crates (by wrapping in a newtype to avoid the orphan problem).
+pub trait ImageData {
+  /// Returns byte color array of pixels.
+  fn into_bytes(self) -> Vec<u8>;
+
+  /// Converts to rgba8, a format that must be supported by at least the Vulkan
+  /// backend
+  fn into_rgba8(self) -> Self;
+
+  /// Returns (width, height) of the image.
+  fn dimensions(&self) -> (u32, u32);
+
+  /// Underlying image format.
+  fn format(&self) -> SarektResult<ImageDataFormat>;
+}
+
+/// The trait used for loading images that already contain their whole mip
+/// chain, such as those baked by an asset pipeline into KTX2 or DDS containers
+/// (see the [ktx2](ktx2/index.html) and [dds](dds/index.html) modules).
+///
+/// Unlike [ImageData](trait.ImageData.html) no conversion is attempted, the
+/// format must be usable by the backend as is.
+pub trait MipmappedImageData {
+  /// Returns the bytes of each mip level, starting with the full resolution
+  /// level 0.
+  fn into_level_bytes(self) -> Vec<Vec<u8>>;
+
+  /// Returns (width, height) of mip level 0.
+  fn dimensions(&self) -> (u32, u32);
+
+  /// Number of mip levels contained, including level 0.
+  fn mip_levels(&self) -> u32;
+
+  /// Underlying image format, shared by all the levels.
+  fn format(&self) -> SarektResult<ImageDataFormat>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageDataFormat {
+  R8G8B8Srgb,
+  B8G8R8Srgb,
+  B8G8R8A8Srgb,
+  R8G8B8A8Srgb,
+
+  R8G8B8Unorm,
+  B8G8R8Unorm,
+  B8G8R8A8Unorm,
+  R8G8B8A8Unorm,
+  /// 16 bits per texel, packed as 5 bits red, 6 green and 5 blue.  Not to be
+  /// confused with `R16G16B16Unorm`, which has 16 bits per channel.
+  RGB16Unorm,
+  /// 16 bits per texel, packed as 5 bits each of red, green and blue and 1 bit
+  /// of alpha.  Not to be confused with `R16G16B16A16Unorm`.
+  RGBA16Unorm,
+
+  // Single and dual channel formats, for masks, heightmaps and the like.
+  R8Unorm,
+  R8G8Unorm,
+  R8Snorm,
+  R8G8Snorm,
+  R16Unorm,
+  R16G16Unorm,
+
+  // 16 bits per channel and floating point formats, for high precision and HDR
+  // images.
+  R16G16B16Unorm,
+  R16G16B16A16Unorm,
+  // Also the format of temporal anti-aliasing's velocity target.
+  R16G16Sfloat,
+  R16G16B16A16Sfloat,
+  R32G32B32A32Sfloat,
+
+  // Depth Buffer Formats
+  D32Float,
+  D32FloatS8,
+  D24NormS8,
+
+  // Block Compressed Formats, see the Vulkan spec's "Compressed Image Formats"
+  // appendix for their layouts.
+  Bc1RgbUnorm,
+  Bc1RgbSrgb,
+  Bc1RgbaUnorm,
+  Bc1RgbaSrgb,
+  Bc2Unorm,
+  Bc2Srgb,
+  Bc3Unorm,
+  Bc3Srgb,
+  Bc4Unorm,
+  Bc4Snorm,
+  Bc5Unorm,
+  Bc5Snorm,
+  Bc6hUfloat,
+  Bc6hSfloat,
+  Bc7Unorm,
+  Bc7Srgb,
+  Etc2R8G8B8Unorm,
+  Etc2R8G8B8Srgb,
+  Etc2R8G8B8A1Unorm,
+  Etc2R8G8B8A1Srgb,
+  Etc2R8G8B8A8Unorm,
+  Etc2R8G8B8A8Srgb,
+  EacR11Unorm,
+  EacR11G11Unorm,
+  Astc4x4Unorm,
+  Astc4x4Srgb,
+  Astc5x5Unorm,
+  Astc5x5Srgb,
+  Astc6x6Unorm,
+  Astc6x6Srgb,
+  Astc8x8Unorm,
+  Astc8x8Srgb,
+  Astc10x10Unorm,
+  Astc10x10Srgb,
+  Astc12x12Unorm,
+  Astc12x12Srgb,
+}
+
+impl ImageDataFormat {
+  /// Size in bytes of one texel block of this format, which for uncompressed
+  /// formats is a single texel.
+  pub fn texel_block_size(self) -> usize {
+    match self {
+      R8G8B8Srgb | B8G8R8Srgb | R8G8B8Unorm | B8G8R8Unorm => 3,
+      B8G8R8A8Srgb | R8G8B8A8Srgb | B8G8R8A8Unorm | R8G8B8A8Unorm => 4,
+      RGB16Unorm | RGBA16Unorm => 2,
+      R8Unorm | R8Snorm => 1,
+      R8G8Unorm | R8G8Snorm | R16Unorm => 2,
+      R16G16Unorm | R16G16Sfloat => 4,
+      R16G16B16Unorm => 6,
+      R16G16B16A16Unorm | R16G16B16A16Sfloat => 8,
+      R32G32B32A32Sfloat => 16,
+      D32Float | D24NormS8 => 4,
+      D32FloatS8 => 5,
+      Bc1RgbUnorm | Bc1RgbSrgb | Bc1RgbaUnorm | Bc1RgbaSrgb | Bc4Unorm | Bc4Snorm => 8,
+      Etc2R8G8B8Unorm | Etc2R8G8B8Srgb | Etc2R8G8B8A1Unorm | Etc2R8G8B8A1Srgb | EacR11Unorm => 8,
+      // Every other compressed format, including all ASTC block sizes.
+      _ => 16,
+    }
+  }
+
+  /// (width, height) in texels of one texel block of this format.
+  pub fn block_dimensions(self) -> (u32, u32) {
+    match self {
+      Astc5x5Unorm | Astc5x5Srgb => (5, 5),
+      Astc6x6Unorm | Astc6x6Srgb => (6, 6),
+      Astc8x8Unorm | Astc8x8Srgb => (8, 8),
+      Astc10x10Unorm | Astc10x10Srgb => (10, 10),
+      Astc12x12Unorm | Astc12x12Srgb => (12, 12),
+      _ if self.is_compressed() => (4, 4),
+      _ => (1, 1),
+    }
+  }
+
+  /// Whether this is a block compressed format.  These can't be blitted, so
+  /// their mip chains must be pre-generated.
+  pub fn is_compressed(self) -> bool {
+    match self {
+      R8G8B8Srgb | B8G8R8Srgb | B8G8R8A8Srgb | R8G8B8A8Srgb | R8G8B8Unorm | B8G8R8Unorm
+      | B8G8R8A8Unorm | R8G8B8A8Unorm | RGB16Unorm | RGBA16Unorm | R8Unorm | R8G8Unorm
+      | R8Snorm | R8G8Snorm | R16Unorm | R16G16Unorm | R16G16B16Unorm | R16G16B16A16Unorm
+      | R16G16Sfloat | R16G16B16A16Sfloat | R32G32B32A32Sfloat | D32Float | D32FloatS8
+      | D24NormS8 => false,
+      _ => true,
+    }
+  }
+
+  /// Whether this is a depth (and stencil) format, for depth attachments.
+  pub fn is_depth(self) -> bool {
+    matches!(self, D32Float | D32FloatS8 | D24NormS8)
+  }
+
+  /// Whether `decompress::decompress_level` can decode this format on the CPU.
+  /// BC6H and ASTC can't be, so they must be sampled by the device.
+  pub fn has_cpu_decoder(self) -> bool {
+    match self {
+      Bc6hUfloat | Bc6hSfloat | Astc4x4Unorm | Astc4x4Srgb | Astc5x5Unorm | Astc5x5Srgb
+      | Astc6x6Unorm | Astc6x6Srgb | Astc8x8Unorm | Astc8x8Srgb | Astc10x10Unorm
+      | Astc10x10Srgb | Astc12x12Unorm | Astc12x12Srgb => false,
+      _ => self.is_compressed(),
+    }
+  }
+
+  /// The uncompressed format `decompress::decompress_level` produces for this
+  /// format, keeping the color space and signedness.  Formats without a CPU
+  /// decoder are returned as is.
+  pub fn decompressed_format(self) -> ImageDataFormat {
+    match self {
+      _ if !self.has_cpu_decoder() => self,
+      Bc1RgbSrgb | Bc1RgbaSrgb | Bc2Srgb | Bc3Srgb | Bc7Srgb | Etc2R8G8B8Srgb
+      | Etc2R8G8B8A1Srgb | Etc2R8G8B8A8Srgb => R8G8B8A8Srgb,
+      Bc4Snorm => R8Snorm,
+      Bc5Snorm => R8G8Snorm,
+      _ => R8G8B8A8Unorm,
+    }
+  }
+
+  /// Size in bytes of a tightly packed image of this format with the given
+  /// (width, height).  Partial blocks at the edges take up a whole block.
+  ///
+  /// Panics if the size does not fit in a usize, use `checked_level_size` for
+  /// dimensions that come from an untrusted source.
+  pub fn level_size(self, dimensions: (u32, u32)) -> usize {
+    self
+      .checked_level_size(dimensions)
+      .expect("image level size overflows usize")
+  }
+
+  /// Same as `level_size`, but returns None instead of overflowing.
+  pub fn checked_level_size(self, dimensions: (u32, u32)) -> Option<usize> {
+    let (block_width, block_height) = self.block_dimensions();
+    // Written this way so that dimensions near u32::MAX don't overflow.
+    let blocks_wide = dimensions.0 / block_width + (dimensions.0 % block_width != 0) as u32;
+    let blocks_high = dimensions.1 / block_height + (dimensions.1 % block_height != 0) as u32;
+    (blocks_wide as usize)
+      .checked_mul(blocks_high as usize)?
+      .checked_mul(self.texel_block_size())
+  }
+}
+
+/// Returns the (width, height) of the given mip level of an image whose level 0
+/// has the given dimensions.
+pub fn mip_level_dimensions(dimensions: (u32, u32), level: u32) -> (u32, u32) {
+  (
+    dimensions.0.checked_shr(level).unwrap_or(0).max(1),
+    dimensions.1.checked_shr(level).unwrap_or(0).max(1),
+  )
+}
+
+/// Number of levels in a full mip chain for an image with the given
+/// dimensions, ie `floor(log2(max(width, height))) + 1`.
+pub fn max_mip_levels(dimensions: (u32, u32)) -> u32 {
+  32 - dimensions.0.max(dimensions.1).leading_zeros()
+}
+
+impl ImageData for image::DynamicImage {
+  fn into_bytes(self) -> Vec<u8> {
+    match self {
+      image::DynamicImage::ImageBgr8(img) => img.into_raw(),
+      image::DynamicImage::ImageLuma8(img) => img.into_raw(),
+      image::DynamicImage::ImageLumaA8(img) => img.into_raw(),
+      image::DynamicImage::ImageRgb8(img) => img.into_raw(),
+      image::DynamicImage::ImageRgba8(img) => img.into_raw(),
+      image::DynamicImage::ImageBgra8(img) => img.into_raw(),
+      image::DynamicImage::ImageLuma16(img) => transmute_to_bytes(&img.into_raw()).to_vec(),
+      image::DynamicImage::ImageLumaA16(img) => transmute_to_bytes(&img.into_raw()).to_vec(),
+      image::DynamicImage::ImageRgb16(img) => transmute_to_bytes(&img.into_raw()).to_vec(),
+      image::DynamicImage::ImageRgba16(img) => transmute_to_bytes(&img.into_raw()).to_vec(),
+    }
+  }
+
+  fn into_rgba8(self) -> Self {
+    image::DynamicImage::ImageRgba8(self.into_rgba())
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    match self {
+      image::DynamicImage::ImageBgr8(img) => img.dimensions(),
+      image::DynamicImage::ImageLuma8(img) => img.dimensions(),
+      image::DynamicImage::ImageLumaA8(img) => img.dimensions(),
+      image::DynamicImage::ImageRgb8(img) => img.dimensions(),
+      image::DynamicImage::ImageRgba8(img) => img.dimensions(),
+      image::DynamicImage::ImageBgra8(img) => img.dimensions(),
+      image::DynamicImage::ImageLuma16(img) => img.dimensions(),
+      image::DynamicImage::ImageLumaA16(img) => img.dimensions(),
+      image::DynamicImage::ImageRgb16(img) => img.dimensions(),
+      image::DynamicImage::ImageRgba16(img) => img.dimensions(),
+    }
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    match self {
+      image::DynamicImage::ImageBgr8(_) => Ok(B8G8R8A8Srgb),
+      // Single channel images are more often data (masks, heightmaps) than
+      // grayscale pictures, so they are loaded as linear.
+      image::DynamicImage::ImageLuma8(_) => Ok(R8Unorm),
+      image::DynamicImage::ImageLumaA8(_) => Ok(R8G8Unorm),
+      image::DynamicImage::ImageRgb8(_) => Ok(R8G8B8Srgb),
+      image::DynamicImage::ImageRgba8(_) => Ok(R8G8B8A8Srgb),
+      image::DynamicImage::ImageBgra8(_) => Ok(B8G8R8A8Srgb),
+      image::DynamicImage::ImageLuma16(_) => Ok(R16Unorm),
+      image::DynamicImage::ImageLumaA16(_) => Ok(R16G16Unorm),
+      image::DynamicImage::ImageRgb16(_) => Ok(R16G16B16Unorm),
+      image::DynamicImage::ImageRgba16(_) => Ok(R16G16B16A16Unorm),
+    }
+  }
+}
+
+/// Image data that represents a single color.
+pub struct Monocolor {
+  inner: [u8; 4],
+}
+impl Monocolor {
+  pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+    Monocolor {
+      inner: [r, g, b, a],
+    }
+  }
+
+  pub fn clear() -> Self {
+    Self::new(1, 1, 1, 0)
+  }
+}
+impl ImageData for Monocolor {
+  fn into_bytes(self) -> Vec<u8> {
+    self.inner.to_vec()
+  }
+
+  fn into_rgba8(self) -> Self {
+    self
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    (1, 1)
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(R8G8B8A8Srgb)
+  }
+}
+
+/// A pre-generated mip chain held in memory, level 0 first.  This is what the
+/// container loaders return, but it can also be built out of images resized by
+/// the application.
+pub struct MipChain {
+  format: ImageDataFormat,
+  dimensions: (u32, u32),
+  levels: Vec<Vec<u8>>,
+}
+impl MipChain {
+  /// Creates a mip chain out of the raw bytes of each level, checking that
+  /// every level is the size its format and dimensions call for.
+  pub fn new(
+    format: ImageDataFormat, dimensions: (u32, u32), levels: Vec<Vec<u8>>,
+  ) -> SarektResult<Self> {
+    if levels.is_empty() {
+      return Err(SarektError::IllegalMipmapCount);
+    }
+
+    if levels.len() > max_mip_levels(dimensions) as usize {
+      return Err(SarektError::InvalidMipChain(format!(
+        "{} levels is more than an image with dimensions {:?} can have",
+        levels.len(),
+        dimensions
+      )));
+    }
+
+    for (level, bytes) in levels.iter().enumerate() {
+      let level_dimensions = mip_level_dimensions(dimensions, level as u32);
+      let expected_size = format.checked_level_size(level_dimensions);
+      if expected_size != Some(bytes.len()) {
+        return Err(SarektError::InvalidMipChain(format!(
+          "level {} of {:?} image with dimensions {:?} should be {:?} bytes but was {}",
+          level,
+          format,
+          level_dimensions,
+          expected_size,
+          bytes.len()
+        )));
+      }
+    }
+
+    Ok(Self {
+      format,
+      dimensions,
+      levels,
+    })
+  }
+
+  /// Builds a mip chain out of images that were already downsampled, for
+  /// example with `image::imageops::resize`.  They must all share a format.
+  pub fn from_images<I: ImageData>(images: Vec<I>) -> SarektResult<Self> {
+    let first = images.first().ok_or(SarektError::IllegalMipmapCount)?;
+    let format = first.format()?;
+    let dimensions = first.dimensions();
+
+    let mut levels = Vec::with_capacity(images.len());
+    for image in images.into_iter() {
+      if image.format()? != format {
+        return Err(SarektError::InvalidMipChain(format!(
+          "all levels must be {:?}",
+          format
+        )));
+      }
+      levels.push(image.into_bytes());
+    }
+
+    Self::new(format, dimensions, levels)
+  }
+}
+impl MipmappedImageData for MipChain {
+  fn into_level_bytes(self) -> Vec<Vec<u8>> {
+    self.levels
+  }
+
+  fn dimensions(&self) -> (u32, u32) {
+    self.dimensions
+  }
+
+  fn mip_levels(&self) -> u32 {
+    self.levels.len() as u32
+  }
+
+  fn format(&self) -> SarektResult<ImageDataFormat> {
+    Ok(self.format)
+  }
+}
+
+/// Largest width or height accepted from a container file header.  This is
+/// well past what any Vulkan implementation supports for 2D images, and keeps
+/// the level size math of a malicious header in range.
+const MAX_CONTAINER_DIMENSION: u32 = 1 << 16;
+
+/// Checks the dimensions and level count read out of a container file header
+/// before anything is sized off of them.
+fn validate_container_header(dimensions: (u32, u32), level_count: u32) -> SarektResult<()> {
+  let (width, height) = dimensions;
+  if width == 0
+    || height == 0
+    || width > MAX_CONTAINER_DIMENSION
+    || height > MAX_CONTAINER_DIMENSION
+  {
+    return Err(SarektError::InvalidImageContainer(format!(
+      "dimensions {:?} must be between 1 and {}",
+      dimensions, MAX_CONTAINER_DIMENSION
+    )));
+  }
+
+  let max_levels = max_mip_levels(dimensions);
+  if level_count > max_levels {
+    return Err(SarektError::InvalidImageContainer(format!(
+      "{} mip levels is more than the {} an image with dimensions {:?} can have",
+      level_count, max_levels, dimensions
+    )));
+  }
+
+  Ok(())
+}
+
+/// Reads a little endian u32 out of a container file, failing instead of
+/// panicking if the file is truncated.
+fn read_u32_le(bytes: &[u8], offset: usize) -> SarektResult<u32> {
+  read_slice(bytes, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Same as `read_u32_le` but for u64s.
+fn read_u64_le(bytes: &[u8], offset: usize) -> SarektResult<u64> {
+  let b = read_slice(bytes, offset, 8)?;
+  Ok(u64::from_le_bytes([
+    b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+  ]))
+}
+
+/// Returns the bytes of a container file between start and start + len.
+fn read_slice(bytes: &[u8], start: usize, len: usize) -> SarektResult<&[u8]> {
+  start
+    .checked_add(len)
+    .and_then(|end| bytes.get(start..end))
+    .ok_or_else(|| {
+      SarektError::InvalidImageContainer(format!(
+        "{} bytes at offset {} runs past the end of the file",
+        len, start
+      ))
+    })
+}
@@ -3,11 +3,18 @@
 //! supports that...
 //!
 //! See renderer crate for how to use.
+// Public for the uniform_field! macro.
+#[doc(hidden)]
 #[macro_use]
-extern crate memoffset;
+pub extern crate memoffset as __memoffset;
 #[macro_use]
 extern crate derive_builder;
+// So code generated by sarekt_derive works inside this crate too.
+extern crate self as sarekt;
 
+#[doc(hidden)]
+pub mod derive_support;
 pub mod error;
+mod half;
 pub mod image_data;
 pub mod renderer;
@@ -1,9 +1,18 @@
 use crate::{
   error::{SarektError, SarektResult},
-  image_data::{ImageData, ImageDataFormat},
-  renderer::config::NumSamples,
+  image_data::{
+    cubemap::{CubemapImageData, MipmappedCubemapImageData},
+    layered::LayeredImageData,
+    ImageData, ImageDataFormat, MipmappedImageData,
+  },
+  renderer::{
+    config::NumSamples,
+    deletion_queue::DeletionQueue,
+    memory_stats::{DefragmentationStats, HeapStats, MemoryStats},
+    upload_batch::{UploadBatch, UploadToken},
+  },
 };
-use log::warn;
+use log::{error, warn};
 use slotmap::{DefaultKey, SlotMap};
 use std::{
   fmt::Debug,
@@ -12,6 +21,8 @@ use std::{
 
 /// A type that can be used to retrieve a buffer from the renderer and
 /// BufferStore that will destroy the shader when it goes out of scope.
+/// Destruction waits for the frames in flight that may still be using it, so
+/// it is safe to drop at any point in a frame.
 ///
 /// As always, In order to pass this around with multiple ownership, wrap it in
 /// an Arc.
@@ -150,7 +161,38 @@ pub unsafe trait BufferAndImageLoader {
     &self, buffer_type: BufferType, buffer: &[BufElem],
   ) -> SarektResult<Self::BackendHandle>;
 
-  // TODO(issue#31) Allow cpu accessible images and updating all images.
+  /// Stages every resource in the batch and submits their transfers together
+  /// without waiting for them.  Returns the backend handles in the order the
+  /// resources were added and a token identifying the batch.
+  ///
+  /// The backend must make sure the resources aren't used before they finish
+  /// uploading, see `wait_for_resource_upload`.
+  fn submit_upload_batch(
+    &self, batch: UploadBatch,
+  ) -> SarektResult<(Vec<Self::BackendHandle>, UploadToken)>;
+
+  /// Whether the batch has finished uploading, freeing what was needed to
+  /// upload it (and any other finished batches) if so.  Unknown tokens are
+  /// complete.
+  fn is_upload_complete(&self, token: UploadToken) -> SarektResult<bool>;
+
+  /// Blocks until the batch has finished uploading.
+  fn wait_for_upload(&self, token: UploadToken) -> SarektResult<()>;
+
+  /// Blocks until the batch the resource was submitted in, if it is still
+  /// uploading, finishes.  Returns immediately for anything else.
+  fn wait_for_resource_upload(&self, handle: Self::BackendHandle) -> SarektResult<()>;
+
+  /// Overwrites the elements of a loaded buffer starting at element `offset`.
+  /// Buffers loaded without staging are written through mapped memory, others
+  /// through a staging buffer.
+  ///
+  /// If the buffer was submitted in an upload batch that is still uploading
+  /// the batch is waited on first.  Otherwise the buffer must not be in use by
+  /// the GPU, waiting for that is up to the caller.
+  fn update_buffer<BufElem: Sized + Copy>(
+    &self, handle: Self::BackendHandle, offset: usize, buffer: &[BufElem],
+  ) -> SarektResult<()>;
 
   /// Same as `load_buffer_with_staging` but loads an r8g8b8a8 32 bit format
   /// image instead.
@@ -160,6 +202,62 @@ pub unsafe trait BufferAndImageLoader {
     address_y: TextureAddressMode, address_z: TextureAddressMode, mip_levels: u32,
   ) -> SarektResult<Self::BackendHandle>;
 
+  /// Same as `load_image_with_staging_initialization` but every mip level is
+  /// supplied by `pixels` instead of being generated, and all of them are
+  /// uploaded in one staging copy.
+  fn load_mipmapped_image_with_staging_initialization(
+    &self, pixels: impl MipmappedImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Same as `load_image_with_staging_initialization` but loads the six faces
+  /// of a cube image.  There are no address modes, cube sampling always wraps
+  /// onto the neighbouring face.
+  fn load_cubemap_with_staging_initialization(
+    &self, faces: impl CubemapImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, mip_levels: u32,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Same as `load_cubemap_with_staging_initialization` but every mip level of
+  /// every face is supplied by `faces`, as for a prefiltered environment map.
+  fn load_mipmapped_cubemap_with_staging_initialization(
+    &self, faces: impl MipmappedCubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Same as `load_image_with_staging_initialization` but loads every layer of
+  /// a 2D array image, mipmaps are generated for each layer.
+  fn load_image_array_with_staging_initialization(
+    &self, layers: impl LayeredImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Same as `load_image_with_staging_initialization` but loads a 3D image
+  /// whose depth slices are the layers, mipmaps are downsampled in all three
+  /// dimensions.
+  fn load_volume_image_with_staging_initialization(
+    &self, slices: impl LayeredImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Replaces the texels in `region` of an image that was already loaded, using
+  /// a staging buffer.  `pixels` must be the size of the region and in the
+  /// format the image was loaded with, or one it was converted from.  If
+  /// `regenerate_mipmaps` is set every level below `region.mip_level` is
+  /// blitted again from it.
+  ///
+  /// If the image was submitted in an upload batch that is still uploading the
+  /// batch is waited on first.  Otherwise the image must not be in use by the
+  /// GPU, waiting for that is up to the caller.
+  fn update_image(
+    &self, handle: Self::BackendHandle, region: ImageRegion, pixels: impl ImageData,
+    regenerate_mipmaps: bool,
+  ) -> SarektResult<()>;
+
   /// Loads an image, much like `load_image_with_staging_initialization`, but
   /// does not give it any initial value, only a size and format.  This is
   /// useful for initializing internally used attachments, depth buffers, etc.
@@ -167,8 +265,49 @@ pub unsafe trait BufferAndImageLoader {
     &self, dimensions: (u32, u32), format: ImageDataFormat, num_msaa_samples: NumSamples,
   ) -> SarektResult<Self::BackendHandle>;
 
+  /// Creates an image that can be both rendered into as a color attachment
+  /// and sampled, see `Renderer::load_render_texture`.  It starts out ready to
+  /// be sampled, with undefined contents.
+  fn create_render_texture(
+    &self, dimensions: (u32, u32), format: ImageDataFormat,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode,
+  ) -> SarektResult<Self::BackendHandle>;
+
+  /// Creates a depth image that can be rendered into and sampled with a
+  /// comparison sampler, for shadow maps.  It starts out ready to be sampled,
+  /// with undefined contents.
+  fn create_shadow_map(
+    &self, dimensions: (u32, u32), format: ImageDataFormat,
+  ) -> SarektResult<Self::BackendHandle>;
+
   /// Deletes that resource, baby!
   fn delete_buffer_or_image(&self, handle: Self::BackendHandle) -> SarektResult<()>;
+
+  /// Returns what the device can do with images of the given format.
+  fn query_image_format_support(&self, format: ImageDataFormat) -> ImageFormatSupport;
+
+  /// Usage and budget of every memory heap.
+  fn heap_stats(&self) -> SarektResult<Vec<HeapStats>>;
+
+  /// Bytes of memory backing the resource.
+  fn allocation_size(&self, handle: Self::BackendHandle) -> SarektResult<u64>;
+
+  /// Everything the backend's allocator reports about its memory, as JSON.
+  /// `detailed` includes every block and allocation.
+  fn memory_stats_json(&self, detailed: bool) -> SarektResult<String>;
+
+  /// Moves resources in `resources` that can be moved so that they take up
+  /// fewer memory blocks, replacing each one that moved with its new backend
+  /// handle.
+  ///
+  /// None of them may be in use by the GPU, waiting for that is up to the
+  /// caller.  If it fails, `resources` still holds every resource's current
+  /// handle, including the ones that moved before the failure, and those old
+  /// handles are no longer valid.
+  fn defragment(&self, resources: &mut [Self::BackendHandle])
+    -> SarektResult<DefragmentationStats>;
 }
 
 /// A storage for all buffers to be loaded or destroyed from.  Returns a handle
@@ -180,6 +319,8 @@ where
   BL::BackendHandle: BackendHandleTrait + Copy + Debug,
 {
   loaded_buffers_and_images: SlotMap<DefaultKey, BufferOrImage<BL::BackendHandle>>,
+  // Dropped resources that frames in flight may still be using.
+  deletion_queue: DeletionQueue<BL::BackendHandle>,
   buffer_image_loader: BL,
 }
 impl<BL> BufferImageStore<BL>
@@ -190,6 +331,7 @@ where
   pub fn new(buffer_loader: BL) -> Self {
     Self {
       loaded_buffers_and_images: SlotMap::new(),
+      deletion_queue: DeletionQueue::new(),
       buffer_image_loader: buffer_loader,
     }
   }
@@ -264,17 +406,106 @@ where
     ))
   }
 
+  /// Uploads a batch of buffers and images, returning a handle for each in the
+  /// order they were added.
+  pub(crate) fn submit_upload_batch(
+    this: &Arc<RwLock<Self>>, batch: UploadBatch,
+  ) -> SarektResult<(
+    Vec<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)>,
+    UploadToken,
+  )> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let resource_types = batch.resource_types();
+    let (backend_handles, token) = buffer_store
+      .buffer_image_loader
+      .submit_upload_batch(batch)?;
+
+    let mut handles = Vec::with_capacity(backend_handles.len());
+    for (backend_handle, resource_type) in backend_handles.into_iter().zip(resource_types) {
+      let buffer_or_image = BufferOrImage::new(backend_handle, resource_type);
+      let inner_key = buffer_store
+        .loaded_buffers_and_images
+        .insert(buffer_or_image);
+      handles.push((
+        BufferImageHandle {
+          inner_key,
+          resource_type,
+          buffer_store: Arc::downgrade(this),
+        },
+        buffer_or_image,
+      ));
+    }
+
+    Ok((handles, token))
+  }
+
+  pub(crate) fn is_upload_complete(&self, token: UploadToken) -> SarektResult<bool> {
+    self.buffer_image_loader.is_upload_complete(token)
+  }
+
+  pub(crate) fn wait_for_upload(&self, token: UploadToken) -> SarektResult<()> {
+    self.buffer_image_loader.wait_for_upload(token)
+  }
+
+  /// See `BufferAndImageLoader::wait_for_resource_upload`.
+  pub(crate) fn wait_for_resource_upload(&self, handle: BL::BackendHandle) -> SarektResult<()> {
+    self.buffer_image_loader.wait_for_resource_upload(handle)
+  }
+
+  /// Updates part of a loaded buffer, see
+  /// [update_buffer](trait.BufferAndImageLoader.html#tymethod.update_buffer).
+  pub(crate) fn update_buffer<BufElem: Sized + Copy>(
+    &self, handle: &BufferImageHandle<BL>, offset: usize, buffer: &[BufElem],
+  ) -> SarektResult<()> {
+    let gpu_buffer = self.get_buffer(handle)?;
+    // Uniforms have one buffer per frame in flight, use `set_uniform`.
+    if matches!(
+      gpu_buffer.resource_type,
+      ResourceType::Buffer(BufferType::Uniform)
+    ) {
+      return Err(SarektError::IncorrectLoaderFunction);
+    }
+
+    self
+      .buffer_image_loader
+      .update_buffer(gpu_buffer.handle, offset, buffer)
+  }
+
   /// Destroy a buffer and free the memory associated with it from the
-  /// backend/GPU.
+  /// backend/GPU once the frames that may be using it are done, see
+  /// `start_frame`.
   fn destroy_buffer(&mut self, inner_key: DefaultKey) -> SarektResult<()> {
     let buffer = self.loaded_buffers_and_images.remove(inner_key);
     if buffer.is_none() {
       return Err(SarektError::UnknownResource);
     }
 
-    self
-      .buffer_image_loader
-      .delete_buffer_or_image(buffer.unwrap().handle)
+    self.deletion_queue.push(buffer.unwrap().handle);
+    Ok(())
+  }
+
+  /// Must be called by the backend when it starts recording `frame`.  Destroys
+  /// buffers and images dropped during frames up to and including
+  /// `completed_frame`, which the GPU must be done with.
+  ///
+  /// Every one of them is destroyed even if some fail, the first error is
+  /// returned.
+  pub(crate) fn start_frame(
+    &mut self, frame: u64, completed_frame: Option<u64>,
+  ) -> SarektResult<()> {
+    let mut result = Ok(());
+    for handle in self.deletion_queue.start_frame(frame, completed_frame) {
+      if let Err(err) = self.buffer_image_loader.delete_buffer_or_image(handle) {
+        error!("Could not destroy dropped buffer/image: {:?}", err);
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+    result
   }
 
   /// Same as `load_buffer_with_staging` but loads an r8b8g8a8 image instead.
@@ -315,6 +546,242 @@ where
     ))
   }
 
+  /// Same as `load_image_with_staging_initialization` but with a pre-generated
+  /// mip chain.
+  pub(crate) fn load_mipmapped_image_with_staging_initialization(
+    this: &Arc<RwLock<Self>>, pixels: impl MipmappedImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .load_mipmapped_image_with_staging_initialization(
+        pixels,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        address_z,
+      )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Same as `load_image_with_staging_initialization` but for a cube image
+  /// with a pre-generated mip chain.
+  pub(crate) fn load_mipmapped_cubemap_with_staging_initialization(
+    this: &Arc<RwLock<Self>>, faces: impl MipmappedCubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .load_mipmapped_cubemap_with_staging_initialization(
+        faces,
+        magnification_filter,
+        minification_filter,
+      )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Same as `load_image_with_staging_initialization` but for a cube image.
+  pub(crate) fn load_cubemap_with_staging_initialization(
+    this: &Arc<RwLock<Self>>, faces: impl CubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, mip_levels: u32,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .load_cubemap_with_staging_initialization(
+        faces,
+        magnification_filter,
+        minification_filter,
+        mip_levels,
+      )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Same as `load_image_with_staging_initialization` but for a 2D array
+  /// image.
+  pub(crate) fn load_image_array_with_staging_initialization(
+    this: &Arc<RwLock<Self>>, layers: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .load_image_array_with_staging_initialization(
+        layers,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        mip_levels,
+      )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Same as `load_image_with_staging_initialization` but for a 3D image.
+  pub(crate) fn load_volume_image_with_staging_initialization(
+    this: &Arc<RwLock<Self>>, slices: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .load_volume_image_with_staging_initialization(
+        slices,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        address_z,
+        mip_levels,
+      )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Updates part of a loaded image, see
+  /// [update_image](trait.BufferAndImageLoader.html#tymethod.update_image).
+  pub(crate) fn update_image(
+    &self, handle: &BufferImageHandle<BL>, region: ImageRegion, pixels: impl ImageData,
+    regenerate_mipmaps: bool,
+  ) -> SarektResult<()> {
+    let image = self.get_image(handle)?;
+    self
+      .buffer_image_loader
+      .update_image(image.handle, region, pixels, regenerate_mipmaps)
+  }
+
+  pub(crate) fn query_image_format_support(&self, format: ImageDataFormat) -> ImageFormatSupport {
+    self.buffer_image_loader.query_image_format_support(format)
+  }
+
+  /// Gathers heap usage from the backend and totals up every loaded resource,
+  /// see [memory_stats](../memory_stats/index.html).
+  pub(crate) fn memory_stats(&self) -> SarektResult<MemoryStats> {
+    let mut stats = MemoryStats {
+      heaps: self.buffer_image_loader.heap_stats()?,
+      ..MemoryStats::default()
+    };
+
+    for (_, resource) in self.loaded_buffers_and_images.iter() {
+      let size = self.buffer_image_loader.allocation_size(resource.handle)?;
+      stats.add_resource(resource.resource_type, size);
+    }
+
+    Ok(stats)
+  }
+
+  pub(crate) fn memory_stats_json(&self, detailed: bool) -> SarektResult<String> {
+    self.buffer_image_loader.memory_stats_json(detailed)
+  }
+
+  /// Defragments every loaded resource and stores the moved ones' new backend
+  /// handles, so the `BufferImageHandle`s out there stay valid.  The handles
+  /// are stored even if defragmenting fails partway through.
+  pub(crate) fn defragment(&mut self) -> SarektResult<DefragmentationStats> {
+    let (keys, mut backend_handles): (Vec<_>, Vec<_>) = self
+      .loaded_buffers_and_images
+      .iter()
+      .map(|(key, resource)| (key, resource.handle))
+      .unzip();
+
+    let result = self.buffer_image_loader.defragment(&mut backend_handles);
+
+    for (key, backend_handle) in keys.into_iter().zip(backend_handles) {
+      self.loaded_buffers_and_images[key].handle = backend_handle;
+    }
+    result
+  }
+
   /// Returns the handle to buffer or image and the backend buffer or image and
   /// memory.
   pub(crate) fn create_uninitialized_image(
@@ -352,6 +819,68 @@ where
     ))
   }
 
+  /// Returns the handle to a new render texture and its backend image.
+  pub(crate) fn create_render_texture(
+    this: &Arc<RwLock<Self>>, dimensions: (u32, u32), format: ImageDataFormat,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store.buffer_image_loader.create_render_texture(
+      dimensions,
+      format,
+      magnification_filter,
+      minification_filter,
+      address_x,
+      address_y,
+    )?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
+  /// Returns the handle to a new shadow map and its backend image.
+  pub(crate) fn create_shadow_map(
+    this: &Arc<RwLock<Self>>, dimensions: (u32, u32), format: ImageDataFormat,
+  ) -> SarektResult<(BufferImageHandle<BL>, BufferOrImage<BL::BackendHandle>)> {
+    let mut buffer_store = this
+      .write()
+      .expect("Could not unlock BufferStore due to previous panic");
+
+    let buffer_backend_handle = buffer_store
+      .buffer_image_loader
+      .create_shadow_map(dimensions, format)?;
+    let buffer_or_image = BufferOrImage::new(buffer_backend_handle, ResourceType::Image);
+
+    let inner_key = buffer_store
+      .loaded_buffers_and_images
+      .insert(buffer_or_image);
+
+    Ok((
+      BufferImageHandle {
+        inner_key,
+        resource_type: ResourceType::Image,
+        buffer_store: Arc::downgrade(this),
+      },
+      buffer_or_image,
+    ))
+  }
+
   /// Same as `destroy_buffer` but for images.
   fn destroy_image(&mut self, inner_key: DefaultKey) -> SarektResult<()> {
     let image = self.loaded_buffers_and_images.remove(inner_key);
@@ -359,9 +888,8 @@ where
       return Err(SarektError::UnknownResource);
     }
 
-    self
-      .buffer_image_loader
-      .delete_buffer_or_image(image.unwrap().handle)
+    self.deletion_queue.push(image.unwrap().handle);
+    Ok(())
   }
 
   /// Retrieves the buffer associated with the handle to be bound etc.
@@ -397,11 +925,13 @@ where
   /// Does what it says on the tin, but for all the buffers.  See
   /// destroy_buffers.
   pub(crate) fn destroy_all_images_and_buffers(&mut self) {
-    for resource in self.loaded_buffers_and_images.iter() {
-      if let Err(err) = self
-        .buffer_image_loader
-        .delete_buffer_or_image(resource.1.handle)
-      {
+    let dropped = self.deletion_queue.drain_all();
+    let loaded = self
+      .loaded_buffers_and_images
+      .iter()
+      .map(|(_, resource)| resource.handle);
+    for handle in dropped.into_iter().chain(loaded) {
+      if let Err(err) = self.buffer_image_loader.delete_buffer_or_image(handle) {
         warn!(
           "Buffer/image not destroyed, maybe it was already? Error: {:?}",
           err
@@ -436,6 +966,57 @@ pub enum MagnificationMinificationFilter {
   Nearest,
 }
 
+/// The part of an image replaced by `update_image`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageRegion {
+  /// (x, y) of the first texel to replace.
+  pub offset: (u32, u32),
+  /// (width, height) of the region.  For block compressed images the offset
+  /// and dimensions must be multiples of the block size, except where the
+  /// region reaches the edge of the level.
+  pub dimensions: (u32, u32),
+  /// The mip level to write to.
+  pub mip_level: u32,
+  /// The array layer or cube face to write to, or the depth slice for 3D
+  /// images.
+  pub layer: u32,
+}
+impl ImageRegion {
+  /// All of mip level 0 (and layer 0) of an image with the given dimensions.
+  pub fn whole_image(dimensions: (u32, u32)) -> Self {
+    Self {
+      offset: (0, 0),
+      dimensions,
+      mip_level: 0,
+      layer: 0,
+    }
+  }
+}
+
+/// What the device supports for an image format, returned by
+/// `query_image_format_support`.
+///
+/// Images loaded in a format that can't be sampled are converted, block
+/// compressed ones by decoding them on the CPU (see
+/// [decompress](../../image_data/decompress/index.html)), so checking this
+/// first lets an application pick a better format for the device instead.
+/// BC6H and ASTC have no CPU decoder, so loading them fails when they can't be
+/// sampled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageFormatSupport {
+  /// Can be sampled in shaders.
+  pub sampled: bool,
+  /// Can be sampled with `MagnificationMinificationFilter::Linear`.
+  pub linear_filtering: bool,
+  /// Mip levels can be generated by the backend when loading, otherwise they
+  /// must be pre-generated.
+  pub mipmap_generation: bool,
+  /// Can be rendered to as a color attachment.
+  pub color_attachment: bool,
+  /// Can be used as a depth/stencil attachment.
+  pub depth_stencil_attachment: bool,
+}
+
 /// What to do when u/v are greater than extent.
 pub enum TextureAddressMode {
   Repeat,
@@ -1,4 +1,7 @@
-use crate::error::{SarektError, SarektResult};
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::ImageDataFormat,
+};
 use std::convert::TryFrom;
 
 /// Sarekt configuration.  Sane defaults provided (no AA, etc).
@@ -11,6 +14,26 @@ pub struct Config {
   pub engine_details: EngineDetails<'static>,
   pub present_mode: PresentMode,
   pub msaa_config: MsaaConfig,
+  pub anti_aliasing: AntiAliasingMode,
+  pub rendering_path: RenderingPath,
+  /// Bytes of uniform data that can be passed to `Drawer::draw_with_uniform`
+  /// each frame.  One buffer of this size is allocated per frame in flight.
+  pub transient_uniform_buffer_size: u64,
+  /// Width and height in texels of each cascade's tile of the shadow map.
+  /// The shadow map is a 2x2 atlas of tiles, so it is twice this on a side.
+  pub shadow_map_size: u32,
+  /// Draw the scene into an HDR target and run the post process chain over it
+  /// before presenting, see [post_process](../post_process/index.html).
+  pub post_processing: bool,
+  /// Format of the target the scene is drawn into when post processing, and
+  /// of the targets between the passes.  Falls back to another HDR format, and
+  /// then to R8G8B8A8Unorm, if the device can't render to, blend and sample it.
+  pub scene_color_format: ImageDataFormat,
+  /// Depth buffer format, one of the depth formats of `ImageDataFormat`.  None
+  /// (or a format the device can't use as a depth attachment) picks the most
+  /// precise one available.
+  pub depth_format: Option<ImageDataFormat>,
+  pub surface_format: SurfaceFormatPreference,
 }
 impl Config {
   pub fn builder() -> ConfigBuilder {
@@ -26,6 +49,14 @@ impl<'a> Default for Config {
       engine_details: EngineDetails::default(),
       present_mode: PresentMode::default(),
       msaa_config: MsaaConfig::default(),
+      anti_aliasing: AntiAliasingMode::default(),
+      rendering_path: RenderingPath::default(),
+      transient_uniform_buffer_size: 256 * 1024,
+      shadow_map_size: 1024,
+      post_processing: false,
+      scene_color_format: ImageDataFormat::R16G16B16A16Sfloat,
+      depth_format: None,
+      surface_format: SurfaceFormatPreference::default(),
     }
   }
 }
@@ -130,9 +161,28 @@ impl Default for PresentMode {
   }
 }
 
+/// The kind of swapchain image format and color space to present with.  If
+/// the surface doesn't support it `Unorm` and `Srgb` fall back to each other
+/// and `Hdr10` to `Srgb`, and failing that to whatever the surface offers
+/// first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SurfaceFormatPreference {
+  /// 8 bit UNORM, colors are written to the display as they are.
+  Unorm,
+  /// 8 bit sRGB, linear colors are gamma encoded when written.
+  Srgb,
+  /// 10 bit HDR10 (BT.2020 primaries, ST 2084 PQ encoding), needs
+  /// `Config::post_processing`, which encodes the linear output of the chain
+  /// with 1.0 as a paper white of 203 nits.  Falls back to `Srgb` without it.
+  Hdr10,
+}
+impl Default for SurfaceFormatPreference {
+  fn default() -> SurfaceFormatPreference {
+    SurfaceFormatPreference::Unorm
+  }
+}
+
 /// Configuration for MSAA.
-/// TODO(issue#32) SSAA.
-/// TODO(issue#33) other AA styles (TXAA?).
 #[derive(Copy, Clone, Debug, Default)]
 pub struct MsaaConfig {
   pub samples: NumSamples,
@@ -147,6 +197,60 @@ impl MsaaConfig {
   }
 }
 
+/// Anti-aliasing of the whole frame, on top of (or instead of) MSAA.  Both
+/// modes draw the scene into an intermediate target like
+/// `Config::post_processing` does, and are applied before the post process
+/// chain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AntiAliasingMode {
+  None,
+  /// Draws the scene `scale` times larger in each dimension, from 1 to 4, and
+  /// filters it down to the swapchain's size.  Clamped to the largest
+  /// framebuffer the device supports.
+  Supersampling {
+    scale: f32,
+  },
+  /// Offsets the projection by a different sub-pixel amount each frame and
+  /// blends the frame into the history of previous ones, reprojected with
+  /// velocities computed from the depth buffer and the camera.  Objects must
+  /// be drawn with projections from `Renderer::jitter_projection` and the
+  /// camera set each frame with `Renderer::set_view_projection`.
+  ///
+  /// Limitation: velocities only account for the camera moving.  There is no
+  /// per-object previous model matrix, so the history behind an object that
+  /// moves on its own is reprojected as if it were static and only clamped to
+  /// the colors around it, which can leave it blurred or ghosting.
+  Temporal,
+}
+impl Default for AntiAliasingMode {
+  fn default() -> AntiAliasingMode {
+    AntiAliasingMode::None
+  }
+}
+
+/// How the scene is shaded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderingPath {
+  /// Each object is shaded as it is drawn, with the lights in its uniform.
+  Forward,
+  /// Opaque objects drawn with `DefaultPbrShaderLayout` only write their
+  /// surfaces into a G-buffer (albedo, normal, material and depth), which is
+  /// then shaded once per pixel by the lights set with
+  /// `Renderer::set_scene_lights` instead of the ones in their uniforms.  Point
+  /// and spot lights only shade the pixels within their range, so scenes can
+  /// have hundreds of them.  Objects with other layouts or with
+  /// `PrimitiveState::alpha_blending` are drawn forward afterwards.
+  ///
+  /// MSAA isn't supported, the renderer falls back to one sample.  The scene
+  /// lights take about 17KB of `transient_uniform_buffer_size` each frame.
+  Deferred,
+}
+impl Default for RenderingPath {
+  fn default() -> RenderingPath {
+    RenderingPath::Forward
+  }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum NumSamples {
   One,
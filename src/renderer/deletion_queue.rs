@@ -0,0 +1,144 @@
+//! Destruction of resources deferred until the GPU is done with them.
+//!
+//! Dropping a handle while frames that use the resource are still in flight
+//! can't destroy it right away, so the stores queue it here along with the
+//! frame it was dropped during.  The renderer reports which frames have
+//! finished and the stores destroy whatever was dropped up to then.
+use std::collections::VecDeque;
+
+/// The newest frame the GPU is known to have finished when recording of
+/// `frame` starts, if any.
+///
+/// Recording waits on the fence of the frame `frames_in_flight` earlier, but
+/// dropped resources are released before that wait, so the last frame waited
+/// on was the one before that.
+pub(crate) fn completed_frame(frame: u64, frames_in_flight: usize) -> Option<u64> {
+  frame.checked_sub(frames_in_flight as u64 + 1)
+}
+
+pub(crate) struct DeletionQueue<T> {
+  /// The frame currently being recorded.
+  current_frame: u64,
+  /// Oldest first, so frames only ever increase front to back.
+  pending: VecDeque<(u64, T)>,
+}
+impl<T> DeletionQueue<T> {
+  pub(crate) fn new() -> Self {
+    Self {
+      current_frame: 0,
+      pending: VecDeque::new(),
+    }
+  }
+
+  /// Queues `resource` to be destroyed once the frame being recorded is done.
+  pub(crate) fn push(&mut self, resource: T) {
+    self.pending.push_back((self.current_frame, resource));
+  }
+
+  /// Moves on to recording `frame` and takes out everything dropped during
+  /// frames up to and including `completed_frame`, which the GPU has finished.
+  pub(crate) fn start_frame(&mut self, frame: u64, completed_frame: Option<u64>) -> Vec<T> {
+    self.current_frame = frame;
+
+    let completed_frame = match completed_frame {
+      Some(completed_frame) => completed_frame,
+      None => return Vec::new(),
+    };
+    let ready = self
+      .pending
+      .iter()
+      .take_while(|(dropped_frame, _)| *dropped_frame <= completed_frame)
+      .count();
+    self
+      .pending
+      .drain(..ready)
+      .map(|(_, resource)| resource)
+      .collect()
+  }
+
+  /// Takes out everything still queued, for when the device is idle.
+  pub(crate) fn drain_all(&mut self) -> Vec<T> {
+    self
+      .pending
+      .drain(..)
+      .map(|(_, resource)| resource)
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nothing_completes_until_the_first_frames_in_flight_are_waited_on() {
+    assert_eq!(completed_frame(0, 2), None);
+    assert_eq!(completed_frame(1, 2), None);
+    assert_eq!(completed_frame(2, 2), None);
+    assert_eq!(completed_frame(3, 2), Some(0));
+    assert_eq!(completed_frame(10, 2), Some(7));
+    assert_eq!(completed_frame(1, 0), Some(0));
+  }
+
+  #[test]
+  fn nothing_is_released_before_its_frame_completes() {
+    let mut queue = DeletionQueue::new();
+    queue.start_frame(5, completed_frame(5, 2));
+    queue.push("dropped during 5");
+
+    for frame in 6..8 {
+      assert!(queue
+        .start_frame(frame, completed_frame(frame, 2))
+        .is_empty());
+    }
+    assert_eq!(
+      queue.start_frame(8, completed_frame(8, 2)),
+      vec!["dropped during 5"]
+    );
+    assert!(queue.start_frame(9, completed_frame(9, 2)).is_empty());
+  }
+
+  #[test]
+  fn frames_are_drained_in_order() {
+    let mut queue = DeletionQueue::new();
+    queue.start_frame(0, None);
+    queue.push(0);
+    queue.push(1);
+    queue.start_frame(1, None);
+    queue.push(2);
+    queue.start_frame(2, None);
+    queue.push(3);
+
+    assert_eq!(queue.start_frame(3, Some(0)), vec![0, 1]);
+    assert_eq!(queue.start_frame(4, Some(2)), vec![2, 3]);
+    assert!(queue.start_frame(5, Some(4)).is_empty());
+  }
+
+  #[test]
+  fn no_completed_frame_releases_nothing() {
+    let mut queue = DeletionQueue::new();
+    queue.push(0);
+    queue.start_frame(1, None);
+    queue.push(1);
+
+    assert!(queue.start_frame(2, None).is_empty());
+    // Still queued, and pushes now belong to frame 2.
+    queue.push(2);
+    assert_eq!(queue.start_frame(3, Some(1)), vec![0, 1]);
+    assert_eq!(queue.start_frame(4, Some(2)), vec![2]);
+  }
+
+  #[test]
+  fn drain_all_takes_everything_regardless_of_frame() {
+    let mut queue = DeletionQueue::new();
+    queue.push(0);
+    queue.start_frame(1, None);
+    queue.push(1);
+    queue.start_frame(2, None);
+    queue.push(2);
+
+    assert_eq!(queue.drain_all(), vec![0, 1, 2]);
+    assert!(queue.drain_all().is_empty());
+    assert!(queue.start_frame(3, Some(2)).is_empty());
+  }
+}
@@ -1,23 +1,32 @@
 use std::fmt::Debug;
 
 use crate::{
-  error::SarektResult,
+  error::{SarektError, SarektResult},
   renderer::{
     buffers_and_images::{
       BackendHandleTrait, BufferAndImageLoader, BufferImageHandle, UniformBufferHandle,
     },
+    primitive_state::PrimitiveState,
     vertex_bindings::DefaultForwardShaderLayout,
     Renderer, VulkanRenderer,
   },
 };
 
+/// How many textures a drawable object can be drawn with, see
+/// `DrawableObjectBuilder::texture_image_at`.
+pub const MAX_TEXTURE_SLOTS: usize = 8;
+
 /// The object that is passed to Drawer's draw method.  Contains all the
 /// necessary information to perform a draw command.
 ///
 /// vertex_buffer is the list of vertices for the mesh to draw, index_buffer is
 /// optional and contains the order of indices to make the mesh in the vertex
 /// buffer, and uniform_buffer contains the uniform data for the associated
-/// shaders/pipeline.
+/// shaders/pipeline.  uniform_buffer is optional too when the object is drawn
+/// with `Drawer::draw_with_uniform`.  texture_images are bound in order to the
+/// texture bindings of the shaders, empty slots get a transparent null
+/// texture.  primitive_state selects the topology and line/point size the
+/// mesh is drawn with, triangle lists by default.
 ///
 /// This struct is constructed using references and the lifetime specifications
 /// will not allow this class to outlive them.
@@ -32,15 +41,15 @@ pub struct DrawableObject<
   R::BL: BufferAndImageLoader,
   <R::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug,
 {
-  pub(crate) vertex_buffer: <R::BL as BufferAndImageLoader>::BackendHandle,
-  pub(crate) index_buffer: Option<<R::BL as BufferAndImageLoader>::BackendHandle>,
-  pub(crate) uniform_buffer: <R::BL as BufferAndImageLoader>::UniformBufferDataHandle,
-  pub(crate) texture_image: Option<<R::BL as BufferAndImageLoader>::BackendHandle>,
+  // The backend handles are looked up when drawing, since they can change when
+  // the renderer defragments memory.
+  pub(crate) vertex_buffer: &'a BufferImageHandle<R::BL>,
+  pub(crate) index_buffer: Option<&'b BufferImageHandle<R::BL>>,
+  pub(crate) uniform_buffer: Option<<R::BL as BufferAndImageLoader>::UniformBufferDataHandle>,
+  pub(crate) texture_images: [Option<&'d BufferImageHandle<R::BL>>; MAX_TEXTURE_SLOTS],
+  pub(crate) primitive_state: PrimitiveState,
 
-  _vertex_marker: std::marker::PhantomData<&'a BufferImageHandle<R::BL>>,
-  _index_marker: std::marker::PhantomData<&'b BufferImageHandle<R::BL>>,
   _uniform_marker: std::marker::PhantomData<&'c BufferImageHandle<R::BL>>,
-  _texture_image_marker: std::marker::PhantomData<&'d BufferImageHandle<R::BL>>,
 
   _uniform_type: std::marker::PhantomData<DescriptorLayoutStruct>,
 }
@@ -58,35 +67,39 @@ where
       vertex_buffer: None,
       index_buffer: None,
       uniform_buffer: None,
-      texture_image: None,
+      texture_images: [None; MAX_TEXTURE_SLOTS],
+      primitive_state: PrimitiveState::default(),
     }
   }
 
   pub fn new(
     renderer: &R, vertex_buffer: &'a BufferImageHandle<R::BL>,
     index_buffer: Option<&'b BufferImageHandle<R::BL>>,
-    uniform_buffer_handle: &'c UniformBufferHandle<R::BL, DescriptorLayoutStruct>,
-    texture_image: Option<&'d BufferImageHandle<R::BL>>,
+    uniform_buffer_handle: Option<&'c UniformBufferHandle<R::BL, DescriptorLayoutStruct>>,
+    texture_images: [Option<&'d BufferImageHandle<R::BL>>; MAX_TEXTURE_SLOTS],
+    primitive_state: PrimitiveState,
   ) -> SarektResult<Self> {
-    let vertex_buffer = renderer.get_buffer(vertex_buffer)?;
-    let index_buffer = index_buffer
-      .map(|ibh| renderer.get_buffer(ibh))
-      .transpose()?;
-    let uniform_buffer = renderer.get_uniform_buffer(uniform_buffer_handle)?;
-    let texture_image = texture_image
-      .map(|tih| renderer.get_image(tih))
+    // Check they are what they claim to be up front.
+    renderer.get_buffer(vertex_buffer)?;
+    if let Some(ibh) = index_buffer {
+      renderer.get_buffer(ibh)?;
+    }
+    let uniform_buffer = uniform_buffer_handle
+      .map(|ubh| renderer.get_uniform_buffer(ubh))
       .transpose()?;
+    for tih in texture_images.iter().flatten() {
+      renderer.get_image(tih)?;
+    }
+    renderer.check_primitive_state_support(&primitive_state)?;
 
     Ok(Self {
       vertex_buffer,
       index_buffer,
       uniform_buffer,
-      texture_image,
+      texture_images,
+      primitive_state,
 
-      _vertex_marker: std::marker::PhantomData,
-      _index_marker: std::marker::PhantomData,
       _uniform_marker: std::marker::PhantomData,
-      _texture_image_marker: std::marker::PhantomData,
 
       _uniform_type: std::marker::PhantomData,
     })
@@ -94,11 +107,35 @@ where
 
   // TODO(issue#6) BUFFERS BACKLOG for UniformBufferHandle/DataHandle can specify
   // push_constant type and switch on that in update uniform.
-  // TODO(issue#7) PERFORMANCE allow setting at offsets/fields in uniform so you
-  // don't have to copy over the whole thing.
   /// Set the value of a uniform in the renderer.
   pub fn set_uniform(&self, renderer: &R, data: &DescriptorLayoutStruct) -> SarektResult<()> {
-    renderer.set_uniform(&self.uniform_buffer, data)
+    match &self.uniform_buffer {
+      Some(uniform_buffer) => renderer.set_uniform(uniform_buffer, data),
+      None => Err(SarektError::NoUniformBuffer),
+    }
+  }
+
+  /// Overwrites only part of the uniform, see `Renderer::set_uniform_bytes`.
+  pub fn set_uniform_bytes(
+    &self, renderer: &R, byte_offset: usize, bytes: &[u8],
+  ) -> SarektResult<()> {
+    match &self.uniform_buffer {
+      Some(uniform_buffer) => renderer.set_uniform_bytes(uniform_buffer, byte_offset, bytes),
+      None => Err(SarektError::NoUniformBuffer),
+    }
+  }
+
+  /// Overwrites consecutive elements of the uniform starting at `byte_offset`,
+  /// such as a few entries of an array of lights.  Get the offset of a field
+  /// with `offset_of!` from the memoffset crate and add the index times the
+  /// element size.
+  pub fn set_uniform_slice<T: Sized + Copy>(
+    &self, renderer: &R, byte_offset: usize, data: &[T],
+  ) -> SarektResult<()> {
+    let bytes = unsafe {
+      std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+    };
+    self.set_uniform_bytes(renderer, byte_offset, bytes)
   }
 }
 
@@ -119,7 +156,8 @@ pub struct DrawableObjectBuilder<
   pub vertex_buffer: Option<&'a BufferImageHandle<R::BL>>,
   pub index_buffer: Option<&'b BufferImageHandle<R::BL>>,
   pub uniform_buffer: Option<&'c UniformBufferHandle<R::BL, DescriptorLayoutStruct>>,
-  pub texture_image: Option<&'d BufferImageHandle<R::BL>>,
+  pub texture_images: [Option<&'d BufferImageHandle<R::BL>>; MAX_TEXTURE_SLOTS],
+  pub primitive_state: PrimitiveState,
 }
 impl<'r, 'a, 'b, 'c, 'd, R: Renderer, DescriptorLayoutStruct: Sized + Copy>
   DrawableObjectBuilder<'r, 'a, 'b, 'c, 'd, R, DescriptorLayoutStruct>
@@ -132,8 +170,9 @@ where
       self.renderer.unwrap(),
       self.vertex_buffer.unwrap(),
       self.index_buffer,
-      self.uniform_buffer.unwrap(),
-      self.texture_image,
+      self.uniform_buffer,
+      self.texture_images,
+      self.primitive_state,
     )
   }
 
@@ -156,8 +195,21 @@ where
     self
   }
 
-  pub fn texture_image(mut self, texture_image: &'d BufferImageHandle<R::BL>) -> Self {
-    self.texture_image = Some(texture_image);
+  /// Sets the first texture, the only one the default shaders sample.
+  pub fn texture_image(self, texture_image: &'d BufferImageHandle<R::BL>) -> Self {
+    self.texture_image_at(0, texture_image)
+  }
+
+  /// Sets the texture in `slot`, which must be less than `MAX_TEXTURE_SLOTS`.
+  pub fn texture_image_at(
+    mut self, slot: usize, texture_image: &'d BufferImageHandle<R::BL>,
+  ) -> Self {
+    self.texture_images[slot] = Some(texture_image);
+    self
+  }
+
+  pub fn primitive_state(mut self, primitive_state: PrimitiveState) -> Self {
+    self.primitive_state = primitive_state;
     self
   }
 }
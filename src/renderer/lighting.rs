@@ -0,0 +1,214 @@
+//! Lights and materials for the built-in lit forward shaders,
+//! sarekt_forward_lit, which shade with Phong or Blinn-Phong.
+//!
+//! Objects are drawn lit when their uniform layout is `DefaultLitShaderLayout`
+//! and their vertices are `DefaultLitShaderVertex`, which have normals.  The
+//! lights are part of every object's uniform, so each object can be lit by the
+//! lights nearest to it.
+//!
+//! ```ignore
+//! let mut uniform = DefaultLitShaderLayout::new(
+//!   model,
+//!   view,
+//!   projection,
+//!   camera_position,
+//!   Material::new(uv::Vec3::new(0.8f32, 0.2f32, 0.2f32), uv::Vec3::one(), 32f32),
+//! );
+//! uniform.set_directional_lights(&[DirectionalLight::new(
+//!   uv::Vec3::new(-1f32, -1f32, 0f32),
+//!   uv::Vec3::one(),
+//!   0.8f32,
+//! )])?;
+//! uniform.set_point_lights(&[PointLight::new(lamp_position, uv::Vec3::one(), 2f32, 10f32)])?;
+//! renderer.draw_with_uniform(&object, &uniform)?;
+//! ```
+//!
+//! With `RenderingPath::Deferred` opaque PBR objects are lit by the lights of
+//! the whole scene instead, which can be many more:
+//!
+//! ```ignore
+//! renderer.set_scene_lights(SceneLights {
+//!   point_lights: lamps.iter().map(|lamp| lamp.light()).collect(),
+//!   directional_lights: vec![sun],
+//!   ..SceneLights::default()
+//! })?;
+//! ```
+use crate::error::{SarektError, SarektResult};
+use ultraviolet as uv;
+
+/// Most point lights a lit object can be lit by.
+pub const MAX_POINT_LIGHTS: usize = 4;
+/// Most directional lights a lit object can be lit by.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 2;
+/// Most spot lights a lit object can be lit by.
+pub const MAX_SPOT_LIGHTS: usize = 4;
+/// Most point lights the deferred renderer lights the scene with.
+pub const MAX_DEFERRED_POINT_LIGHTS: usize = 256;
+/// Most directional lights the deferred renderer lights the scene with.
+pub const MAX_DEFERRED_DIRECTIONAL_LIGHTS: usize = 4;
+/// Most spot lights the deferred renderer lights the scene with.
+pub const MAX_DEFERRED_SPOT_LIGHTS: usize = 128;
+
+/// How specular highlights are computed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadingModel {
+  /// Reflects the light about the normal and compares it to the view
+  /// direction.
+  Phong,
+  /// Compares the normal to the half vector between the light and view
+  /// directions, which gives wider and more natural highlights.
+  BlinnPhong,
+}
+impl Default for ShadingModel {
+  fn default() -> Self {
+    ShadingModel::BlinnPhong
+  }
+}
+impl From<ShadingModel> for u32 {
+  fn from(shading_model: ShadingModel) -> u32 {
+    match shading_model {
+      ShadingModel::Phong => 0,
+      ShadingModel::BlinnPhong => 1,
+    }
+  }
+}
+
+/// Light shining in all directions from a position.  It fades out smoothly
+/// and is gone at `range`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PointLight {
+  pub position: uv::Vec3,
+  pub intensity: f32,
+  pub color: uv::Vec3,
+  pub range: f32,
+}
+impl PointLight {
+  pub fn new(position: uv::Vec3, color: uv::Vec3, intensity: f32, range: f32) -> Self {
+    Self {
+      position,
+      intensity,
+      color,
+      range,
+    }
+  }
+}
+
+/// Light infinitely far away, such as the sun.  `direction` is the direction
+/// the light travels in.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DirectionalLight {
+  pub direction: uv::Vec3,
+  pub intensity: f32,
+  pub color: uv::Vec3,
+  _padding: f32,
+}
+impl DirectionalLight {
+  pub fn new(direction: uv::Vec3, color: uv::Vec3, intensity: f32) -> Self {
+    Self {
+      direction,
+      intensity,
+      color,
+      _padding: 0f32,
+    }
+  }
+}
+
+/// A cone of light from `position` along `direction`.  It is full strength
+/// within `inner_cone_angle` of the direction and fades out by
+/// `outer_cone_angle`, both in radians.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpotLight {
+  pub position: uv::Vec3,
+  pub intensity: f32,
+  pub direction: uv::Vec3,
+  pub range: f32,
+  pub color: uv::Vec3,
+  /// Cosines of the cone angles, which is what the shader compares against.
+  pub inner_cone_cos: f32,
+  pub outer_cone_cos: f32,
+  _padding: [f32; 3],
+}
+impl SpotLight {
+  pub fn new(
+    position: uv::Vec3, direction: uv::Vec3, color: uv::Vec3, intensity: f32, range: f32,
+    inner_cone_angle: f32, outer_cone_angle: f32,
+  ) -> Self {
+    Self {
+      position,
+      intensity,
+      direction,
+      range,
+      color,
+      inner_cone_cos: inner_cone_angle.cos(),
+      outer_cone_cos: outer_cone_angle.cos(),
+      _padding: [0f32; 3],
+    }
+  }
+}
+
+/// How a lit surface reflects light.  The diffuse color is multiplied with the
+/// vertex color and texture when those are enabled.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Material {
+  pub diffuse: uv::Vec3,
+  /// Higher is a smaller, sharper highlight.
+  pub shininess: f32,
+  pub specular: uv::Vec3,
+  /// Fraction of the diffuse color shown without any light.
+  pub ambient: f32,
+}
+impl Material {
+  pub fn new(diffuse: uv::Vec3, specular: uv::Vec3, shininess: f32) -> Self {
+    Self {
+      diffuse,
+      shininess,
+      specular,
+      ambient: 0.1f32,
+    }
+  }
+}
+impl Default for Material {
+  fn default() -> Self {
+    Self::new(
+      uv::Vec3::one(),
+      uv::Vec3::new(0.5f32, 0.5f32, 0.5f32),
+      32f32,
+    )
+  }
+}
+
+/// Every light in the scene, which the deferred renderer shades the G-buffer
+/// with, see `Renderer::set_scene_lights`.
+#[derive(Clone, Debug, Default)]
+pub struct SceneLights {
+  pub point_lights: Vec<PointLight>,
+  pub directional_lights: Vec<DirectionalLight>,
+  pub spot_lights: Vec<SpotLight>,
+}
+impl SceneLights {
+  /// Checks there are no more lights than the deferred renderer supports.
+  pub fn check_counts(&self) -> SarektResult<()> {
+    let counts = [
+      ("point", self.point_lights.len(), MAX_DEFERRED_POINT_LIGHTS),
+      (
+        "directional",
+        self.directional_lights.len(),
+        MAX_DEFERRED_DIRECTIONAL_LIGHTS,
+      ),
+      ("spot", self.spot_lights.len(), MAX_DEFERRED_SPOT_LIGHTS),
+    ];
+    for &(kind, count, max) in counts.iter() {
+      if count > max {
+        return Err(SarektError::TooManyLights(format!(
+          "{} {} lights in the scene, at most {} are supported",
+          count, kind, max
+        )));
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,246 @@
+//! GPU memory usage, retrieved with `Renderer::memory_stats`, for keeping an
+//! eye on VRAM in an overlay or failing a CI run that suddenly uses more.
+//!
+//! `Renderer::memory_stats_json` additionally dumps everything the backend's
+//! allocator knows, which for Vulkan is VMA's JSON statistics string.
+//!
+//! When loading and unloading lots of assets leaves memory fragmented,
+//! `Renderer::defragment` packs resources back together.
+//!
+//! Allocations that the allocator may take back under memory pressure ("lost"
+//! allocations in VMA) are not supported, every resource stays in memory until
+//! it is dropped.
+use crate::renderer::buffers_and_images::{BufferType, ResourceType};
+
+/// How many of the largest allocations are kept in
+/// [MemoryStats::largest_allocations](struct.MemoryStats.html#structfield.
+/// largest_allocations).
+pub const LARGEST_ALLOCATIONS_KEPT: usize = 16;
+
+/// A snapshot of memory used by the renderer.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStats {
+  /// One for every memory heap the device has, in the backend's order.
+  pub heaps: Vec<HeapStats>,
+  /// Every loaded image, including the renderer's own attachments.
+  pub images: ResourceMemoryStats,
+  pub vertex_buffers: ResourceMemoryStats,
+  pub index_buffers: ResourceMemoryStats,
+  /// Each uniform buffer is counted once per frame in flight, the transient
+  /// uniform buffer used by `Drawer::draw_with_uniform` is counted once.
+  pub uniform_buffers: ResourceMemoryStats,
+  /// The largest buffers and images, biggest first.
+  pub largest_allocations: Vec<AllocationStats>,
+}
+impl MemoryStats {
+  /// Adds a loaded resource to the per type totals and the largest
+  /// allocations.
+  pub(crate) fn add_resource(&mut self, resource_type: ResourceType, size: u64) {
+    let totals = match resource_type {
+      ResourceType::Image => &mut self.images,
+      ResourceType::Buffer(BufferType::Vertex) => &mut self.vertex_buffers,
+      ResourceType::Buffer(BufferType::Index(_)) => &mut self.index_buffers,
+      ResourceType::Buffer(BufferType::Uniform) => &mut self.uniform_buffers,
+    };
+    totals.count += 1;
+    totals.bytes += size;
+
+    let position = self
+      .largest_allocations
+      .iter()
+      .position(|allocation| allocation.size < size)
+      .unwrap_or(self.largest_allocations.len());
+    if position < LARGEST_ALLOCATIONS_KEPT {
+      self.largest_allocations.insert(
+        position,
+        AllocationStats {
+          resource_type,
+          size,
+        },
+      );
+      self.largest_allocations.truncate(LARGEST_ALLOCATIONS_KEPT);
+    }
+  }
+
+  /// Bytes allocated across all heaps.
+  pub fn total_allocated(&self) -> u64 {
+    self.heaps.iter().map(|heap| heap.allocated).sum()
+  }
+}
+
+/// Usage of a single memory heap, all in bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+  /// Total size of the heap.
+  pub size: u64,
+  /// Whether this is the GPU's own memory (VRAM on discrete cards).
+  pub device_local: bool,
+  /// Memory the renderer has allocated from the heap, including free space
+  /// inside its blocks.
+  pub allocated: u64,
+  /// The part of `allocated` actually occupied by buffers and images.
+  pub used: u64,
+  /// How much of the heap the whole process can expect to use before
+  /// allocations start failing or paging, as reported by the driver.  This
+  /// changes as other applications allocate from the heap.
+  ///
+  /// None if the device doesn't support `VK_EXT_memory_budget`, in which case
+  /// only `size` is known.
+  pub budget: Option<u64>,
+  /// Number of buffers and images in the heap.
+  pub allocation_count: u32,
+}
+
+/// Count and size of all resources of one type.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceMemoryStats {
+  pub count: u32,
+  pub bytes: u64,
+}
+
+/// One entry of
+/// [MemoryStats::largest_allocations](struct.MemoryStats.html#structfield.
+/// largest_allocations).
+#[derive(Copy, Clone, Debug)]
+pub struct AllocationStats {
+  pub resource_type: ResourceType,
+  /// Size in bytes, including any padding required by the device.
+  pub size: u64,
+}
+
+/// What `Renderer::defragment` did.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DefragmentationStats {
+  /// Buffers and images that were moved.
+  pub allocations_moved: u32,
+  /// Bytes copied while moving them.
+  pub bytes_moved: u64,
+  /// Memory blocks returned to the driver.
+  pub blocks_freed: u32,
+  /// Bytes returned to the driver.
+  pub bytes_freed: u64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::buffers_and_images::IndexBufferElemSize;
+
+  fn sizes(stats: &MemoryStats) -> Vec<u64> {
+    stats
+      .largest_allocations
+      .iter()
+      .map(|allocation| allocation.size)
+      .collect()
+  }
+
+  #[test]
+  fn totals_are_bucketed_by_resource_type() {
+    let mut stats = MemoryStats::default();
+    stats.add_resource(ResourceType::Image, 100);
+    stats.add_resource(ResourceType::Image, 50);
+    stats.add_resource(ResourceType::Buffer(BufferType::Vertex), 10);
+    stats.add_resource(
+      ResourceType::Buffer(BufferType::Index(IndexBufferElemSize::UInt16)),
+      20,
+    );
+    stats.add_resource(
+      ResourceType::Buffer(BufferType::Index(IndexBufferElemSize::UInt32)),
+      40,
+    );
+    stats.add_resource(ResourceType::Buffer(BufferType::Uniform), 256);
+
+    assert_eq!(
+      stats.images,
+      ResourceMemoryStats {
+        count: 2,
+        bytes: 150
+      }
+    );
+    assert_eq!(
+      stats.vertex_buffers,
+      ResourceMemoryStats {
+        count: 1,
+        bytes: 10
+      }
+    );
+    assert_eq!(
+      stats.index_buffers,
+      ResourceMemoryStats {
+        count: 2,
+        bytes: 60
+      }
+    );
+    assert_eq!(
+      stats.uniform_buffers,
+      ResourceMemoryStats {
+        count: 1,
+        bytes: 256
+      }
+    );
+  }
+
+  #[test]
+  fn largest_allocations_are_sorted_biggest_first() {
+    let mut stats = MemoryStats::default();
+    for &size in [30, 10, 50, 20, 40].iter() {
+      stats.add_resource(ResourceType::Image, size);
+    }
+
+    assert_eq!(sizes(&stats), vec![50, 40, 30, 20, 10]);
+  }
+
+  #[test]
+  fn equal_sizes_keep_the_order_they_were_added_in() {
+    let mut stats = MemoryStats::default();
+    stats.add_resource(ResourceType::Image, 64);
+    stats.add_resource(ResourceType::Buffer(BufferType::Vertex), 64);
+    stats.add_resource(ResourceType::Buffer(BufferType::Uniform), 128);
+
+    assert_eq!(sizes(&stats), vec![128, 64, 64]);
+    match stats.largest_allocations[1].resource_type {
+      ResourceType::Image => {}
+      other => panic!("expected the image first, got {:?}", other),
+    }
+    match stats.largest_allocations[2].resource_type {
+      ResourceType::Buffer(BufferType::Vertex) => {}
+      other => panic!("expected the vertex buffer second, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn only_the_largest_allocations_are_kept() {
+    let mut stats = MemoryStats::default();
+    let count = LARGEST_ALLOCATIONS_KEPT as u64 * 2;
+    for size in 1..=count {
+      stats.add_resource(ResourceType::Image, size);
+    }
+    // Smaller than everything kept, so it is dropped.
+    stats.add_resource(ResourceType::Image, 1);
+
+    let expected: Vec<u64> = (count - LARGEST_ALLOCATIONS_KEPT as u64 + 1..=count)
+      .rev()
+      .collect();
+    assert_eq!(sizes(&stats), expected);
+    // Totals still count everything.
+    assert_eq!(stats.images.count, count as u32 + 1);
+    assert_eq!(stats.images.bytes, count * (count + 1) / 2 + 1);
+  }
+
+  #[test]
+  fn equal_to_the_smallest_kept_allocation_is_dropped() {
+    let mut stats = MemoryStats::default();
+    for _ in 0..LARGEST_ALLOCATIONS_KEPT {
+      stats.add_resource(ResourceType::Image, 8);
+    }
+    stats.add_resource(ResourceType::Buffer(BufferType::Vertex), 8);
+
+    assert_eq!(stats.largest_allocations.len(), LARGEST_ALLOCATIONS_KEPT);
+    for allocation in stats.largest_allocations.iter() {
+      match allocation.resource_type {
+        ResourceType::Image => {}
+        other => panic!("expected only images, got {:?}", other),
+      }
+    }
+  }
+}
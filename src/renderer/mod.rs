@@ -50,9 +50,12 @@
 //! - [x] Actually rendering something of your choosing
 //! - [x] Mipmapping, AA.
 //! - [ ] Multiple pipeline creation.
-//! - [ ] Dynamic lighting using a Phong shader.
-//! - [ ] Dynamic lighting using PBR.
-//! - [ ] Advanced lighting and shadows.
+//! - [x] Dynamic lighting using a Phong shader.
+//! - [x] Dynamic lighting using PBR.
+//! - [x] Shadows.
+//! - [ ] Advanced lighting.
+//! - [x] Deferred rendering.
+//! - [x] Post processing.
 //! - [ ] Multiple uniform buffers/descriptors for drawable objects.
 //! - [ ] Multiple uniform buffers for drawable objects.
 //! - [ ] Multithreading.
@@ -63,9 +66,20 @@
 pub mod buffers_and_images;
 pub mod config;
 pub mod drawable_object;
+pub mod lighting;
+pub mod memory_stats;
+pub mod pbr;
+pub mod post_process;
+pub mod primitive_state;
+pub mod render_texture;
 pub mod shaders;
+pub mod shadows;
+pub mod uniform_layout;
+pub mod upload_batch;
 pub mod vertex_bindings;
+pub mod vertex_formats;
 
+mod deletion_queue;
 mod vulkan;
 
 pub use crate::{
@@ -75,20 +89,34 @@ pub use crate::{
 pub use shaders::{ShaderHandle, ShaderType};
 pub use vulkan::{
   vulkan_buffer_image_functions::VulkanBufferImageFunctions, vulkan_renderer::VulkanRenderer,
+  vulkan_vertex_bindings::VertexAttributeFormat,
 };
 
 use crate::{
-  image_data::ImageData,
+  image_data::{
+    cubemap::{CubemapImageData, MipmappedCubemapImageData},
+    environment::EnvironmentMaps,
+    layered::LayeredImageData,
+    ImageData, ImageDataFormat, MipmappedImageData,
+  },
   renderer::{
     buffers_and_images::{
-      BackendHandleTrait, BufferAndImageLoader, BufferImageHandle, BufferType,
-      MagnificationMinificationFilter, TextureAddressMode, UniformBufferHandle,
+      BackendHandleTrait, BufferAndImageLoader, BufferImageHandle, BufferType, ImageFormatSupport,
+      ImageRegion, MagnificationMinificationFilter, TextureAddressMode, UniformBufferHandle,
     },
     drawable_object::DrawableObject,
+    lighting::SceneLights,
+    memory_stats::{DefragmentationStats, MemoryStats},
+    post_process::PostProcessPass,
+    primitive_state::PrimitiveState,
+    render_texture::RenderTextureHandle,
+    shadows::ShadowLight,
+    upload_batch::{UploadBatch, UploadToken},
     vertex_bindings::DescriptorLayoutInfo,
   },
 };
 use std::fmt::Debug;
+use ultraviolet as uv;
 
 // ================================================================================
 //  Compile Time Constants and Configurations
@@ -148,6 +176,47 @@ pub trait Renderer {
     Self::BL: BufferAndImageLoader,
     <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
 
+  /// Same as `load_buffer` but keeps the buffer in host visible memory, for
+  /// vertices and indices that are rewritten often such as deformable meshes
+  /// and procedural geometry.  Drawing from it may be slower.
+  fn load_dynamic_buffer<BufElem: Sized + Copy>(
+    &mut self, buffer_type: BufferType, buffer: &[BufElem],
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Overwrites part of a vertex or index buffer, starting at element
+  /// `offset`.  The buffer keeps the size it was loaded with.  Buffers from
+  /// `load_dynamic_buffer` are written directly, others through a staging
+  /// buffer.
+  ///
+  /// Waits for the frames in flight, which may be drawing from the buffer, and
+  /// for its upload batch if it is still uploading, to finish first.
+  fn update_buffer<BufElem: Sized + Copy>(
+    &self, handle: &BufferImageHandle<Self::BL>, offset: usize, buffer: &[BufElem],
+  ) -> SarektResult<()>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Submits every buffer and image in the batch together and returns right
+  /// away, see [upload_batch](upload_batch/index.html).  The handles are in the
+  /// order the resources were added to the batch.
+  fn submit_upload_batch(
+    &mut self, batch: UploadBatch,
+  ) -> SarektResult<(Vec<BufferImageHandle<Self::BL>>, UploadToken)>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Whether a submitted batch has finished uploading.  Polling this also
+  /// frees the staging memory of finished batches.
+  fn is_upload_complete(&self, token: UploadToken) -> SarektResult<bool>;
+
+  /// Blocks until a submitted batch has finished uploading.
+  fn wait_for_upload(&self, token: UploadToken) -> SarektResult<()>;
+
   /// Gets a buffer given th handle generated when it was loaded (see
   /// load_buffer).
   fn get_buffer(
@@ -165,6 +234,22 @@ pub trait Renderer {
     Self::BL: BufferAndImageLoader,
     <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
 
+  /// Same as `load_uniform_buffer`, but if the type describes its GLSL block
+  /// with `DescriptorLayoutInfo::get_uniform_block_layout` the Rust layout is
+  /// checked against it first, see [uniform_layout](uniform_layout/index.html).
+  fn load_checked_uniform_buffer<UniformBufElem: Sized + Copy + DescriptorLayoutInfo>(
+    &mut self, buffer: UniformBufElem,
+  ) -> SarektResult<UniformBufferHandle<Self::BL, UniformBufElem>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug,
+  {
+    if let Some(block_layout) = UniformBufElem::get_uniform_block_layout() {
+      block_layout.validate()?;
+    }
+    self.load_uniform_buffer(buffer)
+  }
+
   /// Returns a uniform buffer given the handle returned in load_uniform_buffer.
   fn get_uniform_buffer<UniformBufElem: Sized + Copy>(
     &self, handle: &UniformBufferHandle<Self::BL, UniformBufElem>,
@@ -180,6 +265,19 @@ pub trait Renderer {
   where
     Self::BL: BufferAndImageLoader;
 
+  /// Overwrites `bytes` of the uniform buffer's value starting at
+  /// `byte_offset`, leaving the rest as it was, for uniforms with large arrays
+  /// where only a few entries change.  The range must fit in the buffer.
+  ///
+  /// The frame being recorded uses the change right away, the copies of the
+  /// uniform for the other frames in flight get it when those frames start.
+  fn set_uniform_bytes(
+    &self, handle_data: &<Self::BL as BufferAndImageLoader>::UniformBufferDataHandle,
+    byte_offset: usize, bytes: &[u8],
+  ) -> SarektResult<()>
+  where
+    Self::BL: BufferAndImageLoader;
+
   /// Loads a 32 bit r8b8g8a8 image (texture) into the renderer using a staging
   /// buffer. [ImageData](trait.ImageData.html) must be implemented for the
   /// type, see its documentation for details.
@@ -201,6 +299,199 @@ pub trait Renderer {
     Self::BL: BufferAndImageLoader,
     <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
 
+  /// Same as `load_image_with_staging_initialization`, but the mip chain comes
+  /// pre-generated with the image (for example from a KTX2 or DDS container,
+  /// see [image_data](../image_data/index.html)) instead of being generated
+  /// by the backend.  This works for formats that can't be blitted and
+  /// uploads every level in a single staging copy.
+  fn load_mipmapped_image_with_staging_initialization(
+    &mut self, pixels: impl MipmappedImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode,
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Loads the six faces of a cube image, see
+  /// [cubemap](../image_data/cubemap/index.html) for building one out of
+  /// separate faces or an equirectangular panorama.  Filters and mip levels
+  /// work the same as in `load_image_with_staging_initialization`.
+  fn load_cubemap_with_staging_initialization(
+    &mut self, faces: impl CubemapImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Same as `load_cubemap_with_staging_initialization` but with every mip
+  /// level of every face supplied, for example a prefiltered environment map
+  /// from [environment](../image_data/environment/index.html).
+  fn load_mipmapped_cubemap_with_staging_initialization(
+    &mut self, faces: impl MipmappedCubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Loads a 2D array image, each layer is sampled separately by its index
+  /// (a `sampler2DArray` in GLSL).  See
+  /// [layered](../image_data/layered/index.html) for building the layers.
+  /// Mipmaps are generated for every layer.
+  fn load_image_array_with_staging_initialization(
+    &mut self, layers: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Loads a 3D image whose depth slices are the given layers (a `sampler3D`
+  /// in GLSL).  Mipmaps are downsampled in depth as well.
+  fn load_volume_image_with_staging_initialization(
+    &mut self, slices: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Replaces part of an image after it was loaded, for video frames, dynamic
+  /// font atlases, painted decals and the like.  `pixels` must have the
+  /// dimensions of the region and the format the image was loaded with.  If
+  /// `regenerate_mipmaps` is set the levels below the updated one are
+  /// regenerated from it.
+  ///
+  /// Waits for the frames in flight, which may be sampling the image, and for
+  /// its upload batch if it is still uploading, to finish first.
+  fn update_image(
+    &self, handle: &BufferImageHandle<Self::BL>, region: ImageRegion, pixels: impl ImageData,
+    regenerate_mipmaps: bool,
+  ) -> SarektResult<()>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Creates a render texture of the given color format with its own depth
+  /// buffer, see [render_texture](render_texture/index.html).  Drawing into it
+  /// clears it to `clear_color` first.  Filters and address modes are how it
+  /// is sampled, like in `load_image_with_staging_initialization`.
+  ///
+  /// Fails with `UnsupportedRenderTextureFormat` if the device can't both
+  /// render to and sample the format.
+  fn load_render_texture(
+    &mut self, dimensions: (u32, u32), format: ImageDataFormat, clear_color: [f32; 4],
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode,
+  ) -> SarektResult<RenderTextureHandle<Self::BL>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Sets the cubemap the built-in skybox pass draws behind everything else at
+  /// the end of each frame, or disables the pass with None.  The renderer keeps
+  /// the handle alive while it is in use and hands back the previous one.
+  fn set_skybox(
+    &mut self, cubemap: Option<BufferImageHandle<Self::BL>>,
+  ) -> SarektResult<Option<BufferImageHandle<Self::BL>>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug;
+
+  /// Sets the camera the skybox is drawn with.  The translation of `view` is
+  /// ignored so the sky stays infinitely far away.
+  fn set_skybox_view_projection(&mut self, view: uv::Mat4, projection: uv::Mat4);
+
+  /// Sets the environment objects drawn with `DefaultPbrShaderLayout` take
+  /// their ambient light from, or removes it with None, see
+  /// [environment](../image_data/environment/index.html).  The BRDF lookup
+  /// table is generated the first time an environment is set.
+  fn set_image_based_lighting(&mut self, environment: Option<EnvironmentMaps>) -> SarektResult<()>;
+
+  /// Sets the passes run over each frame before it is presented, in order, see
+  /// [post_process](post_process/index.html).  The renderer keeps the passes
+  /// alive while they are in use and hands back the previous chain.
+  ///
+  /// Fails with `PostProcessingDisabled` if the renderer was created without
+  /// `Config::post_processing`.
+  fn set_post_process_chain(
+    &mut self, passes: Vec<PostProcessPass<Self::BL, Self::SL>>,
+  ) -> SarektResult<Vec<PostProcessPass<Self::BL, Self::SL>>>
+  where
+    Self::BL: BufferAndImageLoader,
+    <Self::BL as BufferAndImageLoader>::BackendHandle: BackendHandleTrait + Copy + Debug,
+    Self::SL: ShaderLoader,
+    <Self::SL as ShaderLoader>::SBH: ShaderBackendHandleTrait + Copy + Debug;
+
+  /// Offsets `projection` by this frame's subpixel jitter when the renderer
+  /// was created with `AntiAliasingMode::Temporal`, and returns it unchanged
+  /// otherwise.  Objects should be drawn with the jittered projection.
+  fn jitter_projection(&self, projection: uv::Mat4) -> uv::Mat4;
+
+  /// Sets the camera the frame is drawn with, without the jitter, which
+  /// temporal anti-aliasing reprojects the previous frames with and the
+  /// deferred renderer reconstructs positions from depth with.  Should be
+  /// called every frame the camera moves.
+  fn set_view_projection(&mut self, view: uv::Mat4, projection: uv::Mat4);
+
+  /// Sets the lights the deferred renderer shades the G-buffer with, see
+  /// [lighting](lighting/index.html).  They stay until set again.
+  ///
+  /// Fails with `DeferredRenderingDisabled` if the renderer was created with
+  /// `RenderingPath::Forward`, and with `TooManyLights` if there are more than
+  /// the `MAX_DEFERRED_*_LIGHTS`.
+  fn set_scene_lights(&mut self, lights: SceneLights) -> SarektResult<()>;
+
+  /// Sets the light that casts shadows onto objects drawn with
+  /// `DefaultShadowedShaderLayout`, or turns shadows off with None, see
+  /// [shadows](shadows/index.html).
+  fn set_shadow_light(&mut self, light: Option<ShadowLight>) -> SarektResult<()>;
+
+  /// Sets the camera a directional light's cascades are fit to, and the
+  /// distances to its near and far planes.  Call it whenever the camera moves,
+  /// before the frame's shadow casters are drawn.
+  fn set_shadow_camera(
+    &mut self, view: uv::Mat4, projection: uv::Mat4, near: f32, far: f32,
+  ) -> SarektResult<()>;
+
+  /// Checks that the device can draw with the primitive state, for example
+  /// that it supports wide lines if the line width isn't 1.  Drawable objects
+  /// check their state when they are built.
+  fn check_primitive_state_support(&self, primitive_state: &PrimitiveState) -> SarektResult<()>;
+
+  /// Returns what the device supports for images of the given format, such as
+  /// whether block compressed textures can be sampled directly or will be
+  /// decoded on the CPU when loaded.
+  fn query_image_format_support(&self, format: ImageDataFormat) -> ImageFormatSupport;
+
+  /// Reports memory usage and budget per heap and how much of it each type of
+  /// resource takes, see [memory_stats](memory_stats/index.html).  Cheap
+  /// enough to call every few frames but not meant for every frame.
+  fn memory_stats(&self) -> SarektResult<MemoryStats>;
+
+  /// Dumps the backend allocator's own statistics as JSON, for Vulkan the
+  /// output of VMA's `vmaBuildStatsString`.  `detailed` lists every block and
+  /// allocation.
+  fn memory_stats_json(&self, detailed: bool) -> SarektResult<String>;
+
+  /// Moves buffers and images together so that memory fragmented by loading
+  /// and unloading returns to the driver.  Every `BufferImageHandle` and
+  /// `DrawableObject` stays valid.
+  ///
+  /// Waits for the frames in flight and copies the textures in sparsely used
+  /// memory blocks, so this is meant for loading screens and the like.  Call it
+  /// after `frame` and before the next frame's first `draw`.
+  fn defragment(&mut self) -> SarektResult<DefragmentationStats>;
+
   /// Retrieves an image using the handle returned by the `load_image_*` family
   /// of functions.
   fn get_image(
@@ -232,6 +523,53 @@ pub trait Drawer {
     <<Self::R as Renderer>::BL as BufferAndImageLoader>::BackendHandle:
       BackendHandleTrait + Copy + Debug;
 
+  /// Draws `object` with `uniform` instead of the data in its uniform buffer,
+  /// which it then doesn't need.  The data is copied into a buffer that is
+  /// reused every frame, so it costs much less than a uniform buffer per
+  /// object when the uniforms change every frame anyway.
+  ///
+  /// The space per frame is set by `Config::transient_uniform_buffer_size`.
+  fn draw_with_uniform<UniformBufElem>(
+    &self, object: &DrawableObject<Self::R, UniformBufElem>, uniform: &UniformBufElem,
+  ) -> SarektResult<()>
+  where
+    UniformBufElem: Sized + Copy + DescriptorLayoutInfo,
+    Self::R: Renderer,
+    <Self::R as Renderer>::BL: BufferAndImageLoader,
+    <<Self::R as Renderer>::BL as BufferAndImageLoader>::BackendHandle:
+      BackendHandleTrait + Copy + Debug;
+
+  /// Sends the draws that follow to `target` instead of the screen, until this
+  /// is called again.  None goes back to the screen.  Selecting a render
+  /// texture clears it, so draw everything it should show in one go each
+  /// frame.
+  ///
+  /// Render textures are drawn before the screen, so one rendered this frame
+  /// can be sampled by the screen's draws in the same frame.  Don't sample a
+  /// render texture in draws to itself.
+  fn set_render_target(
+    &self, target: Option<&RenderTextureHandle<<Self::R as Renderer>::BL>>,
+  ) -> SarektResult<()>
+  where
+    Self::R: Renderer,
+    <Self::R as Renderer>::BL: BufferAndImageLoader,
+    <<Self::R as Renderer>::BL as BufferAndImageLoader>::BackendHandle:
+      BackendHandleTrait + Copy + Debug;
+
+  /// Draws `object` into the shadow map from the shadow casting light, with
+  /// `model` placing it in the world.  Only its positions are used, and only
+  /// triangle lists cast shadows, other objects are skipped.  Does nothing
+  /// while no light is set.
+  fn draw_shadow_caster<UniformBufElem>(
+    &self, object: &DrawableObject<Self::R, UniformBufElem>, model: uv::Mat4,
+  ) -> SarektResult<()>
+  where
+    UniformBufElem: Sized + Copy + DescriptorLayoutInfo,
+    Self::R: Renderer,
+    <Self::R as Renderer>::BL: BufferAndImageLoader,
+    <<Self::R as Renderer>::BL as BufferAndImageLoader>::BackendHandle:
+      BackendHandleTrait + Copy + Debug;
+
   // TODO(issue#2) PIPELINE use method select render pass (predefined set?) log
   // when pipeline not compatible and dont draw? End previous render pass and
   // keep track of last render pass to end it as well.
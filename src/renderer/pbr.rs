@@ -0,0 +1,202 @@
+//! Materials for the built-in physically based forward shaders,
+//! sarekt_forward_pbr, which shade with the Cook-Torrance BRDF (GGX
+//! distribution, Smith geometry and Schlick fresnel) using the metallic
+//! roughness model from glTF.
+//!
+//! Objects are drawn this way when their uniform layout is
+//! `DefaultPbrShaderLayout` and their vertices are `DefaultPbrShaderVertex`,
+//! which have tangents for normal mapping, see `generate_tangents` for meshes
+//! that come without them.  The lights are the same as in
+//! [lighting](../lighting/index.html).
+//!
+//! Each texture of the material goes in its `PbrTextureSlot` of the drawable
+//! object, and the material says which of them are there:
+//!
+//! ```ignore
+//! let object: DrawableObject<VulkanRenderer, DefaultPbrShaderLayout> =
+//!   DrawableObject::builder(&renderer)
+//!     .vertex_buffer(&vertex_buffer)
+//!     .texture_image_at(PbrTextureSlot::BaseColor.index(), &base_color)
+//!     .texture_image_at(PbrTextureSlot::Normal.index(), &normal_map)
+//!     .build()?;
+//! let material = PbrMaterial::new(uv::Vec4::one(), 0f32, 0.5f32)
+//!   .with_texture(PbrTextureSlot::BaseColor)
+//!   .with_texture(PbrTextureSlot::Normal);
+//! ```
+//!
+//! Ambient light comes from the environment set with
+//! `Renderer::set_image_based_lighting`, see
+//! [environment](../../image_data/environment/index.html).  Without one
+//! objects are only lit by their lights and emission.
+use crate::{
+  error::{SarektError, SarektResult},
+  renderer::vertex_bindings::DefaultPbrShaderVertex,
+};
+use ultraviolet as uv;
+
+/// The textures of a PBR material, each is sampled from the drawable object's
+/// texture at `index()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PbrTextureSlot {
+  /// sRGB color, multiplied with `base_color_factor`.
+  BaseColor,
+  /// Roughness in the green channel and metalness in the blue channel, as in
+  /// glTF.
+  MetallicRoughness,
+  /// Tangent space normals.
+  Normal,
+  /// Ambient occlusion in the red channel.
+  Occlusion,
+  /// sRGB emitted color, multiplied with `emissive_factor`.
+  Emissive,
+}
+impl PbrTextureSlot {
+  pub const ALL: [PbrTextureSlot; 5] = [
+    PbrTextureSlot::BaseColor,
+    PbrTextureSlot::MetallicRoughness,
+    PbrTextureSlot::Normal,
+    PbrTextureSlot::Occlusion,
+    PbrTextureSlot::Emissive,
+  ];
+
+  /// The drawable object texture slot this texture is bound from.
+  pub fn index(self) -> usize {
+    match self {
+      PbrTextureSlot::BaseColor => 0,
+      PbrTextureSlot::MetallicRoughness => 1,
+      PbrTextureSlot::Normal => 2,
+      PbrTextureSlot::Occlusion => 3,
+      PbrTextureSlot::Emissive => 4,
+    }
+  }
+
+  fn mask_bit(self) -> u32 {
+    1 << self.index()
+  }
+}
+
+/// How a PBR surface reflects light, with the same meaning and defaults as a
+/// glTF metallic roughness material.  Factors multiply the textures of the
+/// slots in `texture_mask`, and are used alone for the others.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PbrMaterial {
+  /// Linear RGBA.
+  pub base_color_factor: uv::Vec4,
+  /// Linear RGB.
+  pub emissive_factor: uv::Vec3,
+  pub metallic_factor: f32,
+  pub roughness_factor: f32,
+  /// Scales the x and y of the normal map.
+  pub normal_scale: f32,
+  /// How much of the occlusion texture is applied, 0 is none.
+  pub occlusion_strength: f32,
+  /// A bit set for each `PbrTextureSlot` the object has a texture in.
+  pub texture_mask: u32,
+}
+impl PbrMaterial {
+  /// No textures and no emission.
+  pub fn new(base_color_factor: uv::Vec4, metallic_factor: f32, roughness_factor: f32) -> Self {
+    Self {
+      base_color_factor,
+      emissive_factor: uv::Vec3::zero(),
+      metallic_factor,
+      roughness_factor,
+      normal_scale: 1f32,
+      occlusion_strength: 1f32,
+      texture_mask: 0u32,
+    }
+  }
+
+  /// Samples the texture in `slot`.
+  pub fn with_texture(mut self, slot: PbrTextureSlot) -> Self {
+    self.texture_mask |= slot.mask_bit();
+    self
+  }
+
+  pub fn with_emissive(mut self, emissive_factor: uv::Vec3) -> Self {
+    self.emissive_factor = emissive_factor;
+    self
+  }
+
+  pub fn has_texture(&self, slot: PbrTextureSlot) -> bool {
+    self.texture_mask & slot.mask_bit() != 0
+  }
+}
+impl Default for PbrMaterial {
+  fn default() -> Self {
+    Self::new(uv::Vec4::one(), 1f32, 1f32)
+  }
+}
+
+/// Fills in the tangents of a triangle list from its texture coordinates, for
+/// normal mapping meshes that come without them.  Tangents are averaged
+/// between the triangles sharing a vertex and made perpendicular to its
+/// normal, and w is the handedness of the bitangent.
+pub fn generate_tangents(
+  vertices: &mut [DefaultPbrShaderVertex], indices: &[u32],
+) -> SarektResult<()> {
+  if indices.len() % 3 != 0 {
+    return Err(SarektError::InvalidMesh(format!(
+      "{} indices is not a triangle list",
+      indices.len()
+    )));
+  }
+  if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+    return Err(SarektError::InvalidMesh(format!(
+      "index {} is out of range of {} vertices",
+      index,
+      vertices.len()
+    )));
+  }
+
+  let mut tangents = vec![uv::Vec3::zero(); vertices.len()];
+  let mut bitangents = vec![uv::Vec3::zero(); vertices.len()];
+  for triangle in indices.chunks(3) {
+    let [a, b, c] = [
+      &vertices[triangle[0] as usize],
+      &vertices[triangle[1] as usize],
+      &vertices[triangle[2] as usize],
+    ];
+    let edge_1 = b.position - a.position;
+    let edge_2 = c.position - a.position;
+    let delta_uv_1 = b.texture_coordinates - a.texture_coordinates;
+    let delta_uv_2 = c.texture_coordinates - a.texture_coordinates;
+
+    let determinant = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+    if determinant.abs() < std::f32::EPSILON {
+      // No texture space to follow, the fallback below picks any tangent.
+      continue;
+    }
+    let r = 1f32 / determinant;
+    let tangent = (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) * r;
+    let bitangent = (edge_2 * delta_uv_1.x - edge_1 * delta_uv_2.x) * r;
+    for &i in triangle {
+      tangents[i as usize] += tangent;
+      bitangents[i as usize] += bitangent;
+    }
+  }
+
+  for (i, vertex) in vertices.iter_mut().enumerate() {
+    let normal = vertex.normal;
+    // Gram-Schmidt.
+    let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+    if tangent.mag_sq() < std::f32::EPSILON {
+      let axis = if normal.x.abs() < 0.9f32 {
+        uv::Vec3::unit_x()
+      } else {
+        uv::Vec3::unit_y()
+      };
+      tangent = axis - normal * normal.dot(axis);
+    }
+    let tangent = tangent.normalized();
+    let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0f32 {
+      -1f32
+    } else {
+      1f32
+    };
+    vertex.tangent = uv::Vec4::new(tangent.x, tangent.y, tangent.z, handedness);
+  }
+
+  Ok(())
+}
@@ -0,0 +1,98 @@
+//! Full screen effects run on the finished frame before it is presented.
+//!
+//! With `Config::post_processing` set the forward pass draws into an
+//! intermediate target of `Config::scene_color_format` (R16G16B16A16Sfloat by
+//! default) instead of the swapchain image, so lighting can go past 1.0.  At
+//! the end of the frame the passes of the chain set with
+//! `Renderer::set_post_process_chain` run in order, each reading the output of
+//! the one before, and the last one writes the swapchain image.  Without a
+//! `Tonemap` pass HDR colors are simply clamped when they are written, unless
+//! the swapchain is HDR10 (see `SurfaceFormatPreference::Hdr10`).
+//!
+//! ```ignore
+//! renderer.set_post_process_chain(vec![
+//!   PostProcessPass::Bloom {
+//!     threshold: 1f32,
+//!     intensity: 0.5f32,
+//!   },
+//!   PostProcessPass::Tonemap {
+//!     operator: TonemapOperator::Aces,
+//!     exposure: 1f32,
+//!   },
+//!   PostProcessPass::Fxaa,
+//!   PostProcessPass::Vignette {
+//!     intensity: 0.4f32,
+//!     radius: 0.5f32,
+//!   },
+//! ])?;
+//! ```
+//!
+//! A `Custom` pass is a fragment shader loaded with `Renderer::load_shader`
+//! that is drawn over a full screen triangle.  Its interface is:
+//!
+//! ```glsl
+//! // The output of the previous pass, or the scene for the first one.
+//! layout(binding = 0) uniform sampler2D source;
+//! // The scene as the forward pass left it.
+//! layout(binding = 1) uniform sampler2D scene;
+//!
+//! layout(push_constant) uniform PostProcessConstants {
+//!   // The parameters of the pass.
+//!   vec4 parameters[2];
+//!   // 1 / the dimensions of source.
+//!   vec2 texelSize;
+//! } pc;
+//!
+//! layout(location = 0) in vec2 uv;
+//! layout(location = 0) out vec4 outColor;
+//! ```
+use crate::renderer::{
+  buffers_and_images::{BackendHandleTrait, BufferAndImageLoader, BufferImageHandle},
+  shaders::{ShaderBackendHandleTrait, ShaderHandle, ShaderLoader},
+};
+use std::fmt::Debug;
+
+/// Curve that maps HDR colors into the displayable range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TonemapOperator {
+  /// c / (1 + c), keeps hues but washes out highlights.
+  Reinhard,
+  /// Narkowicz's fit of the ACES filmic curve, more contrast and saturation.
+  Aces,
+}
+
+/// One full screen pass of the post processing chain.  The renderer keeps the
+/// handles of the passes alive while the chain is set.
+pub enum PostProcessPass<BL, SL>
+where
+  BL: BufferAndImageLoader,
+  BL::BackendHandle: BackendHandleTrait + Copy + Debug,
+  SL: ShaderLoader,
+  SL::SBH: ShaderBackendHandleTrait + Copy + Debug,
+{
+  /// Scales the colors by `exposure` and maps them to 0..1 with `operator`.
+  Tonemap {
+    operator: TonemapOperator,
+    exposure: f32,
+  },
+  /// Blurs the parts brighter than `threshold` at half resolution and adds
+  /// them back scaled by `intensity`.  Goes before `Tonemap`.
+  Bloom { threshold: f32, intensity: f32 },
+  /// Fast approximate anti-aliasing of edges.  Goes after `Tonemap`, it
+  /// expects colors in 0..1.
+  Fxaa,
+  /// Remaps colors with a lookup table, an unwrapped N×N×N cube that is N
+  /// slices of N×N side by side (N*N wide, N high), with red along the width
+  /// of each slice, green along the height and blue selecting the slice.  Load
+  /// it with linear filtering, clamped to the edge, and a Unorm format.  Goes
+  /// after `Tonemap`.
+  ColorGrading { lut: BufferImageHandle<BL> },
+  /// Darkens towards the corners, by up to `intensity` from `radius` (0 is the
+  /// center, 1 the corners) outwards.
+  Vignette { intensity: f32, radius: f32 },
+  /// A user fragment shader, see the module documentation.
+  Custom {
+    fragment_shader: ShaderHandle<SL>,
+    parameters: [f32; 8],
+  },
+}
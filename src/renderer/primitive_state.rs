@@ -0,0 +1,112 @@
+//! How the vertices of a drawable object are assembled into primitives and
+//! rasterized, for drawing debug paths as line strips, point clouds and so on
+//! instead of triangle lists.
+//!
+//! Each distinct state gets its own pipeline, derived from the base pipeline
+//! the first time an object with it is drawn (and again after the swapchain
+//! is recreated).
+//!
+//! ```ignore
+//! let path = DrawableObject::builder(&renderer)
+//!   .vertex_buffer(&path_vertices)
+//!   .uniform_buffer(&uniform)
+//!   .primitive_state(PrimitiveState {
+//!     line_width: 3.0,
+//!     ..PrimitiveState::new(PrimitiveTopology::LineStrip)
+//!   })
+//!   .build()?;
+//! ```
+use std::hash::{Hash, Hasher};
+
+/// What each group of vertices (or indices) makes, see the Vulkan
+/// specification's "Primitive Topologies" for the exact rules.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PrimitiveTopology {
+  PointList,
+  LineList,
+  LineStrip,
+  TriangleList,
+  TriangleStrip,
+  TriangleFan,
+  // TODO(issue#2) PIPELINES patch lists once custom pipelines can have
+  // tessellation shaders.
+}
+impl PrimitiveTopology {
+  /// Strips and fans, the topologies primitive restart applies to.
+  pub fn is_strip(self) -> bool {
+    matches!(
+      self,
+      PrimitiveTopology::LineStrip
+        | PrimitiveTopology::TriangleStrip
+        | PrimitiveTopology::TriangleFan
+    )
+  }
+}
+impl Default for PrimitiveTopology {
+  fn default() -> PrimitiveTopology {
+    PrimitiveTopology::TriangleList
+  }
+}
+
+/// Primitive assembly, rasterization and blending state of a pipeline.  The
+/// default is the base pipeline's, opaque triangle lists with one pixel lines
+/// and points.
+#[derive(Copy, Clone, Debug)]
+pub struct PrimitiveState {
+  pub topology: PrimitiveTopology,
+  /// Whether the largest index value (`u16::max_value()` or
+  /// `u32::max_value()`) ends the current strip or fan in indexed draws.
+  /// Only allowed for strips and fans.
+  pub primitive_restart: bool,
+  /// Width of rasterized lines in pixels.  Anything but 1 needs the device's
+  /// `wideLines` feature.
+  pub line_width: f32,
+  /// Size of rasterized points in pixels.  Anything but 1 needs the device's
+  /// `largePoints` feature.
+  pub point_size: f32,
+  /// Blends the fragments over what is already drawn by their alpha, for
+  /// transparent objects, which should be drawn back to front after the
+  /// opaque ones.  They are depth tested but don't write depth.  The deferred
+  /// renderer draws them forward after the lighting.
+  pub alpha_blending: bool,
+}
+impl PrimitiveState {
+  pub fn new(topology: PrimitiveTopology) -> Self {
+    Self {
+      topology,
+      ..Self::default()
+    }
+  }
+}
+impl Default for PrimitiveState {
+  fn default() -> Self {
+    Self {
+      topology: PrimitiveTopology::TriangleList,
+      primitive_restart: false,
+      line_width: 1.0,
+      point_size: 1.0,
+      alpha_blending: false,
+    }
+  }
+}
+
+// Compared bitwise so states can key the pipeline cache.
+impl PartialEq for PrimitiveState {
+  fn eq(&self, other: &Self) -> bool {
+    self.topology == other.topology
+      && self.primitive_restart == other.primitive_restart
+      && self.line_width.to_bits() == other.line_width.to_bits()
+      && self.point_size.to_bits() == other.point_size.to_bits()
+      && self.alpha_blending == other.alpha_blending
+  }
+}
+impl Eq for PrimitiveState {}
+impl Hash for PrimitiveState {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.topology.hash(state);
+    self.primitive_restart.hash(state);
+    self.line_width.to_bits().hash(state);
+    self.point_size.to_bits().hash(state);
+    self.alpha_blending.hash(state);
+  }
+}
@@ -0,0 +1,83 @@
+//! Offscreen color targets that later draws can sample, for mirrors, security
+//! camera screens, minimaps and the like.
+//!
+//! A render texture is loaded with `Renderer::load_render_texture` and drawn
+//! into by selecting it with `Drawer::set_render_target`.  Its `texture` is an
+//! ordinary image handle, so it is given to a `DrawableObject` like any loaded
+//! texture.  The backend transitions it between being rendered into and being
+//! sampled.
+//!
+//! ```ignore
+//! let mirror = renderer.load_render_texture(
+//!   (512, 512),
+//!   ImageDataFormat::R8G8B8A8Srgb,
+//!   [0f32, 0f32, 0f32, 1f32],
+//!   MagnificationMinificationFilter::Linear,
+//!   MagnificationMinificationFilter::Linear,
+//!   TextureAddressMode::ClampToEdge,
+//!   TextureAddressMode::ClampToEdge,
+//! )?;
+//! let mirror_quad = DrawableObject::builder(&renderer)
+//!   .vertex_buffer(&quad_vertices)
+//!   .uniform_buffer(&quad_uniform)
+//!   .texture_image(mirror.texture())
+//!   .build()?;
+//!
+//! // Every frame.
+//! renderer.set_render_target(Some(&mirror))?;
+//! renderer.draw(&scene)?;
+//! renderer.set_render_target(None)?;
+//! renderer.draw(&scene)?;
+//! renderer.draw(&mirror_quad)?;
+//! renderer.frame()?;
+//! ```
+use crate::renderer::buffers_and_images::{
+  BackendHandleTrait, BufferAndImageLoader, BufferImageHandle,
+};
+use std::fmt::Debug;
+
+/// RAII handle to a render texture, its color image and its depth buffer are
+/// destroyed when it is dropped (once the frames using them are done).
+pub struct RenderTextureHandle<BL>
+where
+  BL: BufferAndImageLoader,
+  BL::BackendHandle: BackendHandleTrait + Copy + Debug,
+{
+  pub(crate) texture: BufferImageHandle<BL>,
+  pub(crate) depth_buffer: BufferImageHandle<BL>,
+  pub(crate) dimensions: (u32, u32),
+  pub(crate) clear_color: [f32; 4],
+}
+impl<BL> RenderTextureHandle<BL>
+where
+  BL: BufferAndImageLoader,
+  BL::BackendHandle: BackendHandleTrait + Copy + Debug,
+{
+  pub(crate) fn new(
+    texture: BufferImageHandle<BL>, depth_buffer: BufferImageHandle<BL>, dimensions: (u32, u32),
+    clear_color: [f32; 4],
+  ) -> Self {
+    Self {
+      texture,
+      depth_buffer,
+      dimensions,
+      clear_color,
+    }
+  }
+
+  /// The color image, to sample in later draws as a drawable object's
+  /// texture.
+  pub fn texture(&self) -> &BufferImageHandle<BL> {
+    &self.texture
+  }
+
+  pub fn dimensions(&self) -> (u32, u32) {
+    self.dimensions
+  }
+
+  /// The color the texture is cleared to each time it is selected as the
+  /// render target.
+  pub fn clear_color(&self) -> [f32; 4] {
+    self.clear_color
+  }
+}
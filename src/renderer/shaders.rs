@@ -1,6 +1,9 @@
-use crate::error::{SarektError, SarektResult};
+use crate::{
+  error::{SarektError, SarektResult},
+  renderer::deletion_queue::DeletionQueue,
+};
 
-use log::warn;
+use log::{error, warn};
 use slotmap::{DefaultKey, SlotMap};
 use std::{
   fmt::Debug,
@@ -8,7 +11,8 @@ use std::{
 };
 
 /// A type that can be used to retrieve a shader from the renderer and
-/// ShaderStore that will destroy the shader when it goes out of scope.
+/// ShaderStore that will destroy the shader when it goes out of scope, once
+/// the frames in flight that may still be using it are done.
 ///
 /// As always, In order to pass this around with multiple ownership, wrap it in
 /// an Arc.
@@ -95,6 +99,8 @@ where
   SL::SBH: ShaderBackendHandleTrait + Copy + Debug,
 {
   loaded_shaders: SlotMap<DefaultKey, Shader<SL::SBH>>,
+  // Dropped shaders that frames in flight may still be using.
+  deletion_queue: DeletionQueue<SL::SBH>,
   shader_loader: SL,
 }
 
@@ -107,6 +113,7 @@ where
   pub(crate) fn new(shader_loader: SL) -> Self {
     Self {
       loaded_shaders: SlotMap::new(),
+      deletion_queue: DeletionQueue::new(),
       shader_loader,
     }
   }
@@ -130,23 +137,48 @@ where
     })
   }
 
-  /// Using the handle, destroy the shader from the backend.
+  /// Using the handle, destroy the shader from the backend once the frames
+  /// that may be using it are done, see `start_frame`.
   fn destroy_shader(&mut self, inner_key: DefaultKey) -> SarektResult<()> {
     let shader = self.loaded_shaders.remove(inner_key);
     if shader.is_none() {
       return Err(SarektError::UnknownShader);
     }
-    self
-      .shader_loader
-      .delete_shader(shader.unwrap().shader_handle)?;
+    self.deletion_queue.push(shader.unwrap().shader_handle);
     Ok(())
   }
 
+  /// Must be called by the backend when it starts recording `frame`.  Destroys
+  /// shaders dropped during frames up to and including `completed_frame`,
+  /// which the GPU must be done with.
+  ///
+  /// Every one of them is destroyed even if some fail, the first error is
+  /// returned.
+  pub(crate) fn start_frame(
+    &mut self, frame: u64, completed_frame: Option<u64>,
+  ) -> SarektResult<()> {
+    let mut result = Ok(());
+    for shader_handle in self.deletion_queue.start_frame(frame, completed_frame) {
+      if let Err(err) = self.shader_loader.delete_shader(shader_handle) {
+        error!("Could not destroy dropped shader: {:?}", err);
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+    result
+  }
+
   /// Destroys all the shaders.  Unsafe because any outstanding handles will not
   /// result in errors when they drop, so they must be forgotten.
   pub(crate) unsafe fn destroy_all_shaders(&mut self) {
-    for shader in self.loaded_shaders.iter() {
-      if let Err(err) = self.shader_loader.delete_shader(shader.1.shader_handle) {
+    let dropped = self.deletion_queue.drain_all();
+    let loaded = self
+      .loaded_shaders
+      .iter()
+      .map(|(_, shader)| shader.shader_handle);
+    for shader_handle in dropped.into_iter().chain(loaded) {
+      if let Err(err) = self.shader_loader.delete_shader(shader_handle) {
         warn!(
           "Shader not destroyed, maybe it was already? Error: {:?}",
           err
@@ -0,0 +1,410 @@
+//! Shadows cast by a single directional or spot light.
+//!
+//! Objects drawn with `Drawer::draw_shadow_caster` are rendered depth only
+//! from the light into a shadow map, and objects whose uniform layout is
+//! `DefaultShadowedShaderLayout` are drawn with a variant of the forward
+//! shader that samples it with a comparison sampler and 3x3 percentage closer
+//! filtering.
+//!
+//! Directional lights use cascaded shadow maps: the camera frustum, out to
+//! `CascadeConfig::max_distance`, is split into up to `MAX_SHADOW_CASCADES`
+//! slices that each get their own tile of the shadow map, so nearby shadows
+//! stay sharp.  They need the camera, see `Renderer::set_shadow_camera`.
+//!
+//! ```ignore
+//! renderer.set_shadow_light(Some(ShadowLight::Directional {
+//!   direction: uv::Vec3::new(-1f32, -2f32, -1f32),
+//!   cascades: CascadeConfig::default(),
+//! }))?;
+//!
+//! // Every frame, before drawing casters.
+//! renderer.set_shadow_camera(view, projection, 0.1f32, 100f32)?;
+//! renderer.draw_shadow_caster(&ground, ground_model)?;
+//! renderer.draw_shadow_caster(&tree, tree_model)?;
+//! renderer.draw_with_uniform(
+//!   &ground,
+//!   &DefaultShadowedShaderLayout::new(projection * view * ground_model, ground_model, false, true),
+//! )?;
+//! ```
+use crate::error::{SarektError, SarektResult};
+use ultraviolet as uv;
+
+/// The most cascades a directional light can have, the shadow map is a 2x2
+/// atlas of tiles.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// How a directional light's shadows are split into cascades.
+#[derive(Copy, Clone, Debug)]
+pub struct CascadeConfig {
+  /// Number of cascades, 1 to `MAX_SHADOW_CASCADES`.
+  pub count: u32,
+  /// Blend between splitting the distance uniformly (0) and logarithmically
+  /// (1).  Logarithmic splits give the cascades near the camera more
+  /// resolution.
+  pub split_lambda: f32,
+  /// How far from the camera shadows are drawn, clamped to the camera's far
+  /// plane.
+  pub max_distance: f32,
+}
+impl Default for CascadeConfig {
+  fn default() -> Self {
+    Self {
+      count: 3,
+      split_lambda: 0.75,
+      max_distance: 50f32,
+    }
+  }
+}
+
+/// The light that casts shadows.
+#[derive(Copy, Clone, Debug)]
+pub enum ShadowLight {
+  /// A light infinitely far away, such as the sun.  `direction` is the
+  /// direction the light travels in.
+  Directional {
+    direction: uv::Vec3,
+    cascades: CascadeConfig,
+  },
+  /// A cone of light from `position`.  `cone_angle` is the full angle of the
+  /// cone in radians, and `near` and `far` bound the distances at which
+  /// objects cast shadows.
+  Spot {
+    position: uv::Vec3,
+    direction: uv::Vec3,
+    cone_angle: f32,
+    near: f32,
+    far: f32,
+  },
+}
+
+/// The camera directional light cascades are fit to.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ShadowCamera {
+  pub view: uv::Mat4,
+  pub projection: uv::Mat4,
+  pub near: f32,
+  pub far: f32,
+}
+
+/// The light's view projection for each tile of the shadow map, and the view
+/// distance each cascade ends at.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ShadowCascades {
+  pub light_view_projections: [uv::Mat4; MAX_SHADOW_CASCADES],
+  pub splits: [f32; MAX_SHADOW_CASCADES],
+  pub count: usize,
+}
+impl ShadowCascades {
+  /// Fits the light's cascades to the camera.  Returns None for a directional
+  /// light when there is no camera yet.  `tile_size` is the size of each tile
+  /// in texels, cascades are snapped to texels so shadow edges don't shimmer
+  /// when the camera moves.
+  pub fn new(
+    light: &ShadowLight, camera: Option<&ShadowCamera>, tile_size: u32,
+  ) -> SarektResult<Option<Self>> {
+    Self::validate(light)?;
+
+    match *light {
+      ShadowLight::Directional {
+        direction,
+        cascades,
+      } => Ok(camera.map(|camera| Self::directional(direction, &cascades, camera, tile_size))),
+      ShadowLight::Spot {
+        position,
+        direction,
+        cone_angle,
+        near,
+        far,
+      } => {
+        let direction = direction.normalized();
+        let view = uv::Mat4::look_at(position, position + direction, up_vector(direction));
+        let projection = uv::projection::rh_yup::perspective_vk(cone_angle, 1f32, near, far);
+
+        let mut light_view_projections = [uv::Mat4::identity(); MAX_SHADOW_CASCADES];
+        light_view_projections[0] = projection * view;
+        Ok(Some(Self {
+          light_view_projections,
+          splits: [std::f32::MAX; MAX_SHADOW_CASCADES],
+          count: 1,
+        }))
+      }
+    }
+  }
+
+  fn validate(light: &ShadowLight) -> SarektResult<()> {
+    match *light {
+      ShadowLight::Directional {
+        direction,
+        cascades,
+      } => {
+        if direction.mag() == 0f32 {
+          return Err(SarektError::InvalidShadowLight(
+            "the light's direction is zero".to_owned(),
+          ));
+        }
+        if cascades.count == 0 || cascades.count as usize > MAX_SHADOW_CASCADES {
+          return Err(SarektError::InvalidShadowLight(format!(
+            "{} cascades, there must be 1 to {}",
+            cascades.count, MAX_SHADOW_CASCADES
+          )));
+        }
+        if !(0f32..=1f32).contains(&cascades.split_lambda) || cascades.max_distance <= 0f32 {
+          return Err(SarektError::InvalidShadowLight(format!(
+            "split lambda {} must be in 0 to 1 and max distance {} positive",
+            cascades.split_lambda, cascades.max_distance
+          )));
+        }
+      }
+      ShadowLight::Spot {
+        direction,
+        cone_angle,
+        near,
+        far,
+        ..
+      } => {
+        if direction.mag() == 0f32 {
+          return Err(SarektError::InvalidShadowLight(
+            "the light's direction is zero".to_owned(),
+          ));
+        }
+        if cone_angle <= 0f32 || cone_angle >= std::f32::consts::PI {
+          return Err(SarektError::InvalidShadowLight(format!(
+            "cone angle {} must be between 0 and pi",
+            cone_angle
+          )));
+        }
+        if near <= 0f32 || far <= near {
+          return Err(SarektError::InvalidShadowLight(format!(
+            "near {} and far {} must be positive and increasing",
+            near, far
+          )));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn directional(
+    direction: uv::Vec3, cascades: &CascadeConfig, camera: &ShadowCamera, tile_size: u32,
+  ) -> Self {
+    let direction = direction.normalized();
+    let up = up_vector(direction);
+    let near = camera.near;
+    let shadow_far = camera.far.min(cascades.max_distance).max(near);
+
+    // Corners of the camera frustum on the near and far planes, a point at a
+    // given fraction between them along each edge is at that fraction of the
+    // view depth.
+    let inverse_view_projection = (camera.projection * camera.view).inversed();
+    let corner = |x: f32, y: f32, z: f32| {
+      let corner = inverse_view_projection * uv::Vec4::new(x, y, z, 1f32);
+      corner.xyz() / corner.w
+    };
+    let ndc_corners = [(-1f32, -1f32), (1f32, -1f32), (1f32, 1f32), (-1f32, 1f32)];
+    let near_corners: Vec<uv::Vec3> = ndc_corners
+      .iter()
+      .map(|&(x, y)| corner(x, y, 0f32))
+      .collect();
+    let far_corners: Vec<uv::Vec3> = ndc_corners
+      .iter()
+      .map(|&(x, y)| corner(x, y, 1f32))
+      .collect();
+    let depth_fraction = |depth: f32| (depth - camera.near) / (camera.far - camera.near);
+
+    let count = cascades.count as usize;
+    let mut light_view_projections = [uv::Mat4::identity(); MAX_SHADOW_CASCADES];
+    let mut splits = [std::f32::MAX; MAX_SHADOW_CASCADES];
+    let mut previous_split = near;
+    for i in 0..count {
+      let fraction = (i + 1) as f32 / count as f32;
+      let log_split = near * (shadow_far / near).powf(fraction);
+      let uniform_split = near + (shadow_far - near) * fraction;
+      let split =
+        cascades.split_lambda * log_split + (1f32 - cascades.split_lambda) * uniform_split;
+
+      let (start, end) = (depth_fraction(previous_split), depth_fraction(split));
+      let slice_corners: Vec<uv::Vec3> = near_corners
+        .iter()
+        .zip(far_corners.iter())
+        .flat_map(|(&near_corner, &far_corner)| {
+          let edge = far_corner - near_corner;
+          vec![near_corner + edge * start, near_corner + edge * end]
+        })
+        .collect();
+
+      // A bounding sphere keeps the size of the cascade the same as the camera
+      // turns.
+      let center = slice_corners
+        .iter()
+        .fold(uv::Vec3::zero(), |sum, &corner| sum + corner)
+        / slice_corners.len() as f32;
+      let radius = slice_corners
+        .iter()
+        .map(|&corner| (corner - center).mag())
+        .fold(0f32, f32::max);
+      let radius = (radius * 16f32).ceil() / 16f32;
+
+      // Extra room towards the light for casters outside the slice.
+      let view = uv::Mat4::look_at(center - direction * radius * 2f32, center, up);
+      let mut projection = uv::projection::rh_yup::orthographic_vk(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0f32,
+        radius * 3f32,
+      );
+
+      // Move in whole texels.
+      let half_tile = tile_size as f32 / 2f32;
+      let origin = (projection * view) * uv::Vec4::new(0f32, 0f32, 0f32, 1f32);
+      let (origin_x, origin_y) = (origin.x * half_tile, origin.y * half_tile);
+      projection.cols[3].x += (origin_x.round() - origin_x) / half_tile;
+      projection.cols[3].y += (origin_y.round() - origin_y) / half_tile;
+
+      light_view_projections[i] = projection * view;
+      splits[i] = split;
+      previous_split = split;
+    }
+
+    Self {
+      light_view_projections,
+      splits,
+      count,
+    }
+  }
+}
+
+/// An up vector for looking along `direction` that isn't parallel to it.
+fn up_vector(direction: uv::Vec3) -> uv::Vec3 {
+  if direction.y.abs() > 0.99f32 {
+    uv::Vec3::unit_x()
+  } else {
+    uv::Vec3::unit_y()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TILE_SIZE: u32 = 1024;
+
+  fn camera(position: uv::Vec3) -> ShadowCamera {
+    ShadowCamera {
+      view: uv::Mat4::look_at(
+        position,
+        position + uv::Vec3::new(0f32, 0f32, -1f32),
+        uv::Vec3::unit_y(),
+      ),
+      projection: uv::projection::rh_yup::perspective_vk(1f32, 16f32 / 9f32, 1f32, 100f32),
+      near: 1f32,
+      far: 100f32,
+    }
+  }
+
+  fn directional(split_lambda: f32) -> ShadowLight {
+    ShadowLight::Directional {
+      direction: uv::Vec3::new(-1f32, -2f32, -1f32),
+      cascades: CascadeConfig {
+        count: 3,
+        split_lambda,
+        max_distance: 27f32,
+      },
+    }
+  }
+
+  fn cascades(light: &ShadowLight, camera: &ShadowCamera) -> ShadowCascades {
+    ShadowCascades::new(light, Some(camera), TILE_SIZE)
+      .unwrap()
+      .unwrap()
+  }
+
+  fn assert_close(actual: f32, expected: f32) {
+    assert!(
+      (actual - expected).abs() < 1e-3,
+      "{} != {}",
+      actual,
+      expected
+    );
+  }
+
+  #[test]
+  fn uniform_splits_divide_the_distance_evenly() {
+    let cascades = cascades(&directional(0f32), &camera(uv::Vec3::zero()));
+    assert_eq!(cascades.count, 3);
+    assert_close(cascades.splits[0], 9.666_667);
+    assert_close(cascades.splits[1], 18.333_334);
+    assert_close(cascades.splits[2], 27f32);
+    assert_eq!(cascades.splits[3], std::f32::MAX);
+  }
+
+  #[test]
+  fn logarithmic_splits_grow_geometrically() {
+    let cascades = cascades(&directional(1f32), &camera(uv::Vec3::zero()));
+    assert_close(cascades.splits[0], 3f32);
+    assert_close(cascades.splits[1], 9f32);
+    assert_close(cascades.splits[2], 27f32);
+  }
+
+  #[test]
+  fn splits_stop_at_the_camera_far_plane() {
+    let mut light = directional(0f32);
+    if let ShadowLight::Directional { cascades, .. } = &mut light {
+      cascades.max_distance = 1000f32;
+    }
+    let cascades = cascades(&light, &camera(uv::Vec3::zero()));
+    assert_close(cascades.splits[2], 100f32);
+  }
+
+  #[test]
+  fn cascades_move_in_whole_texels() {
+    let light = directional(0.75f32);
+    let before = cascades(&light, &camera(uv::Vec3::zero()));
+    let after = cascades(&light, &camera(uv::Vec3::new(0.013f32, 0f32, 0.007f32)));
+
+    // Any point lands the same fraction of a texel from the texel grid in both.
+    let half_tile = TILE_SIZE as f32 / 2f32;
+    let point = uv::Vec4::new(3.3f32, -1.7f32, -8.1f32, 1f32);
+    for i in 0..before.count {
+      let moved = (after.light_view_projections[i] * point
+        - before.light_view_projections[i] * point)
+        * half_tile;
+      assert_close(moved.x, moved.x.round());
+      assert_close(moved.y, moved.y.round());
+    }
+  }
+
+  #[test]
+  fn spot_light_has_one_cascade() {
+    let light = ShadowLight::Spot {
+      position: uv::Vec3::new(0f32, 5f32, 0f32),
+      direction: uv::Vec3::new(0f32, -1f32, 0f32),
+      cone_angle: 1f32,
+      near: 0.5f32,
+      far: 20f32,
+    };
+    let cascades = ShadowCascades::new(&light, None, TILE_SIZE)
+      .unwrap()
+      .unwrap();
+    assert_eq!(cascades.count, 1);
+    assert_eq!(cascades.splits[0], std::f32::MAX);
+  }
+
+  #[test]
+  fn directional_light_needs_a_camera_and_valid_cascades() {
+    assert!(ShadowCascades::new(&directional(0.5f32), None, TILE_SIZE)
+      .unwrap()
+      .is_none());
+
+    let mut light = directional(0.5f32);
+    if let ShadowLight::Directional { cascades, .. } = &mut light {
+      cascades.count = MAX_SHADOW_CASCADES as u32 + 1;
+    }
+    assert!(matches!(
+      ShadowCascades::new(&light, Some(&camera(uv::Vec3::zero())), TILE_SIZE),
+      Err(SarektError::InvalidShadowLight(_))
+    ));
+  }
+}
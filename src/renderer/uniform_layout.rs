@@ -0,0 +1,378 @@
+//! Checks that a uniform struct's Rust layout matches the GLSL block it is
+//! bound to.
+//!
+//! `#[repr(C)]` lays fields out by C rules, but GLSL uniform blocks follow
+//! std140 (or std430 for storage blocks), which for example aligns a `vec3`
+//! and every array element to 16 bytes.  A field in the wrong place doesn't
+//! fail anywhere, the shader just reads garbage.
+//!
+//! Describe the block with a
+//! [UniformBlockLayout](struct.UniformBlockLayout.html) returned from
+//! `DescriptorLayoutInfo::get_uniform_block_layout` and
+//! `Renderer::load_checked_uniform_buffer` reports every mismatched field:
+//!
+//! ```ignore
+//! fn get_uniform_block_layout() -> Option<UniformBlockLayout> {
+//!   Some(UniformBlockLayout::new::<MyUniform>(
+//!     LayoutRules::Std140,
+//!     vec![
+//!       uniform_field!(MyUniform, model, GlslType::Matrix { columns: 4, rows: 4 }),
+//!       uniform_field!(MyUniform, light_colors, GlslType::array(GlslType::Vector(4), 8)),
+//!     ],
+//!   ))
+//! }
+//! ```
+use crate::error::{SarektError, SarektResult};
+use ultraviolet as uv;
+
+/// Which set of GLSL block layout rules to check against.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutRules {
+  /// The default for uniform blocks.
+  Std140,
+  /// Storage blocks (and uniform blocks with `GL_EXT_scalar_block_layout`),
+  /// which don't round arrays and structs up to 16 bytes.
+  Std430,
+}
+
+/// The GLSL type of a block member.  Only 32 bit components are supported,
+/// which covers `float`, `int`, `uint` and `bool` (a `u32` in Rust).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GlslType {
+  Scalar,
+  /// A vector with 2, 3 or 4 components.
+  Vector(u32),
+  /// A column major matrix, `mat4` is 4 columns of 4 rows.
+  Matrix {
+    columns: u32,
+    rows: u32,
+  },
+  Array(Box<GlslType>, usize),
+  /// A nested struct, its members in declaration order.
+  Struct(Vec<GlslType>),
+}
+impl GlslType {
+  pub fn array(element: GlslType, length: usize) -> Self {
+    GlslType::Array(Box::new(element), length)
+  }
+
+  /// Base alignment in bytes.
+  pub fn alignment(&self, rules: LayoutRules) -> usize {
+    match self {
+      GlslType::Scalar => 4,
+      GlslType::Vector(2) => 8,
+      GlslType::Vector(_) => 16,
+      GlslType::Matrix { rows, .. } => array_element_alignment(&GlslType::Vector(*rows), rules),
+      GlslType::Array(element, _) => array_element_alignment(element, rules),
+      GlslType::Struct(members) => {
+        let alignment = members
+          .iter()
+          .map(|member| member.alignment(rules))
+          .max()
+          .unwrap_or(4);
+        match rules {
+          LayoutRules::Std140 => align_up(alignment, 16),
+          LayoutRules::Std430 => alignment,
+        }
+      }
+    }
+  }
+
+  /// Size in bytes, including the padding between array elements and at the
+  /// end of structs.
+  pub fn size(&self, rules: LayoutRules) -> usize {
+    match self {
+      GlslType::Scalar => 4,
+      GlslType::Vector(components) => 4 * *components as usize,
+      GlslType::Matrix { columns, rows } => {
+        array_stride(&GlslType::Vector(*rows), rules) * *columns as usize
+      }
+      GlslType::Array(element, length) => array_stride(element, rules) * length,
+      GlslType::Struct(members) => {
+        let end = members.iter().fold(0, |offset, member| {
+          align_up(offset, member.alignment(rules)) + member.size(rules)
+        });
+        align_up(end, self.alignment(rules))
+      }
+    }
+  }
+}
+
+/// Rust types that can be members of a uniform block, used by
+/// `#[derive(DescriptorLayout)]` to describe the block.  Rust arrays of up to
+/// 32 elements or a power of two up to 1024 are GLSL arrays, use ultraviolet
+/// types for vectors and matrices.
+pub trait GlslField {
+  fn glsl_type() -> GlslType;
+}
+macro_rules! glsl_fields {
+  ($($ty:ty => $glsl_type:expr,)*) => {
+    $(
+      impl GlslField for $ty {
+        fn glsl_type() -> GlslType {
+          $glsl_type
+        }
+      }
+    )*
+  };
+}
+glsl_fields! {
+  f32 => GlslType::Scalar,
+  u32 => GlslType::Scalar,
+  i32 => GlslType::Scalar,
+  uv::Vec2 => GlslType::Vector(2),
+  uv::Vec3 => GlslType::Vector(3),
+  uv::Vec4 => GlslType::Vector(4),
+  uv::Mat2 => GlslType::Matrix { columns: 2, rows: 2 },
+  uv::Mat3 => GlslType::Matrix { columns: 3, rows: 3 },
+  uv::Mat4 => GlslType::Matrix { columns: 4, rows: 4 },
+}
+// Implemented per length instead of with const generics, which need Rust 1.51.
+macro_rules! glsl_arrays {
+  ($($length:expr)*) => {
+    $(
+      impl<T: GlslField> GlslField for [T; $length] {
+        fn glsl_type() -> GlslType {
+          GlslType::array(T::glsl_type(), $length)
+        }
+      }
+    )*
+  };
+}
+glsl_arrays! {
+  1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+  17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+  64 128 256 512 1024
+}
+
+/// A member of the block as laid out in Rust, usually made with
+/// [uniform_field!](../../macro.uniform_field.html).
+#[derive(Clone, Debug)]
+pub struct UniformField {
+  pub name: &'static str,
+  pub glsl_type: GlslType,
+  /// Offset of the field in the Rust struct.
+  pub offset: usize,
+  /// Size of the field in the Rust struct.
+  pub size: usize,
+}
+impl UniformField {
+  pub fn new(name: &'static str, glsl_type: GlslType, offset: usize, size: usize) -> Self {
+    Self {
+      name,
+      glsl_type,
+      offset,
+      size,
+    }
+  }
+}
+
+/// Makes a [UniformField](renderer/uniform_layout/struct.UniformField.html)
+/// out of a field of a struct, with its Rust offset and size filled in.
+#[macro_export]
+macro_rules! uniform_field {
+  ($struct:path, $field:tt, $glsl_type:expr) => {{
+    let span = $crate::__memoffset::span_of!($struct, $field);
+    $crate::renderer::uniform_layout::UniformField::new(
+      stringify!($field),
+      $glsl_type,
+      span.start,
+      span.end - span.start,
+    )
+  }};
+}
+
+/// Every member of a GLSL block in declaration order, along with the Rust
+/// struct it is compared to.
+#[derive(Clone, Debug)]
+pub struct UniformBlockLayout {
+  pub rules: LayoutRules,
+  pub fields: Vec<UniformField>,
+  /// Size of the Rust struct.
+  pub size: usize,
+}
+impl UniformBlockLayout {
+  pub fn new<T: Sized>(rules: LayoutRules, fields: Vec<UniformField>) -> Self {
+    Self {
+      rules,
+      fields,
+      size: std::mem::size_of::<T>(),
+    }
+  }
+
+  /// Where GLSL puts each field, in the same order as `fields`.
+  pub fn glsl_offsets(&self) -> Vec<usize> {
+    let mut offset = 0;
+    self
+      .fields
+      .iter()
+      .map(|field| {
+        let field_offset = align_up(offset, field.glsl_type.alignment(self.rules));
+        offset = field_offset + field.glsl_type.size(self.rules);
+        field_offset
+      })
+      .collect()
+  }
+
+  /// Compares the Rust layout with the GLSL one, listing every field whose
+  /// offset or size differs in the error.
+  pub fn validate(&self) -> SarektResult<()> {
+    let mut mismatches = Vec::new();
+    let mut block_end = 0;
+    for (field, glsl_offset) in self.fields.iter().zip(self.glsl_offsets()) {
+      let glsl_size = field.glsl_type.size(self.rules);
+      if field.offset != glsl_offset {
+        mismatches.push(format!(
+          "{} is at offset {} but {:?} puts it at {}",
+          field.name, field.offset, self.rules, glsl_offset
+        ));
+      }
+      if field.size != glsl_size {
+        mismatches.push(format!(
+          "{} is {} bytes but its {:?} {:?} size is {}",
+          field.name, field.size, self.rules, field.glsl_type, glsl_size
+        ));
+      }
+      block_end = glsl_offset + glsl_size;
+    }
+
+    if self.size < block_end {
+      mismatches.push(format!(
+        "the struct is {} bytes but the block needs {}",
+        self.size, block_end
+      ));
+    }
+
+    if mismatches.is_empty() {
+      Ok(())
+    } else {
+      Err(SarektError::UniformLayoutMismatch(mismatches.join(", ")))
+    }
+  }
+}
+
+/// Arrays (and matrix columns) are aligned to 16 bytes in std140.
+fn array_element_alignment(element: &GlslType, rules: LayoutRules) -> usize {
+  let alignment = element.alignment(rules);
+  match rules {
+    LayoutRules::Std140 => align_up(alignment, 16),
+    LayoutRules::Std430 => alignment,
+  }
+}
+
+fn array_stride(element: &GlslType, rules: LayoutRules) -> usize {
+  align_up(element.size(rules), array_element_alignment(element, rules))
+}
+
+fn align_up(value: usize, alignment: usize) -> usize {
+  (value + alignment - 1) / alignment * alignment
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn offsets(rules: LayoutRules, glsl_types: Vec<GlslType>) -> Vec<usize> {
+    let fields = glsl_types
+      .into_iter()
+      .map(|glsl_type| UniformField::new("field", glsl_type, 0, 0))
+      .collect();
+    UniformBlockLayout {
+      rules,
+      fields,
+      size: 0,
+    }
+    .glsl_offsets()
+  }
+
+  #[test]
+  fn float_packs_after_vec3() {
+    let glsl_types = vec![GlslType::Vector(3), GlslType::Scalar, GlslType::Vector(3)];
+    assert_eq!(
+      offsets(LayoutRules::Std140, glsl_types.clone()),
+      [0, 12, 16]
+    );
+    assert_eq!(offsets(LayoutRules::Std430, glsl_types), [0, 12, 16]);
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Light {
+      direction: uv::Vec3,
+      intensity: f32,
+    }
+    let layout = UniformBlockLayout::new::<Light>(
+      LayoutRules::Std140,
+      vec![
+        uniform_field!(Light, direction, GlslType::Vector(3)),
+        uniform_field!(Light, intensity, GlslType::Scalar),
+      ],
+    );
+    assert!(layout.validate().is_ok());
+  }
+
+  #[test]
+  fn scalar_arrays_have_16_byte_stride_only_in_std140() {
+    let float_array = GlslType::array(GlslType::Scalar, 4);
+    assert_eq!(float_array.size(LayoutRules::Std140), 64);
+    assert_eq!(float_array.size(LayoutRules::Std430), 16);
+
+    let glsl_types = vec![GlslType::Scalar, float_array, GlslType::Scalar];
+    assert_eq!(
+      offsets(LayoutRules::Std140, glsl_types.clone()),
+      [0, 16, 80]
+    );
+    assert_eq!(offsets(LayoutRules::Std430, glsl_types), [0, 4, 20]);
+  }
+
+  #[test]
+  fn nested_structs_round_up_in_std140() {
+    let pair = GlslType::Struct(vec![GlslType::Scalar, GlslType::Scalar]);
+    assert_eq!(pair.alignment(LayoutRules::Std140), 16);
+    assert_eq!(pair.size(LayoutRules::Std140), 16);
+    assert_eq!(pair.alignment(LayoutRules::Std430), 4);
+    assert_eq!(pair.size(LayoutRules::Std430), 8);
+
+    let glsl_types = vec![GlslType::Scalar, pair, GlslType::Scalar];
+    assert_eq!(
+      offsets(LayoutRules::Std140, glsl_types.clone()),
+      [0, 16, 32]
+    );
+    assert_eq!(offsets(LayoutRules::Std430, glsl_types), [0, 4, 12]);
+  }
+
+  #[test]
+  fn mat3_columns_are_padded_to_vec4() {
+    let mat3 = GlslType::Matrix {
+      columns: 3,
+      rows: 3,
+    };
+    assert_eq!(mat3.size(LayoutRules::Std140), 48);
+    assert_eq!(mat3.size(LayoutRules::Std430), 48);
+    assert_eq!(
+      offsets(LayoutRules::Std140, vec![mat3.clone(), GlslType::Scalar]),
+      [0, 48]
+    );
+
+    // ultraviolet's Mat3 is tightly packed, so it can't be a mat3 member.
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Normals {
+      normal_matrix: uv::Mat3,
+      scale: f32,
+    }
+    let layout = UniformBlockLayout::new::<Normals>(
+      LayoutRules::Std140,
+      vec![
+        uniform_field!(Normals, normal_matrix, mat3),
+        uniform_field!(Normals, scale, GlslType::Scalar),
+      ],
+    );
+    match layout.validate() {
+      Err(SarektError::UniformLayoutMismatch(mismatches)) => {
+        assert!(mismatches.contains("normal_matrix is 36 bytes"));
+        assert!(mismatches.contains("scale is at offset 36 but Std140 puts it at 48"));
+      }
+      result => panic!("expected a layout mismatch, got {:?}", result),
+    }
+  }
+}
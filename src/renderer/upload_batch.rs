@@ -0,0 +1,140 @@
+//! Batched uploads of buffers and images.
+//!
+//! The `load_*` functions of the [Renderer](../trait.Renderer.html) each
+//! stage, submit and wait for their resource before returning, which adds up
+//! when loading a whole level.  Instead many resources can be added to an
+//! [UploadBatch](struct.UploadBatch.html) and submitted together with
+//! `Renderer::submit_upload_batch`, which returns right away with their
+//! handles and an [UploadToken](struct.UploadToken.html) to check on.
+//!
+//! Drawing with, updating or deleting a resource that is still uploading waits
+//! for its batch to finish.
+use crate::{
+  error::SarektResult,
+  image_data::{ImageData, ImageDataFormat},
+  renderer::buffers_and_images::{
+    BufferType, MagnificationMinificationFilter, ResourceType, TextureAddressMode,
+  },
+};
+
+/// Buffers and images to be uploaded together, see the
+/// [module docs](index.html).  The data is copied in when added.
+#[derive(Default)]
+pub struct UploadBatch {
+  uploads: Vec<PendingUpload>,
+}
+impl UploadBatch {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds a vertex or index buffer, the same as `Renderer::load_buffer`.
+  /// Returns the index of its handle in the ones returned on submission.
+  pub fn add_buffer<BufElem: Sized + Copy>(
+    &mut self, buffer_type: BufferType, buffer: &[BufElem],
+  ) -> usize {
+    let bytes = unsafe {
+      std::slice::from_raw_parts(
+        buffer.as_ptr() as *const u8,
+        buffer.len() * std::mem::size_of::<BufElem>(),
+      )
+    };
+    self.uploads.push(PendingUpload::Buffer {
+      buffer_type,
+      length: buffer.len() as u32,
+      bytes: bytes.to_vec(),
+    });
+    self.uploads.len() - 1
+  }
+
+  /// Adds an image, the same as
+  /// `Renderer::load_image_with_staging_initialization` except that formats
+  /// the device can't sample aren't converted, apart from block compressed
+  /// ones which are still decoded on the CPU.  Check
+  /// `Renderer::query_image_format_support` first.
+  ///
+  /// Returns the index of its handle in the ones returned on submission.
+  pub fn add_image(
+    &mut self, pixels: impl ImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
+    address_v: TextureAddressMode, address_w: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<usize> {
+    let format = pixels.format()?;
+    let dimensions = pixels.dimensions();
+    self.uploads.push(PendingUpload::Image {
+      format,
+      dimensions,
+      bytes: pixels.into_bytes(),
+      magnification_filter,
+      minification_filter,
+      address_modes: [address_u, address_v, address_w],
+      mip_levels,
+    });
+    Ok(self.uploads.len() - 1)
+  }
+
+  /// Number of resources in the batch.
+  pub fn len(&self) -> usize {
+    self.uploads.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.uploads.is_empty()
+  }
+
+  /// Takes out the resources for the backend to upload, in the order they were
+  /// added.
+  pub fn into_uploads(self) -> Vec<PendingUpload> {
+    self.uploads
+  }
+
+  /// The type of each resource, in the order they were added.
+  pub(crate) fn resource_types(&self) -> Vec<ResourceType> {
+    self
+      .uploads
+      .iter()
+      .map(|upload| match upload {
+        PendingUpload::Buffer { buffer_type, .. } => ResourceType::Buffer(*buffer_type),
+        PendingUpload::Image { .. } => ResourceType::Image,
+      })
+      .collect()
+  }
+}
+
+/// One resource of an [UploadBatch](struct.UploadBatch.html), consumed by the
+/// backend when the batch is submitted.
+pub enum PendingUpload {
+  Buffer {
+    buffer_type: BufferType,
+    /// Number of elements.
+    length: u32,
+    bytes: Vec<u8>,
+  },
+  Image {
+    format: ImageDataFormat,
+    dimensions: (u32, u32),
+    bytes: Vec<u8>,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+    address_modes: [TextureAddressMode; 3],
+    mip_levels: u32,
+  },
+}
+
+/// Identifies a submitted [UploadBatch](struct.UploadBatch.html), used to
+/// check whether it has finished with `Renderer::is_upload_complete` or to
+/// wait for it with `Renderer::wait_for_upload`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UploadToken {
+  id: u64,
+}
+impl UploadToken {
+  /// Backends number their batches however they like.
+  pub fn new(id: u64) -> Self {
+    Self { id }
+  }
+
+  pub fn id(self) -> u64 {
+    self.id
+  }
+}
@@ -1,6 +1,19 @@
-use crate::error::SarektResult;
+use crate::{
+  error::{SarektError, SarektResult},
+  renderer::{
+    lighting::{
+      DirectionalLight, Material, PointLight, ShadingModel, SpotLight, MAX_DIRECTIONAL_LIGHTS,
+      MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS,
+    },
+    pbr::PbrMaterial,
+    uniform_layout::UniformBlockLayout,
+  },
+};
 use ultraviolet as uv;
 
+/// Derives for the traits below, see the sarekt_derive crate.
+pub use sarekt_derive::{DescriptorLayout, VertexBindings};
+
 /// A trait that provides a static function that generates backend specific
 /// vertex bindings.  This is mainly provided out of convenience and would need
 /// to be custom defined for each backend otherwise.  It is possible to seperate
@@ -30,7 +43,7 @@ pub unsafe trait VertexBindings {
 
 /// Input vertices to the sarekt_forward shader set.
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, VertexBindings)]
 pub struct DefaultForwardShaderVertex {
   pub position: uv::Vec3,
   pub color: uv::Vec3,
@@ -59,9 +72,73 @@ impl DefaultForwardShaderVertex {
   }
 }
 
+/// Input vertices to the sarekt_forward_lit shader set, like
+/// `DefaultForwardShaderVertex` with a normal.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DefaultLitShaderVertex {
+  pub position: uv::Vec3,
+  pub normal: uv::Vec3,
+  pub color: uv::Vec3,
+  pub texture_coordinates: uv::Vec2,
+}
+impl DefaultLitShaderVertex {
+  pub fn new_with_texture(
+    pos: &[f32; 3], normal: &[f32; 3], texture_coordinates: &[f32; 2],
+  ) -> Self {
+    Self::new(pos, normal, &[1f32, 1f32, 1f32], texture_coordinates)
+  }
+
+  pub fn new(
+    pos: &[f32; 3], normal: &[f32; 3], color: &[f32; 3], texture_coordinates: &[f32; 2],
+  ) -> Self {
+    Self {
+      position: uv::Vec3::from(pos),
+      normal: uv::Vec3::from(normal),
+      color: uv::Vec3::from(color),
+      texture_coordinates: uv::Vec2::from(texture_coordinates),
+    }
+  }
+}
+
+/// Input vertices to the sarekt_forward_pbr shader set.  The tangent's w is
+/// the handedness of the bitangent, 1 or -1, as in glTF.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DefaultPbrShaderVertex {
+  pub position: uv::Vec3,
+  pub normal: uv::Vec3,
+  pub tangent: uv::Vec4,
+  pub texture_coordinates: uv::Vec2,
+}
+impl DefaultPbrShaderVertex {
+  /// For meshes without tangents, fill them in afterwards with
+  /// [generate_tangents](../pbr/fn.generate_tangents.html).
+  pub fn without_tangent(
+    pos: &[f32; 3], normal: &[f32; 3], texture_coordinates: &[f32; 2],
+  ) -> Self {
+    Self::new(pos, normal, &[0f32, 0f32, 0f32, 1f32], texture_coordinates)
+  }
+
+  pub fn new(
+    pos: &[f32; 3], normal: &[f32; 3], tangent: &[f32; 4], texture_coordinates: &[f32; 2],
+  ) -> Self {
+    Self {
+      position: uv::Vec3::from(pos),
+      normal: uv::Vec3::from(normal),
+      tangent: uv::Vec4::from(tangent),
+      texture_coordinates: uv::Vec2::from(texture_coordinates),
+    }
+  }
+}
+
 /// Returns the descriptor layouts for the specific backend.  These contain
 /// information such as which bindings to attach each part of uniform to in the
 /// shader, which stages they are used, etc.
+///
+/// In Vulkan the uniform bindings must be `UNIFORM_BUFFER_DYNAMIC`, the
+/// renderer binds them with an offset into either the object's uniform buffer
+/// or the transient uniform buffer.
 pub unsafe trait DescriptorLayoutInfo {
   type BackendDescriptorSetLayoutBindings;
 
@@ -76,6 +153,36 @@ pub unsafe trait DescriptorLayoutInfo {
   /// Gets the information needed to allocate/bind descroptors in teh backend
   /// for textures.
   fn get_bind_texture_info() -> SarektResult<BindTextureInfo>;
+
+  /// Describes the GLSL uniform block so its layout can be checked against
+  /// the struct's when loading a uniform buffer.  None skips the check.
+  fn get_uniform_block_layout() -> Option<UniformBlockLayout> {
+    None
+  }
+
+  /// Which built-in forward shaders objects with this layout are drawn with.
+  fn forward_shader_variant() -> ForwardShaderVariant {
+    ForwardShaderVariant::Plain
+  }
+}
+
+/// The built-in forward shaders, selected by the uniform layout of what is
+/// drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ForwardShaderVariant {
+  /// sarekt_forward, for `DefaultForwardShaderLayout`.
+  Plain,
+  /// sarekt_forward_shadowed, for `DefaultShadowedShaderLayout`.  It also
+  /// samples the shadow map and reads the light's matrices, which the
+  /// renderer binds itself.
+  ShadowReceiving,
+  /// sarekt_forward_lit, for `DefaultLitShaderLayout`.  Its vertices are
+  /// `DefaultLitShaderVertex`.
+  Lit,
+  /// sarekt_forward_pbr, for `DefaultPbrShaderLayout`.  Its vertices are
+  /// `DefaultPbrShaderVertex`, and it also samples the image based lighting
+  /// maps, which the renderer binds itself.
+  Pbr,
 }
 #[derive(Clone, Debug)]
 /// Contains information needed by various backends to configure their
@@ -99,7 +206,9 @@ pub struct BindTextureInfo {
 /// The initial backend is Vulkan, which is why booleans are u32, all scalars
 /// need to be aligned to 4 bytes.
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, DescriptorLayout)]
+#[binding(0)]
+#[texture_binding(1)]
 pub struct DefaultForwardShaderLayout {
   /// The model view projection matrix to apply to the containing
   /// DrawableObject.
@@ -125,3 +234,211 @@ impl Default for DefaultForwardShaderLayout {
     }
   }
 }
+
+/// Input uniforms to the sarekt_forward_shadowed shader set, which draws like
+/// sarekt_forward but darkens what is in shadow, see
+/// [shadows](../shadows/index.html).
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DefaultShadowedShaderLayout {
+  /// The model view projection matrix to apply to the containing
+  /// DrawableObject.
+  pub mvp: uv::Mat4,
+  /// Just the model matrix, to find where the object is in the shadow map.
+  pub model: uv::Mat4,
+  pub enable_color_mixing: u32,
+  pub enable_texture_mixing: u32,
+}
+impl DefaultShadowedShaderLayout {
+  pub fn new(
+    mvp: uv::Mat4, model: uv::Mat4, enable_color_mixing: bool, enable_texture_mixing: bool,
+  ) -> Self {
+    Self {
+      mvp,
+      model,
+      enable_color_mixing: u32::from(enable_color_mixing),
+      enable_texture_mixing: u32::from(enable_texture_mixing),
+    }
+  }
+}
+impl Default for DefaultShadowedShaderLayout {
+  fn default() -> Self {
+    DefaultShadowedShaderLayout {
+      mvp: uv::Mat4::identity(),
+      model: uv::Mat4::identity(),
+      enable_color_mixing: 0u32,
+      enable_texture_mixing: 1u32,
+    }
+  }
+}
+
+/// Input uniforms to the sarekt_forward_lit shader set, which shades with the
+/// lights and material, see [lighting](../lighting/index.html).
+///
+/// Only the first `*_light_count` lights of each array are used, set them with
+/// `set_point_lights` and friends.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DefaultLitShaderLayout {
+  pub model: uv::Mat4,
+  pub view: uv::Mat4,
+  pub projection: uv::Mat4,
+  /// World space position of the camera, for specular highlights.
+  pub camera_position: uv::Vec3,
+  /// A `ShadingModel` as u32.
+  pub shading_model: u32,
+  pub point_lights: [PointLight; MAX_POINT_LIGHTS],
+  pub directional_lights: [DirectionalLight; MAX_DIRECTIONAL_LIGHTS],
+  pub spot_lights: [SpotLight; MAX_SPOT_LIGHTS],
+  pub material: Material,
+  pub point_light_count: u32,
+  pub directional_light_count: u32,
+  pub spot_light_count: u32,
+  pub enable_color_mixing: u32,
+  pub enable_texture_mixing: u32,
+}
+impl DefaultLitShaderLayout {
+  /// No lights, Blinn-Phong shading and texture mixing on.
+  pub fn new(
+    model: uv::Mat4, view: uv::Mat4, projection: uv::Mat4, camera_position: uv::Vec3,
+    material: Material,
+  ) -> Self {
+    Self {
+      model,
+      view,
+      projection,
+      camera_position,
+      material,
+      ..Self::default()
+    }
+  }
+
+  pub fn set_shading_model(&mut self, shading_model: ShadingModel) {
+    self.shading_model = u32::from(shading_model);
+  }
+
+  pub fn set_point_lights(&mut self, lights: &[PointLight]) -> SarektResult<()> {
+    self.point_light_count = copy_lights(&mut self.point_lights, lights, "point")?;
+    Ok(())
+  }
+
+  pub fn set_directional_lights(&mut self, lights: &[DirectionalLight]) -> SarektResult<()> {
+    self.directional_light_count =
+      copy_lights(&mut self.directional_lights, lights, "directional")?;
+    Ok(())
+  }
+
+  pub fn set_spot_lights(&mut self, lights: &[SpotLight]) -> SarektResult<()> {
+    self.spot_light_count = copy_lights(&mut self.spot_lights, lights, "spot")?;
+    Ok(())
+  }
+}
+impl Default for DefaultLitShaderLayout {
+  fn default() -> Self {
+    DefaultLitShaderLayout {
+      model: uv::Mat4::identity(),
+      view: uv::Mat4::identity(),
+      projection: uv::Mat4::identity(),
+      camera_position: uv::Vec3::zero(),
+      shading_model: u32::from(ShadingModel::default()),
+      point_lights: [PointLight::default(); MAX_POINT_LIGHTS],
+      directional_lights: [DirectionalLight::default(); MAX_DIRECTIONAL_LIGHTS],
+      spot_lights: [SpotLight::default(); MAX_SPOT_LIGHTS],
+      material: Material::default(),
+      point_light_count: 0u32,
+      directional_light_count: 0u32,
+      spot_light_count: 0u32,
+      enable_color_mixing: 0u32,
+      enable_texture_mixing: 1u32,
+    }
+  }
+}
+
+/// Input uniforms to the sarekt_forward_pbr shader set, which shades with the
+/// lights and a metallic roughness material, see [pbr](../pbr/index.html).
+///
+/// Only the first `*_light_count` lights of each array are used, set them with
+/// `set_point_lights` and friends.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DefaultPbrShaderLayout {
+  pub model: uv::Mat4,
+  pub view: uv::Mat4,
+  pub projection: uv::Mat4,
+  /// World space position of the camera, for reflections.
+  pub camera_position: uv::Vec3,
+  /// Scales the light from the image based lighting environment.
+  pub environment_intensity: f32,
+  pub point_lights: [PointLight; MAX_POINT_LIGHTS],
+  pub directional_lights: [DirectionalLight; MAX_DIRECTIONAL_LIGHTS],
+  pub spot_lights: [SpotLight; MAX_SPOT_LIGHTS],
+  pub material: PbrMaterial,
+  pub point_light_count: u32,
+  pub directional_light_count: u32,
+  pub spot_light_count: u32,
+}
+impl DefaultPbrShaderLayout {
+  /// No lights and full strength environment lighting.
+  pub fn new(
+    model: uv::Mat4, view: uv::Mat4, projection: uv::Mat4, camera_position: uv::Vec3,
+    material: PbrMaterial,
+  ) -> Self {
+    Self {
+      model,
+      view,
+      projection,
+      camera_position,
+      material,
+      ..Self::default()
+    }
+  }
+
+  pub fn set_point_lights(&mut self, lights: &[PointLight]) -> SarektResult<()> {
+    self.point_light_count = copy_lights(&mut self.point_lights, lights, "point")?;
+    Ok(())
+  }
+
+  pub fn set_directional_lights(&mut self, lights: &[DirectionalLight]) -> SarektResult<()> {
+    self.directional_light_count =
+      copy_lights(&mut self.directional_lights, lights, "directional")?;
+    Ok(())
+  }
+
+  pub fn set_spot_lights(&mut self, lights: &[SpotLight]) -> SarektResult<()> {
+    self.spot_light_count = copy_lights(&mut self.spot_lights, lights, "spot")?;
+    Ok(())
+  }
+}
+impl Default for DefaultPbrShaderLayout {
+  fn default() -> Self {
+    DefaultPbrShaderLayout {
+      model: uv::Mat4::identity(),
+      view: uv::Mat4::identity(),
+      projection: uv::Mat4::identity(),
+      camera_position: uv::Vec3::zero(),
+      environment_intensity: 1f32,
+      point_lights: [PointLight::default(); MAX_POINT_LIGHTS],
+      directional_lights: [DirectionalLight::default(); MAX_DIRECTIONAL_LIGHTS],
+      spot_lights: [SpotLight::default(); MAX_SPOT_LIGHTS],
+      material: PbrMaterial::default(),
+      point_light_count: 0u32,
+      directional_light_count: 0u32,
+      spot_light_count: 0u32,
+    }
+  }
+}
+
+/// Copies `lights` into the start of `slots`, returning how many there are.
+fn copy_lights<L: Copy>(slots: &mut [L], lights: &[L], kind: &str) -> SarektResult<u32> {
+  if lights.len() > slots.len() {
+    return Err(SarektError::TooManyLights(format!(
+      "{} {} lights, at most {} are supported",
+      lights.len(),
+      kind,
+      slots.len()
+    )));
+  }
+
+  slots[..lights.len()].copy_from_slice(lights);
+  Ok(lights.len() as u32)
+}
@@ -0,0 +1,243 @@
+//! Compact vertex attribute types, for cutting mesh memory compared to `f32`
+//! vectors.  The shader still reads floats, normalized types are converted
+//! to [0, 1] (unorm) or [-1, 1] (snorm) by the device.
+//!
+//! Use them as fields of a `#[derive(VertexBindings)]` struct.  Not every
+//! device can read every format from a vertex buffer (half floats with three
+//! components are rarely supported, so only two and four are provided),
+//! check with `VulkanRenderer::check_vertex_format_support`.
+use crate::half::f32_to_f16_bits;
+
+/// Two u8 normalized to [0, 1].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unorm8x2(pub [u8; 2]);
+impl Unorm8x2 {
+  pub fn from_f32(values: [f32; 2]) -> Self {
+    Self([to_unorm8(values[0]), to_unorm8(values[1])])
+  }
+}
+
+/// Four u8 normalized to [0, 1], for colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unorm8x4(pub [u8; 4]);
+impl Unorm8x4 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    Self([
+      to_unorm8(values[0]),
+      to_unorm8(values[1]),
+      to_unorm8(values[2]),
+      to_unorm8(values[3]),
+    ])
+  }
+}
+
+/// Four i8 normalized to [-1, 1].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snorm8x4(pub [i8; 4]);
+impl Snorm8x4 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    Self([
+      to_snorm8(values[0]),
+      to_snorm8(values[1]),
+      to_snorm8(values[2]),
+      to_snorm8(values[3]),
+    ])
+  }
+}
+
+/// Two u16 normalized to [0, 1], for texture coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unorm16x2(pub [u16; 2]);
+impl Unorm16x2 {
+  pub fn from_f32(values: [f32; 2]) -> Self {
+    Self([to_unorm16(values[0]), to_unorm16(values[1])])
+  }
+}
+
+/// Four u16 normalized to [0, 1].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unorm16x4(pub [u16; 4]);
+impl Unorm16x4 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    Self([
+      to_unorm16(values[0]),
+      to_unorm16(values[1]),
+      to_unorm16(values[2]),
+      to_unorm16(values[3]),
+    ])
+  }
+}
+
+/// Two i16 normalized to [-1, 1], for texture coordinates that wrap.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snorm16x2(pub [i16; 2]);
+impl Snorm16x2 {
+  pub fn from_f32(values: [f32; 2]) -> Self {
+    Self([to_snorm16(values[0]), to_snorm16(values[1])])
+  }
+}
+
+/// Four i16 normalized to [-1, 1].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snorm16x4(pub [i16; 4]);
+impl Snorm16x4 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    Self([
+      to_snorm16(values[0]),
+      to_snorm16(values[1]),
+      to_snorm16(values[2]),
+      to_snorm16(values[3]),
+    ])
+  }
+}
+
+/// Two half precision floats, stored as their bits.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Half2(pub [u16; 2]);
+impl Half2 {
+  pub fn from_f32(values: [f32; 2]) -> Self {
+    Self([f32_to_f16_bits(values[0]), f32_to_f16_bits(values[1])])
+  }
+}
+
+/// Four half precision floats, stored as their bits.  For positions put 1 in
+/// w, the shader can read it as a `vec3` all the same.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Half4(pub [u16; 4]);
+impl Half4 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    Self([
+      f32_to_f16_bits(values[0]),
+      f32_to_f16_bits(values[1]),
+      f32_to_f16_bits(values[2]),
+      f32_to_f16_bits(values[3]),
+    ])
+  }
+}
+
+/// x, y and z in 10 bits each and w in 2, all normalized to [0, 1].  x is in
+/// the lowest bits.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unorm1010102(pub u32);
+impl Unorm1010102 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    let to_bits = |value: f32, max: u32| (value.max(0.0).min(1.0) * max as f32).round() as u32;
+    Self(
+      to_bits(values[0], 0x3FF)
+        | to_bits(values[1], 0x3FF) << 10
+        | to_bits(values[2], 0x3FF) << 20
+        | to_bits(values[3], 0x3) << 30,
+    )
+  }
+}
+
+/// x, y and z in 10 bits each and w in 2, all normalized to [-1, 1], for
+/// normals and tangents (w being the bitangent sign).  x is in the lowest
+/// bits.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snorm1010102(pub u32);
+impl Snorm1010102 {
+  pub fn from_f32(values: [f32; 4]) -> Self {
+    // Two's complement, masked to the field width.
+    let to_bits = |value: f32, max: i32, mask: u32| {
+      ((value.max(-1.0).min(1.0) * max as f32).round() as i32) as u32 & mask
+    };
+    Self(
+      to_bits(values[0], 511, 0x3FF)
+        | to_bits(values[1], 511, 0x3FF) << 10
+        | to_bits(values[2], 511, 0x3FF) << 20
+        | to_bits(values[3], 1, 0x3) << 30,
+    )
+  }
+}
+
+fn to_unorm8(value: f32) -> u8 {
+  (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn to_snorm8(value: f32) -> i8 {
+  (value.max(-1.0).min(1.0) * 127.0).round() as i8
+}
+
+fn to_unorm16(value: f32) -> u16 {
+  (value.max(0.0).min(1.0) * 65535.0).round() as u16
+}
+
+fn to_snorm16(value: f32) -> i16 {
+  (value.max(-1.0).min(1.0) * 32767.0).round() as i16
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unorm1010102_packs_x_into_the_lowest_bits() {
+    assert_eq!(Unorm1010102::from_f32([1.0, 0.0, 0.0, 0.0]).0, 0x3FF);
+    assert_eq!(Unorm1010102::from_f32([0.0, 1.0, 0.0, 0.0]).0, 0x3FF << 10);
+    assert_eq!(Unorm1010102::from_f32([0.0, 0.0, 1.0, 0.0]).0, 0x3FF << 20);
+    assert_eq!(Unorm1010102::from_f32([0.0, 0.0, 0.0, 1.0]).0, 0xC000_0000);
+    assert_eq!(Unorm1010102::from_f32([1.0; 4]).0, u32::MAX);
+  }
+
+  #[test]
+  fn unorm1010102_clamps_and_rounds() {
+    assert_eq!(
+      Unorm1010102::from_f32([2.0, -1.0, 0.5, 0.5]).0,
+      0x3FF | 0x200 << 20 | 2 << 30
+    );
+  }
+
+  #[test]
+  fn snorm1010102_packs_twos_complement_fields() {
+    assert_eq!(Snorm1010102::from_f32([1.0, 0.0, 0.0, 0.0]).0, 0x1FF);
+    assert_eq!(Snorm1010102::from_f32([-1.0, 0.0, 0.0, 0.0]).0, 0x201);
+    assert_eq!(Snorm1010102::from_f32([0.0, -1.0, 0.0, 0.0]).0, 0x201 << 10);
+    assert_eq!(Snorm1010102::from_f32([0.0, 0.0, 1.0, 0.0]).0, 0x1FF << 20);
+    assert_eq!(Snorm1010102::from_f32([0.0, 0.0, 0.0, 1.0]).0, 1 << 30);
+    assert_eq!(Snorm1010102::from_f32([0.0, 0.0, 0.0, -1.0]).0, 0xC000_0000);
+  }
+
+  #[test]
+  fn snorm1010102_clamps() {
+    assert_eq!(
+      Snorm1010102::from_f32([-2.0, 2.0, 0.0, -3.0]).0,
+      0x201 | 0x1FF << 10 | 0xC000_0000
+    );
+  }
+
+  #[test]
+  fn snorm_packers_clamp_to_the_symmetric_range() {
+    assert_eq!(
+      Snorm8x4::from_f32([1.0, -1.0, 2.0, -2.0]).0,
+      [127, -127, 127, -127]
+    );
+    assert_eq!(Snorm8x4::from_f32([0.5, -0.5, 0.0, 0.0]).0, [64, -64, 0, 0]);
+    assert_eq!(Snorm16x2::from_f32([5.0, -5.0]).0, [32767, -32767]);
+    assert_eq!(
+      Snorm16x4::from_f32([0.5, -0.5, 1.0, -1.0]).0,
+      [16384, -16384, 32767, -32767]
+    );
+  }
+
+  #[test]
+  fn unorm_packers_clamp_and_round() {
+    assert_eq!(
+      Unorm8x4::from_f32([0.5, -1.0, 2.0, 1.0]).0,
+      [128, 0, 255, 255]
+    );
+    assert_eq!(Unorm16x2::from_f32([0.5, 1.5]).0, [32768, 65535]);
+    assert_eq!(Half2::from_f32([1.0, -2.0]).0, [0x3C00, 0xC000]);
+  }
+}
@@ -2,7 +2,8 @@ use crate::{
   error::{SarektError, SarektResult},
   image_data::ImageDataFormat,
   renderer::{
-    config::NumSamples, vulkan::vulkan_shader_functions::VulkanShaderFunctions, ShaderHandle,
+    config::NumSamples, primitive_state::PrimitiveTopology,
+    vulkan::vulkan_shader_functions::VulkanShaderFunctions, ShaderHandle,
   },
 };
 use ash::vk;
@@ -28,6 +29,19 @@ impl From<NumSamples> for vk::SampleCountFlags {
   }
 }
 
+impl From<PrimitiveTopology> for vk::PrimitiveTopology {
+  fn from(topology: PrimitiveTopology) -> vk::PrimitiveTopology {
+    match topology {
+      PrimitiveTopology::PointList => vk::PrimitiveTopology::POINT_LIST,
+      PrimitiveTopology::LineList => vk::PrimitiveTopology::LINE_LIST,
+      PrimitiveTopology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+      PrimitiveTopology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+      PrimitiveTopology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+      PrimitiveTopology::TriangleFan => vk::PrimitiveTopology::TRIANGLE_FAN,
+    }
+  }
+}
+
 impl From<ImageDataFormat> for vk::Format {
   fn from(image_data_format: ImageDataFormat) -> vk::Format {
     match image_data_format {
@@ -44,9 +58,61 @@ impl From<ImageDataFormat> for vk::Format {
       ImageDataFormat::RGB16Unorm => vk::Format::R5G6B5_UNORM_PACK16,
       ImageDataFormat::RGBA16Unorm => vk::Format::R5G5B5A1_UNORM_PACK16,
 
+      ImageDataFormat::R8Unorm => vk::Format::R8_UNORM,
+      ImageDataFormat::R8G8Unorm => vk::Format::R8G8_UNORM,
+      ImageDataFormat::R8Snorm => vk::Format::R8_SNORM,
+      ImageDataFormat::R8G8Snorm => vk::Format::R8G8_SNORM,
+      ImageDataFormat::R16Unorm => vk::Format::R16_UNORM,
+      ImageDataFormat::R16G16Unorm => vk::Format::R16G16_UNORM,
+
+      ImageDataFormat::R16G16B16Unorm => vk::Format::R16G16B16_UNORM,
+      ImageDataFormat::R16G16B16A16Unorm => vk::Format::R16G16B16A16_UNORM,
+      ImageDataFormat::R16G16Sfloat => vk::Format::R16G16_SFLOAT,
+      ImageDataFormat::R16G16B16A16Sfloat => vk::Format::R16G16B16A16_SFLOAT,
+      ImageDataFormat::R32G32B32A32Sfloat => vk::Format::R32G32B32A32_SFLOAT,
+
       ImageDataFormat::D32Float => vk::Format::D32_SFLOAT,
       ImageDataFormat::D32FloatS8 => vk::Format::D32_SFLOAT_S8_UINT,
       ImageDataFormat::D24NormS8 => vk::Format::D24_UNORM_S8_UINT,
+
+      ImageDataFormat::Bc1RgbUnorm => vk::Format::BC1_RGB_UNORM_BLOCK,
+      ImageDataFormat::Bc1RgbSrgb => vk::Format::BC1_RGB_SRGB_BLOCK,
+      ImageDataFormat::Bc1RgbaUnorm => vk::Format::BC1_RGBA_UNORM_BLOCK,
+      ImageDataFormat::Bc1RgbaSrgb => vk::Format::BC1_RGBA_SRGB_BLOCK,
+      ImageDataFormat::Bc2Unorm => vk::Format::BC2_UNORM_BLOCK,
+      ImageDataFormat::Bc2Srgb => vk::Format::BC2_SRGB_BLOCK,
+      ImageDataFormat::Bc3Unorm => vk::Format::BC3_UNORM_BLOCK,
+      ImageDataFormat::Bc3Srgb => vk::Format::BC3_SRGB_BLOCK,
+      ImageDataFormat::Bc4Unorm => vk::Format::BC4_UNORM_BLOCK,
+      ImageDataFormat::Bc4Snorm => vk::Format::BC4_SNORM_BLOCK,
+      ImageDataFormat::Bc5Unorm => vk::Format::BC5_UNORM_BLOCK,
+      ImageDataFormat::Bc5Snorm => vk::Format::BC5_SNORM_BLOCK,
+      ImageDataFormat::Bc6hUfloat => vk::Format::BC6H_UFLOAT_BLOCK,
+      ImageDataFormat::Bc6hSfloat => vk::Format::BC6H_SFLOAT_BLOCK,
+      ImageDataFormat::Bc7Unorm => vk::Format::BC7_UNORM_BLOCK,
+      ImageDataFormat::Bc7Srgb => vk::Format::BC7_SRGB_BLOCK,
+
+      ImageDataFormat::Etc2R8G8B8Unorm => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+      ImageDataFormat::Etc2R8G8B8Srgb => vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+      ImageDataFormat::Etc2R8G8B8A1Unorm => vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+      ImageDataFormat::Etc2R8G8B8A1Srgb => vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+      ImageDataFormat::Etc2R8G8B8A8Unorm => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+      ImageDataFormat::Etc2R8G8B8A8Srgb => vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+      ImageDataFormat::EacR11Unorm => vk::Format::EAC_R11_UNORM_BLOCK,
+      ImageDataFormat::EacR11G11Unorm => vk::Format::EAC_R11G11_UNORM_BLOCK,
+
+      ImageDataFormat::Astc4x4Unorm => vk::Format::ASTC_4X4_UNORM_BLOCK,
+      ImageDataFormat::Astc4x4Srgb => vk::Format::ASTC_4X4_SRGB_BLOCK,
+      ImageDataFormat::Astc5x5Unorm => vk::Format::ASTC_5X5_UNORM_BLOCK,
+      ImageDataFormat::Astc5x5Srgb => vk::Format::ASTC_5X5_SRGB_BLOCK,
+      ImageDataFormat::Astc6x6Unorm => vk::Format::ASTC_6X6_UNORM_BLOCK,
+      ImageDataFormat::Astc6x6Srgb => vk::Format::ASTC_6X6_SRGB_BLOCK,
+      ImageDataFormat::Astc8x8Unorm => vk::Format::ASTC_8X8_UNORM_BLOCK,
+      ImageDataFormat::Astc8x8Srgb => vk::Format::ASTC_8X8_SRGB_BLOCK,
+      ImageDataFormat::Astc10x10Unorm => vk::Format::ASTC_10X10_UNORM_BLOCK,
+      ImageDataFormat::Astc10x10Srgb => vk::Format::ASTC_10X10_SRGB_BLOCK,
+      ImageDataFormat::Astc12x12Unorm => vk::Format::ASTC_12X12_UNORM_BLOCK,
+      ImageDataFormat::Astc12x12Srgb => vk::Format::ASTC_12X12_SRGB_BLOCK,
     }
   }
 }
@@ -69,10 +135,62 @@ impl TryFrom<vk::Format> for ImageDataFormat {
       vk::Format::R5G6B5_UNORM_PACK16 => Ok(ImageDataFormat::RGB16Unorm),
       vk::Format::R5G5B5A1_UNORM_PACK16 => Ok(ImageDataFormat::RGBA16Unorm),
 
+      vk::Format::R8_UNORM => Ok(ImageDataFormat::R8Unorm),
+      vk::Format::R8G8_UNORM => Ok(ImageDataFormat::R8G8Unorm),
+      vk::Format::R8_SNORM => Ok(ImageDataFormat::R8Snorm),
+      vk::Format::R8G8_SNORM => Ok(ImageDataFormat::R8G8Snorm),
+      vk::Format::R16_UNORM => Ok(ImageDataFormat::R16Unorm),
+      vk::Format::R16G16_UNORM => Ok(ImageDataFormat::R16G16Unorm),
+
+      vk::Format::R16G16B16_UNORM => Ok(ImageDataFormat::R16G16B16Unorm),
+      vk::Format::R16G16B16A16_UNORM => Ok(ImageDataFormat::R16G16B16A16Unorm),
+      vk::Format::R16G16_SFLOAT => Ok(ImageDataFormat::R16G16Sfloat),
+      vk::Format::R16G16B16A16_SFLOAT => Ok(ImageDataFormat::R16G16B16A16Sfloat),
+      vk::Format::R32G32B32A32_SFLOAT => Ok(ImageDataFormat::R32G32B32A32Sfloat),
+
       vk::Format::D32_SFLOAT => Ok(ImageDataFormat::D32Float),
       vk::Format::D32_SFLOAT_S8_UINT => Ok(ImageDataFormat::D32FloatS8),
       vk::Format::D24_UNORM_S8_UINT => Ok(ImageDataFormat::D24NormS8),
 
+      vk::Format::BC1_RGB_UNORM_BLOCK => Ok(ImageDataFormat::Bc1RgbUnorm),
+      vk::Format::BC1_RGB_SRGB_BLOCK => Ok(ImageDataFormat::Bc1RgbSrgb),
+      vk::Format::BC1_RGBA_UNORM_BLOCK => Ok(ImageDataFormat::Bc1RgbaUnorm),
+      vk::Format::BC1_RGBA_SRGB_BLOCK => Ok(ImageDataFormat::Bc1RgbaSrgb),
+      vk::Format::BC2_UNORM_BLOCK => Ok(ImageDataFormat::Bc2Unorm),
+      vk::Format::BC2_SRGB_BLOCK => Ok(ImageDataFormat::Bc2Srgb),
+      vk::Format::BC3_UNORM_BLOCK => Ok(ImageDataFormat::Bc3Unorm),
+      vk::Format::BC3_SRGB_BLOCK => Ok(ImageDataFormat::Bc3Srgb),
+      vk::Format::BC4_UNORM_BLOCK => Ok(ImageDataFormat::Bc4Unorm),
+      vk::Format::BC4_SNORM_BLOCK => Ok(ImageDataFormat::Bc4Snorm),
+      vk::Format::BC5_UNORM_BLOCK => Ok(ImageDataFormat::Bc5Unorm),
+      vk::Format::BC5_SNORM_BLOCK => Ok(ImageDataFormat::Bc5Snorm),
+      vk::Format::BC6H_UFLOAT_BLOCK => Ok(ImageDataFormat::Bc6hUfloat),
+      vk::Format::BC6H_SFLOAT_BLOCK => Ok(ImageDataFormat::Bc6hSfloat),
+      vk::Format::BC7_UNORM_BLOCK => Ok(ImageDataFormat::Bc7Unorm),
+      vk::Format::BC7_SRGB_BLOCK => Ok(ImageDataFormat::Bc7Srgb),
+
+      vk::Format::ETC2_R8G8B8_UNORM_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8Unorm),
+      vk::Format::ETC2_R8G8B8_SRGB_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8Srgb),
+      vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8A1Unorm),
+      vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8A1Srgb),
+      vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8A8Unorm),
+      vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => Ok(ImageDataFormat::Etc2R8G8B8A8Srgb),
+      vk::Format::EAC_R11_UNORM_BLOCK => Ok(ImageDataFormat::EacR11Unorm),
+      vk::Format::EAC_R11G11_UNORM_BLOCK => Ok(ImageDataFormat::EacR11G11Unorm),
+
+      vk::Format::ASTC_4X4_UNORM_BLOCK => Ok(ImageDataFormat::Astc4x4Unorm),
+      vk::Format::ASTC_4X4_SRGB_BLOCK => Ok(ImageDataFormat::Astc4x4Srgb),
+      vk::Format::ASTC_5X5_UNORM_BLOCK => Ok(ImageDataFormat::Astc5x5Unorm),
+      vk::Format::ASTC_5X5_SRGB_BLOCK => Ok(ImageDataFormat::Astc5x5Srgb),
+      vk::Format::ASTC_6X6_UNORM_BLOCK => Ok(ImageDataFormat::Astc6x6Unorm),
+      vk::Format::ASTC_6X6_SRGB_BLOCK => Ok(ImageDataFormat::Astc6x6Srgb),
+      vk::Format::ASTC_8X8_UNORM_BLOCK => Ok(ImageDataFormat::Astc8x8Unorm),
+      vk::Format::ASTC_8X8_SRGB_BLOCK => Ok(ImageDataFormat::Astc8x8Srgb),
+      vk::Format::ASTC_10X10_UNORM_BLOCK => Ok(ImageDataFormat::Astc10x10Unorm),
+      vk::Format::ASTC_10X10_SRGB_BLOCK => Ok(ImageDataFormat::Astc10x10Srgb),
+      vk::Format::ASTC_12X12_UNORM_BLOCK => Ok(ImageDataFormat::Astc12x12Unorm),
+      vk::Format::ASTC_12X12_SRGB_BLOCK => Ok(ImageDataFormat::Astc12x12Srgb),
+
       _ => Err(SarektError::UnsupportedImageFormat),
     }
   }
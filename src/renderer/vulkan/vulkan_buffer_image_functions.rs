@@ -1,12 +1,19 @@
 use crate::{
   error::{SarektError, SarektResult},
-  image_data::{ImageData, ImageDataFormat},
+  image_data::{
+    cubemap::{CubemapImageData, MipmappedCubemapImageData},
+    decompress::decompress_level,
+    layered::LayeredImageData,
+    mip_level_dimensions, ImageData, ImageDataFormat, MipmappedImageData,
+  },
   renderer::{
     buffers_and_images::{
-      BackendHandleTrait, BufferAndImageLoader, BufferImageHandle, BufferType, IndexBufferElemSize,
-      MagnificationMinificationFilter, TextureAddressMode,
+      BackendHandleTrait, BufferAndImageLoader, BufferImageHandle, BufferType, ImageFormatSupport,
+      ImageRegion, IndexBufferElemSize, MagnificationMinificationFilter, TextureAddressMode,
     },
     config::NumSamples,
+    memory_stats::{DefragmentationStats, HeapStats},
+    upload_batch::{PendingUpload, UploadBatch, UploadToken},
     vulkan::{
       images::ImageAndView,
       vulkan_renderer::vulkan_core::{VulkanCoreStructures, VulkanDeviceStructures},
@@ -14,18 +21,16 @@ use crate::{
   },
 };
 use ash::{
-  version::{DeviceV1_0, InstanceV1_0},
+  version::{DeviceV1_0, InstanceV1_0, InstanceV1_1},
   vk, Device, Instance,
 };
-use log::{info, warn};
-use std::sync::Arc;
-
-/// TODO(issue#27) PERFORMANCE stage buffer allocations to be transfered in one
-/// staging buffer commit load operation instead of doing each one seperate and
-/// waiting. Be sure to only delete staging stuff after the commit operation.
-
-/// TODO(issue#28) PERFORMANCE MEMORY allow swapping memory with "lost" in VMA.
-/// TODO(issue#28) PERFORMANCE MEMORY defragmentation of VMA
+use log::{error, info, warn};
+use std::{
+  collections::{HashMap, HashSet},
+  convert::TryInto,
+  ffi::CStr,
+  sync::{Arc, Mutex},
+};
 
 /// Vulkan implementation of [BufferLoader](trait.BufferLoader.html).
 #[derive(Clone)]
@@ -35,14 +40,19 @@ pub struct VulkanBufferImageFunctions {
   physical_device: vk::PhysicalDevice,
 
   allocator: Arc<vk_mem::Allocator>,
+  transfer_command_pool: vk::CommandPool,
   transfer_command_buffer: vk::CommandBuffer,
   transfer_command_queue: vk::Queue,
+  graphics_command_pool: vk::CommandPool,
   graphics_command_buffer: vk::CommandBuffer,
   graphics_command_queue: vk::Queue,
   graphics_queue_family: u32,
   transfer_queue_family: u32,
 
   ownership_semaphore: [vk::Semaphore; 1],
+
+  /// Upload batches submitted but not yet known to be complete.
+  pending_uploads: Arc<Mutex<PendingUploads>>,
 }
 impl VulkanBufferImageFunctions {
   pub fn new(
@@ -94,14 +104,18 @@ impl VulkanBufferImageFunctions {
       physical_device: device_bundle.physical_device,
 
       allocator,
+      transfer_command_pool,
       transfer_command_buffer,
       transfer_command_queue,
+      graphics_command_pool,
       graphics_command_buffer,
       graphics_command_queue,
       graphics_queue_family,
       transfer_queue_family,
 
       ownership_semaphore,
+
+      pending_uploads: Arc::new(Mutex::new(PendingUploads::default())),
     })
   }
 
@@ -177,19 +191,20 @@ impl VulkanBufferImageFunctions {
   /// Creates a buffer with TRANSFER_DST and appropriate image type flags
   /// flipped.
   fn create_gpu_image(
-    &self, dimens: (u32, u32), format: vk::Format, usage: vk::ImageUsageFlags,
+    &self, dimens: (u32, u32), shape: ImageShape, format: vk::Format, usage: vk::ImageUsageFlags,
     queue_family_index: u32, mip_levels: u32, num_msaa_samples: NumSamples,
   ) -> SarektResult<(vk::Image, vk_mem::Allocation, vk_mem::AllocationInfo)> {
     let image_ci = vk::ImageCreateInfo::builder()
-      .image_type(vk::ImageType::TYPE_2D)
+      .flags(shape.create_flags())
+      .image_type(shape.image_type())
       .usage(usage)
       .extent(vk::Extent3D {
         width: dimens.0,
         height: dimens.1,
-        depth: 1,
+        depth: shape.depth(),
       })
       .mip_levels(mip_levels)
-      .array_layers(1) // Not an array.
+      .array_layers(shape.array_layers())
       .format(format)
       .tiling(vk::ImageTiling::OPTIMAL) // Texels are laid out in hardware optimal format, not necessarily linearly.
       .initial_layout(vk::ImageLayout::UNDEFINED)
@@ -205,11 +220,11 @@ impl VulkanBufferImageFunctions {
     Ok(self.allocator.create_image(&image_ci, &alloc_ci)?)
   }
 
-  // TODO(issue#30) IMAGES support pre-generated mip levels.
   fn transfer_staging_to_gpu_buffer_or_image(
     &self, buffer_size: u64, staging_buffer: vk::Buffer, gpu_buffer_or_image: ImageOrBuffer,
-    mip_levels: Option<u32>,
+    mip_chain: Option<MipChainSource>,
   ) -> SarektResult<()> {
+    let mip_levels = mip_chain.map(|mip_chain| mip_chain.level_count());
     info!("Initiating transfer command to transfer from staging buffer to device only memory...");
     let transfer_command_buffer = self.transfer_command_buffer;
 
@@ -237,7 +252,7 @@ impl VulkanBufferImageFunctions {
 
           (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
         }
-        ImageOrBuffer::Image(gpu_image, _format, extent) => {
+        ImageOrBuffer::Image(gpu_image, _format, extent, shape) => {
           // Transition layout to transfer destination.
           // This wont transfer ownership of queues, no need to check.
           self.insert_layout_transition_barrier(
@@ -248,23 +263,22 @@ impl VulkanBufferImageFunctions {
             vk::QUEUE_FAMILY_IGNORED,
             vk::QUEUE_FAMILY_IGNORED,
             mip_levels.unwrap_or(1),
+            shape.array_layers(),
           )?;
 
-          // Do the copy
-          let image_subresource = vk::ImageSubresourceLayers::builder()
-            .aspect_mask(vk::ImageAspectFlags::COLOR)
-            .mip_level(0)
-            .base_array_layer(0)
-            .layer_count(1)
-            .build();
-          let regions = [vk::BufferImageCopy::builder()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(image_subresource)
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(extent)
-            .build()];
+          // Do the copy, one region per level that is present in the staging buffer.
+          // Every layer of a level is tightly packed after the previous one.
+          let layer_count = shape.array_layers();
+          let regions: Vec<_> = match mip_chain {
+            Some(MipChainSource::Pregenerated(level_offsets)) => level_offsets
+              .iter()
+              .enumerate()
+              .map(|(level, &offset)| {
+                Self::level_copy_region(extent, level as u32, layer_count, offset)
+              })
+              .collect(),
+            _ => vec![Self::level_copy_region(extent, 0, layer_count, 0)],
+          };
           self.logical_device.cmd_copy_buffer_to_image(
             transfer_command_buffer,
             staging_buffer,
@@ -285,6 +299,7 @@ impl VulkanBufferImageFunctions {
             self.transfer_queue_family,
             self.graphics_queue_family,
             mip_levels.unwrap_or(1),
+            shape.array_layers(),
           )?
         }
       };
@@ -309,7 +324,7 @@ impl VulkanBufferImageFunctions {
 
       self.accept_image_transfer_and_generate_mipmaps(
         &gpu_buffer_or_image,
-        mip_levels,
+        mip_chain,
         src_queue_family,
         dst_queue_family,
       )?;
@@ -329,14 +344,43 @@ impl VulkanBufferImageFunctions {
     Ok(())
   }
 
+  /// Describes copying one mip level of every layer out of the staging buffer
+  /// at the given offset.
+  fn level_copy_region(
+    extent: vk::Extent3D, level: u32, layer_count: u32, offset: u64,
+  ) -> vk::BufferImageCopy {
+    let (width, height) = mip_level_dimensions((extent.width, extent.height), level);
+    let depth = (extent.depth >> level).max(1);
+    let image_subresource = vk::ImageSubresourceLayers::builder()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .mip_level(level)
+      .base_array_layer(0)
+      .layer_count(layer_count)
+      .build();
+    vk::BufferImageCopy::builder()
+      .buffer_offset(offset)
+      .buffer_row_length(0) // Tightly packed.
+      .buffer_image_height(0)
+      .image_subresource(image_subresource)
+      .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+      .image_extent(vk::Extent3D {
+        width,
+        height,
+        depth,
+      })
+      .build()
+  }
+
   /// Accepts ownership of the image and generates the requested number of mip
-  /// levels using a blit.
+  /// levels using a blit, or if they were pre-generated just transitions them
+  /// all to shader read only optimal.
   unsafe fn accept_image_transfer_and_generate_mipmaps(
-    &self, gpu_image: &ImageOrBuffer, mip_levels: Option<u32>, src_queue_family: u32,
+    &self, gpu_image: &ImageOrBuffer, mip_chain: Option<MipChainSource>, src_queue_family: u32,
     dst_queue_family: u32,
   ) -> SarektResult<()> {
+    let mip_levels = mip_chain.map(|mip_chain| mip_chain.level_count());
     match gpu_image {
-      ImageOrBuffer::Image(gpu_image, format, extent) => {
+      ImageOrBuffer::Image(gpu_image, format, extent, shape) => {
         let command_begin_info = vk::CommandBufferBeginInfo::builder()
           .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
           .build();
@@ -344,21 +388,39 @@ impl VulkanBufferImageFunctions {
           .logical_device
           .begin_command_buffer(self.graphics_command_buffer, &command_begin_info)?;
         self.transfer_image_queue_ownership_if_necessary(
+          self.graphics_command_buffer,
           *gpu_image,
           src_queue_family,
           dst_queue_family,
           mip_levels,
+          shape.array_layers(),
         )?;
-        // This is an image with mipmaps, generate them now that image is owned by
-        // graphics command queue and can therefore do blit operations.
-        self.generate_mipmaps_shader_ro_optimal(
-          self.graphics_command_buffer,
-          *gpu_image,
-          *format,
-          extent.width,
-          extent.height,
-          mip_levels.unwrap_or(1),
-        )?;
+        if let Some(MipChainSource::Pregenerated(_)) = mip_chain {
+          // Every level was copied over, nothing to blit.
+          self.insert_layout_transition_barrier(
+            self.graphics_command_buffer,
+            *gpu_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::QUEUE_FAMILY_IGNORED,
+            vk::QUEUE_FAMILY_IGNORED,
+            mip_levels.unwrap_or(1),
+            shape.array_layers(),
+          )?;
+        } else {
+          // This is an image with mipmaps, generate them now that image is owned by
+          // graphics command queue and can therefore do blit operations.
+          self.generate_mipmaps_shader_ro_optimal(
+            self.graphics_command_buffer,
+            *gpu_image,
+            *format,
+            *extent,
+            0,
+            mip_levels.unwrap_or(1),
+            0,
+            shape.array_layers(),
+          )?;
+        }
       }
       _ => (),
     }
@@ -394,8 +456,8 @@ impl VulkanBufferImageFunctions {
   /// accept end of the memory barrier must also be run in a command buffer of
   /// the queue taking ownership of the resource.
   unsafe fn transfer_image_queue_ownership_if_necessary(
-    &self, gpu_image: vk::Image, src_queue_family: u32, dst_queue_family: u32,
-    mip_levels: Option<u32>,
+    &self, graphics_command_buffer: vk::CommandBuffer, gpu_image: vk::Image, src_queue_family: u32,
+    dst_queue_family: u32, mip_levels: Option<u32>, layer_count: u32,
   ) -> SarektResult<()> {
     if src_queue_family == dst_queue_family {
       return Ok(());
@@ -407,7 +469,7 @@ impl VulkanBufferImageFunctions {
       .base_mip_level(0)
       .level_count(mip_levels.unwrap_or(1))
       .base_array_layer(0)
-      .layer_count(1)
+      .layer_count(layer_count)
       .build();
     let barriers = [vk::ImageMemoryBarrier::builder()
         .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
@@ -420,7 +482,7 @@ impl VulkanBufferImageFunctions {
         .dst_access_mask(vk::AccessFlags::SHADER_READ)
         .build()];
     self.logical_device.cmd_pipeline_barrier(
-      self.graphics_command_buffer,
+      graphics_command_buffer,
       vk::PipelineStageFlags::TOP_OF_PIPE,
       vk::PipelineStageFlags::FRAGMENT_SHADER,
       vk::DependencyFlags::empty(),
@@ -432,13 +494,15 @@ impl VulkanBufferImageFunctions {
     Ok(())
   }
 
-  /// Use blitting to create mipmap textures.
-  /// Returns the source and destination queue family indices.
+  /// Use blitting to create mipmap textures, every layer is blitted at once.
+  /// Levels from `base_mip_level` on must be in TRANSFER_DST_OPTIMAL, each one
+  /// after it is blitted from the previous.  `extent` is that of level 0.
   unsafe fn generate_mipmaps_shader_ro_optimal(
     &self, graphics_command_buffer: vk::CommandBuffer, image: vk::Image, format: vk::Format,
-    width: u32, height: u32, mip_levels: u32,
+    extent: vk::Extent3D, base_mip_level: u32, mip_levels: u32, base_array_layer: u32,
+    layer_count: u32,
   ) -> SarektResult<()> {
-    if mip_levels > 1 {
+    if mip_levels > base_mip_level + 1 {
       // Check blitting supported.
       let format_properties = self
         .instance
@@ -455,14 +519,15 @@ impl VulkanBufferImageFunctions {
       }
     }
 
-    let mut mip_width = width;
-    let mut mip_height = height;
-    for i in 1..mip_levels {
+    let mut mip_width = (extent.width >> base_mip_level).max(1);
+    let mut mip_height = (extent.height >> base_mip_level).max(1);
+    let mut mip_depth = (extent.depth >> base_mip_level).max(1);
+    for i in base_mip_level + 1..mip_levels {
       // First transition previous image layout to transfer src optimal.
       let subresource_range = vk::ImageSubresourceRange::builder()
         .aspect_mask(vk::ImageAspectFlags::COLOR)
-        .base_array_layer(0)
-        .layer_count(1)
+        .base_array_layer(base_array_layer)
+        .layer_count(layer_count)
         .base_mip_level(i - 1)
         .level_count(1)
         .build();
@@ -492,28 +557,28 @@ impl VulkanBufferImageFunctions {
         vk::Offset3D::builder()
           .x(mip_width as i32)
           .y(mip_height as i32)
-          .z(1)
+          .z(mip_depth as i32)
           .build(),
       ];
       let src_subresource = vk::ImageSubresourceLayers::builder()
         .aspect_mask(vk::ImageAspectFlags::COLOR)
         .mip_level(i - 1)
-        .base_array_layer(0)
-        .layer_count(1)
+        .base_array_layer(base_array_layer)
+        .layer_count(layer_count)
         .build();
       let dst_offsets = [
         vk::Offset3D::default(),
         vk::Offset3D::builder()
           .x(if mip_width > 1 { mip_width / 2 } else { 1 } as i32)
           .y(if mip_height > 1 { mip_height / 2 } else { 1 } as i32)
-          .z(1)
+          .z(if mip_depth > 1 { mip_depth / 2 } else { 1 } as i32)
           .build(),
       ];
       let dst_subresource = vk::ImageSubresourceLayers::builder()
         .aspect_mask(vk::ImageAspectFlags::COLOR)
         .mip_level(i)
-        .base_array_layer(0)
-        .layer_count(1)
+        .base_array_layer(base_array_layer)
+        .layer_count(layer_count)
         .build();
       let blit = [vk::ImageBlit::builder()
         .src_offsets(src_offsets)
@@ -562,6 +627,9 @@ impl VulkanBufferImageFunctions {
       if mip_height > 1 {
         mip_height /= 2;
       }
+      if mip_depth > 1 {
+        mip_depth /= 2;
+      }
     }
 
     // Transition the final mip level to shader ro optimal (not handled by loop),
@@ -570,8 +638,8 @@ impl VulkanBufferImageFunctions {
       .aspect_mask(vk::ImageAspectFlags::COLOR)
       .base_mip_level(mip_levels - 1)
       .level_count(1)
-      .base_array_layer(0)
-      .layer_count(1)
+      .base_array_layer(base_array_layer)
+      .layer_count(layer_count)
       .build();
     let barrier = [vk::ImageMemoryBarrier::builder()
       .image(image)
@@ -597,229 +665,1582 @@ impl VulkanBufferImageFunctions {
     Ok(())
   }
 
-  /// vk::ImageAspectFlags specify what kind of attachment this image can be
-  /// used for (COLOR, DEPTH, etc).
-  fn create_image_view(
-    &self, image: vk::Image, format: vk::Format, aspect: vk::ImageAspectFlags, mip_levels: u32,
-  ) -> SarektResult<vk::ImageView> {
-    let subresource_range = vk::ImageSubresourceRange::builder()
-      .base_mip_level(0)
-      .level_count(mip_levels)
-      .aspect_mask(aspect)
-      .base_array_layer(0)
-      .layer_count(1)
-      .build();
-    let image_view_ci = vk::ImageViewCreateInfo::builder()
-      .image(image)
-      .view_type(vk::ImageViewType::TYPE_2D)
-      .format(format)
-      .subresource_range(subresource_range)
-      .build();
-    unsafe {
-      Ok(
-        self
-          .logical_device
-          .create_image_view(&image_view_ci, None)?,
-      )
+  /// Shared loading path for images made of several layers (or depth slices)
+  /// of the same size.  Every layer goes into one staging buffer back to back
+  /// and mipmaps are blitted for all of them at once.
+  fn load_layers_with_staging_initialization(
+    &self, dimens: (u32, u32), shape: ImageShape, mut image_data_format: ImageDataFormat,
+    mut layers: Vec<Vec<u8>>, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_modes: [TextureAddressMode; 3],
+    mip_levels: u32,
+  ) -> SarektResult<ResourceWithMemory> {
+    if mip_levels < 1 {
+      return Err(SarektError::IllegalMipmapCount);
     }
-  }
-
-  fn create_sampler(
-    &self, magnification_filter: MagnificationMinificationFilter,
-    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
-    address_v: TextureAddressMode, address_w: TextureAddressMode, mip_levels: u32,
-  ) -> SarektResult<vk::Sampler> {
-    // TODO(issue#18) CONFIG anisotropy
-    // TODO(issue#18) CONFIG border color (as part of TextureAddressMode enum)
-    // TODO(issue#18) CONFIG MIPMAPPING
-    let mag_filter = match magnification_filter {
-      MagnificationMinificationFilter::Linear => vk::Filter::LINEAR,
-      MagnificationMinificationFilter::Nearest => vk::Filter::NEAREST,
-    };
-    let min_filter = match minification_filter {
-      MagnificationMinificationFilter::Linear => vk::Filter::LINEAR,
-      MagnificationMinificationFilter::Nearest => vk::Filter::NEAREST,
-    };
-    let address_u = match address_u {
-      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
-      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
-      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
-      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
-    };
-    let address_v = match address_v {
-      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
-      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
-      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
-      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
-    };
-    let address_w = match address_w {
-      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
-      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
-      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
-      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
-    };
-    let sampler_ci = vk::SamplerCreateInfo::builder()
-      .mag_filter(mag_filter)
-      .min_filter(min_filter)
-      .address_mode_u(address_u)
-      .address_mode_v(address_v)
-      .address_mode_w(address_w)
-      .anisotropy_enable(true)
-      .max_anisotropy(16f32)
-      .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-      .unnormalized_coordinates(false)
-      .compare_enable(false)
-      .compare_op(vk::CompareOp::ALWAYS)
-      .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-      .mip_lod_bias(0.0f32)
-      .min_lod(0.0f32)
-      .max_lod(mip_levels as _)
-      .build();
-    unsafe { Ok(self.logical_device.create_sampler(&sampler_ci, None)?) }
-  }
-
-  // TODO(issue#18) IMAGE MIPMAPPING levels as params
-  /// Returns the source and destination queue family indices.
-  fn insert_layout_transition_barrier(
-    &self, command_buffer: vk::CommandBuffer, image: vk::Image, old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout, src_queue_family: u32, dst_queue_family: u32, mip_levels: u32,
-  ) -> SarektResult<(u32, u32)> {
-    let subresource_range = vk::ImageSubresourceRange::builder()
-      .aspect_mask(vk::ImageAspectFlags::COLOR)
-      .base_mip_level(0)
-      .level_count(mip_levels)
-      .base_array_layer(0)
-      .layer_count(1)
-      .build();
-
-    let source_stage: vk::PipelineStageFlags;
-    let source_access_mask: vk::AccessFlags;
-    let destination_stage: vk::PipelineStageFlags;
-    let destination_access_mask: vk::AccessFlags;
-    if old_layout == vk::ImageLayout::UNDEFINED
-      && new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-    {
-      source_access_mask = vk::AccessFlags::empty();
-      destination_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-
-      source_stage = vk::PipelineStageFlags::TOP_OF_PIPE;
-      destination_stage = vk::PipelineStageFlags::TRANSFER;
-    } else if old_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-      && new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-    {
-      source_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-      destination_access_mask = vk::AccessFlags::SHADER_READ;
-
-      source_stage = vk::PipelineStageFlags::TRANSFER;
-      destination_stage = vk::PipelineStageFlags::FRAGMENT_SHADER;
-    } else {
-      source_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-      destination_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-
-      source_stage = vk::PipelineStageFlags::TRANSFER;
-      destination_stage = vk::PipelineStageFlags::TRANSFER;
+    if layers.is_empty() || layers.len() as u32 != shape.array_layers() * shape.depth() {
+      return Err(SarektError::InvalidImageLayers(format!(
+        "{:?} image needs {} layers but {} were given",
+        shape,
+        shape.array_layers() * shape.depth(),
+        layers.len()
+      )));
     }
-
-    let barriers = [vk::ImageMemoryBarrier::builder()
-      .old_layout(old_layout)
-      .new_layout(new_layout)
-      .src_queue_family_index(src_queue_family) // Transfer ownership to graphics queue if necessary.
-      .dst_queue_family_index(dst_queue_family)
-      .image(image)
-      .subresource_range(subresource_range)
-      .src_access_mask(source_access_mask)
-      .dst_access_mask(destination_access_mask)
-      .build()];
-
-    unsafe {
-      self.logical_device.cmd_pipeline_barrier(
-        command_buffer,
-        source_stage,
-        destination_stage,
-        vk::DependencyFlags::empty(),
-        &[],
-        &[],
-        &barriers,
-      );
+    if image_data_format.is_compressed() && mip_levels > 1 {
+      return Err(SarektError::FormatDoesNotSupportMipmapping(format!(
+        "{:?} is block compressed and can't be blitted",
+        image_data_format
+      )));
     }
 
-    Ok((src_queue_family, dst_queue_family))
-  }
-}
-unsafe impl BufferAndImageLoader for VulkanBufferImageFunctions {
-  type BackendHandle = ResourceWithMemory;
-  type UniformBufferDataHandle = Vec<BufferAndMemoryMapped>;
-  type UniformBufferHandle = Vec<BufferImageHandle<VulkanBufferImageFunctions>>;
+    if !self.query_image_format_support(image_data_format).sampled {
+      if !image_data_format.is_compressed() {
+        warn!(
+          "Layered image has a format not usable for sampling: {:?}",
+          image_data_format
+        );
+        return Err(SarektError::UnsupportedImageFormat);
+      }
 
-  unsafe fn cleanup(&self) -> SarektResult<()> {
-    if self.ownership_semaphore[0] != vk::Semaphore::null() {
-      return Ok(
-        self
-          .logical_device
-          .destroy_semaphore(self.ownership_semaphore[0], None),
+      warn!(
+        "Using a layered image with unsupported compressed format: {:?}, decoding it on the CPU, \
+         consider baking a texture in a format the device supports",
+        image_data_format
       );
+      for layer in layers.iter_mut() {
+        *layer = decompress_level(image_data_format, dimens, layer)?;
+      }
+      image_data_format = image_data_format.decompressed_format();
     }
+    let format = image_data_format.into();
 
-    Ok(())
-  }
-
-  /// I could create a buffer myself and allocate memory with VMA, but their
-  /// recomended approach is to allow the library to create a buffer and bind
-  /// the memory, effectively replacing all of this code.  See their [docs](https://gpuopen-librariesandsdks.github.io/VulkanMemoryAllocator/html/choosing_memory_type.html).
-  /// To see manual creation of this (with super naive memory allocation) see
-  /// this file at tag 17_vertex_buffer_creation.
-  ///
-  /// So in summary, VMA handles creating the buffer, finding the appropriate
-  /// memory type index, allocating the memory, and binding the buffer to the
-  /// memory.
-  ///
-  /// The way this function operates to keep things as efficient as possible at
-  /// GPU runtime is to copy into a staging buffer and initiate a transfer
-  /// operation on the GPU to a more efficient device only GPU memory buffer.
-  fn load_buffer_with_staging<BufElem: Sized + Copy>(
-    &self, buffer_type: BufferType, buffer: &[BufElem],
-  ) -> SarektResult<ResourceWithMemory> {
-    let buffer_size =
-      (std::mem::size_of::<BufElem>() as vk::DeviceSize) * buffer.len() as vk::DeviceSize;
+    let layer_len = layers[0].len();
+    let staging_size = (layer_len * layers.len()) as u64;
+    info!(
+      "Loading {:?} image with dimensions {:?}, and {} bytes",
+      shape, dimens, staging_size
+    );
 
-    // Create the staging buffer and memory.
-    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(buffer_size)?;
+    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(staging_size)?;
 
-    // Copy over all the bytes from host memory to mapped device memory
-    let data = self.allocator.map_memory(&staging_allocation)? as *mut BufElem;
-    unsafe {
-      data.copy_from_nonoverlapping(buffer.as_ptr(), buffer.len());
+    let data = self.allocator.map_memory(&staging_allocation)?;
+    for (i, layer) in layers.iter().enumerate() {
+      unsafe {
+        data
+          .add(i * layer_len)
+          .copy_from_nonoverlapping(layer.as_ptr(), layer.len());
+      }
     }
     self.allocator.unmap_memory(&staging_allocation)?;
 
-    let (gpu_buffer, gpu_allocation, _gpu_allocation_info) =
-      self.create_gpu_buffer(buffer_type, buffer_size, self.transfer_queue_family)?;
+    let (image, image_allocation, _) = self.create_gpu_image(
+      dimens,
+      shape,
+      format,
+      sampled_image_usage(),
+      self.transfer_queue_family,
+      mip_levels,
+      NumSamples::One,
+    )?;
 
+    let extent = vk::Extent3D {
+      width: dimens.0,
+      height: dimens.1,
+      depth: shape.depth(),
+    };
     self.transfer_staging_to_gpu_buffer_or_image(
-      buffer_size,
+      staging_size,
       staging_buffer,
-      ImageOrBuffer::Buffer(gpu_buffer),
-      None,
+      ImageOrBuffer::Image(image, format, extent, shape),
+      Some(MipChainSource::Generated(mip_levels)),
     )?;
 
-    // Staging buffer no longer needed, delete it.
     info!("Destroying staging buffer and memory...");
     self
       .allocator
       .destroy_buffer(staging_buffer, &staging_allocation)?;
 
-    // If this is an index buffer, keep track of the size of the elements (16 or
-    // 32).
-    let index_buffer_elem_size = match buffer_type {
-      BufferType::Index(size) => Some(size),
-      _ => None,
-    };
+    let image_view = self.create_image_view(
+      image,
+      shape,
+      format,
+      vk::ImageAspectFlags::COLOR,
+      mip_levels,
+    )?;
+    let [address_u, address_v, address_w] = address_modes;
+    let sampler = self.create_sampler(
+      magnification_filter,
+      minification_filter,
+      address_u,
+      address_v,
+      address_w,
+      mip_levels,
+    )?;
+
+    Ok(ResourceWithMemory::Image(ImageAndMemory {
+      allocation: image_allocation,
+      image_and_view: unsafe { ImageAndView::new(image, image_view) },
+      sampler: Some(sampler),
+      format,
+      extent,
+      shape,
+      mip_levels,
+      is_attachment: false,
+    }))
+  }
+
+  /// Shared loading path for images whose every mip level is supplied, each
+  /// level holding all of its layers back to back.  Every level is packed into
+  /// one staging buffer and copied over in a single command.
+  fn load_pregenerated_levels_with_staging_initialization(
+    &self, dimens: (u32, u32), shape: ImageShape, image_data_format: ImageDataFormat,
+    levels: Vec<Vec<u8>>, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_modes: [TextureAddressMode; 3],
+  ) -> SarektResult<ResourceWithMemory> {
+    let mip_levels = levels.len() as u32;
+    let format = image_data_format.into();
+
+    // Each level's offset must be a multiple of 4 and of the texel block size for
+    // the copy regions to be valid.
+    let level_alignment = 4 * image_data_format.texel_block_size() as u64;
+    let mut level_offsets = Vec::with_capacity(levels.len());
+    let mut staging_size = 0u64;
+    for level in levels.iter() {
+      staging_size = (staging_size + level_alignment - 1) / level_alignment * level_alignment;
+      level_offsets.push(staging_size);
+      staging_size += level.len() as u64;
+    }
+
+    info!(
+      "Loading {:?} image with dimensions {:?}, {} pre-generated mip levels, and {} bytes",
+      shape, dimens, mip_levels, staging_size
+    );
+
+    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(staging_size)?;
+
+    let data = self.allocator.map_memory(&staging_allocation)?;
+    for (level, &offset) in levels.iter().zip(level_offsets.iter()) {
+      unsafe {
+        data
+          .add(offset as usize)
+          .copy_from_nonoverlapping(level.as_ptr(), level.len());
+      }
+    }
+    self.allocator.unmap_memory(&staging_allocation)?;
+
+    let (image, image_allocation, _) = self.create_gpu_image(
+      dimens,
+      shape,
+      format,
+      sampled_image_usage(),
+      self.transfer_queue_family,
+      mip_levels,
+      NumSamples::One,
+    )?;
+
+    let extent = vk::Extent3D {
+      width: dimens.0,
+      height: dimens.1,
+      depth: shape.depth(),
+    };
+    self.transfer_staging_to_gpu_buffer_or_image(
+      staging_size,
+      staging_buffer,
+      ImageOrBuffer::Image(image, format, extent, shape),
+      Some(MipChainSource::Pregenerated(&level_offsets)),
+    )?;
+
+    info!("Destroying staging buffer and memory...");
+    self
+      .allocator
+      .destroy_buffer(staging_buffer, &staging_allocation)?;
+
+    let image_view = self.create_image_view(
+      image,
+      shape,
+      format,
+      vk::ImageAspectFlags::COLOR,
+      mip_levels,
+    )?;
+    let [address_u, address_v, address_w] = address_modes;
+    let sampler = self.create_sampler(
+      magnification_filter,
+      minification_filter,
+      address_u,
+      address_v,
+      address_w,
+      mip_levels,
+    )?;
+
+    Ok(ResourceWithMemory::Image(ImageAndMemory {
+      allocation: image_allocation,
+      image_and_view: unsafe { ImageAndView::new(image, image_view) },
+      sampler: Some(sampler),
+      format,
+      extent,
+      shape,
+      mip_levels,
+      is_attachment: false,
+    }))
+  }
+
+  /// Records and submits the copy of a staging buffer into part of a GPU only
+  /// buffer, on the graphics queue that draws from it.  Waits for the copy to
+  /// finish.
+  fn copy_staging_to_buffer_region(
+    &self, staging_buffer: vk::Buffer, gpu_buffer: vk::Buffer, dst_offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+  ) -> SarektResult<()> {
+    let command_buffer = self.graphics_command_buffer;
+    let command_begin_info = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    unsafe {
+      self
+        .logical_device
+        .begin_command_buffer(command_buffer, &command_begin_info)?;
+
+      let copy_region = vk::BufferCopy::builder()
+        .src_offset(0)
+        .dst_offset(dst_offset)
+        .size(size)
+        .build();
+      self.logical_device.cmd_copy_buffer(
+        command_buffer,
+        staging_buffer,
+        gpu_buffer,
+        &[copy_region],
+      );
+
+      // Make the new contents visible to vertex input in later frames.
+      let barriers = [vk::BufferMemoryBarrier::builder()
+        .buffer(gpu_buffer)
+        .offset(dst_offset)
+        .size(size)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ)
+        .build()];
+      self.logical_device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::VERTEX_INPUT,
+        vk::DependencyFlags::empty(),
+        &[],
+        &barriers,
+        &[],
+      );
+
+      self.logical_device.end_command_buffer(command_buffer)?;
+      let command_buffers = [command_buffer];
+      let submit_info = [vk::SubmitInfo::builder()
+        .command_buffers(&command_buffers)
+        .build()];
+      self.logical_device.queue_submit(
+        self.graphics_command_queue,
+        &submit_info,
+        vk::Fence::null(),
+      )?;
+
+      self.logical_device.device_wait_idle()?;
+      self
+        .logical_device
+        .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+    }
+
+    Ok(())
+  }
+
+  /// Records and submits the copy of a staging buffer into one region of an
+  /// image that is in SHADER_READ_ONLY_OPTIMAL, on the graphics queue which
+  /// already owns it.  Waits for the copy (and mipmap regeneration) to finish.
+  fn copy_staging_to_image_region(
+    &self, image: &ImageAndMemory, staging_buffer: vk::Buffer, region: ImageRegion,
+    regenerate_mipmaps: bool,
+  ) -> SarektResult<()> {
+    // The depth slice of a 3D image is an offset, not a layer.
+    let (base_array_layer, offset_z) = match image.shape {
+      ImageShape::Volume(_) => (0, region.layer),
+      _ => (region.layer, 0),
+    };
+    let level_count = if regenerate_mipmaps {
+      image.mip_levels - region.mip_level
+    } else {
+      1
+    };
+    let subresource_range = vk::ImageSubresourceRange::builder()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .base_mip_level(region.mip_level)
+      .level_count(level_count)
+      .base_array_layer(base_array_layer)
+      .layer_count(1)
+      .build();
+
+    let command_buffer = self.graphics_command_buffer;
+    let command_begin_info = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    unsafe {
+      self
+        .logical_device
+        .begin_command_buffer(command_buffer, &command_begin_info)?;
+
+      // Only the updated level, and those regenerated from it, leave shader read
+      // only.
+      let barriers = [vk::ImageMemoryBarrier::builder()
+        .image(image.image_and_view.image)
+        .subresource_range(subresource_range)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .src_access_mask(vk::AccessFlags::SHADER_READ)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .build()];
+      self.logical_device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &barriers,
+      );
+
+      let image_subresource = vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(region.mip_level)
+        .base_array_layer(base_array_layer)
+        .layer_count(1)
+        .build();
+      let copy_region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0) // Tightly packed.
+        .buffer_image_height(0)
+        .image_subresource(image_subresource)
+        .image_offset(vk::Offset3D {
+          x: region.offset.0 as i32,
+          y: region.offset.1 as i32,
+          z: offset_z as i32,
+        })
+        .image_extent(vk::Extent3D {
+          width: region.dimensions.0,
+          height: region.dimensions.1,
+          depth: 1,
+        })
+        .build();
+      self.logical_device.cmd_copy_buffer_to_image(
+        command_buffer,
+        staging_buffer,
+        image.image_and_view.image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &[copy_region],
+      );
+
+      if level_count > 1 {
+        self.generate_mipmaps_shader_ro_optimal(
+          command_buffer,
+          image.image_and_view.image,
+          image.format,
+          image.extent,
+          region.mip_level,
+          image.mip_levels,
+          base_array_layer,
+          1,
+        )?;
+      } else {
+        let barriers = [vk::ImageMemoryBarrier::builder()
+          .image(image.image_and_view.image)
+          .subresource_range(subresource_range)
+          .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+          .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+          .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+          .dst_access_mask(vk::AccessFlags::SHADER_READ)
+          .build()];
+        self.logical_device.cmd_pipeline_barrier(
+          command_buffer,
+          vk::PipelineStageFlags::TRANSFER,
+          vk::PipelineStageFlags::FRAGMENT_SHADER,
+          vk::DependencyFlags::empty(),
+          &[],
+          &[],
+          &barriers,
+        );
+      }
+
+      self.logical_device.end_command_buffer(command_buffer)?;
+      let command_buffers = [command_buffer];
+      let submit_info = [vk::SubmitInfo::builder()
+        .command_buffers(&command_buffers)
+        .build()];
+      self.logical_device.queue_submit(
+        self.graphics_command_queue,
+        &submit_info,
+        vk::Fence::null(),
+      )?;
+
+      self.logical_device.device_wait_idle()?;
+      self
+        .logical_device
+        .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+    }
+
+    Ok(())
+  }
+
+  /// Per heap budgets reported by VK_EXT_memory_budget, or None if the device
+  /// doesn't support it.  vk_mem doesn't expose vmaGetBudget, so this asks the
+  /// driver directly.
+  fn heap_budgets(&self) -> SarektResult<Option<[vk::DeviceSize; vk::MAX_MEMORY_HEAPS]>> {
+    let memory_budget = CStr::from_bytes_with_nul(b"VK_EXT_memory_budget\0").unwrap();
+    unsafe {
+      let properties = self
+        .instance
+        .get_physical_device_properties(self.physical_device);
+      // vkGetPhysicalDeviceMemoryProperties2 is core in Vulkan 1.1.
+      if vk::version_major(properties.api_version) == 1
+        && vk::version_minor(properties.api_version) < 1
+      {
+        return Ok(None);
+      }
+
+      let has_memory_budget = self
+        .instance
+        .enumerate_device_extension_properties(self.physical_device)?
+        .iter()
+        .any(|e| CStr::from_ptr(e.extension_name.as_ptr()) == memory_budget);
+      if !has_memory_budget {
+        return Ok(None);
+      }
+
+      let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+      let mut memory_properties = vk::PhysicalDeviceMemoryProperties2::builder()
+        .push_next(&mut budget_properties)
+        .build();
+      self
+        .instance
+        .get_physical_device_memory_properties2(self.physical_device, &mut memory_properties);
+      Ok(Some(budget_properties.heap_budget))
+    }
+  }
+
+  /// Creates the GPU side of one resource of an upload batch, its contents are
+  /// copied in later by `record_upload_batch_copy`.
+  fn create_upload_batch_resource(
+    &self, upload: PendingUpload,
+  ) -> SarektResult<ResourceWithMemory> {
+    match upload {
+      PendingUpload::Buffer {
+        buffer_type,
+        length,
+        bytes,
+      } => {
+        let (buffer, allocation, _) =
+          self.create_gpu_buffer(buffer_type, bytes.len() as u64, self.transfer_queue_family)?;
+        let index_buffer_elem_size = match buffer_type {
+          BufferType::Index(size) => Some(size),
+          _ => None,
+        };
+        Ok(ResourceWithMemory::Buffer(BufferAndMemory {
+          buffer,
+          length,
+          size: bytes.len() as u64,
+          host_visible: false,
+          index_buffer_elem_size,
+          allocation,
+          buffer_type,
+        }))
+      }
+      PendingUpload::Image {
+        format,
+        dimensions,
+        magnification_filter,
+        minification_filter,
+        address_modes,
+        mip_levels,
+        ..
+      } => {
+        let format = format.into();
+        let (image, allocation, _) = self.create_gpu_image(
+          dimensions,
+          ImageShape::Flat,
+          format,
+          sampled_image_usage(),
+          self.transfer_queue_family,
+          mip_levels,
+          NumSamples::One,
+        )?;
+        let image_view = self.create_image_view(
+          image,
+          ImageShape::Flat,
+          format,
+          vk::ImageAspectFlags::COLOR,
+          mip_levels,
+        )?;
+        let [address_u, address_v, address_w] = address_modes;
+        let sampler = self.create_sampler(
+          magnification_filter,
+          minification_filter,
+          address_u,
+          address_v,
+          address_w,
+          mip_levels,
+        )?;
+
+        Ok(ResourceWithMemory::Image(ImageAndMemory {
+          allocation,
+          image_and_view: unsafe { ImageAndView::new(image, image_view) },
+          sampler: Some(sampler),
+          format,
+          extent: vk::Extent3D {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth: 1,
+          },
+          shape: ImageShape::Flat,
+          mip_levels,
+          is_attachment: false,
+        }))
+      }
+    }
+  }
+
+  /// Records copying one resource of an upload batch out of the shared staging
+  /// buffer.  Images are handed over to the graphics queue (if it is a
+  /// different family) and have their mipmaps generated there.
+  unsafe fn record_upload_batch_copy(
+    &self, transfer_command_buffer: vk::CommandBuffer, graphics_command_buffer: vk::CommandBuffer,
+    staging_buffer: vk::Buffer, staging_offset: u64, resource: &ResourceWithMemory,
+  ) -> SarektResult<()> {
+    match resource {
+      ResourceWithMemory::Buffer(buffer) => {
+        let copy_region = vk::BufferCopy::builder()
+          .src_offset(staging_offset)
+          .dst_offset(0)
+          .size(buffer.size)
+          .build();
+        self.logical_device.cmd_copy_buffer(
+          transfer_command_buffer,
+          staging_buffer,
+          buffer.buffer,
+          &[copy_region],
+        );
+      }
+      ResourceWithMemory::Image(image) => {
+        let gpu_image = image.image_and_view.image;
+        self.insert_layout_transition_barrier(
+          transfer_command_buffer,
+          gpu_image,
+          vk::ImageLayout::UNDEFINED,
+          vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+          vk::QUEUE_FAMILY_IGNORED,
+          vk::QUEUE_FAMILY_IGNORED,
+          image.mip_levels,
+          1,
+        )?;
+        self.logical_device.cmd_copy_buffer_to_image(
+          transfer_command_buffer,
+          staging_buffer,
+          gpu_image,
+          vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+          &[Self::level_copy_region(image.extent, 0, 1, staging_offset)],
+        );
+
+        if self.transfer_queue_family != self.graphics_queue_family {
+          // Release on the transfer queue, acquire on the graphics queue.
+          self.insert_layout_transition_barrier(
+            transfer_command_buffer,
+            gpu_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            self.transfer_queue_family,
+            self.graphics_queue_family,
+            image.mip_levels,
+            1,
+          )?;
+          self.transfer_image_queue_ownership_if_necessary(
+            graphics_command_buffer,
+            gpu_image,
+            self.transfer_queue_family,
+            self.graphics_queue_family,
+            Some(image.mip_levels),
+            1,
+          )?;
+        }
+
+        self.generate_mipmaps_shader_ro_optimal(
+          graphics_command_buffer,
+          gpu_image,
+          image.format,
+          image.extent,
+          0,
+          image.mip_levels,
+          0,
+          1,
+        )?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Frees everything that was only needed while the batch was uploading.  Its
+  /// fence must have signaled.
+  unsafe fn release_upload_batch(&self, batch: InFlightUploadBatch) -> SarektResult<()> {
+    info!(
+      "Upload batch {} complete, releasing staging memory...",
+      batch.id
+    );
+    for &(command_pool, command_buffer) in batch.command_buffers.iter() {
+      self
+        .logical_device
+        .free_command_buffers(command_pool, &[command_buffer]);
+    }
+    self.logical_device.destroy_fence(batch.fence, None);
+    if batch.semaphore != vk::Semaphore::null() {
+      self.logical_device.destroy_semaphore(batch.semaphore, None);
+    }
+    self
+      .allocator
+      .destroy_buffer(batch.staging_buffer, &batch.staging_allocation)?;
+    Ok(())
+  }
+
+  /// Copies the uploads into the batch's staging buffer, creates their
+  /// resources and submits the copies, recording everything it creates in
+  /// `batch` so it can be destroyed if a later step fails.
+  unsafe fn record_and_submit_upload_batch(
+    &self, batch: &mut UploadBatchInProgress, uploads: Vec<PendingUpload>, staging_offsets: &[u64],
+  ) -> SarektResult<()> {
+    let data = self.allocator.map_memory(&batch.staging_allocation)?;
+    for (upload, &offset) in uploads.iter().zip(staging_offsets.iter()) {
+      let bytes = match upload {
+        PendingUpload::Buffer { bytes, .. } | PendingUpload::Image { bytes, .. } => bytes,
+      };
+      data
+        .add(offset as usize)
+        .copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+    }
+    self.allocator.unmap_memory(&batch.staging_allocation)?;
+
+    for upload in uploads.into_iter() {
+      let resource = self.create_upload_batch_resource(upload)?;
+      batch.resources.push(resource);
+    }
+
+    // Record every copy into one command buffer on the transfer queue, and the
+    // ownership transfers and mipmap generation into one on the graphics queue
+    // if that is a different family.
+    let separate_queues = self.transfer_queue_family != self.graphics_queue_family;
+    let allocate_command_buffer = |command_pool| -> SarektResult<vk::CommandBuffer> {
+      let alloc_info = vk::CommandBufferAllocateInfo::builder()
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_pool(command_pool)
+        .command_buffer_count(1)
+        .build();
+      Ok(self.logical_device.allocate_command_buffers(&alloc_info)?[0])
+    };
+    batch.command_buffers.push((
+      self.transfer_command_pool,
+      allocate_command_buffer(self.transfer_command_pool)?,
+    ));
+    if separate_queues {
+      batch.command_buffers.push((
+        self.graphics_command_pool,
+        allocate_command_buffer(self.graphics_command_pool)?,
+      ));
+    }
+    let transfer_command_buffer = batch.command_buffers[0].1;
+    let graphics_command_buffer = batch.command_buffers[batch.command_buffers.len() - 1].1;
+
+    let command_begin_info = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    for &(_, command_buffer) in batch.command_buffers.iter() {
+      self
+        .logical_device
+        .begin_command_buffer(command_buffer, &command_begin_info)?;
+    }
+    for (resource, &offset) in batch.resources.iter().zip(staging_offsets.iter()) {
+      self.record_upload_batch_copy(
+        transfer_command_buffer,
+        graphics_command_buffer,
+        batch.staging_buffer,
+        offset,
+        resource,
+      )?;
+    }
+    for &(_, command_buffer) in batch.command_buffers.iter() {
+      self.logical_device.end_command_buffer(command_buffer)?;
+    }
+
+    let fence_ci = vk::FenceCreateInfo::default();
+    batch.fence = self.logical_device.create_fence(&fence_ci, None)?;
+    let transfer_command_buffers = [transfer_command_buffer];
+    if separate_queues {
+      let semaphore_ci = vk::SemaphoreCreateInfo::default();
+      batch.semaphore = self.logical_device.create_semaphore(&semaphore_ci, None)?;
+
+      let signal_semaphores = [batch.semaphore];
+      let transfer_submit_info = [vk::SubmitInfo::builder()
+        .command_buffers(&transfer_command_buffers)
+        .signal_semaphores(&signal_semaphores)
+        .build()];
+      self.logical_device.queue_submit(
+        self.transfer_command_queue,
+        &transfer_submit_info,
+        vk::Fence::null(),
+      )?;
+      batch.submitted = true;
+
+      let graphics_command_buffers = [graphics_command_buffer];
+      let graphics_submit_info = [vk::SubmitInfo::builder()
+        .command_buffers(&graphics_command_buffers)
+        .wait_semaphores(&signal_semaphores)
+        .wait_dst_stage_mask(&[vk::PipelineStageFlags::TOP_OF_PIPE])
+        .build()];
+      self.logical_device.queue_submit(
+        self.graphics_command_queue,
+        &graphics_submit_info,
+        batch.fence,
+      )?;
+    } else {
+      let submit_info = [vk::SubmitInfo::builder()
+        .command_buffers(&transfer_command_buffers)
+        .build()];
+      self
+        .logical_device
+        .queue_submit(self.transfer_command_queue, &submit_info, batch.fence)?;
+    }
+
+    Ok(())
+  }
+
+  /// Destroys everything a failed `record_and_submit_upload_batch` created,
+  /// including the staging buffer.  The pending uploads lock must not be held.
+  unsafe fn destroy_upload_batch_in_progress(
+    &self, batch: UploadBatchInProgress,
+  ) -> SarektResult<()> {
+    if batch.submitted {
+      // Only the graphics submit can fail after the transfer one went through,
+      // and the copies may still be running.
+      self
+        .logical_device
+        .queue_wait_idle(self.transfer_command_queue)?;
+    }
+
+    for &(command_pool, command_buffer) in batch.command_buffers.iter() {
+      self
+        .logical_device
+        .free_command_buffers(command_pool, &[command_buffer]);
+    }
+    if batch.fence != vk::Fence::null() {
+      self.logical_device.destroy_fence(batch.fence, None);
+    }
+    if batch.semaphore != vk::Semaphore::null() {
+      self.logical_device.destroy_semaphore(batch.semaphore, None);
+    }
+
+    // Try to destroy everything before reporting the first error.
+    let mut result = Ok(());
+    for resource in batch.resources.into_iter() {
+      if let Err(err) = self.delete_buffer_or_image(resource) {
+        error!("Could not destroy uploaded resource: {:?}", err);
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+    let staging = self
+      .allocator
+      .destroy_buffer(batch.staging_buffer, &batch.staging_allocation)
+      .map_err(SarektError::from);
+    result.and(staging)
+  }
+
+  /// Releases every batch whose fence has signaled.
+  fn release_completed_uploads(&self, pending_uploads: &mut PendingUploads) -> SarektResult<()> {
+    let mut i = 0;
+    while i < pending_uploads.batches.len() {
+      let fence = pending_uploads.batches[i].fence;
+      if unsafe { self.logical_device.get_fence_status(fence)? } {
+        let batch = pending_uploads.batches.swap_remove(i);
+        unsafe { self.release_upload_batch(batch)? };
+      } else {
+        i += 1;
+      }
+    }
+    Ok(())
+  }
+
+  /// Waits for the batch at `index` and releases it.
+  fn wait_for_upload_batch(
+    &self, pending_uploads: &mut PendingUploads, index: usize,
+  ) -> SarektResult<()> {
+    let batch = pending_uploads.batches.swap_remove(index);
+    info!("Waiting for upload batch {}...", batch.id);
+    unsafe {
+      self
+        .logical_device
+        .wait_for_fences(&[batch.fence], true, u64::max_value())?;
+      self.release_upload_batch(batch)
+    }
+  }
+
+  /// Lets VMA move every device local buffer in `resources` with copies on the
+  /// graphics queue, then recreates the ones that moved and binds them to
+  /// their new place.  Host visible buffers may be mapped, so they stay put.
+  ///
+  /// Returns the number of buffers moved and bytes copied.
+  unsafe fn defragment_buffers(
+    &self, resources: &mut [ResourceWithMemory],
+  ) -> SarektResult<(u32, u64)> {
+    let movable: Vec<usize> = resources
+      .iter()
+      .enumerate()
+      .filter(|(_, resource)| matches!(resource, ResourceWithMemory::Buffer(buffer) if !buffer.host_visible))
+      .map(|(i, _)| i)
+      .collect();
+    if movable.is_empty() {
+      return Ok((0, 0));
+    }
+    let allocations: Vec<vk_mem::Allocation> = movable
+      .iter()
+      .map(|&i| resources[i].buffer().map(|buffer| buffer.allocation))
+      .collect::<SarektResult<_>>()?;
+
+    let command_buffer = self.graphics_command_buffer;
+    let command_begin_info = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    self
+      .logical_device
+      .begin_command_buffer(command_buffer, &command_begin_info)?;
+
+    // Earlier writes to the buffers must land before VMA copies them, and its
+    // copies before anything reads them again.
+    let before_barrier = [vk::MemoryBarrier::builder()
+      .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+      .dst_access_mask(vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE)
+      .build()];
+    self.logical_device.cmd_pipeline_barrier(
+      command_buffer,
+      vk::PipelineStageFlags::ALL_COMMANDS,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::DependencyFlags::empty(),
+      &before_barrier,
+      &[],
+      &[],
+    );
+
+    let defragmentation_info = vk_mem::DefragmentationInfo2 {
+      allocations: &allocations,
+      pools: None,
+      max_cpu_bytes_to_move: 0,
+      max_cpu_allocations_to_move: 0,
+      max_gpu_bytes_to_move: vk::WHOLE_SIZE,
+      max_gpu_allocations_to_move: u32::max_value(),
+      command_buffer: Some(command_buffer),
+    };
+    let mut context = self
+      .allocator
+      .defragmentation_begin(&defragmentation_info)?;
+
+    let after_barrier = [vk::MemoryBarrier::builder()
+      .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+      .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+      .build()];
+    self.logical_device.cmd_pipeline_barrier(
+      command_buffer,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::PipelineStageFlags::ALL_COMMANDS,
+      vk::DependencyFlags::empty(),
+      &after_barrier,
+      &[],
+      &[],
+    );
+    self.logical_device.end_command_buffer(command_buffer)?;
+
+    let command_buffers = [command_buffer];
+    let submit_info = vk::SubmitInfo::builder()
+      .command_buffers(&command_buffers)
+      .build();
+    self.logical_device.queue_submit(
+      self.graphics_command_queue,
+      &[submit_info],
+      vk::Fence::null(),
+    )?;
+    self.logical_device.device_wait_idle()?;
+    self
+      .logical_device
+      .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+
+    let (vma_stats, moved) = self.allocator.defragmentation_end(&mut context)?;
+
+    // The allocations kept their identity but the buffers are still bound to
+    // the old memory.  Every one is rebound even if some fail, since the old
+    // memory is gone either way.
+    let mut result = Ok((vma_stats.allocations_moved, vma_stats.bytes_moved as u64));
+    for (&i, _) in movable.iter().zip(moved).filter(|(_, moved)| *moved) {
+      if let Err(err) = self.rebind_moved_buffer(&mut resources[i]) {
+        error!("Could not rebind a defragmented buffer: {:?}", err);
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+    result
+  }
+
+  /// Replaces the buffer of a resource whose allocation VMA moved with one
+  /// bound to the new place.
+  unsafe fn rebind_moved_buffer(&self, resource: &mut ResourceWithMemory) -> SarektResult<()> {
+    let mut buffer = resource.buffer()?;
+    let buffer_ci = vk::BufferCreateInfo::builder()
+      .size(buffer.size)
+      .usage(vk::BufferUsageFlags::TRANSFER_DST | usage_flags_from_buffer_type(buffer.buffer_type))
+      .sharing_mode(vk::SharingMode::EXCLUSIVE)
+      .build();
+    let new_buffer = self.logical_device.create_buffer(&buffer_ci, None)?;
+    if let Err(err) = self
+      .allocator
+      .bind_buffer_memory(new_buffer, &buffer.allocation)
+    {
+      self.logical_device.destroy_buffer(new_buffer, None);
+      return Err(err.into());
+    }
+
+    self.logical_device.destroy_buffer(buffer.buffer, None);
+    buffer.buffer = new_buffer;
+    *resource = ResourceWithMemory::Buffer(buffer);
+    Ok(())
+  }
+
+  /// Picks sampled images to move out of memory blocks that hold nothing else,
+  /// emptiest block first, and creates the images they will move to.  A
+  /// block's images only move if every one of them fits in another block that
+  /// already exists, so each move lets VMA free a block.
+  ///
+  /// VMA can't move images with optimal tiling itself, their layout in memory
+  /// is up to the driver, so `move_images` copies them instead.  Nothing is
+  /// copied or destroyed here, on failure the new images are destroyed and the
+  /// old ones are untouched.
+  unsafe fn allocate_image_moves(
+    &self, resources: &[ResourceWithMemory],
+  ) -> SarektResult<Vec<ImageMove>> {
+    // Attachments can't move since framebuffers refer to them, that includes
+    // render textures even though they are sampled too.  Buffers were already
+    // defragmented by VMA.
+    let mut movable_images: HashMap<vk::DeviceMemory, Vec<(usize, u64)>> = HashMap::new();
+    let mut pinned_blocks = HashSet::new();
+    for (i, resource) in resources.iter().enumerate() {
+      let (allocation, movable) = match resource {
+        ResourceWithMemory::Buffer(buffer) => (&buffer.allocation, false),
+        ResourceWithMemory::Image(image) => (
+          &image.allocation,
+          image.sampler.is_some() && !image.is_attachment,
+        ),
+      };
+      let info = self.allocator.get_allocation_info(allocation)?;
+      if movable {
+        movable_images
+          .entry(info.get_device_memory())
+          .or_default()
+          .push((i, info.get_size() as u64));
+      } else {
+        pinned_blocks.insert(info.get_device_memory());
+      }
+    }
+
+    // Allocations may only land in blocks that existed before, not in new ones.
+    let known_blocks: HashSet<vk::DeviceMemory> = movable_images
+      .keys()
+      .chain(pinned_blocks.iter())
+      .copied()
+      .collect();
+    let mut source_blocks: Vec<(vk::DeviceMemory, Vec<(usize, u64)>)> = movable_images
+      .into_iter()
+      .filter(|(block, _)| !pinned_blocks.contains(block))
+      .collect();
+    source_blocks.sort_by_key(|(_, images)| images.iter().map(|&(_, size)| size).sum::<u64>());
+
+    let mut emptied_blocks = HashSet::new();
+    let mut receiving_blocks = HashSet::new();
+    let mut moves = Vec::new();
+    for (block, images) in source_blocks.into_iter() {
+      if receiving_blocks.contains(&block) {
+        continue;
+      }
+
+      let mut block_moves = Vec::with_capacity(images.len());
+      let mut destinations = Vec::with_capacity(images.len());
+      for &(index, size) in images.iter() {
+        let image = resources[index].image()?;
+        let new_image = match self.create_image_move(index, size, image) {
+          Ok(new_image) => new_image,
+          Err(err) => {
+            self.destroy_image_moves(block_moves.into_iter().chain(moves));
+            return Err(err);
+          }
+        };
+        let destination = new_image.1;
+        block_moves.push(new_image.0);
+
+        if destination == block
+          || !known_blocks.contains(&destination)
+          || emptied_blocks.contains(&destination)
+        {
+          break;
+        }
+        destinations.push(destination);
+      }
+
+      if destinations.len() == images.len() {
+        emptied_blocks.insert(block);
+        receiving_blocks.extend(destinations);
+        moves.extend(block_moves);
+      } else {
+        // Only part of the block would be emptied, so nothing would be freed.
+        self.destroy_image_moves(block_moves);
+      }
+    }
+
+    Ok(moves)
+  }
+
+  /// Creates the image and view `image` would move to, along with the memory
+  /// block it was placed in.
+  unsafe fn create_image_move(
+    &self, index: usize, size: u64, image: ImageAndMemory,
+  ) -> SarektResult<(ImageMove, vk::DeviceMemory)> {
+    let (new_image, allocation, allocation_info) = self.create_gpu_image(
+      (image.extent.width, image.extent.height),
+      image.shape,
+      image.format,
+      sampled_image_usage(),
+      self.graphics_queue_family,
+      image.mip_levels,
+      NumSamples::One,
+    )?;
+    let view = match self.create_image_view(
+      new_image,
+      image.shape,
+      image.format,
+      vk::ImageAspectFlags::COLOR,
+      image.mip_levels,
+    ) {
+      Ok(view) => view,
+      Err(err) => {
+        self.allocator.destroy_image(new_image, &allocation)?;
+        return Err(err);
+      }
+    };
+
+    Ok((
+      ImageMove {
+        index,
+        size,
+        image_and_view: ImageAndView::new(new_image, view),
+        allocation,
+      },
+      allocation_info.get_device_memory(),
+    ))
+  }
+
+  /// Destroys images created by `allocate_image_moves` that won't be used.
+  unsafe fn destroy_image_moves(&self, moves: impl IntoIterator<Item = ImageMove>) {
+    for image_move in moves {
+      self
+        .logical_device
+        .destroy_image_view(image_move.image_and_view.view, None);
+      if let Err(err) = self
+        .allocator
+        .destroy_image(image_move.image_and_view.image, &image_move.allocation)
+      {
+        error!(
+          "Could not destroy an unused defragmentation image: {:?}",
+          err
+        );
+      }
+    }
+  }
+
+  /// Copies every image in `moves` to its new place with one submission, then
+  /// destroys the old images and replaces them in `resources`.  If the copies
+  /// fail the new images are destroyed and `resources` is left as it was.
+  ///
+  /// Returns the number of images moved and bytes copied.
+  unsafe fn move_images(
+    &self, resources: &mut [ResourceWithMemory], moves: Vec<ImageMove>,
+  ) -> SarektResult<(u32, u64)> {
+    if moves.is_empty() {
+      return Ok((0, 0));
+    }
+
+    if let Err(err) = self.copy_image_moves(resources, &moves) {
+      self
+        .logical_device
+        .reset_command_buffer(
+          self.graphics_command_buffer,
+          vk::CommandBufferResetFlags::empty(),
+        )
+        .ok();
+      self.destroy_image_moves(moves);
+      return Err(err);
+    }
+
+    // The sampler doesn't refer to the image, so it is kept.
+    let mut result = Ok((moves.len() as u32, moves.iter().map(|m| m.size).sum()));
+    for image_move in moves.into_iter() {
+      let old_image = resources[image_move.index].image()?;
+      self
+        .logical_device
+        .destroy_image_view(old_image.image_and_view.view, None);
+      if let Err(err) = self
+        .allocator
+        .destroy_image(old_image.image_and_view.image, &old_image.allocation)
+      {
+        error!("Could not destroy a defragmented image: {:?}", err);
+        if result.is_ok() {
+          result = Err(err.into());
+        }
+      }
+      resources[image_move.index] = ResourceWithMemory::Image(ImageAndMemory {
+        image_and_view: image_move.image_and_view,
+        allocation: image_move.allocation,
+        ..old_image
+      });
+    }
+    result
+  }
+
+  /// Records the copies for `move_images` into one command buffer, submits it
+  /// and waits for it.
+  unsafe fn copy_image_moves(
+    &self, resources: &[ResourceWithMemory], moves: &[ImageMove],
+  ) -> SarektResult<()> {
+    let command_buffer = self.graphics_command_buffer;
+    let command_begin_info = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    self
+      .logical_device
+      .begin_command_buffer(command_buffer, &command_begin_info)?;
+
+    let mut to_transfer_barriers = Vec::with_capacity(moves.len() * 2);
+    let mut to_shader_barriers = Vec::with_capacity(moves.len());
+    for image_move in moves.iter() {
+      let image = resources[image_move.index].image()?;
+      let subresource_range = vk::ImageSubresourceRange::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(image.mip_levels)
+        .base_array_layer(0)
+        .layer_count(image.shape.array_layers())
+        .build();
+      to_transfer_barriers.push(
+        vk::ImageMemoryBarrier::builder()
+          .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+          .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+          .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .image(image.image_and_view.image)
+          .subresource_range(subresource_range)
+          .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+          .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+          .build(),
+      );
+      to_transfer_barriers.push(
+        vk::ImageMemoryBarrier::builder()
+          .old_layout(vk::ImageLayout::UNDEFINED)
+          .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+          .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .image(image_move.image_and_view.image)
+          .subresource_range(subresource_range)
+          .src_access_mask(vk::AccessFlags::empty())
+          .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+          .build(),
+      );
+      to_shader_barriers.push(
+        vk::ImageMemoryBarrier::builder()
+          .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+          .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+          .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+          .image(image_move.image_and_view.image)
+          .subresource_range(subresource_range)
+          .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+          .dst_access_mask(vk::AccessFlags::SHADER_READ)
+          .build(),
+      );
+    }
+    self.logical_device.cmd_pipeline_barrier(
+      command_buffer,
+      vk::PipelineStageFlags::ALL_COMMANDS,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::DependencyFlags::empty(),
+      &[],
+      &[],
+      &to_transfer_barriers,
+    );
+
+    for image_move in moves.iter() {
+      let image = resources[image_move.index].image()?;
+      let dimensions = (image.extent.width, image.extent.height);
+      let regions: Vec<vk::ImageCopy> = (0..image.mip_levels)
+        .map(|level| {
+          let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(level)
+            .base_array_layer(0)
+            .layer_count(image.shape.array_layers())
+            .build();
+          let (width, height) = mip_level_dimensions(dimensions, level);
+          vk::ImageCopy::builder()
+            .src_subresource(subresource)
+            .dst_subresource(subresource)
+            .extent(vk::Extent3D {
+              width,
+              height,
+              depth: (image.extent.depth >> level).max(1),
+            })
+            .build()
+        })
+        .collect();
+      self.logical_device.cmd_copy_image(
+        command_buffer,
+        image.image_and_view.image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        image_move.image_and_view.image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &regions,
+      );
+    }
+
+    self.logical_device.cmd_pipeline_barrier(
+      command_buffer,
+      vk::PipelineStageFlags::TRANSFER,
+      vk::PipelineStageFlags::ALL_COMMANDS,
+      vk::DependencyFlags::empty(),
+      &[],
+      &[],
+      &to_shader_barriers,
+    );
+    self.logical_device.end_command_buffer(command_buffer)?;
+
+    let command_buffers = [command_buffer];
+    let submit_info = vk::SubmitInfo::builder()
+      .command_buffers(&command_buffers)
+      .build();
+    self.logical_device.queue_submit(
+      self.graphics_command_queue,
+      &[submit_info],
+      vk::Fence::null(),
+    )?;
+    self.logical_device.device_wait_idle()?;
+    self
+      .logical_device
+      .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+    Ok(())
+  }
+
+  /// vk::ImageAspectFlags specify what kind of attachment this image can be
+  /// used for (COLOR, DEPTH, etc).
+  fn create_image_view(
+    &self, image: vk::Image, shape: ImageShape, format: vk::Format, aspect: vk::ImageAspectFlags,
+    mip_levels: u32,
+  ) -> SarektResult<vk::ImageView> {
+    let subresource_range = vk::ImageSubresourceRange::builder()
+      .base_mip_level(0)
+      .level_count(mip_levels)
+      .aspect_mask(aspect)
+      .base_array_layer(0)
+      .layer_count(shape.array_layers())
+      .build();
+    let image_view_ci = vk::ImageViewCreateInfo::builder()
+      .image(image)
+      .view_type(shape.view_type())
+      .format(format)
+      .subresource_range(subresource_range)
+      .build();
+    unsafe {
+      Ok(
+        self
+          .logical_device
+          .create_image_view(&image_view_ci, None)?,
+      )
+    }
+  }
+
+  fn create_sampler(
+    &self, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
+    address_v: TextureAddressMode, address_w: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<vk::Sampler> {
+    // TODO(issue#18) CONFIG anisotropy
+    // TODO(issue#18) CONFIG border color (as part of TextureAddressMode enum)
+    // TODO(issue#18) CONFIG MIPMAPPING
+    let mag_filter = match magnification_filter {
+      MagnificationMinificationFilter::Linear => vk::Filter::LINEAR,
+      MagnificationMinificationFilter::Nearest => vk::Filter::NEAREST,
+    };
+    let min_filter = match minification_filter {
+      MagnificationMinificationFilter::Linear => vk::Filter::LINEAR,
+      MagnificationMinificationFilter::Nearest => vk::Filter::NEAREST,
+    };
+    let address_u = match address_u {
+      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
+      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
+    };
+    let address_v = match address_v {
+      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
+      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
+    };
+    let address_w = match address_w {
+      TextureAddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
+      TextureAddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+      TextureAddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      TextureAddressMode::MirroredClampToEdge => vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
+    };
+    let sampler_ci = vk::SamplerCreateInfo::builder()
+      .mag_filter(mag_filter)
+      .min_filter(min_filter)
+      .address_mode_u(address_u)
+      .address_mode_v(address_v)
+      .address_mode_w(address_w)
+      .anisotropy_enable(true)
+      .max_anisotropy(16f32)
+      .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+      .unnormalized_coordinates(false)
+      .compare_enable(false)
+      .compare_op(vk::CompareOp::ALWAYS)
+      .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+      .mip_lod_bias(0.0f32)
+      .min_lod(0.0f32)
+      .max_lod(mip_levels as _)
+      .build();
+    unsafe { Ok(self.logical_device.create_sampler(&sampler_ci, None)?) }
+  }
+
+  /// Moves a new attachment from the undefined layout to `layout`, in which it
+  /// can be sampled before anything is rendered into it, and waits for that to
+  /// finish.
+  fn initialize_attachment_layout(
+    &self, image: vk::Image, aspect_mask: vk::ImageAspectFlags, layout: vk::ImageLayout,
+  ) -> SarektResult<()> {
+    unsafe {
+      let command_buffer = self.graphics_command_buffer;
+      let command_begin_info = vk::CommandBufferBeginInfo::builder()
+        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+        .build();
+      self
+        .logical_device
+        .begin_command_buffer(command_buffer, &command_begin_info)?;
+
+      let subresource_range = vk::ImageSubresourceRange::builder()
+        .aspect_mask(aspect_mask)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1)
+        .build();
+      let barriers = [vk::ImageMemoryBarrier::builder()
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .build()];
+      self.logical_device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &barriers,
+      );
+      self.logical_device.end_command_buffer(command_buffer)?;
+
+      let command_buffers = [command_buffer];
+      let submit_info = vk::SubmitInfo::builder()
+        .command_buffers(&command_buffers)
+        .build();
+      self.logical_device.queue_submit(
+        self.graphics_command_queue,
+        &[submit_info],
+        vk::Fence::null(),
+      )?;
+      self
+        .logical_device
+        .queue_wait_idle(self.graphics_command_queue)?;
+      self
+        .logical_device
+        .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+    }
+
+    Ok(())
+  }
+
+  // TODO(issue#18) IMAGE MIPMAPPING levels as params
+  /// Returns the source and destination queue family indices.
+  fn insert_layout_transition_barrier(
+    &self, command_buffer: vk::CommandBuffer, image: vk::Image, old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout, src_queue_family: u32, dst_queue_family: u32, mip_levels: u32,
+    layer_count: u32,
+  ) -> SarektResult<(u32, u32)> {
+    let subresource_range = vk::ImageSubresourceRange::builder()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .base_mip_level(0)
+      .level_count(mip_levels)
+      .base_array_layer(0)
+      .layer_count(layer_count)
+      .build();
+
+    let source_stage: vk::PipelineStageFlags;
+    let source_access_mask: vk::AccessFlags;
+    let destination_stage: vk::PipelineStageFlags;
+    let destination_access_mask: vk::AccessFlags;
+    if old_layout == vk::ImageLayout::UNDEFINED
+      && new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
+    {
+      source_access_mask = vk::AccessFlags::empty();
+      destination_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+
+      source_stage = vk::PipelineStageFlags::TOP_OF_PIPE;
+      destination_stage = vk::PipelineStageFlags::TRANSFER;
+    } else if old_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
+      && new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    {
+      source_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+      destination_access_mask = vk::AccessFlags::SHADER_READ;
+
+      source_stage = vk::PipelineStageFlags::TRANSFER;
+      destination_stage = vk::PipelineStageFlags::FRAGMENT_SHADER;
+    } else {
+      source_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+      destination_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+
+      source_stage = vk::PipelineStageFlags::TRANSFER;
+      destination_stage = vk::PipelineStageFlags::TRANSFER;
+    }
+
+    let barriers = [vk::ImageMemoryBarrier::builder()
+      .old_layout(old_layout)
+      .new_layout(new_layout)
+      .src_queue_family_index(src_queue_family) // Transfer ownership to graphics queue if necessary.
+      .dst_queue_family_index(dst_queue_family)
+      .image(image)
+      .subresource_range(subresource_range)
+      .src_access_mask(source_access_mask)
+      .dst_access_mask(destination_access_mask)
+      .build()];
+
+    unsafe {
+      self.logical_device.cmd_pipeline_barrier(
+        command_buffer,
+        source_stage,
+        destination_stage,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &barriers,
+      );
+    }
+
+    Ok((src_queue_family, dst_queue_family))
+  }
+}
+unsafe impl BufferAndImageLoader for VulkanBufferImageFunctions {
+  type BackendHandle = ResourceWithMemory;
+  type UniformBufferDataHandle = Vec<BufferAndMemoryMapped>;
+  type UniformBufferHandle = Vec<BufferImageHandle<VulkanBufferImageFunctions>>;
+
+  unsafe fn cleanup(&self) -> SarektResult<()> {
+    let mut pending_uploads = self.pending_uploads.lock().unwrap();
+    while !pending_uploads.batches.is_empty() {
+      self.wait_for_upload_batch(&mut pending_uploads, 0)?;
+    }
+
+    if self.ownership_semaphore[0] != vk::Semaphore::null() {
+      self
+        .logical_device
+        .destroy_semaphore(self.ownership_semaphore[0], None);
+    }
+
+    Ok(())
+  }
+
+  /// I could create a buffer myself and allocate memory with VMA, but their
+  /// recomended approach is to allow the library to create a buffer and bind
+  /// the memory, effectively replacing all of this code.  See their [docs](https://gpuopen-librariesandsdks.github.io/VulkanMemoryAllocator/html/choosing_memory_type.html).
+  /// To see manual creation of this (with super naive memory allocation) see
+  /// this file at tag 17_vertex_buffer_creation.
+  ///
+  /// So in summary, VMA handles creating the buffer, finding the appropriate
+  /// memory type index, allocating the memory, and binding the buffer to the
+  /// memory.
+  ///
+  /// The way this function operates to keep things as efficient as possible at
+  /// GPU runtime is to copy into a staging buffer and initiate a transfer
+  /// operation on the GPU to a more efficient device only GPU memory buffer.
+  fn load_buffer_with_staging<BufElem: Sized + Copy>(
+    &self, buffer_type: BufferType, buffer: &[BufElem],
+  ) -> SarektResult<ResourceWithMemory> {
+    let buffer_size =
+      (std::mem::size_of::<BufElem>() as vk::DeviceSize) * buffer.len() as vk::DeviceSize;
+
+    // Create the staging buffer and memory.
+    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(buffer_size)?;
+
+    // Copy over all the bytes from host memory to mapped device memory
+    let data = self.allocator.map_memory(&staging_allocation)? as *mut BufElem;
+    unsafe {
+      data.copy_from_nonoverlapping(buffer.as_ptr(), buffer.len());
+    }
+    self.allocator.unmap_memory(&staging_allocation)?;
+
+    let (gpu_buffer, gpu_allocation, _gpu_allocation_info) =
+      self.create_gpu_buffer(buffer_type, buffer_size, self.transfer_queue_family)?;
+
+    self.transfer_staging_to_gpu_buffer_or_image(
+      buffer_size,
+      staging_buffer,
+      ImageOrBuffer::Buffer(gpu_buffer),
+      None,
+    )?;
+
+    // Staging buffer no longer needed, delete it.
+    info!("Destroying staging buffer and memory...");
+    self
+      .allocator
+      .destroy_buffer(staging_buffer, &staging_allocation)?;
+
+    // If this is an index buffer, keep track of the size of the elements (16 or
+    // 32).
+    let index_buffer_elem_size = match buffer_type {
+      BufferType::Index(size) => Some(size),
+      _ => None,
+    };
 
     Ok(ResourceWithMemory::Buffer(BufferAndMemory {
       buffer: gpu_buffer,
       length: buffer.len() as u32,
+      size: buffer_size,
+      host_visible: false,
       index_buffer_elem_size,
       allocation: gpu_allocation,
+      buffer_type,
     }))
   }
 
@@ -841,161 +2262,915 @@ unsafe impl BufferAndImageLoader for VulkanBufferImageFunctions {
     }
     self.allocator.unmap_memory(&allocation)?;
 
-    // If this is an index buffer, keep track of the size of the elements (16 or
-    // 32).
-    let index_buffer_elem_size = match buffer_type {
-      BufferType::Index(size) => Some(size),
-      _ => None,
-    };
+    // If this is an index buffer, keep track of the size of the elements (16 or
+    // 32).
+    let index_buffer_elem_size = match buffer_type {
+      BufferType::Index(size) => Some(size),
+      _ => None,
+    };
+
+    Ok(ResourceWithMemory::Buffer(BufferAndMemory {
+      buffer: vk_buffer,
+      length: buffer.len() as u32,
+      size: buffer_size,
+      host_visible: true,
+      index_buffer_elem_size,
+      allocation,
+      buffer_type,
+    }))
+  }
+
+  fn submit_upload_batch(
+    &self, batch: UploadBatch,
+  ) -> SarektResult<(Vec<ResourceWithMemory>, UploadToken)> {
+    let mut pending_uploads = self.pending_uploads.lock().unwrap();
+    self.release_completed_uploads(&mut pending_uploads)?;
+
+    let token = UploadToken::new(pending_uploads.next_id);
+    pending_uploads.next_id += 1;
+
+    let mut uploads = batch.into_uploads();
+    if uploads.is_empty() {
+      return Ok((Vec::new(), token));
+    }
+
+    // Same checks and CPU decoding as loading images one by one, except that
+    // uncompressed formats can't be converted here.
+    for upload in uploads.iter_mut() {
+      if let PendingUpload::Image {
+        format,
+        dimensions,
+        bytes,
+        mip_levels,
+        ..
+      } = upload
+      {
+        if *mip_levels < 1 {
+          return Err(SarektError::IllegalMipmapCount);
+        }
+        if format.is_compressed() && *mip_levels > 1 {
+          return Err(SarektError::FormatDoesNotSupportMipmapping(format!(
+            "{:?} is block compressed and can't be blitted",
+            format
+          )));
+        }
+        if !self.query_image_format_support(*format).sampled {
+          if !format.is_compressed() {
+            warn!(
+              "Batched image has a format not usable for sampling: {:?}",
+              format
+            );
+            return Err(SarektError::UnsupportedImageFormat);
+          }
+
+          warn!(
+            "Using a batched image with unsupported compressed format: {:?}, decoding it on the \
+             CPU, consider baking a texture in a format the device supports",
+            format
+          );
+          *bytes = decompress_level(*format, *dimensions, bytes)?;
+          *format = format.decompressed_format();
+        }
+      }
+    }
+
+    // Everything goes in one staging buffer.  Image offsets must be a multiple
+    // of 4 and of the texel block size for the copy regions to be valid.
+    let mut staging_offsets = Vec::with_capacity(uploads.len());
+    let mut staging_size = 0u64;
+    for upload in uploads.iter() {
+      let (alignment, len) = match upload {
+        PendingUpload::Buffer { bytes, .. } => (4, bytes.len()),
+        PendingUpload::Image { format, bytes, .. } => {
+          (4 * format.texel_block_size() as u64, bytes.len())
+        }
+      };
+      staging_size = (staging_size + alignment - 1) / alignment * alignment;
+      staging_offsets.push(staging_size);
+      staging_size += len as u64;
+    }
+
+    info!(
+      "Submitting upload batch {} of {} resources and {} bytes",
+      token.id(),
+      uploads.len(),
+      staging_size
+    );
+    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(staging_size)?;
+    let mut batch = UploadBatchInProgress {
+      staging_buffer,
+      staging_allocation,
+      resources: Vec::with_capacity(uploads.len()),
+      command_buffers: Vec::new(),
+      fence: vk::Fence::null(),
+      semaphore: vk::Semaphore::null(),
+      submitted: false,
+    };
+    if let Err(err) =
+      unsafe { self.record_and_submit_upload_batch(&mut batch, uploads, &staging_offsets) }
+    {
+      // Deleting resources waits on pending uploads, which needs the lock.
+      drop(pending_uploads);
+      if let Err(cleanup_err) = unsafe { self.destroy_upload_batch_in_progress(batch) } {
+        error!(
+          "Could not clean up after failing to submit an upload batch: {:?}",
+          cleanup_err
+        );
+      }
+      return Err(err);
+    }
+
+    pending_uploads.batches.push(InFlightUploadBatch {
+      id: token.id(),
+      fence: batch.fence,
+      semaphore: batch.semaphore,
+      command_buffers: batch.command_buffers,
+      staging_buffer: batch.staging_buffer,
+      staging_allocation: batch.staging_allocation,
+      resources: batch.resources.iter().map(UploadedResource::from).collect(),
+    });
+
+    Ok((batch.resources, token))
+  }
+
+  fn is_upload_complete(&self, token: UploadToken) -> SarektResult<bool> {
+    let mut pending_uploads = self.pending_uploads.lock().unwrap();
+    self.release_completed_uploads(&mut pending_uploads)?;
+    Ok(
+      !pending_uploads
+        .batches
+        .iter()
+        .any(|batch| batch.id == token.id()),
+    )
+  }
+
+  fn wait_for_upload(&self, token: UploadToken) -> SarektResult<()> {
+    let mut pending_uploads = self.pending_uploads.lock().unwrap();
+    let index = pending_uploads
+      .batches
+      .iter()
+      .position(|batch| batch.id == token.id());
+    match index {
+      Some(index) => self.wait_for_upload_batch(&mut pending_uploads, index),
+      None => Ok(()),
+    }
+  }
+
+  fn wait_for_resource_upload(&self, handle: ResourceWithMemory) -> SarektResult<()> {
+    let mut pending_uploads = self.pending_uploads.lock().unwrap();
+    if pending_uploads.batches.is_empty() {
+      return Ok(());
+    }
+
+    let resource = UploadedResource::from(&handle);
+    let index = pending_uploads
+      .batches
+      .iter()
+      .position(|batch| batch.resources.contains(&resource));
+    match index {
+      Some(index) => {
+        warn!("Waiting for a resource that is still uploading");
+        self.wait_for_upload_batch(&mut pending_uploads, index)
+      }
+      None => Ok(()),
+    }
+  }
+
+  fn update_buffer<BufElem: Sized + Copy>(
+    &self, handle: ResourceWithMemory, offset: usize, buffer: &[BufElem],
+  ) -> SarektResult<()> {
+    let gpu_buffer = handle.buffer()?;
+    let elem_size = std::mem::size_of::<BufElem>() as vk::DeviceSize;
+    let byte_offset = offset as vk::DeviceSize * elem_size;
+    let update_size = buffer.len() as vk::DeviceSize * elem_size;
+    if byte_offset + update_size > gpu_buffer.size {
+      return Err(SarektError::InvalidBufferUpdate(format!(
+        "{} bytes at offset {} runs past the end of the {} byte buffer",
+        update_size, byte_offset, gpu_buffer.size
+      )));
+    }
+    if buffer.is_empty() {
+      return Ok(());
+    }
+
+    // A batch still copying into the buffer would overwrite the update.
+    self.wait_for_resource_upload(handle)?;
+
+    if gpu_buffer.host_visible {
+      info!("Updating {} bytes of mapped buffer", update_size);
+      let data = self.allocator.map_memory(&gpu_buffer.allocation)?;
+      unsafe {
+        data
+          .add(byte_offset as usize)
+          .copy_from_nonoverlapping(buffer.as_ptr() as *const u8, update_size as usize);
+      }
+      // Memory isn't necessarily host coherent.
+      self.allocator.flush_allocation(
+        &gpu_buffer.allocation,
+        byte_offset as usize,
+        update_size as usize,
+      )?;
+      self.allocator.unmap_memory(&gpu_buffer.allocation)?;
+      return Ok(());
+    }
+
+    let (staging_buffer, staging_allocation, _) = self.create_staging_buffer(update_size)?;
+    let data = self.allocator.map_memory(&staging_allocation)? as *mut BufElem;
+    unsafe {
+      data.copy_from_nonoverlapping(buffer.as_ptr(), buffer.len());
+    }
+    self.allocator.unmap_memory(&staging_allocation)?;
+
+    let result = self.copy_staging_to_buffer_region(
+      staging_buffer,
+      gpu_buffer.buffer,
+      byte_offset,
+      update_size,
+    );
+
+    info!("Destroying staging buffer and memory...");
+    self
+      .allocator
+      .destroy_buffer(staging_buffer, &staging_allocation)?;
+
+    result
+  }
+
+  /// The procedure for loading an image in vulkan could use a staging image,
+  /// but its just as well we use a staging buffer, which is easier and [could even be faster](https://developer.nvidia.com/vulkan-memory-management)
+  /// TODO(issue#18) IMAGES MIPMAPPING
+  fn load_image_with_staging_initialization(
+    &self, pixels: impl ImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
+    address_v: TextureAddressMode, address_w: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<ResourceWithMemory> {
+    if mip_levels < 1 {
+      return Err(SarektError::IllegalMipmapCount);
+    }
+
+    let dimens = pixels.dimensions();
+
+    let image_data_format = pixels.format()?;
+    if image_data_format.is_compressed() && mip_levels > 1 {
+      return Err(SarektError::FormatDoesNotSupportMipmapping(format!(
+        "{:?} is block compressed and can't be blitted, load pre-generated mip levels instead",
+        image_data_format
+      )));
+    }
+
+    let (pixel_bytes, format) = {
+      let format: vk::Format = image_data_format.into();
+      let format_suitable = self.query_image_format_support(image_data_format).sampled;
+
+      if !format_suitable && image_data_format.is_compressed() {
+        // Can't ask the image to convert itself, decode it here.
+        warn!(
+          "Using an image with unsupported compressed format: {:?}, decoding it on the CPU, \
+           consider baking a texture in a format the device supports",
+          format
+        );
+        let pixel_bytes = decompress_level(image_data_format, dimens, &pixels.into_bytes())?;
+        (pixel_bytes, image_data_format.decompressed_format().into())
+      } else if !format_suitable {
+        // Format not usable for a sampled image, convert to one garunteed by vulkan
+        warn!(
+          "Using an image with unsupported format: {:?}, converting to rgba, consider baking a \
+           new texture",
+          format
+        );
+        let pixels = pixels.into_rgba8();
+        let format = pixels.format()?.into();
+        (pixels.into_bytes(), format)
+      } else {
+        let format = pixels.format()?.into();
+        (pixels.into_bytes(), format)
+      }
+    };
+
+    info!(
+      "Loading image with dimensions {:?}, and {} bytes",
+      dimens,
+      pixel_bytes.len()
+    );
+
+    let (staging_buffer, staging_allocation, _) =
+      self.create_staging_buffer(pixel_bytes.len() as u64)?;
+
+    let data = self.allocator.map_memory(&staging_allocation)?;
+    unsafe {
+      data.copy_from_nonoverlapping(pixel_bytes.as_ptr() as *const u8, pixel_bytes.len());
+    }
+    self.allocator.unmap_memory(&staging_allocation)?;
+
+    let (image, image_allocation, _) = self.create_gpu_image(
+      dimens,
+      ImageShape::Flat,
+      format,
+      sampled_image_usage(),
+      self.transfer_queue_family,
+      mip_levels,
+      NumSamples::One,
+    )?;
+
+    let extent = vk::Extent3D {
+      width: dimens.0,
+      height: dimens.1,
+      depth: 1,
+    };
+    self.transfer_staging_to_gpu_buffer_or_image(
+      pixel_bytes.len() as u64,
+      staging_buffer,
+      ImageOrBuffer::Image(image, format, extent, ImageShape::Flat),
+      Some(MipChainSource::Generated(mip_levels)),
+    )?;
+
+    info!("Destroying staging buffer and memory...");
+    self
+      .allocator
+      .destroy_buffer(staging_buffer, &staging_allocation)?;
+
+    // TODO(issue#29) IMAGES propogate up this parameter to allow users to create
+    // stencil etc, this will involve a Sarekt non vulkan enum in
+    // buffers_and_images.
+    let image_view = self.create_image_view(
+      image,
+      ImageShape::Flat,
+      format,
+      vk::ImageAspectFlags::COLOR,
+      mip_levels,
+    )?;
+    let sampler = self.create_sampler(
+      magnification_filter,
+      minification_filter,
+      address_u,
+      address_v,
+      address_w,
+      mip_levels,
+    )?;
+
+    Ok(ResourceWithMemory::Image(ImageAndMemory {
+      allocation: image_allocation,
+      image_and_view: unsafe { ImageAndView::new(image, image_view) },
+      sampler: Some(sampler),
+      format,
+      extent,
+      shape: ImageShape::Flat,
+      mip_levels,
+      is_attachment: false,
+    }))
+  }
+
+  /// Packs every level into one staging buffer and copies them all over in a
+  /// single command, no blitting (and therefore no linear filtering support) is
+  /// required of the format.
+  fn load_mipmapped_image_with_staging_initialization(
+    &self, pixels: impl MipmappedImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
+    address_v: TextureAddressMode, address_w: TextureAddressMode,
+  ) -> SarektResult<ResourceWithMemory> {
+    let mip_levels = pixels.mip_levels();
+    if mip_levels < 1 {
+      return Err(SarektError::IllegalMipmapCount);
+    }
+
+    let dimens = pixels.dimensions();
+    let mut image_data_format = pixels.format()?;
+    let mut levels = pixels.into_level_bytes();
+    if !self.query_image_format_support(image_data_format).sampled {
+      if !image_data_format.is_compressed() {
+        warn!(
+          "Pre-generated mip chain has a format not usable for sampling: {:?}",
+          image_data_format
+        );
+        return Err(SarektError::UnsupportedImageFormat);
+      }
+
+      warn!(
+        "Using an image with unsupported compressed format: {:?}, decoding it on the CPU, \
+         consider baking a texture in a format the device supports",
+        image_data_format
+      );
+      levels = levels
+        .iter()
+        .enumerate()
+        .map(|(level, bytes)| {
+          let level_dimensions = mip_level_dimensions(dimens, level as u32);
+          decompress_level(image_data_format, level_dimensions, bytes)
+        })
+        .collect::<SarektResult<_>>()?;
+      image_data_format = image_data_format.decompressed_format();
+    }
+
+    self.load_pregenerated_levels_with_staging_initialization(
+      dimens,
+      ImageShape::Flat,
+      image_data_format,
+      levels,
+      magnification_filter,
+      minification_filter,
+      [address_u, address_v, address_w],
+    )
+  }
+
+  fn load_mipmapped_cubemap_with_staging_initialization(
+    &self, faces: impl MipmappedCubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+  ) -> SarektResult<ResourceWithMemory> {
+    if faces.mip_levels() < 1 {
+      return Err(SarektError::IllegalMipmapCount);
+    }
+
+    let face_size = faces.face_size();
+    let image_data_format = faces.format()?;
+    if !self.query_image_format_support(image_data_format).sampled {
+      warn!(
+        "Pre-generated cubemap mip chain has a format not usable for sampling: {:?}",
+        image_data_format
+      );
+      return Err(SarektError::UnsupportedImageFormat);
+    }
+
+    self.load_pregenerated_levels_with_staging_initialization(
+      (face_size, face_size),
+      ImageShape::Cube,
+      image_data_format,
+      faces.into_level_bytes(),
+      magnification_filter,
+      minification_filter,
+      [
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+      ],
+    )
+  }
+
+  fn load_cubemap_with_staging_initialization(
+    &self, faces: impl CubemapImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, mip_levels: u32,
+  ) -> SarektResult<ResourceWithMemory> {
+    let face_size = faces.face_size();
+    let format = faces.format()?;
+    self.load_layers_with_staging_initialization(
+      (face_size, face_size),
+      ImageShape::Cube,
+      format,
+      faces.into_face_bytes().to_vec(),
+      magnification_filter,
+      minification_filter,
+      [
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+      ],
+      mip_levels,
+    )
+  }
 
-    Ok(ResourceWithMemory::Buffer(BufferAndMemory {
-      buffer: vk_buffer,
-      length: buffer.len() as u32,
-      index_buffer_elem_size,
-      allocation,
-    }))
+  fn load_image_array_with_staging_initialization(
+    &self, layers: impl LayeredImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
+    address_v: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<ResourceWithMemory> {
+    let dimens = layers.dimensions();
+    let layer_count = layers.layer_count();
+    let format = layers.format()?;
+    self.load_layers_with_staging_initialization(
+      dimens,
+      ImageShape::Array(layer_count),
+      format,
+      layers.into_layer_bytes(),
+      magnification_filter,
+      minification_filter,
+      [address_u, address_v, TextureAddressMode::ClampToEdge],
+      mip_levels,
+    )
   }
 
-  /// The procedure for loading an image in vulkan could use a staging image,
-  /// but its just as well we use a staging buffer, which is easier and [could even be faster](https://developer.nvidia.com/vulkan-memory-management)
-  /// TODO(issue#18) IMAGES MIPMAPPING
-  fn load_image_with_staging_initialization(
-    &self, pixels: impl ImageData, magnification_filter: MagnificationMinificationFilter,
+  fn load_volume_image_with_staging_initialization(
+    &self, slices: impl LayeredImageData, magnification_filter: MagnificationMinificationFilter,
     minification_filter: MagnificationMinificationFilter, address_u: TextureAddressMode,
     address_v: TextureAddressMode, address_w: TextureAddressMode, mip_levels: u32,
   ) -> SarektResult<ResourceWithMemory> {
-    if mip_levels < 1 {
-      return Err(SarektError::IllegalMipmapCount);
-    }
+    let dimens = slices.dimensions();
+    let depth = slices.layer_count();
+    let format = slices.format()?;
+    self.load_layers_with_staging_initialization(
+      dimens,
+      ImageShape::Volume(depth),
+      format,
+      slices.into_layer_bytes(),
+      magnification_filter,
+      minification_filter,
+      [address_u, address_v, address_w],
+      mip_levels,
+    )
+  }
 
-    let dimens = pixels.dimensions();
+  fn update_image(
+    &self, handle: ResourceWithMemory, region: ImageRegion, pixels: impl ImageData,
+    regenerate_mipmaps: bool,
+  ) -> SarektResult<()> {
+    let image = handle.image()?;
+    if image.sampler.is_none() {
+      return Err(SarektError::InvalidImageUpdate(
+        "only images loaded with data can be updated".to_owned(),
+      ));
+    }
+    let image_data_format: ImageDataFormat = image.format.try_into()?;
+
+    // Check the region is inside the image.
+    if region.mip_level >= image.mip_levels {
+      return Err(SarektError::InvalidImageUpdate(format!(
+        "mip level {} of an image with {} levels",
+        region.mip_level, image.mip_levels
+      )));
+    }
+    let (level_width, level_height) =
+      mip_level_dimensions((image.extent.width, image.extent.height), region.mip_level);
+    let layer_count = match image.shape {
+      ImageShape::Volume(_) => (image.extent.depth >> region.mip_level).max(1),
+      shape => shape.array_layers(),
+    };
+    let (block_width, block_height) = image_data_format.block_dimensions();
+    let fits = |offset: u32, size: u32, level_size: u32, block_size: u32| {
+      size > 0
+        && offset
+          .checked_add(size)
+          .map_or(false, |end| end <= level_size)
+        && offset % block_size == 0
+        && (size % block_size == 0 || offset + size == level_size)
+    };
+    if region.layer >= layer_count
+      || !fits(
+        region.offset.0,
+        region.dimensions.0,
+        level_width,
+        block_width,
+      )
+      || !fits(
+        region.offset.1,
+        region.dimensions.1,
+        level_height,
+        block_height,
+      )
+    {
+      return Err(SarektError::InvalidImageUpdate(format!(
+        "{:?} is outside of level {} with dimensions {:?} and {} layers, or not aligned to {:?} \
+         blocks",
+        region,
+        region.mip_level,
+        (level_width, level_height),
+        layer_count,
+        (block_width, block_height)
+      )));
+    }
+    if regenerate_mipmaps && image_data_format.is_compressed() {
+      return Err(SarektError::FormatDoesNotSupportMipmapping(format!(
+        "{:?} is block compressed and can't be blitted",
+        image_data_format
+      )));
+    }
 
-    let (pixel_bytes, format) = {
-      let format = pixels.format()?.into();
-      let format_suitable = unsafe {
-        self
-          .instance
-          .get_physical_device_format_properties(self.physical_device, format)
-          .optimal_tiling_features
-          .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
-      };
+    if pixels.dimensions() != region.dimensions {
+      return Err(SarektError::InvalidImageUpdate(format!(
+        "pixels have dimensions {:?} but the region is {:?}",
+        pixels.dimensions(),
+        region.dimensions
+      )));
+    }
 
-      if !format_suitable {
-        // Format not usable for a sampled image, convert to one garunteed by vulkan
-        warn!(
-          "Using an image with unsupported format: {:?}, converting to rgba, consider baking a \
-           new texture",
-          format
-        );
-        let pixels = pixels.into_rgba8();
-        let format = pixels.format()?.into();
-        (pixels.into_bytes(), format)
-      } else {
-        let format = pixels.format()?.into();
-        (pixels.into_bytes(), format)
+    // Convert the same way the image was when it was loaded.
+    let pixels_format = pixels.format()?;
+    let pixel_bytes = if vk::Format::from(pixels_format) == image.format {
+      pixels.into_bytes()
+    } else if pixels_format.is_compressed()
+      && vk::Format::from(pixels_format.decompressed_format()) == image.format
+    {
+      decompress_level(pixels_format, region.dimensions, &pixels.into_bytes())?
+    } else {
+      let pixels = pixels.into_rgba8();
+      if vk::Format::from(pixels.format()?) != image.format {
+        return Err(SarektError::InvalidImageUpdate(format!(
+          "pixels are {:?} but the image is {:?}",
+          pixels_format, image.format
+        )));
       }
+      pixels.into_bytes()
     };
 
+    let expected_size = image_data_format.level_size(region.dimensions);
+    if pixel_bytes.len() != expected_size {
+      return Err(SarektError::InvalidImageUpdate(format!(
+        "region should be {} bytes but was {}",
+        expected_size,
+        pixel_bytes.len()
+      )));
+    }
+
+    // A batch still copying into the image would overwrite the update, and
+    // owns its layout until it finishes.
+    self.wait_for_resource_upload(handle)?;
+
     info!(
-      "Loading image with dimensions {:?}, and {} bytes",
-      dimens,
+      "Updating image region {:?} with {} bytes",
+      region,
       pixel_bytes.len()
     );
-
     let (staging_buffer, staging_allocation, _) =
       self.create_staging_buffer(pixel_bytes.len() as u64)?;
 
     let data = self.allocator.map_memory(&staging_allocation)?;
     unsafe {
-      data.copy_from_nonoverlapping(pixel_bytes.as_ptr() as *const u8, pixel_bytes.len());
+      data.copy_from_nonoverlapping(pixel_bytes.as_ptr(), pixel_bytes.len());
     }
     self.allocator.unmap_memory(&staging_allocation)?;
 
-    // Only need to be a transfer source if blitting to itself when creating
-    // mipmaps.
-    let transfer_src_flag = if mip_levels > 1 {
-      vk::ImageUsageFlags::TRANSFER_SRC
-    } else {
-      vk::ImageUsageFlags::empty()
-    };
-    let (image, image_allocation, _) = self.create_gpu_image(
-      dimens,
-      format,
-      vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED | transfer_src_flag,
-      self.transfer_queue_family,
-      mip_levels,
-      NumSamples::One,
-    )?;
-
-    let extent = vk::Extent3D {
-      width: dimens.0,
-      height: dimens.1,
-      depth: 1,
-    };
-    self.transfer_staging_to_gpu_buffer_or_image(
-      pixel_bytes.len() as u64,
-      staging_buffer,
-      ImageOrBuffer::Image(image, format, extent),
-      Some(mip_levels),
-    )?;
+    let result =
+      self.copy_staging_to_image_region(&image, staging_buffer, region, regenerate_mipmaps);
 
     info!("Destroying staging buffer and memory...");
     self
       .allocator
       .destroy_buffer(staging_buffer, &staging_allocation)?;
 
-    // TODO(issue#29) IMAGES propogate up this parameter to allow users to create
-    // stencil etc, this will involve a Sarekt non vulkan enum in
-    // buffers_and_images.
+    result
+  }
+
+  fn create_uninitialized_image(
+    &self, dimensions: (u32, u32), format: ImageDataFormat, num_msaa_samples: NumSamples,
+  ) -> SarektResult<ResourceWithMemory> {
+    info!("Creating image with dimensions {:?}", dimensions);
+
+    // Attachments may be read by later subpasses, like the G-buffer and depth
+    // buffer by the deferred lighting subpass.
+    let (usage, aspect) = if format.is_depth() {
+      // Sampled by temporal anti-aliasing when the format allows it.
+      let usage = if self.query_image_format_support(format).sampled {
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+      } else {
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+      };
+      (
+        usage | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+        vk::ImageAspectFlags::DEPTH,
+      )
+    } else {
+      (
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+        vk::ImageAspectFlags::COLOR,
+      )
+    };
+    let (image, image_allocation, _) = self.create_gpu_image(
+      dimensions,
+      ImageShape::Flat,
+      format.into(),
+      usage,
+      self.graphics_queue_family,
+      1,
+      num_msaa_samples,
+    )?;
+    let image_view = self.create_image_view(image, ImageShape::Flat, format.into(), aspect, 1)?;
+    Ok(ResourceWithMemory::Image(ImageAndMemory {
+      allocation: image_allocation,
+      image_and_view: unsafe { ImageAndView::new(image, image_view) },
+      sampler: None,
+      format: format.into(),
+      extent: vk::Extent3D {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth: 1,
+      },
+      shape: ImageShape::Flat,
+      mip_levels: 1,
+      is_attachment: true,
+    }))
+  }
+
+  fn create_render_texture(
+    &self, dimensions: (u32, u32), format: ImageDataFormat,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode,
+  ) -> SarektResult<ResourceWithMemory> {
+    info!(
+      "Creating render texture with dimensions {:?} and format {:?}",
+      dimensions, format
+    );
+    let support = self.query_image_format_support(format);
+    if !support.sampled || !support.color_attachment {
+      return Err(SarektError::UnsupportedRenderTextureFormat(format));
+    }
+
+    let (image, image_allocation, _) = self.create_gpu_image(
+      dimensions,
+      ImageShape::Flat,
+      format.into(),
+      vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+      self.graphics_queue_family,
+      1,
+      NumSamples::One,
+    )?;
     let image_view = self.create_image_view(
       image,
+      ImageShape::Flat,
       format.into(),
       vk::ImageAspectFlags::COLOR,
-      mip_levels,
+      1,
     )?;
     let sampler = self.create_sampler(
       magnification_filter,
       minification_filter,
-      address_u,
-      address_v,
-      address_w,
-      mip_levels,
+      address_x,
+      address_y,
+      TextureAddressMode::ClampToEdge,
+      1,
+    )?;
+
+    // Render passes leave it ready to be sampled, so it starts out that way too
+    // in case it is sampled before it is first rendered.
+    self.initialize_attachment_layout(
+      image,
+      vk::ImageAspectFlags::COLOR,
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
     )?;
 
     Ok(ResourceWithMemory::Image(ImageAndMemory {
       allocation: image_allocation,
       image_and_view: unsafe { ImageAndView::new(image, image_view) },
       sampler: Some(sampler),
+      format: format.into(),
+      extent: vk::Extent3D {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth: 1,
+      },
+      shape: ImageShape::Flat,
+      mip_levels: 1,
+      is_attachment: true,
     }))
   }
 
-  fn create_uninitialized_image(
-    &self, dimensions: (u32, u32), format: ImageDataFormat, num_msaa_samples: NumSamples,
+  fn create_shadow_map(
+    &self, dimensions: (u32, u32), format: ImageDataFormat,
   ) -> SarektResult<ResourceWithMemory> {
-    info!("Creating image with dimensions {:?}", dimensions);
+    info!(
+      "Creating shadow map with dimensions {:?} and format {:?}",
+      dimensions, format
+    );
 
     let (image, image_allocation, _) = self.create_gpu_image(
       dimensions,
+      ImageShape::Flat,
       format.into(),
-      vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+      vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
       self.graphics_queue_family,
       1,
-      num_msaa_samples,
+      NumSamples::One,
+    )?;
+    let image_view = self.create_image_view(
+      image,
+      ImageShape::Flat,
+      format.into(),
+      vk::ImageAspectFlags::DEPTH,
+      1,
+    )?;
+
+    // Compares against the stored depth, linear filtering averages the results
+    // of the four nearest texels.
+    let sampler_ci = vk::SamplerCreateInfo::builder()
+      .mag_filter(vk::Filter::LINEAR)
+      .min_filter(vk::Filter::LINEAR)
+      .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+      .anisotropy_enable(false)
+      .max_anisotropy(1f32)
+      .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+      .unnormalized_coordinates(false)
+      .compare_enable(true)
+      .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+      .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+      .mip_lod_bias(0.0f32)
+      .min_lod(0.0f32)
+      .max_lod(0.0f32)
+      .build();
+    let sampler = unsafe { self.logical_device.create_sampler(&sampler_ci, None)? };
+
+    self.initialize_attachment_layout(
+      image,
+      vk::ImageAspectFlags::DEPTH,
+      vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
     )?;
-    let image_view =
-      self.create_image_view(image, format.into(), vk::ImageAspectFlags::DEPTH, 1)?;
+
     Ok(ResourceWithMemory::Image(ImageAndMemory {
       allocation: image_allocation,
       image_and_view: unsafe { ImageAndView::new(image, image_view) },
-      sampler: None,
+      sampler: Some(sampler),
+      format: format.into(),
+      extent: vk::Extent3D {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth: 1,
+      },
+      shape: ImageShape::Flat,
+      mip_levels: 1,
+      is_attachment: true,
     }))
   }
 
+  fn query_image_format_support(&self, format: ImageDataFormat) -> ImageFormatSupport {
+    let features = unsafe {
+      self
+        .instance
+        .get_physical_device_format_properties(self.physical_device, format.into())
+        .optimal_tiling_features
+    };
+
+    let linear_filtering = features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
+    ImageFormatSupport {
+      sampled: features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE),
+      linear_filtering,
+      // Mipmaps are generated by blitting with linear filtering.
+      mipmap_generation: !format.is_compressed()
+        && linear_filtering
+        && features.contains(vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST),
+      color_attachment: features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT),
+      depth_stencil_attachment: features.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+    }
+  }
+
+  fn heap_stats(&self) -> SarektResult<Vec<HeapStats>> {
+    let memory_properties = self.allocator.get_memory_properties()?;
+    let stats = self.allocator.calculate_stats()?;
+    let budgets = self.heap_budgets()?;
+
+    let heap_count = memory_properties.memory_heap_count as usize;
+    Ok(
+      memory_properties.memory_heaps[..heap_count]
+        .iter()
+        .zip(stats.memoryHeap.iter())
+        .enumerate()
+        .map(|(i, (heap, heap_stats))| HeapStats {
+          size: heap.size,
+          device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+          allocated: heap_stats.usedBytes + heap_stats.unusedBytes,
+          used: heap_stats.usedBytes,
+          budget: budgets.map(|budgets| budgets[i]),
+          allocation_count: heap_stats.allocationCount,
+        })
+        .collect(),
+    )
+  }
+
+  fn allocation_size(&self, handle: ResourceWithMemory) -> SarektResult<u64> {
+    let allocation = match &handle {
+      ResourceWithMemory::Buffer(buffer) => &buffer.allocation,
+      ResourceWithMemory::Image(image) => &image.allocation,
+    };
+    Ok(self.allocator.get_allocation_info(allocation)?.get_size() as u64)
+  }
+
+  fn memory_stats_json(&self, detailed: bool) -> SarektResult<String> {
+    Ok(self.allocator.build_stats_string(detailed)?)
+  }
+
+  fn defragment(&self, resources: &mut [ResourceWithMemory]) -> SarektResult<DefragmentationStats> {
+    info!("Defragmenting buffers and images...");
+    // Nothing may be uploading into memory that is about to move.
+    {
+      let mut pending_uploads = self.pending_uploads.lock().unwrap();
+      while !pending_uploads.batches.is_empty() {
+        self.wait_for_upload_batch(&mut pending_uploads, 0)?;
+      }
+    }
+
+    let before = self.allocator.calculate_stats()?.total;
+
+    let (buffers_moved, buffer_bytes_moved) = unsafe { self.defragment_buffers(resources)? };
+    let (images_moved, image_bytes_moved) = unsafe {
+      let moves = self.allocate_image_moves(resources)?;
+      self.move_images(resources, moves)?
+    };
+
+    let after = self.allocator.calculate_stats()?.total;
+    let allocated = |stats: vk_mem::ffi::VmaStatInfo| stats.usedBytes + stats.unusedBytes;
+    Ok(DefragmentationStats {
+      allocations_moved: buffers_moved + images_moved,
+      bytes_moved: buffer_bytes_moved + image_bytes_moved,
+      blocks_freed: before.blockCount.saturating_sub(after.blockCount),
+      bytes_freed: allocated(before).saturating_sub(allocated(after)),
+    })
+  }
+
   fn delete_buffer_or_image(&self, handle: ResourceWithMemory) -> SarektResult<()> {
     info!(
       "Deleting image or buffer and associated memory {:?}...",
       handle
     );
+    // Can't destroy it out from under a transfer.
+    self.wait_for_resource_upload(handle)?;
 
     match handle {
       ResourceWithMemory::Buffer(handle) => self
@@ -1050,9 +3225,15 @@ unsafe impl BackendHandleTrait for ResourceWithMemory {}
 pub struct BufferAndMemory {
   pub(crate) buffer: vk::Buffer,
   pub(crate) length: u32,
+  /// Size in bytes.
+  pub(crate) size: vk::DeviceSize,
+  /// Loaded without staging, so it can be written through mapped memory.
+  pub(crate) host_visible: bool,
   /// Only present if this is an index buffer.
   pub(crate) index_buffer_elem_size: Option<IndexBufferElemSize>,
   pub(crate) allocation: vk_mem::Allocation,
+  // What the buffer was created for, needed to recreate it when defragmenting.
+  buffer_type: BufferType,
 }
 /// Stores the mapped pointer along with the allocation.  There is no need
 /// tformbo implement drop here because when the memory itself is dropped, it is
@@ -1081,6 +3262,14 @@ fn usage_flags_from_buffer_type(buffer_type: BufferType) -> vk::BufferUsageFlags
   }
 }
 
+/// Usage of every image loaded to be sampled.  Being a transfer source is
+/// needed for blitting mip levels and for being copied by `defragment`.
+fn sampled_image_usage() -> vk::ImageUsageFlags {
+  vk::ImageUsageFlags::TRANSFER_DST
+    | vk::ImageUsageFlags::SAMPLED
+    | vk::ImageUsageFlags::TRANSFER_SRC
+}
+
 /// Just as BufferAndMemory works, this is an Image and it's bound allocated
 /// memory.
 #[derive(Copy, Clone, Debug)]
@@ -1088,19 +3277,168 @@ pub struct ImageAndMemory {
   pub(crate) image_and_view: ImageAndView,
   pub(crate) allocation: vk_mem::Allocation,
   pub(crate) sampler: Option<vk::Sampler>,
+  // What the image was created with, needed to update it later.
+  format: vk::Format,
+  extent: vk::Extent3D,
+  shape: ImageShape,
+  mip_levels: u32,
+  // Rendered into through framebuffers, which refer to its view, so it is never
+  // moved by `defragment`.
+  is_attachment: bool,
+}
+impl ImageAndMemory {
+  pub(crate) fn format(&self) -> vk::Format {
+    self.format
+  }
+
+  pub(crate) fn extent(&self) -> vk::Extent3D {
+    self.extent
+  }
+}
+
+/// A new image for `defragment` to copy an image in `resources[index]` into.
+struct ImageMove {
+  index: usize,
+  /// Size of the old allocation.
+  size: u64,
+  image_and_view: ImageAndView,
+  allocation: vk_mem::Allocation,
+}
+
+/// Upload batches that were submitted, see `submit_upload_batch`.
+#[derive(Default)]
+struct PendingUploads {
+  next_id: u64,
+  batches: Vec<InFlightUploadBatch>,
+}
+
+/// Everything that has to be kept alive until a submitted upload batch's fence
+/// signals.
+struct InFlightUploadBatch {
+  id: u64,
+  fence: vk::Fence,
+  /// Only used if the transfer and graphics queue families differ.
+  semaphore: vk::Semaphore,
+  command_buffers: Vec<(vk::CommandPool, vk::CommandBuffer)>,
+  staging_buffer: vk::Buffer,
+  staging_allocation: vk_mem::Allocation,
+  resources: Vec<UploadedResource>,
+}
+
+/// What `submit_upload_batch` has created so far, so all of it can be
+/// destroyed if a later step fails.
+struct UploadBatchInProgress {
+  staging_buffer: vk::Buffer,
+  staging_allocation: vk_mem::Allocation,
+  resources: Vec<ResourceWithMemory>,
+  command_buffers: Vec<(vk::CommandPool, vk::CommandBuffer)>,
+  /// Null until created.
+  fence: vk::Fence,
+  /// Null until created, and only created if the transfer and graphics queue
+  /// families differ.
+  semaphore: vk::Semaphore,
+  /// Whether the copies were submitted to the transfer queue.
+  submitted: bool,
+}
+
+/// The Vulkan handle of a resource in an upload batch, to check whether it is
+/// still uploading.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum UploadedResource {
+  Buffer(vk::Buffer),
+  Image(vk::Image),
+}
+impl From<&ResourceWithMemory> for UploadedResource {
+  fn from(resource: &ResourceWithMemory) -> Self {
+    match resource {
+      ResourceWithMemory::Buffer(buffer) => UploadedResource::Buffer(buffer.buffer),
+      ResourceWithMemory::Image(image) => UploadedResource::Image(image.image_and_view.image),
+    }
+  }
+}
+
+/// Where the mip levels of an image come from during a staging transfer.
+#[derive(Copy, Clone)]
+enum MipChainSource<'a> {
+  /// Only level 0 is in the staging buffer, the rest of this many levels are
+  /// blitted from it.
+  Generated(u32),
+  /// Every level is in the staging buffer, at these offsets.
+  Pregenerated(&'a [u64]),
+}
+impl MipChainSource<'_> {
+  fn level_count(self) -> u32 {
+    match self {
+      MipChainSource::Generated(mip_levels) => mip_levels,
+      MipChainSource::Pregenerated(level_offsets) => level_offsets.len() as u32,
+    }
+  }
 }
 
 /// Whether the operation will concern a buffer or an image.  Image includes its
-/// extent.
+/// extent and shape.
 enum ImageOrBuffer {
   Buffer(vk::Buffer),
-  Image(vk::Image, vk::Format, vk::Extent3D),
+  Image(vk::Image, vk::Format, vk::Extent3D, ImageShape),
 }
 impl ImageOrBuffer {
-  fn image(&self) -> SarektResult<(vk::Image, vk::Format, vk::Extent3D)> {
+  fn image(&self) -> SarektResult<(vk::Image, vk::Format, vk::Extent3D, ImageShape)> {
     match *self {
-      ImageOrBuffer::Image(image, format, extent) => Ok((image, format, extent)),
+      ImageOrBuffer::Image(image, format, extent, shape) => Ok((image, format, extent, shape)),
       _ => Err(SarektError::IncorrectResourceType),
     }
   }
 }
+
+/// How the layers of an image are laid out, which decides its create flags and
+/// view type.
+#[derive(Copy, Clone, Debug)]
+enum ImageShape {
+  /// A single layer 2D image.
+  Flat,
+  /// Six square layers in +X, -X, +Y, -Y, +Z, -Z order, sampled as a cube.
+  Cube,
+  /// This many 2D layers, sampled as a 2D array.
+  Array(u32),
+  /// A 3D image this many texels deep.
+  Volume(u32),
+}
+impl ImageShape {
+  fn image_type(self) -> vk::ImageType {
+    match self {
+      ImageShape::Volume(_) => vk::ImageType::TYPE_3D,
+      _ => vk::ImageType::TYPE_2D,
+    }
+  }
+
+  fn array_layers(self) -> u32 {
+    match self {
+      ImageShape::Flat | ImageShape::Volume(_) => 1,
+      ImageShape::Cube => 6,
+      ImageShape::Array(layers) => layers,
+    }
+  }
+
+  fn depth(self) -> u32 {
+    match self {
+      ImageShape::Volume(depth) => depth,
+      _ => 1,
+    }
+  }
+
+  fn create_flags(self) -> vk::ImageCreateFlags {
+    match self {
+      ImageShape::Cube => vk::ImageCreateFlags::CUBE_COMPATIBLE,
+      _ => vk::ImageCreateFlags::empty(),
+    }
+  }
+
+  fn view_type(self) -> vk::ImageViewType {
+    match self {
+      ImageShape::Flat => vk::ImageViewType::TYPE_2D,
+      ImageShape::Cube => vk::ImageViewType::CUBE,
+      ImageShape::Array(_) => vk::ImageViewType::TYPE_2D_ARRAY,
+      ImageShape::Volume(_) => vk::ImageViewType::TYPE_3D,
+    }
+  }
+}
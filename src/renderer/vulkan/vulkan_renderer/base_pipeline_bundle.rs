@@ -12,7 +12,6 @@ use ash::vk;
 pub struct BasePipelineBundle {
   pub pipeline: vk::Pipeline,
   pub pipeline_layout: vk::PipelineLayout,
-  pub pipeline_create_info: vk::GraphicsPipelineCreateInfo,
   pub resolve_attachment: Option<ResolveAttachment>,
   pub depth_resources: Option<DepthAttachment>,
   pub descriptor_set_layouts: Option<Vec<vk::DescriptorSetLayout>>,
@@ -22,7 +21,6 @@ pub struct BasePipelineBundle {
 impl BasePipelineBundle {
   pub fn new(
     pipeline: vk::Pipeline, pipeline_layout: vk::PipelineLayout,
-    pipeline_create_info: vk::GraphicsPipelineCreateInfo,
     descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     resolve_attachment: Option<ResolveAttachment>, depth_resources: DepthAttachment,
     vertex_shader_handle: ShaderHandle<VulkanShaderFunctions>,
@@ -31,7 +29,6 @@ impl BasePipelineBundle {
     Self {
       pipeline,
       pipeline_layout,
-      pipeline_create_info,
       resolve_attachment,
       depth_resources: Some(depth_resources),
       descriptor_set_layouts: Some(descriptor_set_layouts),
@@ -0,0 +1,596 @@
+use crate::{
+  error::SarektResult,
+  renderer::{
+    lighting::{
+      DirectionalLight, PointLight, SceneLights, SpotLight, MAX_DEFERRED_DIRECTIONAL_LIGHTS,
+      MAX_DEFERRED_POINT_LIGHTS, MAX_DEFERRED_SPOT_LIGHTS,
+    },
+    shaders::ShaderStore,
+    vulkan::{
+      vulkan_renderer::{
+        render_attachments::{DepthAttachment, GBufferAttachments},
+        uniform_ring::UniformRing,
+      },
+      vulkan_shader_functions::VulkanShaderFunctions,
+      VulkanShaderHandle,
+    },
+    ShaderCode, ShaderType, MAX_FRAMES_IN_FLIGHT,
+  },
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use log::info;
+use std::{
+  cell::Cell,
+  ffi::CStr,
+  sync::{Arc, RwLock},
+};
+use ultraviolet as uv;
+use vk_shader_macros::include_glsl;
+
+/// Vertex shader of the lighting subpass, draws a full screen triangle for the
+/// directional lights and a cube around each point or spot light.
+pub const DEFERRED_LIGHTING_VERTEX_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_deferred_lighting.vert");
+/// Fragment shader of the lighting subpass, shades the G-buffer with the
+/// scene lights.
+pub const DEFERRED_LIGHTING_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_deferred_lighting.frag");
+
+/// Subpasses of the deferred render pass, in order.  Opaque PBR surfaces are
+/// drawn into the G-buffer, the lights shade it, and everything else is drawn
+/// forward over the result.
+pub const G_BUFFER_SUBPASS: u32 = 0;
+pub const LIGHTING_SUBPASS: u32 = 1;
+pub const FORWARD_SUBPASS: u32 = 2;
+
+// Values of LightVolume.kind in the lighting shaders.
+const DIRECTIONAL_LIGHTS: u32 = 0;
+const POINT_LIGHTS: u32 = 1;
+const SPOT_LIGHTS: u32 = 2;
+
+// Vertices of the cube drawn around each point and spot light.
+const LIGHT_VOLUME_VERTEX_COUNT: u32 = 36;
+
+/// DeferredLightingUniform in the lighting shaders.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct DeferredLightingUniform {
+  view_projection: uv::Mat4,
+  inverse_view_projection: uv::Mat4,
+  camera_position: uv::Vec3,
+  directional_light_count: u32,
+  inverse_target_size: uv::Vec2,
+  // The array that follows is 16 byte aligned.
+  _padding: uv::Vec2,
+  directional_lights: [DirectionalLight; MAX_DEFERRED_DIRECTIONAL_LIGHTS],
+}
+
+/// DeferredPointLights in the lighting shaders.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DeferredPointLights {
+  lights: [PointLight; MAX_DEFERRED_POINT_LIGHTS],
+}
+
+/// DeferredSpotLights in the lighting shaders.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DeferredSpotLights {
+  lights: [SpotLight; MAX_DEFERRED_SPOT_LIGHTS],
+}
+
+/// Records the lighting subpass of the deferred render pass, and holds the
+/// command buffers its forward subpass is recorded into.
+///
+/// Opaque PBR objects go straight into the main command buffer's G-buffer
+/// subpass as they are drawn.  Everything drawn forward goes into a secondary
+/// command buffer per frame in flight instead, which `draw_lighting_cmd`
+/// executes in the forward subpass once the lights are recorded, so objects
+/// can be drawn in any order.
+pub struct DeferredPass {
+  logical_device: Arc<Device>,
+  command_pool: vk::CommandPool,
+  command_buffers: Vec<vk::CommandBuffer>,
+  current_frame_num: Cell<usize>,
+  // Pipeline bound in the current forward command buffer.
+  bound_pipeline: Cell<vk::Pipeline>,
+  vertex_shader_handle: Option<VulkanShaderHandle>,
+  fragment_shader_handle: Option<VulkanShaderHandle>,
+  descriptor_set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+  // Full screen for the directional lights, and light volumes for the rest.
+  directional_pipeline: vk::Pipeline,
+  light_volume_pipeline: vk::Pipeline,
+  // The scene lights and camera, uploaded every frame.
+  lighting_uniform: DeferredLightingUniform,
+  point_lights: Box<DeferredPointLights>,
+  point_light_count: u32,
+  spot_lights: Box<DeferredSpotLights>,
+  spot_light_count: u32,
+}
+impl DeferredPass {
+  pub fn new(
+    logical_device: Arc<Device>, command_pool: vk::CommandPool,
+    shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>, render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+  ) -> SarektResult<Self> {
+    let vertex_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(DEFERRED_LIGHTING_VERTEX_SHADER),
+      ShaderType::Vertex,
+    )?;
+    let fragment_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(DEFERRED_LIGHTING_FRAGMENT_SHADER),
+      ShaderType::Fragment,
+    )?;
+
+    let descriptor_set_layout = Self::create_descriptor_set_layout(&logical_device)?;
+    let pipeline_layout = Self::create_pipeline_layout(&logical_device, descriptor_set_layout)?;
+
+    let command_buffer_ci = vk::CommandBufferAllocateInfo::builder()
+      .command_pool(command_pool)
+      .level(vk::CommandBufferLevel::SECONDARY)
+      .command_buffer_count(MAX_FRAMES_IN_FLIGHT as u32)
+      .build();
+    let command_buffers = unsafe { logical_device.allocate_command_buffers(&command_buffer_ci)? };
+
+    let mut deferred_pass = Self {
+      logical_device,
+      command_pool,
+      command_buffers,
+      current_frame_num: Cell::new(0),
+      bound_pipeline: Cell::new(vk::Pipeline::null()),
+      vertex_shader_handle: Some(vertex_shader_handle),
+      fragment_shader_handle: Some(fragment_shader_handle),
+      descriptor_set_layout,
+      pipeline_layout,
+      directional_pipeline: vk::Pipeline::null(),
+      light_volume_pipeline: vk::Pipeline::null(),
+      lighting_uniform: DeferredLightingUniform {
+        view_projection: uv::Mat4::identity(),
+        inverse_view_projection: uv::Mat4::identity(),
+        camera_position: uv::Vec3::zero(),
+        directional_light_count: 0,
+        inverse_target_size: uv::Vec2::one(),
+        _padding: uv::Vec2::zero(),
+        directional_lights: [DirectionalLight::default(); MAX_DEFERRED_DIRECTIONAL_LIGHTS],
+      },
+      point_lights: Box::new(DeferredPointLights {
+        lights: [PointLight::default(); MAX_DEFERRED_POINT_LIGHTS],
+      }),
+      point_light_count: 0,
+      spot_lights: Box::new(DeferredSpotLights {
+        lights: [SpotLight::default(); MAX_DEFERRED_SPOT_LIGHTS],
+      }),
+      spot_light_count: 0,
+    };
+    deferred_pass.recreate_pipelines(shader_store, render_pass, extent)?;
+
+    Ok(deferred_pass)
+  }
+
+  /// Recreates the lighting pipelines for a new render pass or extent.  The
+  /// old ones must already have been cleaned up with `cleanup_pipelines`.
+  pub fn recreate_pipelines(
+    &mut self, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    render_pass: vk::RenderPass, extent: vk::Extent2D,
+  ) -> SarektResult<()> {
+    self.directional_pipeline =
+      self.create_pipeline(shader_store, render_pass, extent, vk::CullModeFlags::NONE)?;
+    // Only the back faces of the volumes, so lights the camera is inside of
+    // still shade.
+    self.light_volume_pipeline =
+      self.create_pipeline(shader_store, render_pass, extent, vk::CullModeFlags::FRONT)?;
+    self.lighting_uniform.inverse_target_size =
+      uv::Vec2::new(1f32 / extent.width as f32, 1f32 / extent.height as f32);
+    Ok(())
+  }
+
+  /// The lights to shade the G-buffer with from the next frame on, already
+  /// checked with `SceneLights::check_counts`.
+  pub fn set_scene_lights(&mut self, lights: &SceneLights) {
+    let uniform = &mut self.lighting_uniform;
+    uniform.directional_light_count = lights.directional_lights.len() as u32;
+    uniform.directional_lights[..lights.directional_lights.len()]
+      .copy_from_slice(&lights.directional_lights);
+
+    self.point_light_count = lights.point_lights.len() as u32;
+    self.point_lights.lights[..lights.point_lights.len()].copy_from_slice(&lights.point_lights);
+
+    self.spot_light_count = lights.spot_lights.len() as u32;
+    self.spot_lights.lights[..lights.spot_lights.len()].copy_from_slice(&lights.spot_lights);
+  }
+
+  /// The camera the G-buffer is drawn with, to find where its pixels are in
+  /// the world.
+  pub fn set_view_projection(&mut self, view: uv::Mat4, projection: uv::Mat4) {
+    let view_projection = projection * view;
+    let uniform = &mut self.lighting_uniform;
+    uniform.view_projection = view_projection;
+    uniform.inverse_view_projection = view_projection.inversed();
+    uniform.camera_position = view.inversed().cols[3].xyz();
+  }
+
+  /// Begins `current_frame_num`'s forward command buffer, whose previous frame
+  /// must be done on the GPU.  It continues the forward subpass of
+  /// `render_pass` in `framebuffer`.
+  pub fn start_frame(
+    &self, current_frame_num: usize, render_pass: vk::RenderPass, framebuffer: vk::Framebuffer,
+  ) -> SarektResult<()> {
+    if current_frame_num >= MAX_FRAMES_IN_FLIGHT {
+      panic!("Invalid input! current_frame_num {}", current_frame_num);
+    }
+
+    self.current_frame_num.set(current_frame_num);
+    self.bound_pipeline.set(vk::Pipeline::null());
+
+    let inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+      .render_pass(render_pass)
+      .subpass(FORWARD_SUBPASS)
+      .framebuffer(framebuffer)
+      .build();
+    let begin_ci = vk::CommandBufferBeginInfo::builder()
+      .flags(
+        vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+          | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+      )
+      .inheritance_info(&inheritance_info)
+      .build();
+    unsafe {
+      self
+        .logical_device
+        .begin_command_buffer(self.command_buffer(), &begin_ci)?
+    };
+
+    Ok(())
+  }
+
+  /// The command buffer forward draws are recorded into this frame.
+  pub fn command_buffer(&self) -> vk::CommandBuffer {
+    self.command_buffers[self.current_frame_num.get()]
+  }
+
+  pub fn bound_pipeline(&self) -> vk::Pipeline {
+    self.bound_pipeline.get()
+  }
+
+  pub fn set_bound_pipeline(&self, pipeline: vk::Pipeline) {
+    self.bound_pipeline.set(pipeline)
+  }
+
+  /// Ends the forward command buffer and records the rest of the deferred
+  /// render pass into `command_buffer`, which must be in the G-buffer subpass:
+  /// the lights, then the forward draws.  The render pass is left in the
+  /// forward subpass to be ended.
+  pub fn draw_lighting_cmd(
+    &self, command_buffer: vk::CommandBuffer, descriptor_pool: vk::DescriptorPool,
+    uniform_ring: &UniformRing, g_buffer: &GBufferAttachments, depth_buffer: &DepthAttachment,
+  ) -> SarektResult<()> {
+    let logical_device = &self.logical_device;
+    let forward_command_buffer = self.command_buffer();
+    unsafe { logical_device.end_command_buffer(forward_command_buffer)? };
+
+    let dynamic_offsets = [
+      uniform_ring.allocate(&self.lighting_uniform)?,
+      uniform_ring.allocate(&*self.point_lights)?,
+      uniform_ring.allocate(&*self.spot_lights)?,
+    ];
+
+    let set_layouts = [self.descriptor_set_layout];
+    let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+      .descriptor_pool(descriptor_pool)
+      .set_layouts(&set_layouts)
+      .build();
+    let descriptor_sets = unsafe { logical_device.allocate_descriptor_sets(&alloc_info)? };
+
+    let buffer_info = |size: usize| {
+      [vk::DescriptorBufferInfo::builder()
+        .buffer(uniform_ring.buffer())
+        .offset(0)
+        .range(size as vk::DeviceSize)
+        .build()]
+    };
+    let buffer_infos = [
+      buffer_info(std::mem::size_of::<DeferredLightingUniform>()),
+      buffer_info(std::mem::size_of::<DeferredPointLights>()),
+      buffer_info(std::mem::size_of::<DeferredSpotLights>()),
+    ];
+    let input_info = |view, image_layout| {
+      [vk::DescriptorImageInfo::builder()
+        .image_layout(image_layout)
+        .image_view(view)
+        .build()]
+    };
+    let input_infos = [
+      input_info(
+        g_buffer.albedo.image_and_view.view,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      ),
+      input_info(
+        g_buffer.normal.image_and_view.view,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      ),
+      input_info(
+        g_buffer.material.image_and_view.view,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      ),
+      input_info(
+        depth_buffer.image_and_memory.image_and_view.view,
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+      ),
+    ];
+
+    // Bindings 0 to 2 are the uniforms, 3 to 6 the input attachments.
+    let uniform_writes = buffer_infos.iter().enumerate().map(|(binding, info)| {
+      vk::WriteDescriptorSet::builder()
+        .dst_set(descriptor_sets[0])
+        .dst_binding(binding as u32)
+        .dst_array_element(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .buffer_info(info)
+        .build()
+    });
+    let input_writes = input_infos.iter().enumerate().map(|(index, info)| {
+      vk::WriteDescriptorSet::builder()
+        .dst_set(descriptor_sets[0])
+        .dst_binding((buffer_infos.len() + index) as u32)
+        .dst_array_element(0)
+        .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+        .image_info(info)
+        .build()
+    });
+    let descriptor_writes: Vec<_> = uniform_writes.chain(input_writes).collect();
+
+    unsafe {
+      logical_device.update_descriptor_sets(&descriptor_writes, &[]);
+
+      logical_device.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+      logical_device.cmd_bind_descriptor_sets(
+        command_buffer,
+        vk::PipelineBindPoint::GRAPHICS,
+        self.pipeline_layout,
+        0,
+        &descriptor_sets,
+        &dynamic_offsets,
+      );
+
+      if self.lighting_uniform.directional_light_count > 0 {
+        logical_device.cmd_bind_pipeline(
+          command_buffer,
+          vk::PipelineBindPoint::GRAPHICS,
+          self.directional_pipeline,
+        );
+        self.push_light_kind_cmd(command_buffer, DIRECTIONAL_LIGHTS);
+        logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+      }
+
+      if self.point_light_count > 0 || self.spot_light_count > 0 {
+        logical_device.cmd_bind_pipeline(
+          command_buffer,
+          vk::PipelineBindPoint::GRAPHICS,
+          self.light_volume_pipeline,
+        );
+      }
+      if self.point_light_count > 0 {
+        self.push_light_kind_cmd(command_buffer, POINT_LIGHTS);
+        logical_device.cmd_draw(
+          command_buffer,
+          LIGHT_VOLUME_VERTEX_COUNT,
+          self.point_light_count,
+          0,
+          0,
+        );
+      }
+      if self.spot_light_count > 0 {
+        self.push_light_kind_cmd(command_buffer, SPOT_LIGHTS);
+        logical_device.cmd_draw(
+          command_buffer,
+          LIGHT_VOLUME_VERTEX_COUNT,
+          self.spot_light_count,
+          0,
+          0,
+        );
+      }
+
+      logical_device.cmd_next_subpass(
+        command_buffer,
+        vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+      );
+      logical_device.cmd_execute_commands(command_buffer, &[forward_command_buffer]);
+    }
+
+    Ok(())
+  }
+
+  /// Destroys the lighting pipelines, for swapchain recreation or cleanup.
+  pub unsafe fn cleanup_pipelines(&self) {
+    info!("Destroying deferred lighting pipelines...");
+    self
+      .logical_device
+      .destroy_pipeline(self.directional_pipeline, None);
+    self
+      .logical_device
+      .destroy_pipeline(self.light_volume_pipeline, None);
+  }
+
+  /// Unsafe because the device must be idle.  The pipelines must already have
+  /// been cleaned up with `cleanup_pipelines`.
+  pub unsafe fn destroy_all(&mut self) {
+    info!("Destroying deferred pass...");
+    let logical_device = &self.logical_device;
+    logical_device.free_command_buffers(self.command_pool, &self.command_buffers);
+    logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+    logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+    self.vertex_shader_handle.take();
+    self.fragment_shader_handle.take();
+  }
+
+  unsafe fn push_light_kind_cmd(&self, command_buffer: vk::CommandBuffer, kind: u32) {
+    self.logical_device.cmd_push_constants(
+      command_buffer,
+      self.pipeline_layout,
+      vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+      0,
+      &kind.to_ne_bytes(),
+    );
+  }
+
+  /// The lights' uniforms and the G-buffer.
+  fn create_descriptor_set_layout(
+    logical_device: &Device,
+  ) -> SarektResult<vk::DescriptorSetLayout> {
+    let stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+    let uniform_bindings = (0..3).map(|binding| {
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(stages)
+        .build()
+    });
+    let input_bindings = (3..7).map(|binding| {
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build()
+    });
+    let bindings: Vec<_> = uniform_bindings.chain(input_bindings).collect();
+
+    let descriptor_set_layout_ci = vk::DescriptorSetLayoutCreateInfo::builder()
+      .bindings(&bindings)
+      .build();
+    Ok(unsafe { logical_device.create_descriptor_set_layout(&descriptor_set_layout_ci, None)? })
+  }
+
+  /// Which kind of light is drawn is pushed before each draw, see
+  /// `push_light_kind_cmd`.
+  fn create_pipeline_layout(
+    logical_device: &Device, descriptor_set_layout: vk::DescriptorSetLayout,
+  ) -> SarektResult<vk::PipelineLayout> {
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+      .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+      .offset(0)
+      .size(std::mem::size_of::<u32>() as u32)
+      .build()];
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(&set_layouts)
+      .push_constant_ranges(&push_constant_ranges)
+      .build();
+    Ok(unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? })
+  }
+
+  /// The lighting pipelines differ only in culling.  Vertices are generated
+  /// from gl_VertexIndex, there is no depth test, and the lights add up in the
+  /// color attachment.
+  fn create_pipeline(
+    &self, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    render_pass: vk::RenderPass, extent: vk::Extent2D, cull_mode: vk::CullModeFlags,
+  ) -> SarektResult<vk::Pipeline> {
+    let shader_store = shader_store.read().unwrap();
+
+    let entry_point_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let shader_stage_cis = [
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(
+          shader_store
+            .get_shader(self.vertex_shader_handle.as_ref().unwrap())?
+            .shader_handle,
+        )
+        .name(entry_point_name)
+        .build(),
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(
+          shader_store
+            .get_shader(self.fragment_shader_handle.as_ref().unwrap())?
+            .shader_handle,
+        )
+        .name(entry_point_name)
+        .build(),
+    ];
+
+    let vertex_input_ci = vk::PipelineVertexInputStateCreateInfo::builder().build();
+    let input_assembly_ci = vk::PipelineInputAssemblyStateCreateInfo::builder()
+      .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .primitive_restart_enable(false)
+      .build();
+
+    let viewports = [vk::Viewport::builder()
+      .x(0f32)
+      .y(0f32)
+      .width(extent.width as f32)
+      .height(extent.height as f32)
+      .min_depth(0f32)
+      .max_depth(1.0f32)
+      .build()];
+    let scissors = [vk::Rect2D::builder()
+      .offset(vk::Offset2D::default())
+      .extent(extent)
+      .build()];
+    let viewport_state_ci = vk::PipelineViewportStateCreateInfo::builder()
+      .viewports(&viewports)
+      .scissors(&scissors)
+      .build();
+
+    let raster_state_ci = vk::PipelineRasterizationStateCreateInfo::builder()
+      .depth_clamp_enable(false)
+      .rasterizer_discard_enable(false)
+      .polygon_mode(vk::PolygonMode::FILL)
+      .line_width(1.0f32)
+      .cull_mode(cull_mode)
+      .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+      .depth_bias_enable(false)
+      .build();
+
+    let multisample_state_ci = vk::PipelineMultisampleStateCreateInfo::builder()
+      .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+      .sample_shading_enable(false)
+      .alpha_to_coverage_enable(false)
+      .alpha_to_one_enable(false)
+      .build();
+
+    let attachments = [vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(true)
+      .src_color_blend_factor(vk::BlendFactor::ONE)
+      .dst_color_blend_factor(vk::BlendFactor::ONE)
+      .color_blend_op(vk::BlendOp::ADD)
+      .src_alpha_blend_factor(vk::BlendFactor::ONE)
+      .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+      .alpha_blend_op(vk::BlendOp::ADD)
+      .build()];
+    let color_blend_ci = vk::PipelineColorBlendStateCreateInfo::builder()
+      .logic_op_enable(false)
+      .logic_op(vk::LogicOp::COPY)
+      .attachments(&attachments)
+      .build();
+
+    let pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
+      .stages(&shader_stage_cis)
+      .vertex_input_state(&vertex_input_ci)
+      .input_assembly_state(&input_assembly_ci)
+      .viewport_state(&viewport_state_ci)
+      .rasterization_state(&raster_state_ci)
+      .multisample_state(&multisample_state_ci)
+      .color_blend_state(&color_blend_ci)
+      .layout(self.pipeline_layout)
+      .render_pass(render_pass)
+      .subpass(LIGHTING_SUBPASS)
+      .build();
+
+    let pipelines = unsafe {
+      self
+        .logical_device
+        .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_ci], None)
+    };
+    match pipelines {
+      Ok(pipelines) => Ok(pipelines[0]),
+      Err(err) => Err(err.1.into()),
+    }
+  }
+}
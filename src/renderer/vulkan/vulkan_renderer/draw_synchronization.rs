@@ -132,6 +132,21 @@ impl DrawSynchronization {
     }
   }
 
+  /// Waits for the last submission of the given frame in flight, so the
+  /// per frame resources it used can be written to again.
+  pub fn wait_for_frame_fence(&self, current_frame_num: usize) -> SarektResult<()> {
+    if current_frame_num >= MAX_FRAMES_IN_FLIGHT {
+      panic!("Invalid input! current_frame_num {}", current_frame_num);
+    }
+    unsafe {
+      Ok(self.logical_device.wait_for_fences(
+        &[self.frame_fences[current_frame_num]],
+        true,
+        u64::max_value(),
+      )?)
+    }
+  }
+
   /// Mark the image as in use by the given frame.
   pub fn set_image_to_in_flight_frame(&self, image_index: usize, current_frame_num: usize) {
     if current_frame_num >= MAX_FRAMES_IN_FLIGHT || image_index >= self.image_to_frame_fence.len() {
@@ -5,39 +5,66 @@ pub mod vulkan_core;
 
 mod base_pipeline_bundle;
 mod debug_utils_ext;
+mod deferred_pass;
 mod draw_synchronization;
 mod pipelines;
+mod post_process_chain;
 mod render_attachments;
 mod render_targets;
+mod render_texture_passes;
+mod shadow_pass;
+mod skybox;
 mod surface;
 mod swap_chain;
+mod uniform_ring;
 
 use crate::{
   error::{SarektError, SarektResult},
-  image_data::{ImageData, Monocolor},
+  image_data::{
+    cubemap::{Cubemap, CubemapImageData, MipmappedCubemapImageData},
+    environment::{brdf_lut, EnvironmentMaps, DEFAULT_BRDF_LUT_SIZE},
+    layered::LayeredImageData,
+    ImageData, ImageDataFormat, MipmappedImageData, Monocolor,
+  },
   renderer::{
     buffers_and_images::{
       BufferAndImageLoader, BufferImageHandle, BufferImageStore, BufferOrImage, BufferType,
-      IndexBufferElemSize, MagnificationMinificationFilter, ResourceType, TextureAddressMode,
-      UniformBufferHandle,
+      ImageFormatSupport, ImageRegion, IndexBufferElemSize, MagnificationMinificationFilter,
+      ResourceType, TextureAddressMode, UniformBufferHandle,
     },
-    config::{Config, NumSamples},
+    config::{AntiAliasingMode, Config, NumSamples, RenderingPath, SurfaceFormatPreference},
+    deletion_queue,
     drawable_object::DrawableObject,
+    lighting::SceneLights,
+    memory_stats::{DefragmentationStats, MemoryStats},
+    primitive_state::{PrimitiveState, PrimitiveTopology},
+    render_texture::RenderTextureHandle,
     shaders::ShaderStore,
-    vertex_bindings::DescriptorLayoutInfo,
+    shadows::{ShadowCamera, ShadowCascades, ShadowLight},
+    upload_batch::{UploadBatch, UploadToken},
+    vertex_bindings::{DescriptorLayoutInfo, ForwardShaderVariant, VertexBindings},
     vulkan::{
       images::ImageAndView,
       queues::QueueFamilyIndices,
       vulkan_buffer_image_functions::{BufferAndMemoryMapped, ImageAndMemory, ResourceWithMemory},
       vulkan_renderer::{
         debug_utils_ext::DebugUserData,
+        deferred_pass::DeferredPass,
         draw_synchronization::DrawSynchronization,
         pipelines::Pipelines,
-        render_attachments::{DepthAttachment, ResolveAttachment},
+        post_process_chain::{PostProcessChain, VulkanPostProcessPass},
+        render_attachments::{DepthAttachment, GBufferAttachments, ResolveAttachment},
         render_targets::RenderTargetBundle,
+        render_texture_passes::RenderTexturePasses,
+        shadow_pass::{ShadowPass, ShadowUniform},
+        uniform_ring::UniformRing,
         vulkan_core::{VulkanCoreStructures, VulkanDeviceStructures},
       },
       vulkan_shader_functions::VulkanShaderFunctions,
+      vulkan_vertex_bindings::{
+        check_vertex_attribute_formats, PBR_BRDF_LUT_BINDING, PBR_IRRADIANCE_BINDING,
+        PBR_PREFILTERED_BINDING, SHADOW_MAP_BINDING, SHADOW_UNIFORM_BINDING,
+      },
     },
     Drawer, Renderer, ShaderCode, ShaderHandle, ShaderType, VulkanBufferImageFunctions,
     MAX_FRAMES_IN_FLIGHT,
@@ -50,24 +77,41 @@ use ash::{
 use log::{error, info, warn};
 use raw_window_handle::HasRawWindowHandle;
 use std::{
-  cell::Cell,
+  cell::{Cell, RefCell},
   convert::TryInto,
   mem::ManuallyDrop,
   pin::Pin,
   sync::{Arc, RwLock},
 };
+use ultraviolet as uv;
 use vk_shader_macros::include_glsl;
 
-// TODO(issue#8) PERFORMANCE can i make things like descriptor set count and
-// uniform buffers allocate number of frames in flight, not render target image
-// count?
-
 /// Default vertex shader that contain their own vertices, will be removed in
 /// the future.
 pub const DEFAULT_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward.vert");
 /// Default fragment shader that contain their own vertices, will be removed in
 /// the future.
 pub const DEFAULT_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward.frag");
+/// Shadow receiving variant of the default vertex shader, also passes the
+/// world position on.
+pub const SHADOWED_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_shadowed.vert");
+/// Shadow receiving variant of the default fragment shader, darkens what the
+/// shadow map says is in shadow.
+pub const SHADOWED_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_shadowed.frag");
+/// Lit vertex shader, reads vertices with normals and passes the world position
+/// and normal on.
+pub const LIT_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_lit.vert");
+/// Lit fragment shader, Phong or Blinn-Phong shading of the uniform's lights.
+pub const LIT_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_lit.frag");
+/// PBR vertex shader, reads vertices with tangents and passes the world
+/// position and tangent frame on.
+pub const PBR_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_pbr.vert");
+/// PBR fragment shader, Cook-Torrance shading of the uniform's lights and the
+/// image based lighting environment.
+pub const PBR_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_forward_pbr.frag");
+/// G-buffer variant of the PBR fragment shader, writes the surface for the
+/// deferred lighting subpass and shades only the environment and emission.
+pub const DEFERRED_PBR_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_deferred_pbr.frag");
 
 /// The Sarekt Vulkan Renderer, see module and crate level documentations for
 /// details.
@@ -87,9 +131,30 @@ pub struct VulkanRenderer {
   // Frame in flight number 0..MAX_FRAMES_IN_FLIGHT
   current_frame_num: Cell<usize>,
   next_image_index: Cell<usize>,
-
-  // Descriptor pools.
+  // Pipeline bound in the command buffer being recorded.
+  bound_pipeline: Cell<vk::Pipeline>,
+  // Passes into render textures, recorded before the main command buffer.
+  render_texture_passes: RenderTexturePasses,
+  // Depth only pass into the shadow map, recorded before the others.
+  shadow_pass: ShadowPass,
+  // Full screen passes from the scene target to the swapchain image, recorded
+  // after the forward pass when post processing or anti-aliasing other than
+  // MSAA is enabled.
+  post_process_chain: Option<PostProcessChain>,
+  // Lighting subpass and forward command buffers of the deferred render pass,
+  // only with the deferred rendering path.
+  deferred_pass: Option<DeferredPass>,
+  // Size the forward pass draws at, larger than the swapchain when
+  // supersampling.
+  scene_extent: vk::Extent2D,
+
+  // Descriptor pools, one per frame in flight.
   main_descriptor_pools: Vec<vk::DescriptorPool>,
+  // Per frame uniform data passed to Drawer::draw_with_uniform.
+  uniform_ring: Option<UniformRing>,
+  // set_uniform_bytes writes still to be made to other frames' copies of
+  // their uniforms.
+  pending_uniform_writes: RefCell<Vec<PendingUniformWrite>>,
 
   // Utilities
   allocator: Arc<vk_mem::Allocator>,
@@ -102,6 +167,33 @@ pub struct VulkanRenderer {
     BufferImageHandle<VulkanBufferImageFunctions>,
     BufferOrImage<ResourceWithMemory>,
   )>,
+  // Black, bound in place of the environment maps when there are none.
+  default_cubemap: Option<(
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  )>,
+
+  // The environment's irradiance and prefiltered maps for the PBR shaders,
+  // and the BRDF lookup table generated with the first of them.
+  image_based_lighting: Option<ImageBasedLighting>,
+  brdf_lut: Option<(
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  )>,
+
+  // Cubemap drawn by the skybox pass and the camera to draw it with.
+  skybox: Option<(
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  )>,
+  skybox_view_projection: uv::Mat4,
+
+  // The light casting shadows and the camera its cascades are fit to.
+  shadow_light: Option<ShadowLight>,
+  shadow_camera: Option<ShadowCamera>,
+  // Where this frame's ShadowUniform is in the uniform ring, written by the
+  // first shadow receiving draw.
+  shadow_uniform_offset: Cell<Option<u32>>,
 
   // Application controllable fields
   rendering_enabled: bool,
@@ -116,10 +208,25 @@ impl VulkanRenderer {
     Self::new_with_debug_user_data(window, config, None)
   }
 
+  /// Checks that the device can read every attribute of the vertex type from
+  /// a vertex buffer, see [vertex_formats](../../vertex_formats/index.html).
+  /// Formats it can't are listed in the error.  The built-in vertex types are
+  /// checked when the renderer creates its pipelines.
+  pub fn check_vertex_format_support<V>(&self) -> SarektResult<()>
+  where
+    V: VertexBindings<BVA = vk::VertexInputAttributeDescription>,
+  {
+    check_vertex_attribute_formats(
+      &self.vulkan_core.instance,
+      self.vulkan_device_structures.physical_device,
+      &V::get_attribute_descriptions(),
+    )
+  }
+
   /// Like new_detailed but allows injection of user data, for unit testing or
   /// metric gathering.
   fn new_with_debug_user_data<W: HasRawWindowHandle, OW: Into<Option<Arc<W>>>>(
-    window: OW, config: Config, debug_user_data: Option<Pin<Arc<DebugUserData>>>,
+    window: OW, mut config: Config, debug_user_data: Option<Pin<Arc<DebugUserData>>>,
   ) -> SarektResult<Self> {
     let window = window
       .into()
@@ -129,6 +236,29 @@ impl VulkanRenderer {
     // is None (change it to an Enum of WindowHandle or OtherSurface).
     info!("Creating Sarekt Renderer with Vulkan Backend...");
 
+    // Only the post process chain can encode for HDR10.
+    if config.surface_format == SurfaceFormatPreference::Hdr10
+      && !config.post_processing
+      && config.anti_aliasing == AntiAliasingMode::None
+    {
+      warn!("HDR10 surface format requires post processing, falling back to sRGB");
+      config.surface_format = SurfaceFormatPreference::Srgb;
+    }
+    // The G-buffer is single sampled.
+    if config.rendering_path == RenderingPath::Deferred
+      && !matches!(config.msaa_config.samples, NumSamples::One)
+    {
+      warn!("MSAA is not supported with deferred rendering, falling back to one sample");
+      config.msaa_config.samples = NumSamples::One;
+    }
+    if let AntiAliasingMode::Supersampling { scale } = config.anti_aliasing {
+      if !scale.is_finite() || !(1f32..=4f32).contains(&scale) {
+        return Err(SarektError::UnsupportedAntiAliasing(
+          "supersampling scale must be between 1 and 4",
+        ));
+      }
+    }
+
     let vulkan_core = ManuallyDrop::new(VulkanCoreStructures::new(
       window.as_ref(),
       config.application_details,
@@ -151,6 +281,7 @@ impl VulkanRenderer {
       config.requested_width,
       config.requested_height,
       config.present_mode,
+      config.surface_format,
     )?;
     let render_targets = &render_target_bundle.render_targets;
 
@@ -179,6 +310,36 @@ impl VulkanRenderer {
       queues.graphics_queue,
     )?);
 
+    let scene_extent = Self::scene_extent(
+      &vulkan_core.instance,
+      physical_device,
+      config.anti_aliasing,
+      render_target_bundle.extent,
+    );
+    let post_process_chain =
+      if config.post_processing || config.anti_aliasing != AntiAliasingMode::None {
+        let scene_format = PostProcessChain::find_scene_format(
+          &vulkan_core.instance,
+          physical_device,
+          config.scene_color_format,
+        )?;
+        Some(PostProcessChain::new(
+          logical_device.clone(),
+          &shader_store,
+          &buffer_image_store,
+          scene_format,
+          render_target_bundle.get_render_target_format(),
+          render_target_bundle.get_render_target_color_space(),
+          config.anti_aliasing,
+          config.msaa_config.samples,
+          render_targets,
+          scene_extent,
+          render_target_bundle.extent,
+        )?)
+      } else {
+        None
+      };
+
     let pipeline = Pipelines::new(
       &config,
       &vulkan_core,
@@ -186,20 +347,76 @@ impl VulkanRenderer {
       &render_target_bundle,
       &shader_store,
       &buffer_image_store,
+      post_process_chain
+        .as_ref()
+        .map(PostProcessChain::scene_target),
+      scene_extent,
     )?;
     let framebuffers = &pipeline.framebuffers;
 
+    // Temporal anti-aliasing reads the depth buffer.
+    if config.anti_aliasing == AntiAliasingMode::Temporal {
+      let depth_format = pipeline.get_depth_attachment().format;
+      let depth_sampled = unsafe {
+        vulkan_core
+          .instance
+          .get_physical_device_format_properties(physical_device, depth_format)
+          .optimal_tiling_features
+          .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
+      };
+      if !depth_sampled {
+        return Err(SarektError::UnsupportedAntiAliasing(
+          "temporal anti-aliasing needs a depth format that can be sampled",
+        ));
+      }
+    }
+
     let primary_gfx_command_buffers =
       Self::create_main_gfx_command_buffers(&logical_device, main_gfx_command_pool, framebuffers)?;
 
     let draw_synchronization =
       DrawSynchronization::new(logical_device.clone(), render_targets.len())?;
 
-    let main_descriptor_pools = Self::create_main_descriptor_pools(
+    let render_texture_passes =
+      RenderTexturePasses::new(logical_device.clone(), main_gfx_command_pool)?;
+
+    let deferred_pass = if pipeline.get_g_buffer().is_some() {
+      Some(DeferredPass::new(
+        logical_device.clone(),
+        main_gfx_command_pool,
+        &shader_store,
+        pipeline.forward_render_pass,
+        scene_extent,
+      )?)
+    } else {
+      None
+    };
+
+    let shadow_pass = ShadowPass::new(
       &vulkan_core.instance,
       physical_device,
-      &logical_device,
-      &render_targets,
+      logical_device.clone(),
+      main_gfx_command_pool,
+      &buffer_image_store,
+      &shader_store,
+      config.shadow_map_size,
+    )?;
+
+    let main_descriptor_pools =
+      Self::create_main_descriptor_pools(&vulkan_core.instance, physical_device, &logical_device)?;
+
+    let min_uniform_buffer_offset_alignment = unsafe {
+      vulkan_core
+        .instance
+        .get_physical_device_properties(physical_device)
+        .limits
+        .min_uniform_buffer_offset_alignment
+    };
+    let uniform_ring = UniformRing::new(
+      &buffer_image_store,
+      allocator.clone(),
+      config.transient_uniform_buffer_size,
+      min_uniform_buffer_offset_alignment,
     )?;
 
     let mut renderer = Self {
@@ -215,8 +432,16 @@ impl VulkanRenderer {
       frame_count: Cell::new(0),
       current_frame_num: Cell::new(0),
       next_image_index: Cell::new(0),
+      bound_pipeline: Cell::new(vk::Pipeline::null()),
+      render_texture_passes,
+      shadow_pass,
+      post_process_chain,
+      deferred_pass,
+      scene_extent,
 
       main_descriptor_pools,
+      uniform_ring: Some(uniform_ring),
+      pending_uniform_writes: RefCell::new(Vec::new()),
 
       allocator,
       shader_store,
@@ -224,6 +449,17 @@ impl VulkanRenderer {
 
       // To be initialized.
       default_texture: None,
+      default_cubemap: None,
+
+      image_based_lighting: None,
+      brdf_lut: None,
+
+      skybox: None,
+      skybox_view_projection: uv::Mat4::identity(),
+
+      shadow_light: None,
+      shadow_camera: None,
+      shadow_uniform_offset: Cell::new(None),
 
       rendering_enabled: true,
 
@@ -252,6 +488,7 @@ impl VulkanRenderer {
     let physical_device = self.vulkan_device_structures.physical_device;
     let shader_store = &self.shader_store;
     let present_mode = self.config.present_mode;
+    let surface_format = self.config.surface_format;
 
     // Procedure: Wait for the device to be idle, make new Swapchain (recycling old
     // one), cleanup old resources and recreate them:
@@ -268,16 +505,39 @@ impl VulkanRenderer {
       width,
       height,
       present_mode,
+      surface_format,
     )?;
     self.cleanup_swapchain(Some((&old_images, old_swapchain)))?;
     let new_format = self.render_target_bundle.swapchain_and_extension.format;
-    let new_extent = self.render_target_bundle.extent;
+    let new_extent = Self::scene_extent(
+      instance,
+      physical_device,
+      self.config.anti_aliasing,
+      self.render_target_bundle.extent,
+    );
+    self.scene_extent = new_extent;
+
+    if let Some(post_process_chain) = &mut self.post_process_chain {
+      post_process_chain.recreate_swapchain_resources(
+        &self.buffer_image_store,
+        new_format,
+        self.render_target_bundle.get_render_target_color_space(),
+        &self.render_target_bundle.render_targets,
+        new_extent,
+        self.render_target_bundle.extent,
+      )?;
+    }
+    let scene_target = self
+      .post_process_chain
+      .as_ref()
+      .map(PostProcessChain::scene_target);
+    let color_format = scene_target.map_or(new_format, |scene_target| scene_target.format());
 
     let resolve_attachment = if !matches!(self.config.msaa_config.samples, NumSamples::One) {
       Some(ResolveAttachment::new(
         &self.buffer_image_store,
-        (width, height),
-        new_format.try_into()?,
+        (new_extent.width, new_extent.height),
+        color_format.try_into()?,
         self.config.msaa_config.samples,
       )?)
     } else {
@@ -288,14 +548,26 @@ impl VulkanRenderer {
       &instance,
       physical_device,
       &self.buffer_image_store,
-      (width, height),
+      (new_extent.width, new_extent.height),
       self.config.msaa_config.samples,
+      self.config.depth_format,
     )?;
 
+    let g_buffer = if self.deferred_pass.is_some() {
+      Some(GBufferAttachments::new(
+        &self.buffer_image_store,
+        (new_extent.width, new_extent.height),
+      )?)
+    } else {
+      None
+    };
+
     self.pipelines.recreate_renderpasses(
       logical_device,
-      new_format,
+      color_format,
       self.config.msaa_config.samples,
+      scene_target.is_some(),
+      self.config.anti_aliasing == AntiAliasingMode::Temporal,
     )?;
 
     let (vertex_shader_handle, fragment_shader_handle, descriptor_set_layouts) =
@@ -305,7 +577,9 @@ impl VulkanRenderer {
       logical_device,
       resolve_attachment.as_ref(),
       &depth_buffer,
+      g_buffer,
       &self.render_target_bundle.render_targets,
+      scene_target,
       new_extent,
     )?;
 
@@ -321,13 +595,24 @@ impl VulkanRenderer {
       fragment_shader_handle.unwrap(),
     )?;
 
-    self.main_descriptor_pools = Self::create_main_descriptor_pools(
-      instance,
-      physical_device,
+    self.pipelines.recreate_skybox_pipeline(
       logical_device,
-      &self.render_target_bundle.render_targets,
+      shader_store,
+      new_extent,
+      &self.config.msaa_config,
     )?;
 
+    if let Some(deferred_pass) = &mut self.deferred_pass {
+      deferred_pass.recreate_pipelines(
+        shader_store,
+        self.pipelines.forward_render_pass,
+        new_extent,
+      )?;
+    }
+
+    self.main_descriptor_pools =
+      Self::create_main_descriptor_pools(instance, physical_device, logical_device)?;
+
     // Reset render_frame_count
     self.current_frame_num.set(0);
 
@@ -336,6 +621,8 @@ impl VulkanRenderer {
       self.main_gfx_command_pool,
       vk::CommandPoolResetFlags::empty(),
     )?;
+    self.render_texture_passes.reset();
+    self.shadow_pass.reset();
     self.draw_synchronization.recreate_semaphores()?;
     self.setup_next_main_command_buffer()?;
 
@@ -365,6 +652,12 @@ impl VulkanRenderer {
     }
 
     self.pipelines.cleanup(logical_device);
+    if let Some(deferred_pass) = &self.deferred_pass {
+      deferred_pass.cleanup_pipelines();
+    }
+    if let Some(post_process_chain) = &self.post_process_chain {
+      post_process_chain.cleanup_swapchain_resources();
+    }
 
     let (images, swapchain) = old_swapchain_bundle.unwrap_or((
       self.render_target_bundle.render_targets.as_slice(),
@@ -460,10 +753,10 @@ impl VulkanRenderer {
     // TODO(issue#1) MULTITHREADING all things that were only main thread, do for
     // all renderers, too.
     let logical_device = &self.vulkan_device_structures.logical_device;
-    let descriptor_pool = self.main_descriptor_pools[image_index as usize];
+    let descriptor_pool = self.main_descriptor_pools[current_frame_num];
     let command_buffer = self.primary_gfx_command_buffers[image_index as usize];
     let framebuffer = self.pipelines.get_framebuffer(image_index as usize);
-    let extent = self.render_target_bundle.extent;
+    let extent = self.scene_extent;
     // TODO(issue#2) PIPELINES when multiple render pass types are supported use the
     // *selected* one.
     let render_pass = self.pipelines.forward_render_pass;
@@ -480,6 +773,17 @@ impl VulkanRenderer {
       }
     }
 
+    // The descriptor pool and uniforms are per frame in flight, so the last
+    // frame to use them must be done as well.
+    self
+      .draw_synchronization
+      .wait_for_frame_fence(current_frame_num)?;
+    self.apply_pending_uniform_writes(current_frame_num)?;
+    self.uniform_ring().start_frame(current_frame_num);
+    self.render_texture_passes.start_frame(current_frame_num);
+    self.shadow_pass.start_frame(current_frame_num);
+    self.shadow_uniform_offset.set(None);
+
     unsafe {
       // TODO(issue#10) PERFORMANCE cache descriptor sets: https://github.com/KhronosGroup/Vulkan-Samples/blob/master/samples/performance/descriptor_management/descriptor_management_tutorial.md
       logical_device
@@ -511,7 +815,14 @@ impl VulkanRenderer {
           stencil: 0u32,
         },
       };
-      let clear_values = [clear_color_value, clear_depth_value];
+      // The deferred render pass also clears the G-buffer.
+      let mut clear_values = vec![clear_color_value, clear_depth_value];
+      if self.deferred_pass.is_some() {
+        let clear_g_buffer_value = vk::ClearValue {
+          color: vk::ClearColorValue { float32: [0f32; 4] },
+        };
+        clear_values.extend_from_slice(&[clear_g_buffer_value; 3]);
+      }
       let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
         .render_pass(render_pass)
         .framebuffer(framebuffer)
@@ -524,14 +835,31 @@ impl VulkanRenderer {
         &render_pass_begin_info,
         vk::SubpassContents::INLINE,
       );
-
-      // Bind the pipeline. Can be overridden in secondary buffer by the user.
-      // TODO(issue#1) MULTITHREADING we can keep track in each thread's
-      // command buffer waht pipeline is bound so we don't insert extra rebind
-      // commands.
-      logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline)
     };
 
+    match &self.deferred_pass {
+      // Nothing is bound in the G-buffer subpass until an object is drawn in
+      // it, the base pipeline is for the forward subpass.
+      Some(deferred_pass) => {
+        deferred_pass.start_frame(current_frame_num, render_pass, framebuffer)?;
+        self.bound_pipeline.set(vk::Pipeline::null());
+      }
+      None => {
+        // Bind the pipeline. Can be overridden in secondary buffer by the user.
+        // TODO(issue#1) MULTITHREADING we can keep track in each thread's
+        // command buffer waht pipeline is bound so we don't insert extra rebind
+        // commands.
+        unsafe {
+          logical_device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline,
+          )
+        };
+        self.bound_pipeline.set(pipeline);
+      }
+    }
+
     // Save image index for frame presentation.
     self.next_image_index.set(image_index as usize);
 
@@ -542,7 +870,6 @@ impl VulkanRenderer {
 
   fn create_main_descriptor_pools(
     instance: &Instance, physical_device: vk::PhysicalDevice, logical_device: &Device,
-    render_targets: &[ImageAndView],
   ) -> SarektResult<Vec<vk::DescriptorPool>> {
     // TODO(issue#1) MULTITHREADING one per per frame per thread.
 
@@ -555,16 +882,23 @@ impl VulkanRenderer {
     let max_combined_image_samplers = physical_device_properties
       .limits
       .max_descriptor_set_samplers;
+    let max_input_attachments = physical_device_properties
+      .limits
+      .max_descriptor_set_input_attachments;
 
     let pool_sizes = [
       vk::DescriptorPoolSize::builder()
-        .ty(vk::DescriptorType::UNIFORM_BUFFER)
+        .ty(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
         .descriptor_count(max_uniform_buffers)
         .build(),
       vk::DescriptorPoolSize::builder()
         .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
         .descriptor_count(max_combined_image_samplers)
         .build(),
+      vk::DescriptorPoolSize::builder()
+        .ty(vk::DescriptorType::INPUT_ATTACHMENT)
+        .descriptor_count(max_input_attachments)
+        .build(),
     ];
 
     info!("Creating descriptor pool with sizes: {:?}", pool_sizes);
@@ -581,8 +915,8 @@ impl VulkanRenderer {
       descriptor_pool_ci
     );
 
-    let mut main_descriptor_pools = Vec::with_capacity(render_targets.len());
-    for _ in 0..render_targets.len() {
+    let mut main_descriptor_pools = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
+    for _ in 0..MAX_FRAMES_IN_FLIGHT {
       unsafe {
         main_descriptor_pools
           .push(logical_device.create_descriptor_pool(&descriptor_pool_ci, None)?);
@@ -645,15 +979,16 @@ impl VulkanRenderer {
   // ================================================================================
   //  Draw Helper Methods
   // ================================================================================
-  fn draw_vertices_cmd<UniformBufElem: Sized + Copy>(
-    &self, object: &DrawableObject<Self, UniformBufElem>, command_buffer: vk::CommandBuffer,
+  fn draw_vertices_cmd(
+    &self, vertex_buffer: ResourceWithMemory, index_buffer: Option<ResourceWithMemory>,
+    command_buffer: vk::CommandBuffer,
   ) -> SarektResult<()> {
     let logical_device = &self.vulkan_device_structures.logical_device;
 
     unsafe {
       // Draw vertices.
-      let vertex_buffers = [object.vertex_buffer.buffer()?.buffer];
-      let vertex_buffer_length = object.vertex_buffer.buffer()?.length;
+      let vertex_buffers = [vertex_buffer.buffer()?.buffer];
+      let vertex_buffer_length = vertex_buffer.buffer()?.length;
       let offsets = [0];
       logical_device.cmd_bind_vertex_buffers(
         command_buffer,
@@ -662,12 +997,12 @@ impl VulkanRenderer {
         &offsets, // There may be offset into memory, but not into the buffer.
       );
 
-      if object.index_buffer.is_none() {
+      if index_buffer.is_none() {
         // Non indexed draw.
         logical_device.cmd_draw(command_buffer, vertex_buffer_length, 1, 0, 0);
       } else {
         // Indexed Draw.
-        let index_buffer = &object.index_buffer.unwrap().buffer()?;
+        let index_buffer = &index_buffer.unwrap().buffer()?;
         let index_buffer_element_size = match index_buffer.index_buffer_elem_size.unwrap() {
           IndexBufferElemSize::UInt16 => vk::IndexType::UINT16,
           IndexBufferElemSize::UInt32 => vk::IndexType::UINT32,
@@ -691,9 +1026,12 @@ impl VulkanRenderer {
     Ok(())
   }
 
+  /// Binds the uniform at `dynamic_offset` in `uniform_buffer` and the
+  /// textures, in order to the layout's texture bindings.
   fn bind_descriptor_sets<DescriptorLayoutStruct>(
-    &self, uniform_buffer: vk::Buffer, texture_image: &Option<ImageAndMemory>,
-    descriptor_pool: vk::DescriptorPool, command_buffer: vk::CommandBuffer,
+    &self, uniform_buffer: vk::Buffer, dynamic_offset: u32,
+    texture_images: &[Option<ImageAndMemory>], descriptor_pool: vk::DescriptorPool,
+    command_buffer: vk::CommandBuffer,
   ) -> SarektResult<()>
   where
     DescriptorLayoutStruct: Sized + Copy + DescriptorLayoutInfo,
@@ -703,7 +1041,8 @@ impl VulkanRenderer {
     // First allocate descriptor sets.
     // TODO(issue#2) PIPELINES pass pipeline layout of the pipeline that is running
     // now.
-    let layouts = self.pipelines.get_pipeline_descriptor_layouts();
+    let variant = DescriptorLayoutStruct::forward_shader_variant();
+    let layouts = self.pipelines.get_pipeline_descriptor_layouts(variant);
     let alloc_info = vk::DescriptorSetAllocateInfo::builder()
       .descriptor_pool(descriptor_pool)
       .set_layouts(&layouts) // Sets descriptor set count.
@@ -719,35 +1058,26 @@ impl VulkanRenderer {
       .range(bind_uniform_info.range as vk::DeviceSize)
       .build()];
 
-    // TODO(issue#11) LIGHTING TEXTURES SHADERS when there is more than one texture
-    // allowed fill a vec with null textures for all unused textures in drawable
-    // objects, which will now be a option vec.
-
-    // Either load the texture in the drawable object or use a transparent null
-    // texture.
+    // Either load the texture in the drawable object's slot or use a
+    // transparent null texture.
     let bind_texture_info = DescriptorLayoutStruct::get_bind_texture_info()?;
-    let image_infos = vec![match texture_image {
-      Option::Some(image_and_memory) => vk::DescriptorImageInfo::builder()
-        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-        .image_view(image_and_memory.image_and_view.view)
-        .sampler(image_and_memory.sampler.unwrap())
-        .build(),
-      None => {
-        let default_texture = self
-          .default_texture
-          .as_ref()
-          .unwrap()
-          .1
-          .handle
-          .image()
-          .unwrap();
-        vk::DescriptorImageInfo::builder()
-          .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-          .sampler(default_texture.sampler.unwrap())
-          .image_view(default_texture.image_and_view.view)
-          .build()
-      }
-    }];
+    let default_texture = self
+      .default_texture
+      .as_ref()
+      .unwrap()
+      .1
+      .handle
+      .image()
+      .unwrap();
+    let image_infos: Vec<[vk::DescriptorImageInfo; 1]> = (0..bind_texture_info.bindings.len())
+      .map(|slot| {
+        let image = texture_images
+          .get(slot)
+          .and_then(|image| image.as_ref())
+          .unwrap_or(&default_texture);
+        [sampled_image_info(image)]
+      })
+      .collect();
 
     // Create descriptor writes for uniforms.
     let uniform_descriptor_writes = bind_uniform_info.bindings.iter().map(|&binding| {
@@ -755,28 +1085,98 @@ impl VulkanRenderer {
       .dst_set(descriptor_sets[0])
       .dst_binding(binding) // corresponds to binding in layout.
       .dst_array_element(0) // We're not using an array yet, just one MVP so index is 0.
-      .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+      .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
       .buffer_info(&uniform_buffer_infos)
       // No image infos or texel buffer views because this is a buffer.
       .build()
     });
 
     // Create and append descriptor writes for textures.
-    let texture_descriptor_writes = bind_texture_info.bindings.iter().map(|&binding| {
-      vk::WriteDescriptorSet::builder()
-        .dst_set(descriptor_sets[0])
-        .dst_binding(binding)
-        .dst_array_element(0)
-        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-        .image_info(&image_infos)
-        .build()
-    });
+    let texture_descriptor_writes = bind_texture_info
+      .bindings
+      .iter()
+      .zip(image_infos.iter())
+      .map(|(&binding, image_info)| {
+        vk::WriteDescriptorSet::builder()
+          .dst_set(descriptor_sets[0])
+          .dst_binding(binding)
+          .dst_array_element(0)
+          .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+          .image_info(image_info)
+          .build()
+      });
+
+    // Every uniform binding is dynamic and reads the same data.
+    let mut dynamic_offsets = vec![dynamic_offset; bind_uniform_info.bindings.len()];
 
     let mut descriptor_writes =
-      Vec::with_capacity(uniform_descriptor_writes.len() + texture_descriptor_writes.len());
+      Vec::with_capacity(uniform_descriptor_writes.len() + texture_descriptor_writes.len() + 3);
     descriptor_writes.extend(uniform_descriptor_writes);
     descriptor_writes.extend(texture_descriptor_writes);
 
+    // The shadow receiving shaders also read the shadow map and the light's
+    // matrices, which are the same for every draw this frame.
+    let shadow_map = self.shadow_pass.shadow_map();
+    let shadow_map_infos = [vk::DescriptorImageInfo::builder()
+      .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+      .image_view(shadow_map.image_and_view.view)
+      .sampler(shadow_map.sampler.unwrap())
+      .build()];
+    let shadow_uniform_infos = [vk::DescriptorBufferInfo::builder()
+      .buffer(self.uniform_ring().buffer())
+      .offset(0)
+      .range(std::mem::size_of::<ShadowUniform>() as vk::DeviceSize)
+      .build()];
+    if variant == ForwardShaderVariant::ShadowReceiving {
+      descriptor_writes.push(
+        vk::WriteDescriptorSet::builder()
+          .dst_set(descriptor_sets[0])
+          .dst_binding(SHADOW_MAP_BINDING)
+          .dst_array_element(0)
+          .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+          .image_info(&shadow_map_infos)
+          .build(),
+      );
+      descriptor_writes.push(
+        vk::WriteDescriptorSet::builder()
+          .dst_set(descriptor_sets[0])
+          .dst_binding(SHADOW_UNIFORM_BINDING)
+          .dst_array_element(0)
+          .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+          .buffer_info(&shadow_uniform_infos)
+          .build(),
+      );
+      dynamic_offsets.push(self.shadow_uniform_offset()?);
+    }
+
+    // The PBR shaders also read the environment maps, or black ones when there
+    // is no environment so only lights and emission shade.
+    let default_cubemap = &self.default_cubemap.as_ref().unwrap().1;
+    let (irradiance, prefiltered, lut) = match &self.image_based_lighting {
+      Some(ibl) => (
+        &ibl.irradiance.1,
+        &ibl.prefiltered.1,
+        &self.brdf_lut.as_ref().unwrap().1,
+      ),
+      None => (default_cubemap, default_cubemap, &default_texture),
+    };
+    let environment_infos = [
+      (PBR_IRRADIANCE_BINDING, [sampled_image_info(irradiance)]),
+      (PBR_PREFILTERED_BINDING, [sampled_image_info(prefiltered)]),
+      (PBR_BRDF_LUT_BINDING, [sampled_image_info(lut)]),
+    ];
+    if variant == ForwardShaderVariant::Pbr {
+      descriptor_writes.extend(environment_infos.iter().map(|(binding, image_info)| {
+        vk::WriteDescriptorSet::builder()
+          .dst_set(descriptor_sets[0])
+          .dst_binding(*binding)
+          .dst_array_element(0)
+          .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+          .image_info(image_info)
+          .build()
+      }));
+    }
+
     unsafe {
       logical_device.update_descriptor_sets(&descriptor_writes, &[]); // No descriptor copies.
 
@@ -785,10 +1185,10 @@ impl VulkanRenderer {
       logical_device.cmd_bind_descriptor_sets(
         command_buffer,
         vk::PipelineBindPoint::GRAPHICS,
-        self.pipelines.get_pipeline_layout(),
+        self.pipelines.get_pipeline_layout(variant),
         0,
         &descriptor_sets,
-        &[], // No dynamic offsets.
+        &dynamic_offsets,
       );
     }
 
@@ -817,11 +1217,155 @@ impl VulkanRenderer {
     .unwrap();
 
     self.default_texture = Some(image_and_handle);
+
+    let black = || Monocolor::new(0, 0, 0, 255);
+    let faces =
+      Cubemap::from_faces([black(), black(), black(), black(), black(), black()]).unwrap();
+    let (handle, cubemap) = BufferImageStore::load_cubemap_with_staging_initialization(
+      &self.buffer_image_store,
+      faces,
+      MagnificationMinificationFilter::Nearest,
+      MagnificationMinificationFilter::Nearest,
+      1,
+    )
+    .unwrap();
+    self.default_cubemap = Some((handle, cubemap.handle.image().unwrap()));
   }
 
   // ================================================================================
   //  Renderer Utility Methods
   // ================================================================================
+  /// The swapchain's extent scaled for supersampling, as far as the device's
+  /// framebuffers allow.
+  fn scene_extent(
+    instance: &Instance, physical_device: vk::PhysicalDevice, anti_aliasing: AntiAliasingMode,
+    extent: vk::Extent2D,
+  ) -> vk::Extent2D {
+    let scale = match anti_aliasing {
+      AntiAliasingMode::Supersampling { scale } => scale,
+      _ => return extent,
+    };
+
+    let limits = unsafe {
+      instance
+        .get_physical_device_properties(physical_device)
+        .limits
+    };
+    let max_width = limits
+      .max_framebuffer_width
+      .min(limits.max_image_dimension2_d);
+    let max_height = limits
+      .max_framebuffer_height
+      .min(limits.max_image_dimension2_d);
+    let scene_extent = vk::Extent2D {
+      width: ((extent.width as f32 * scale) as u32).min(max_width),
+      height: ((extent.height as f32 * scale) as u32).min(max_height),
+    };
+    if scene_extent.width < (extent.width as f32 * scale) as u32
+      || scene_extent.height < (extent.height as f32 * scale) as u32
+    {
+      warn!(
+        "Supersampling limited to {:?} by the device's framebuffer size",
+        scene_extent
+      );
+    }
+    scene_extent
+  }
+
+  /// Destroys the buffers, images and shaders dropped during frames the GPU
+  /// has finished, which are at least `MAX_FRAMES_IN_FLIGHT` frames before the
+  /// one being recorded.
+  fn destroy_dropped_resources(&self) -> SarektResult<()> {
+    let frame = self.frame_count.get();
+    let completed_frame = deletion_queue::completed_frame(frame, MAX_FRAMES_IN_FLIGHT);
+
+    // Shaders are still released if buffers or images fail to be.
+    let buffers_and_images = self
+      .buffer_image_store
+      .write()
+      .expect("Panic occured can't write to buffer store")
+      .start_frame(frame, completed_frame);
+    let shaders = self
+      .shader_store
+      .write()
+      .expect("Panic occured can't write to shader store")
+      .start_frame(frame, completed_frame);
+    buffers_and_images.and(shaders)
+  }
+
+  /// Offset of this frame's `ShadowUniform` in the uniform ring, writing it
+  /// the first time it is needed.
+  fn shadow_uniform_offset(&self) -> SarektResult<u32> {
+    if let Some(offset) = self.shadow_uniform_offset.get() {
+      return Ok(offset);
+    }
+
+    let offset = self
+      .uniform_ring()
+      .allocate(&self.shadow_pass.shadow_uniform())?;
+    self.shadow_uniform_offset.set(Some(offset));
+    Ok(offset)
+  }
+
+  /// Fits the shadow pass's cascades to the current light and camera.
+  fn update_shadow_cascades(&mut self) -> SarektResult<()> {
+    let cascades = match &self.shadow_light {
+      Some(light) => ShadowCascades::new(
+        light,
+        self.shadow_camera.as_ref(),
+        self.shadow_pass.tile_size(),
+      )?,
+      None => None,
+    };
+    let camera_view = self
+      .shadow_camera
+      .map_or(uv::Mat4::identity(), |camera| camera.view);
+
+    self.shadow_pass.set_cascades(cascades, camera_view);
+    self.shadow_uniform_offset.set(None);
+    Ok(())
+  }
+
+  fn uniform_ring(&self) -> &UniformRing {
+    self
+      .uniform_ring
+      .as_ref()
+      .expect("Transient uniform buffer is only taken when dropping the renderer")
+  }
+
+  /// Makes the `set_uniform_bytes` writes to this frame's uniform copies,
+  /// which the GPU is done with once the frame's fence is waited on.
+  fn apply_pending_uniform_writes(&self, current_frame_num: usize) -> SarektResult<()> {
+    let mut pending_uniform_writes = self.pending_uniform_writes.borrow_mut();
+    for write in pending_uniform_writes.iter_mut() {
+      if let Some(copy) = write.copies[current_frame_num].take() {
+        self.write_uniform_bytes(&copy, write.byte_offset, &write.bytes)?;
+      }
+    }
+    pending_uniform_writes.retain(|write| write.copies.iter().any(Option::is_some));
+
+    Ok(())
+  }
+
+  fn write_uniform_bytes(
+    &self, uniform_buffer: &BufferAndMemoryMapped, byte_offset: usize, bytes: &[u8],
+  ) -> SarektResult<()> {
+    unsafe {
+      uniform_buffer
+        .ptr
+        .add(byte_offset)
+        .copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+    }
+    // Memory isn't necessarily host coherent.
+    self.allocator.flush_allocation(
+      &uniform_buffer.buffer_and_memory.allocation,
+      byte_offset,
+      bytes.len(),
+    )?;
+
+    Ok(())
+  }
+
   fn increment_frame_count(&self) {
     self.frame_count.set(self.frame_count.get() + 1u64);
     self
@@ -856,14 +1400,60 @@ impl Renderer for VulkanRenderer {
 
     let image_index = self.next_image_index.get();
     let current_command_buffer = self.primary_gfx_command_buffers[image_index as usize];
+
+    // The deferred render pass records forward draws, the skybox included, in
+    // their own command buffer executed after the lights.
+    let forward_command_buffer = self
+      .deferred_pass
+      .as_ref()
+      .map_or(current_command_buffer, DeferredPass::command_buffer);
+
+    // The skybox goes last so the depth test leaves it only where nothing else
+    // was drawn.
+    if let Some((_, cubemap)) = &self.skybox {
+      self.pipelines.skybox_pipeline.draw_cmd(
+        logical_device,
+        forward_command_buffer,
+        self.main_descriptor_pools[current_frame_num],
+        cubemap,
+        &self.skybox_view_projection,
+      )?;
+    }
+
+    if let Some(deferred_pass) = &self.deferred_pass {
+      deferred_pass.draw_lighting_cmd(
+        current_command_buffer,
+        self.main_descriptor_pools[current_frame_num],
+        self.uniform_ring(),
+        self.pipelines.get_g_buffer().unwrap(),
+        self.pipelines.get_depth_attachment(),
+      )?;
+    }
+
     unsafe {
       // End Render Pass.
       logical_device.cmd_end_render_pass(current_command_buffer);
+    }
 
+    if let Some(post_process_chain) = &self.post_process_chain {
+      post_process_chain.draw_cmd(
+        &self.shader_store,
+        &self.buffer_image_store,
+        current_command_buffer,
+        self.main_descriptor_pools[current_frame_num],
+        image_index,
+        &self.pipelines.get_depth_attachment().image_and_memory,
+      )?;
+    }
+
+    unsafe {
       // Finish recording on all command buffers.
       // TODO(issue#1) MULTITHREADING all of them not just main.
       logical_device.end_command_buffer(current_command_buffer)?;
     }
+    let shadow_command_buffer = self.shadow_pass.finish()?;
+    let render_texture_command_buffer = self.render_texture_passes.finish()?;
+    self.uniform_ring().flush()?;
 
     // Wait for max images in flight.
     let frame_fence = self
@@ -884,7 +1474,21 @@ impl Renderer for VulkanRenderer {
       .command_buffers(&command_buffers) // Only use the command buffer corresponding to this image index.
       .signal_semaphores(&signal_semaphores) // Signal we're done drawing when we are.
       .build();
-    unsafe { logical_device.queue_submit(queues.graphics_queue, &[submit_info], frame_fence)? };
+    // The shadow map and render textures go first, their render passes make
+    // later sampling wait for them.  They don't need the swapchain image.
+    let offscreen_command_buffers: Vec<_> = shadow_command_buffer
+      .into_iter()
+      .chain(render_texture_command_buffer)
+      .collect();
+    let offscreen_submit_info = vk::SubmitInfo::builder()
+      .command_buffers(&offscreen_command_buffers)
+      .build();
+    let submit_infos = if offscreen_command_buffers.is_empty() {
+      vec![submit_info]
+    } else {
+      vec![offscreen_submit_info, submit_info]
+    };
+    unsafe { logical_device.queue_submit(queues.graphics_queue, &submit_infos, frame_fence)? };
 
     // TODO(issue#1) OFFSCREEN only if presenting to swapchain.
     // Present to swapchain and display completed frame.
@@ -898,6 +1502,10 @@ impl Renderer for VulkanRenderer {
     // Increment frames rendered count.
     self.increment_frame_count();
 
+    // Waiting on this frame's fence above means the frame that last used it is
+    // done, so whatever was dropped up to then can be destroyed.
+    self.destroy_dropped_resources()?;
+
     // Set up the next frame for drawing. Will wait on fence.
     self.setup_next_main_command_buffer()?;
 
@@ -920,6 +1528,61 @@ impl Renderer for VulkanRenderer {
     Ok(BufferImageStore::load_buffer_with_staging(&self.buffer_image_store, buffer_type, buffer)?.0)
   }
 
+  fn load_dynamic_buffer<BufElem: Sized + Copy>(
+    &mut self, buffer_type: BufferType, buffer: &[BufElem],
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    if matches!(buffer_type, BufferType::Uniform) {
+      return Err(SarektError::IncorrectLoaderFunction);
+    }
+
+    Ok(
+      BufferImageStore::load_buffer_without_staging(&self.buffer_image_store, buffer_type, buffer)?
+        .0,
+    )
+  }
+
+  fn submit_upload_batch(
+    &mut self, batch: UploadBatch,
+  ) -> SarektResult<(
+    Vec<BufferImageHandle<VulkanBufferImageFunctions>>,
+    UploadToken,
+  )> {
+    let (handles, token) = BufferImageStore::submit_upload_batch(&self.buffer_image_store, batch)?;
+    Ok((
+      handles.into_iter().map(|(handle, _)| handle).collect(),
+      token,
+    ))
+  }
+
+  fn is_upload_complete(&self, token: UploadToken) -> SarektResult<bool> {
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .is_upload_complete(token)
+  }
+
+  fn wait_for_upload(&self, token: UploadToken) -> SarektResult<()> {
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .wait_for_upload(token)
+  }
+
+  fn update_buffer<BufElem: Sized + Copy>(
+    &self, handle: &BufferImageHandle<VulkanBufferImageFunctions>, offset: usize,
+    buffer: &[BufElem],
+  ) -> SarektResult<()> {
+    // Frames in flight may still be drawing from the buffer.
+    self.draw_synchronization.wait_for_all_frames()?;
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .update_buffer(handle, offset, buffer)
+  }
+
   fn load_image_with_staging_initialization(
     &mut self, pixels: impl ImageData, magnification_filter: MagnificationMinificationFilter,
     minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
@@ -940,6 +1603,290 @@ impl Renderer for VulkanRenderer {
     )
   }
 
+  fn load_mipmapped_image_with_staging_initialization(
+    &mut self, pixels: impl MipmappedImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode,
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    Ok(
+      BufferImageStore::load_mipmapped_image_with_staging_initialization(
+        &self.buffer_image_store,
+        pixels,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        address_z,
+      )?
+      .0,
+    )
+  }
+
+  fn load_cubemap_with_staging_initialization(
+    &mut self, faces: impl CubemapImageData, magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    Ok(
+      BufferImageStore::load_cubemap_with_staging_initialization(
+        &self.buffer_image_store,
+        faces,
+        magnification_filter,
+        minification_filter,
+        mip_levels,
+      )?
+      .0,
+    )
+  }
+
+  fn load_mipmapped_cubemap_with_staging_initialization(
+    &mut self, faces: impl MipmappedCubemapImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter,
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    Ok(
+      BufferImageStore::load_mipmapped_cubemap_with_staging_initialization(
+        &self.buffer_image_store,
+        faces,
+        magnification_filter,
+        minification_filter,
+      )?
+      .0,
+    )
+  }
+
+  fn load_image_array_with_staging_initialization(
+    &mut self, layers: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    Ok(
+      BufferImageStore::load_image_array_with_staging_initialization(
+        &self.buffer_image_store,
+        layers,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        mip_levels,
+      )?
+      .0,
+    )
+  }
+
+  fn load_volume_image_with_staging_initialization(
+    &mut self, slices: impl LayeredImageData,
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode, address_z: TextureAddressMode, mip_levels: u32,
+  ) -> SarektResult<BufferImageHandle<VulkanBufferImageFunctions>> {
+    Ok(
+      BufferImageStore::load_volume_image_with_staging_initialization(
+        &self.buffer_image_store,
+        slices,
+        magnification_filter,
+        minification_filter,
+        address_x,
+        address_y,
+        address_z,
+        mip_levels,
+      )?
+      .0,
+    )
+  }
+
+  fn update_image(
+    &self, handle: &BufferImageHandle<VulkanBufferImageFunctions>, region: ImageRegion,
+    pixels: impl ImageData, regenerate_mipmaps: bool,
+  ) -> SarektResult<()> {
+    // Frames in flight may still be sampling the image.
+    self.draw_synchronization.wait_for_all_frames()?;
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .update_image(handle, region, pixels, regenerate_mipmaps)
+  }
+
+  fn load_render_texture(
+    &mut self, dimensions: (u32, u32), format: ImageDataFormat, clear_color: [f32; 4],
+    magnification_filter: MagnificationMinificationFilter,
+    minification_filter: MagnificationMinificationFilter, address_x: TextureAddressMode,
+    address_y: TextureAddressMode,
+  ) -> SarektResult<RenderTextureHandle<VulkanBufferImageFunctions>> {
+    let (texture, _) = BufferImageStore::create_render_texture(
+      &self.buffer_image_store,
+      dimensions,
+      format,
+      magnification_filter,
+      minification_filter,
+      address_x,
+      address_y,
+    )?;
+    // Same format as the main depth buffer, which the render passes expect.
+    let depth_buffer = DepthAttachment::new(
+      &self.vulkan_core.instance,
+      self.vulkan_device_structures.physical_device,
+      &self.buffer_image_store,
+      dimensions,
+      NumSamples::One,
+      self.config.depth_format,
+    )?;
+
+    Ok(RenderTextureHandle::new(
+      texture,
+      depth_buffer.depth_buffer_image_handle,
+      dimensions,
+      clear_color,
+    ))
+  }
+
+  fn set_skybox(
+    &mut self, cubemap: Option<BufferImageHandle<VulkanBufferImageFunctions>>,
+  ) -> SarektResult<Option<BufferImageHandle<VulkanBufferImageFunctions>>> {
+    let new_skybox = match cubemap {
+      Some(handle) => {
+        let image = self.get_image(&handle)?.image()?;
+        Some((handle, image))
+      }
+      None => None,
+    };
+
+    // The previous cubemap may still be in use by frames in flight.
+    self.draw_synchronization.wait_for_all_frames()?;
+    let old_skybox = std::mem::replace(&mut self.skybox, new_skybox);
+    Ok(old_skybox.map(|(handle, _)| handle))
+  }
+
+  fn set_skybox_view_projection(&mut self, view: uv::Mat4, projection: uv::Mat4) {
+    let mut rotation_only = view;
+    rotation_only.cols[3] = uv::Vec4::new(0f32, 0f32, 0f32, 1f32);
+    self.skybox_view_projection = projection * rotation_only;
+  }
+
+  fn set_image_based_lighting(&mut self, environment: Option<EnvironmentMaps>) -> SarektResult<()> {
+    let new_image_based_lighting = match environment {
+      Some(environment) => {
+        if self.brdf_lut.is_none() {
+          info!("Generating BRDF lookup table...");
+          let (handle, lut) = BufferImageStore::load_image_with_staging_initialization(
+            &self.buffer_image_store,
+            brdf_lut(DEFAULT_BRDF_LUT_SIZE)?,
+            MagnificationMinificationFilter::Linear,
+            MagnificationMinificationFilter::Linear,
+            TextureAddressMode::ClampToEdge,
+            TextureAddressMode::ClampToEdge,
+            TextureAddressMode::ClampToEdge,
+            1,
+          )?;
+          self.brdf_lut = Some((handle, lut.handle.image()?));
+        }
+
+        let (irradiance, prefiltered) = environment.into_parts();
+        let (irradiance_handle, irradiance) =
+          BufferImageStore::load_cubemap_with_staging_initialization(
+            &self.buffer_image_store,
+            irradiance,
+            MagnificationMinificationFilter::Linear,
+            MagnificationMinificationFilter::Linear,
+            1,
+          )?;
+        let (prefiltered_handle, prefiltered) =
+          BufferImageStore::load_mipmapped_cubemap_with_staging_initialization(
+            &self.buffer_image_store,
+            prefiltered,
+            MagnificationMinificationFilter::Linear,
+            MagnificationMinificationFilter::Linear,
+          )?;
+        Some(ImageBasedLighting {
+          irradiance: (irradiance_handle, irradiance.handle.image()?),
+          prefiltered: (prefiltered_handle, prefiltered.handle.image()?),
+        })
+      }
+      None => None,
+    };
+
+    // The previous maps may still be in use by frames in flight.
+    self.draw_synchronization.wait_for_all_frames()?;
+    self.image_based_lighting = new_image_based_lighting;
+    Ok(())
+  }
+
+  fn set_post_process_chain(
+    &mut self, passes: Vec<VulkanPostProcessPass>,
+  ) -> SarektResult<Vec<VulkanPostProcessPass>> {
+    // The chain also exists for anti-aliasing alone.
+    if !self.config.post_processing {
+      return Err(SarektError::PostProcessingDisabled);
+    }
+    let post_process_chain = self
+      .post_process_chain
+      .as_mut()
+      .ok_or(SarektError::PostProcessingDisabled)?;
+
+    // The previous passes may still be in use by frames in flight.
+    self.draw_synchronization.wait_for_all_frames()?;
+    post_process_chain.set_passes(passes, &self.shader_store, &self.buffer_image_store)
+  }
+
+  fn jitter_projection(&self, projection: uv::Mat4) -> uv::Mat4 {
+    match &self.post_process_chain {
+      Some(post_process_chain) => {
+        post_process_chain.jitter_projection(projection, self.frame_count.get())
+      }
+      None => projection,
+    }
+  }
+
+  fn set_view_projection(&mut self, view: uv::Mat4, projection: uv::Mat4) {
+    if let Some(post_process_chain) = &mut self.post_process_chain {
+      post_process_chain.set_view_projection(projection * view);
+    }
+    if let Some(deferred_pass) = &mut self.deferred_pass {
+      deferred_pass.set_view_projection(view, projection);
+    }
+  }
+
+  fn set_scene_lights(&mut self, lights: SceneLights) -> SarektResult<()> {
+    let deferred_pass = self
+      .deferred_pass
+      .as_mut()
+      .ok_or(SarektError::DeferredRenderingDisabled)?;
+    lights.check_counts()?;
+    deferred_pass.set_scene_lights(&lights);
+    Ok(())
+  }
+
+  fn set_shadow_light(&mut self, light: Option<ShadowLight>) -> SarektResult<()> {
+    if let Some(light) = &light {
+      // Checks the light before replacing the current one.
+      ShadowCascades::new(light, None, self.shadow_pass.tile_size())?;
+    }
+
+    self.shadow_light = light;
+    self.update_shadow_cascades()
+  }
+
+  fn set_shadow_camera(
+    &mut self, view: uv::Mat4, projection: uv::Mat4, near: f32, far: f32,
+  ) -> SarektResult<()> {
+    if near <= 0f32 || far <= near {
+      return Err(SarektError::InvalidShadowLight(format!(
+        "camera near {} and far {} must be positive and increasing",
+        near, far
+      )));
+    }
+
+    self.shadow_camera = Some(ShadowCamera {
+      view,
+      projection,
+      near,
+      far,
+    });
+    self.update_shadow_cascades()
+  }
+
   fn get_buffer(
     &self, handle: &BufferImageHandle<VulkanBufferImageFunctions>,
   ) -> SarektResult<ResourceWithMemory> {
@@ -959,11 +1906,11 @@ impl Renderer for VulkanRenderer {
     &mut self, buffer: UniformBufElem,
   ) -> SarektResult<UniformBufferHandle<VulkanBufferImageFunctions, UniformBufElem>> {
     info!("Loading a uniform buffer...");
-    // Since each framebuffer may have different values for uniforms, they each need
-    // their own UB.  These are stored in the same ordering as the render target
-    // images.
-    let mut uniform_buffers = Vec::with_capacity(self.pipelines.framebuffers.len());
-    for _ in 0..self.pipelines.framebuffers.len() {
+
+    // Each frame in flight may have different values for uniforms, so they each
+    // need their own UB.  These are indexed by the frame in flight number.
+    let mut uniform_buffers = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
+    for _ in 0..MAX_FRAMES_IN_FLIGHT {
       // TODO(issue#13) PERFORMANCE EASY create a "locked" version of the loading
       // function so I don't have to keep reacquiring it.
       let (uniform_buffer_handle, _) = BufferImageStore::load_buffer_without_staging(
@@ -987,8 +1934,7 @@ impl Renderer for VulkanRenderer {
       .buffer_image_store
       .read()
       .expect("Panic occured can't read from buffer store");
-    let mut buffer_handles: Vec<BufferAndMemoryMapped> =
-      Vec::with_capacity(self.render_target_bundle.render_targets.len());
+    let mut buffer_handles: Vec<BufferAndMemoryMapped> = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT);
     for ubh in handle.uniform_buffer_backend_handle.iter() {
       let handle = store.get_buffer(ubh)?;
 
@@ -1016,17 +1962,54 @@ impl Renderer for VulkanRenderer {
   fn set_uniform<BufElem: Sized + Copy>(
     &self, handle_data: &Vec<BufferAndMemoryMapped>, data: &BufElem,
   ) -> SarektResult<()> {
-    self.draw_synchronization.wait_for_acquire_fence()?;
-
-    let next_image_index = self.next_image_index.get();
+    // Setting up the frame waited for the last frame to use this buffer.
+    let current_frame_num = self.current_frame_num.get();
     unsafe {
-      let ptr = handle_data[next_image_index].ptr as *mut BufElem;
-      ptr.copy_from(data, std::mem::size_of_val(data));
+      let ptr = handle_data[current_frame_num].ptr as *mut BufElem;
+      ptr.copy_from_nonoverlapping(data, 1);
     }
 
     Ok(())
   }
 
+  fn set_uniform_bytes(
+    &self, handle_data: &Vec<BufferAndMemoryMapped>, byte_offset: usize, bytes: &[u8],
+  ) -> SarektResult<()> {
+    let buffer_size = handle_data[0].buffer_and_memory.size;
+    let in_bounds = match byte_offset.checked_add(bytes.len()) {
+      Some(end) => end as vk::DeviceSize <= buffer_size,
+      None => false,
+    };
+    if !in_bounds {
+      return Err(SarektError::InvalidBufferUpdate(format!(
+        "{} bytes at offset {} runs past the end of the {} byte uniform buffer",
+        bytes.len(),
+        byte_offset,
+        buffer_size
+      )));
+    }
+
+    // The copy set_uniform writes is free now, the others may still be read by
+    // frames in flight so they are written when their frames start.
+    let current_frame_num = self.current_frame_num.get();
+    self.write_uniform_bytes(&handle_data[current_frame_num], byte_offset, bytes)?;
+    let copies = handle_data
+      .iter()
+      .enumerate()
+      .map(|(frame_num, &copy)| Some(copy).filter(|_| frame_num != current_frame_num))
+      .collect();
+    self
+      .pending_uniform_writes
+      .borrow_mut()
+      .push(PendingUniformWrite {
+        copies,
+        byte_offset,
+        bytes: bytes.to_vec(),
+      });
+
+    Ok(())
+  }
+
   fn recreate_swapchain(&mut self, width: u32, height: u32) -> SarektResult<()> {
     if width == 0 || height == 0 {
       // It violates the vulkan spec to make extents this small, rendering should be
@@ -1042,6 +2025,71 @@ impl Renderer for VulkanRenderer {
     unsafe { self.do_recreate_swapchain(width, height) }
   }
 
+  fn check_primitive_state_support(&self, primitive_state: &PrimitiveState) -> SarektResult<()> {
+    Pipelines::check_primitive_state(
+      &self.vulkan_core.instance,
+      self.vulkan_device_structures.physical_device,
+      primitive_state,
+    )
+  }
+
+  fn query_image_format_support(&self, format: ImageDataFormat) -> ImageFormatSupport {
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .query_image_format_support(format)
+  }
+
+  fn memory_stats(&self) -> SarektResult<MemoryStats> {
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .memory_stats()
+  }
+
+  fn memory_stats_json(&self, detailed: bool) -> SarektResult<String> {
+    self
+      .buffer_image_store
+      .read()
+      .expect("Panic occured can't read from buffer store")
+      .memory_stats_json(detailed)
+  }
+
+  fn defragment(&mut self) -> SarektResult<DefragmentationStats> {
+    // Frames in flight may still be using the buffers and images being moved.
+    self.draw_synchronization.wait_for_all_frames()?;
+
+    let mut store = self
+      .buffer_image_store
+      .write()
+      .expect("Panic occured can't write to buffer store");
+    // Some resources may have moved even if it fails, so the held images are
+    // refreshed either way.
+    let result = store.defragment();
+
+    // Refresh the images the renderer holds on to itself.
+    if let Some((handle, image)) = self.default_texture.as_mut() {
+      *image = *store.get_image(handle)?;
+    }
+    let held_images = self
+      .skybox
+      .iter_mut()
+      .chain(self.default_cubemap.iter_mut())
+      .chain(self.brdf_lut.iter_mut())
+      .chain(self.image_based_lighting.iter_mut().flat_map(|ibl| {
+        std::iter::once(&mut ibl.irradiance).chain(std::iter::once(&mut ibl.prefiltered))
+      }));
+    for (handle, image) in held_images {
+      *image = store.get_image(handle)?.handle.image()?;
+    }
+
+    let stats = result?;
+    info!("Defragmented memory: {:?}", stats);
+    Ok(stats)
+  }
+
   fn get_image(
     &self, handle: &BufferImageHandle<VulkanBufferImageFunctions>,
   ) -> SarektResult<ResourceWithMemory> {
@@ -1078,29 +2126,296 @@ impl Drawer for VulkanRenderer {
       return Ok(());
     }
 
+    let uniform_buffers = object
+      .uniform_buffer
+      .as_ref()
+      .ok_or(SarektError::NoUniformBuffer)?;
+    let current_uniform_buffer = uniform_buffers[self.current_frame_num.get()]
+      .buffer_and_memory
+      .buffer;
+
+    self.draw_object(object, current_uniform_buffer, 0)
+  }
+
+  fn draw_with_uniform<DescriptorLayoutStruct>(
+    &self, object: &DrawableObject<Self, DescriptorLayoutStruct>, uniform: &DescriptorLayoutStruct,
+  ) -> SarektResult<()>
+  where
+    DescriptorLayoutStruct: Sized + Copy + DescriptorLayoutInfo,
+  {
+    if !self.rendering_enabled {
+      return Ok(());
+    }
+
+    let uniform_ring = self.uniform_ring();
+    let dynamic_offset = uniform_ring.allocate(uniform)?;
+
+    self.draw_object(object, uniform_ring.buffer(), dynamic_offset)
+  }
+
+  fn set_render_target(
+    &self, target: Option<&RenderTextureHandle<VulkanBufferImageFunctions>>,
+  ) -> SarektResult<()> {
+    if !self.rendering_enabled {
+      return Ok(());
+    }
+
+    let target = match target {
+      Some(target) => target,
+      None => {
+        self.render_texture_passes.end_pass();
+        return Ok(());
+      }
+    };
+
+    // The views the pass's framebuffer is made of.
+    let (texture, depth_buffer) = {
+      let buffer_image_store = self
+        .buffer_image_store
+        .read()
+        .expect("Panic occured can't read from buffer store");
+      (
+        buffer_image_store
+          .get_image(&target.texture)?
+          .handle
+          .image()?,
+        buffer_image_store
+          .get_image(&target.depth_buffer)?
+          .handle
+          .image()?,
+      )
+    };
+
+    let color_format = texture.format();
+    let render_pass = self
+      .pipelines
+      .get_render_texture_pass(&self.vulkan_device_structures.logical_device, color_format)?;
+    self.render_texture_passes.begin_pass(
+      render_pass,
+      color_format,
+      texture.image_and_view.view,
+      depth_buffer.image_and_view.view,
+      target.dimensions,
+      target.clear_color,
+    )
+  }
+
+  fn draw_shadow_caster<DescriptorLayoutStruct>(
+    &self, object: &DrawableObject<Self, DescriptorLayoutStruct>, model: uv::Mat4,
+  ) -> SarektResult<()>
+  where
+    DescriptorLayoutStruct: Sized + Copy + DescriptorLayoutInfo,
+  {
+    if !self.rendering_enabled || object.primitive_state.topology != PrimitiveTopology::TriangleList
+    {
+      return Ok(());
+    }
+    let variant = DescriptorLayoutStruct::forward_shader_variant();
+    let command_buffer = match self.shadow_pass.begin_casters(variant)? {
+      Some(command_buffer) => command_buffer,
+      None => return Ok(()),
+    };
+
+    let (vertex_buffer, index_buffer) = {
+      let buffer_image_store = self
+        .buffer_image_store
+        .read()
+        .expect("Panic occured can't read from buffer store");
+      let vertex_buffer = buffer_image_store.get_buffer(object.vertex_buffer)?.handle;
+      let index_buffer = object
+        .index_buffer
+        .map(|handle| buffer_image_store.get_buffer(handle).map(|ib| ib.handle))
+        .transpose()?;
+
+      for resource in std::iter::once(vertex_buffer).chain(index_buffer) {
+        buffer_image_store.wait_for_resource_upload(resource)?;
+      }
+
+      (vertex_buffer, index_buffer)
+    };
+
+    // Once into each cascade's tile.
+    for cascade in 0..self.shadow_pass.cascade_count() {
+      self
+        .shadow_pass
+        .set_cascade_cmd(command_buffer, cascade, model);
+      self.draw_vertices_cmd(vertex_buffer, index_buffer, command_buffer)?;
+    }
+
+    Ok(())
+  }
+}
+impl VulkanRenderer {
+  /// Records the draw of `object` with its uniform at `dynamic_offset` in
+  /// `uniform_buffer`.
+  fn draw_object<DescriptorLayoutStruct>(
+    &self, object: &DrawableObject<Self, DescriptorLayoutStruct>, uniform_buffer: vk::Buffer,
+    dynamic_offset: u32,
+  ) -> SarektResult<()>
+  where
+    DescriptorLayoutStruct: Sized + Copy + DescriptorLayoutInfo,
+  {
     let current_render_target_index = self.next_image_index.get();
+    let render_texture_pass = self.render_texture_passes.current_pass();
+    let variant = DescriptorLayoutStruct::forward_shader_variant();
+
+    // In the deferred render pass opaque PBR objects are drawn into the
+    // G-buffer, and everything else into the forward subpass's command buffer.
+    let deferred_pass = if render_texture_pass.is_none() {
+      self.deferred_pass.as_ref()
+    } else {
+      None
+    };
+    let g_buffer_object = deferred_pass.is_some()
+      && variant == ForwardShaderVariant::Pbr
+      && !object.primitive_state.alpha_blending;
+    let forward_deferred_pass = deferred_pass.filter(|_| !g_buffer_object);
 
     // Current render target command buffer.
-    let current_command_buffer = self.primary_gfx_command_buffers[current_render_target_index];
-    let current_uniform_buffer = object.uniform_buffer[current_render_target_index]
-      .buffer_and_memory
-      .buffer;
-    let current_descriptor_pool = self.main_descriptor_pools[current_render_target_index];
+    let current_command_buffer = if render_texture_pass.is_some() {
+      self.render_texture_passes.command_buffer()
+    } else if let Some(deferred_pass) = forward_deferred_pass {
+      deferred_pass.command_buffer()
+    } else {
+      self.primary_gfx_command_buffers[current_render_target_index]
+    };
+    let current_descriptor_pool = self.main_descriptor_pools[self.current_frame_num.get()];
+
+    // Looked up on every draw since `defragment` may have moved them.
+    let (vertex_buffer, index_buffer, texture_images) = {
+      let buffer_image_store = self
+        .buffer_image_store
+        .read()
+        .expect("Panic occured can't read from buffer store");
+      let vertex_buffer = buffer_image_store.get_buffer(object.vertex_buffer)?.handle;
+      let index_buffer = object
+        .index_buffer
+        .map(|handle| buffer_image_store.get_buffer(handle).map(|ib| ib.handle))
+        .transpose()?;
+      let texture_images = object
+        .texture_images
+        .iter()
+        .map(|texture_image| {
+          texture_image
+            .map(|handle| buffer_image_store.get_image(handle).map(|ti| ti.handle))
+            .transpose()
+        })
+        .collect::<SarektResult<Vec<_>>>()?;
+
+      // Resources submitted in an upload batch may not be ready yet.
+      let resources = std::iter::once(vertex_buffer)
+        .chain(index_buffer)
+        .chain(texture_images.iter().flatten().copied());
+      for resource in resources {
+        buffer_image_store.wait_for_resource_upload(resource)?;
+      }
+
+      (vertex_buffer, index_buffer, texture_images)
+    };
+
+    // Objects with another shader variant or primitive state or drawn into a
+    // render texture draw with a derivative of the base pipeline.
+    let (pipeline, bound_pipeline) = match render_texture_pass {
+      Some(pass) => (
+        self.pipelines.get_render_texture_pipeline(
+          &self.vulkan_device_structures.logical_device,
+          &self.shader_store,
+          pass.color_format,
+          variant,
+          &object.primitive_state,
+        )?,
+        pass.bound_pipeline,
+      ),
+      None if g_buffer_object => (
+        self.pipelines.get_g_buffer_pipeline(
+          &self.vulkan_device_structures.logical_device,
+          &self.shader_store,
+          self.scene_extent,
+          &object.primitive_state,
+        )?,
+        self.bound_pipeline.get(),
+      ),
+      None => (
+        self.pipelines.get_primitive_pipeline(
+          &self.vulkan_device_structures.logical_device,
+          &self.shader_store,
+          self.scene_extent,
+          &self.config.msaa_config,
+          variant,
+          &object.primitive_state,
+        )?,
+        forward_deferred_pass
+          .map_or_else(|| self.bound_pipeline.get(), DeferredPass::bound_pipeline),
+      ),
+    };
+    if pipeline != bound_pipeline {
+      unsafe {
+        self
+          .vulkan_device_structures
+          .logical_device
+          .cmd_bind_pipeline(
+            current_command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline,
+          );
+      }
+      if render_texture_pass.is_some() {
+        self.render_texture_passes.set_bound_pipeline(pipeline);
+      } else if let Some(deferred_pass) = forward_deferred_pass {
+        deferred_pass.set_bound_pipeline(pipeline);
+      } else {
+        self.bound_pipeline.set(pipeline);
+      }
+    }
 
     // Allocate and bind the correct uniform descriptors.
     self.bind_descriptor_sets::<DescriptorLayoutStruct>(
-      current_uniform_buffer,
-      &object.texture_image.map(|ti| ti.image().unwrap()),
+      uniform_buffer,
+      dynamic_offset,
+      &texture_images
+        .iter()
+        .map(|texture_image| texture_image.map(|ti| ti.image().unwrap()))
+        .collect::<Vec<_>>(),
       current_descriptor_pool,
       current_command_buffer,
     )?;
 
     // Draw the vertices (indexed or otherwise).
-    self.draw_vertices_cmd(object, current_command_buffer)?;
+    self.draw_vertices_cmd(vertex_buffer, index_buffer, current_command_buffer)?;
 
     Ok(())
   }
 }
+/// Describes `image` for a combined image sampler binding read by shaders.
+fn sampled_image_info(image: &ImageAndMemory) -> vk::DescriptorImageInfo {
+  vk::DescriptorImageInfo::builder()
+    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    .image_view(image.image_and_view.view)
+    .sampler(image.sampler.unwrap())
+    .build()
+}
+
+/// The maps of the environment set with `set_image_based_lighting`.
+struct ImageBasedLighting {
+  irradiance: (
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  ),
+  prefiltered: (
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  ),
+}
+
+/// A `set_uniform_bytes` write still to be made to the uniform copies of the
+/// frames that are `Some`.  The buffers outlive it since dropped buffers are
+/// only destroyed after more than `MAX_FRAMES_IN_FLIGHT` frames.
+struct PendingUniformWrite {
+  copies: Vec<Option<BufferAndMemoryMapped>>,
+  byte_offset: usize,
+  bytes: Vec<u8>,
+}
+
 impl Drop for VulkanRenderer {
   fn drop(&mut self) {
     unsafe {
@@ -1115,6 +2430,32 @@ impl Drop for VulkanRenderer {
       let default_texture = self.default_texture.take();
       std::mem::drop(default_texture);
 
+      info!("Destroying skybox cubemap...");
+      let skybox = self.skybox.take();
+      std::mem::drop(skybox);
+
+      info!("Destroying image based lighting maps...");
+      let default_cubemap = self.default_cubemap.take();
+      std::mem::drop(default_cubemap);
+      let image_based_lighting = self.image_based_lighting.take();
+      std::mem::drop(image_based_lighting);
+      let brdf_lut = self.brdf_lut.take();
+      std::mem::drop(brdf_lut);
+
+      info!("Destroying post process chain...");
+      let post_process_chain = self.post_process_chain.take();
+      if let Some(post_process_chain) = &post_process_chain {
+        post_process_chain.cleanup_swapchain_resources();
+        post_process_chain.cleanup();
+      }
+      std::mem::drop(post_process_chain);
+
+      info!("Destroying transient uniform buffer...");
+      let uniform_ring = self.uniform_ring.take();
+      std::mem::drop(uniform_ring);
+
+      self.shadow_pass.destroy_shadow_map();
+
       info!("Destroying all images, buffers, and associated synchronization semaphores...");
       self.buffer_image_store.write().unwrap().cleanup().unwrap();
       ManuallyDrop::drop(&mut self.buffer_image_store);
@@ -1128,6 +2469,8 @@ impl Drop for VulkanRenderer {
         self.main_gfx_command_pool,
         &self.primary_gfx_command_buffers,
       );
+      self.render_texture_passes.destroy_all();
+      self.shadow_pass.destroy_all();
 
       self
         .cleanup_swapchain(None)
@@ -1136,6 +2479,9 @@ impl Drop for VulkanRenderer {
       self
         .pipelines
         .cleanup_descriptor_set_layouts(logical_device);
+      if let Some(deferred_pass) = &mut self.deferred_pass {
+        deferred_pass.destroy_all();
+      }
 
       self.draw_synchronization.destroy_all();
 
@@ -1158,8 +2504,10 @@ impl Drop for VulkanRenderer {
 mod tests {
   use super::{debug_utils_ext::DebugUserData, VulkanRenderer};
   use crate::renderer::{
+    buffers_and_images::BufferType,
     config::{ApplicationDetails, Config, EngineDetails, Version},
-    IS_DEBUG_MODE,
+    upload_batch::UploadBatch,
+    Renderer, IS_DEBUG_MODE,
   };
   use log::Level;
   use std::{pin::Pin, sync::Arc};
@@ -1224,6 +2572,37 @@ mod tests {
     assert_no_warnings_or_errors_in_debug_user_data(&debug_user_data);
   }
 
+  #[test]
+  fn can_update_buffer_still_in_upload_batch() {
+    let _log = simple_logger::init_with_level(Level::Info);
+    let event_loop = EventLoop::<()>::new_any_thread();
+    let window = Arc::new(WindowBuilder::new().build(&event_loop).unwrap());
+    let debug_user_data = Arc::pin(DebugUserData::new());
+    let config = Config::builder()
+      .requested_width(WIDTH)
+      .requested_height(HEIGHT)
+      .build()
+      .unwrap();
+    let mut renderer =
+      VulkanRenderer::new_with_debug_user_data(window, config, Some(debug_user_data.clone()))
+        .unwrap();
+
+    let mut batch = UploadBatch::new();
+    batch.add_buffer(BufferType::Vertex, &[0u32; 1024]);
+    let (handles, token) = renderer.submit_upload_batch(batch).unwrap();
+
+    // The update has to wait for the batch, or the batch's copy could land
+    // after it.
+    renderer
+      .update_buffer(&handles[0], 0, &[1u32; 1024])
+      .unwrap();
+    assert!(renderer.is_upload_complete(token).unwrap());
+
+    std::mem::drop(handles);
+    std::mem::drop(renderer);
+    assert_no_warnings_or_errors_in_debug_user_data(&debug_user_data);
+  }
+
   // TODO(issue#14) TESTING write triangle sanity check that can dump buffer and
   // compare to golden image.
 
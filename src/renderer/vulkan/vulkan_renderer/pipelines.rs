@@ -1,59 +1,111 @@
 use crate::{
-  error::SarektResult,
+  error::{SarektError, SarektResult},
   renderer::{
     buffers_and_images::BufferImageStore,
-    config::{Config, MsaaConfig, NumSamples},
+    config::{AntiAliasingMode, Config, MsaaConfig, NumSamples, RenderingPath},
+    primitive_state::PrimitiveState,
     shaders::ShaderStore,
     vertex_bindings::{
-      DefaultForwardShaderLayout, DefaultForwardShaderVertex, DescriptorLayoutInfo, VertexBindings,
+      DefaultForwardShaderLayout, DefaultForwardShaderVertex, DefaultLitShaderLayout,
+      DefaultLitShaderVertex, DefaultPbrShaderLayout, DefaultPbrShaderVertex,
+      DefaultShadowedShaderLayout, DescriptorLayoutInfo, ForwardShaderVariant, VertexBindings,
     },
     vulkan::{
       images::ImageAndView,
+      vulkan_buffer_image_functions::ImageAndMemory,
       vulkan_renderer::{
         base_pipeline_bundle::BasePipelineBundle,
-        render_attachments::{DepthAttachment, ResolveAttachment},
+        deferred_pass::{FORWARD_SUBPASS, G_BUFFER_SUBPASS, LIGHTING_SUBPASS},
+        render_attachments::{DepthAttachment, GBufferAttachments, ResolveAttachment},
         render_targets::RenderTargetBundle,
+        skybox::SkyboxPipeline,
         vulkan_core::{VulkanCoreStructures, VulkanDeviceStructures},
-        DEFAULT_FRAGMENT_SHADER, DEFAULT_VERTEX_SHADER,
+        DEFAULT_FRAGMENT_SHADER, DEFAULT_VERTEX_SHADER, DEFERRED_PBR_FRAGMENT_SHADER,
+        LIT_FRAGMENT_SHADER, LIT_VERTEX_SHADER, PBR_FRAGMENT_SHADER, PBR_VERTEX_SHADER,
+        SHADOWED_FRAGMENT_SHADER, SHADOWED_VERTEX_SHADER,
       },
       vulkan_shader_functions::VulkanShaderFunctions,
+      vulkan_vertex_bindings::check_vertex_attribute_formats,
       VulkanShaderHandle,
     },
     ShaderCode, ShaderHandle, ShaderType, VulkanBufferImageFunctions,
   },
 };
-use ash::{version::DeviceV1_0, vk, vk::DescriptorSetLayout, Device};
+use ash::{
+  version::{DeviceV1_0, InstanceV1_0},
+  vk,
+  vk::DescriptorSetLayout,
+  Device, Instance,
+};
 use log::info;
 use std::{
+  collections::HashMap,
   convert::TryInto,
   ffi::CStr,
   sync::{Arc, RwLock},
 };
 
 /// Pipeline related fields and methods, including forward render pass, base
-/// pipeline, and fraembuffers.
+/// pipeline, skybox pipeline, and fraembuffers.
 pub struct Pipelines {
   pub framebuffers: Vec<vk::Framebuffer>,
+  /// The render pass the scene is drawn in, the deferred one when the G-buffer
+  /// is there.
   pub forward_render_pass: vk::RenderPass,
   base_graphics_pipeline_bundle: BasePipelineBundle,
+  /// Only with the deferred rendering path, along with the fragment shader
+  /// that writes it and the PBR pipelines drawing into it by primitive state.
+  g_buffer: Option<GBufferAttachments>,
+  g_buffer_fragment_shader_handle: Option<VulkanShaderHandle>,
+  g_buffer_pipelines: RwLock<HashMap<PrimitiveState, vk::Pipeline>>,
+  /// Shaders and layouts of the shadow receiving and lit forward shader
+  /// variants.
+  shadowed_shaders: VariantShaders,
+  lit_shaders: VariantShaders,
+  pbr_shaders: VariantShaders,
+  /// Derivatives of the base pipeline for other shader variants and primitive
+  /// states, created on first use.
+  primitive_pipelines: RwLock<HashMap<(ForwardShaderVariant, PrimitiveState), vk::Pipeline>>,
+  /// Render passes for drawing into render textures of each color format,
+  /// created on first use.
+  render_texture_passes: RwLock<HashMap<vk::Format, vk::RenderPass>>,
+  /// Pipelines compatible with those render passes, by color format, shader
+  /// variant and primitive state.
+  render_texture_pipelines:
+    RwLock<HashMap<(vk::Format, ForwardShaderVariant, PrimitiveState), vk::Pipeline>>,
+  pub skybox_pipeline: SkyboxPipeline,
 }
 impl Pipelines {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     config: &Config, vulkan_core: &VulkanCoreStructures, device_bundle: &VulkanDeviceStructures,
     render_target_bundle: &RenderTargetBundle,
     shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
     buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    scene_target: Option<&ImageAndMemory>, extent: vk::Extent2D,
   ) -> SarektResult<Pipelines> {
-    let dimensions = (
-      render_target_bundle.extent.width,
-      render_target_bundle.extent.height,
-    );
+    let dimensions = (extent.width, extent.height);
     let num_msaa_samples = config.msaa_config.samples;
     info!(
       "Creating pipeline with MSAA sample count of: {:?}. Minsample shading: {:?}",
       num_msaa_samples, config.msaa_config.min_sample_shading,
     );
 
+    // The vertex types of every shader variant, their pipelines are created
+    // on first use.
+    for &variant in &[
+      ForwardShaderVariant::Plain,
+      ForwardShaderVariant::Lit,
+      ForwardShaderVariant::Pbr,
+    ] {
+      let (_, attribute_descriptions) = Self::get_variant_vertex_input(variant);
+      check_vertex_attribute_formats(
+        &vulkan_core.instance,
+        device_bundle.physical_device,
+        &attribute_descriptions,
+      )?;
+    }
+
     // TODO(issue#2) RENDERING_CAPABILITIES support other render pass types.
     let depth_buffer = DepthAttachment::new(
       &vulkan_core.instance,
@@ -61,15 +113,20 @@ impl Pipelines {
       buffer_image_store,
       dimensions,
       num_msaa_samples,
+      config.depth_format,
     )?;
 
+    // When post processing the scene is drawn into its target instead of the
+    // swapchain images.
+    let color_format = scene_target.map_or(
+      render_target_bundle.get_render_target_format(),
+      |scene_target| scene_target.format(),
+    );
     let resolve_attachment = if !matches!(num_msaa_samples, NumSamples::One) {
       Some(ResolveAttachment::new(
         buffer_image_store,
         dimensions,
-        render_target_bundle
-          .swapchain_and_extension
-          .format
+        color_format
           .try_into()
           .expect("Format not supported by sarekt for msaa color buffer"),
         num_msaa_samples,
@@ -78,12 +135,41 @@ impl Pipelines {
       None
     };
 
-    let forward_render_pass = Self::create_forward_render_pass(
-      &device_bundle.logical_device,
-      render_target_bundle.get_render_target_format(),
-      &depth_buffer,
-      num_msaa_samples,
-    )?;
+    let g_buffer = if config.rendering_path == RenderingPath::Deferred {
+      Some(GBufferAttachments::new(buffer_image_store, dimensions)?)
+    } else {
+      None
+    };
+    let g_buffer_fragment_shader_handle = if g_buffer.is_some() {
+      Some(ShaderStore::load_shader(
+        shader_store,
+        &ShaderCode::Spirv(DEFERRED_PBR_FRAGMENT_SHADER),
+        ShaderType::Fragment,
+      )?)
+    } else {
+      None
+    };
+
+    let depth_sampled = config.anti_aliasing == AntiAliasingMode::Temporal;
+    let forward_render_pass = if g_buffer.is_some() {
+      Self::create_deferred_render_pass(
+        &device_bundle.logical_device,
+        color_format,
+        &depth_buffer,
+        scene_target.is_some(),
+        depth_sampled,
+      )?
+    } else {
+      Self::create_forward_render_pass(
+        &device_bundle.logical_device,
+        color_format,
+        &depth_buffer,
+        num_msaa_samples,
+        scene_target.is_some(),
+        depth_sampled,
+      )?
+    };
+    let forward_subpass = Self::forward_subpass_of(g_buffer.as_ref());
 
     // TODO(issue#2) RENDERING_CAPABILITIES when I can have multiple render pass
     // types I need new framebuffers for each.
@@ -92,24 +178,67 @@ impl Pipelines {
       forward_render_pass,
       resolve_attachment.as_ref(),
       &depth_buffer,
-      &render_target_bundle.render_targets,
-      render_target_bundle.extent,
+      g_buffer.as_ref(),
+      &Self::forward_render_targets(&render_target_bundle.render_targets, scene_target),
+      extent,
     )?;
 
     let base_graphics_pipeline_bundle = Self::create_base_graphics_pipeline_and_shaders(
       &device_bundle.logical_device,
       &shader_store, // Unlock and get a local mut ref to shaderstore.
-      render_target_bundle.extent,
+      extent,
       forward_render_pass,
+      forward_subpass,
       resolve_attachment,
       depth_buffer,
       &config.msaa_config,
     )?;
 
+    let skybox_pipeline = SkyboxPipeline::new(
+      &device_bundle.logical_device,
+      shader_store,
+      extent,
+      forward_render_pass,
+      forward_subpass,
+      &config.msaa_config,
+    )?;
+
+    let shadowed_shaders = VariantShaders::new(
+      &device_bundle.logical_device,
+      shader_store,
+      SHADOWED_VERTEX_SHADER,
+      SHADOWED_FRAGMENT_SHADER,
+      &DefaultShadowedShaderLayout::get_descriptor_set_layout_bindings(),
+    )?;
+    let lit_shaders = VariantShaders::new(
+      &device_bundle.logical_device,
+      shader_store,
+      LIT_VERTEX_SHADER,
+      LIT_FRAGMENT_SHADER,
+      &DefaultLitShaderLayout::get_descriptor_set_layout_bindings(),
+    )?;
+    let pbr_shaders = VariantShaders::new(
+      &device_bundle.logical_device,
+      shader_store,
+      PBR_VERTEX_SHADER,
+      PBR_FRAGMENT_SHADER,
+      &DefaultPbrShaderLayout::get_descriptor_set_layout_bindings(),
+    )?;
+
     Ok(Pipelines {
       framebuffers,
       forward_render_pass,
       base_graphics_pipeline_bundle,
+      g_buffer,
+      g_buffer_fragment_shader_handle,
+      g_buffer_pipelines: RwLock::new(HashMap::new()),
+      shadowed_shaders,
+      lit_shaders,
+      pbr_shaders,
+      primitive_pipelines: RwLock::new(HashMap::new()),
+      render_texture_passes: RwLock::new(HashMap::new()),
+      render_texture_pipelines: RwLock::new(HashMap::new()),
+      skybox_pipeline,
     })
   }
 
@@ -118,21 +247,277 @@ impl Pipelines {
     self.base_graphics_pipeline_bundle.pipeline
   }
 
-  /// Returns the current pipeline layout (just base for now).
-  pub fn get_pipeline_layout(&self) -> vk::PipelineLayout {
-    self.base_graphics_pipeline_bundle.pipeline_layout
+  /// Returns the base pipeline or its derivative for the shader `variant` and
+  /// `primitive_state`, creating the derivative if this is the first time it
+  /// is used since the pipelines were (re)created.  The state should have been
+  /// checked with `check_primitive_state`.
+  pub fn get_primitive_pipeline(
+    &self, logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    extent: vk::Extent2D, msaa_config: &MsaaConfig, variant: ForwardShaderVariant,
+    primitive_state: &PrimitiveState,
+  ) -> SarektResult<vk::Pipeline> {
+    if variant == ForwardShaderVariant::Plain && *primitive_state == PrimitiveState::default() {
+      return Ok(self.base_graphics_pipeline_bundle.pipeline);
+    }
+    let key = (variant, *primitive_state);
+    if let Some(&pipeline) = self.primitive_pipelines.read().unwrap().get(&key) {
+      return Ok(pipeline);
+    }
+
+    info!(
+      "Creating pipeline derivative for {:?} shaders and primitive state {:?}",
+      variant, primitive_state
+    );
+    let base = &self.base_graphics_pipeline_bundle;
+    let (vertex_shader_handle, fragment_shader_handle, pipeline_layout) =
+      self.get_variant_shaders_and_layout(variant);
+    let pipeline = Self::create_graphics_pipeline(
+      logical_device,
+      shader_store,
+      vertex_shader_handle,
+      fragment_shader_handle,
+      pipeline_layout,
+      variant,
+      Some(extent),
+      self.forward_render_pass,
+      self.forward_subpass(),
+      1,
+      msaa_config,
+      primitive_state,
+      Some(base.pipeline),
+    )?;
+    self
+      .primitive_pipelines
+      .write()
+      .unwrap()
+      .insert(key, pipeline);
+
+    Ok(pipeline)
+  }
+
+  /// Same as `get_primitive_pipeline` but for drawing opaque PBR objects into
+  /// the G-buffer, only with the deferred rendering path.
+  pub fn get_g_buffer_pipeline(
+    &self, logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    extent: vk::Extent2D, primitive_state: &PrimitiveState,
+  ) -> SarektResult<vk::Pipeline> {
+    if let Some(&pipeline) = self.g_buffer_pipelines.read().unwrap().get(primitive_state) {
+      return Ok(pipeline);
+    }
+
+    info!(
+      "Creating G-buffer pipeline for primitive state {:?}",
+      primitive_state
+    );
+    let base = &self.base_graphics_pipeline_bundle;
+    let pipeline = Self::create_graphics_pipeline(
+      logical_device,
+      shader_store,
+      &self.pbr_shaders.vertex_shader_handle,
+      self.g_buffer_fragment_shader_handle.as_ref().unwrap(),
+      self.pbr_shaders.pipeline_layout,
+      ForwardShaderVariant::Pbr,
+      Some(extent),
+      self.forward_render_pass,
+      G_BUFFER_SUBPASS,
+      1 + GBufferAttachments::formats().len(),
+      &MsaaConfig::default(),
+      primitive_state,
+      Some(base.pipeline),
+    )?;
+    self
+      .g_buffer_pipelines
+      .write()
+      .unwrap()
+      .insert(*primitive_state, pipeline);
+
+    Ok(pipeline)
+  }
+
+  /// Returns the render pass that draws into render textures of
+  /// `color_format`, creating it if this is the first time it is used since
+  /// the pipelines were (re)created.
+  pub fn get_render_texture_pass(
+    &self, logical_device: &Device, color_format: vk::Format,
+  ) -> SarektResult<vk::RenderPass> {
+    if let Some(&render_pass) = self
+      .render_texture_passes
+      .read()
+      .unwrap()
+      .get(&color_format)
+    {
+      return Ok(render_pass);
+    }
+
+    info!("Creating render texture pass for {:?}", color_format);
+    let depth_format = self
+      .base_graphics_pipeline_bundle
+      .depth_resources
+      .as_ref()
+      .unwrap()
+      .format;
+    let render_pass = Self::create_render_texture_pass(logical_device, color_format, depth_format)?;
+    self
+      .render_texture_passes
+      .write()
+      .unwrap()
+      .insert(color_format, render_pass);
+
+    Ok(render_pass)
+  }
+
+  /// Same as `get_primitive_pipeline` but for drawing into render textures of
+  /// `color_format`.  The viewport and scissor are dynamic since render
+  /// textures have their own dimensions.
+  pub fn get_render_texture_pipeline(
+    &self, logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    color_format: vk::Format, variant: ForwardShaderVariant, primitive_state: &PrimitiveState,
+  ) -> SarektResult<vk::Pipeline> {
+    let key = (color_format, variant, *primitive_state);
+    if let Some(&pipeline) = self.render_texture_pipelines.read().unwrap().get(&key) {
+      return Ok(pipeline);
+    }
+
+    info!(
+      "Creating render texture pipeline for {:?}, {:?} shaders and primitive state {:?}",
+      color_format, variant, primitive_state
+    );
+    let render_pass = self.get_render_texture_pass(logical_device, color_format)?;
+    let base = &self.base_graphics_pipeline_bundle;
+    let (vertex_shader_handle, fragment_shader_handle, pipeline_layout) =
+      self.get_variant_shaders_and_layout(variant);
+    let pipeline = Self::create_graphics_pipeline(
+      logical_device,
+      shader_store,
+      vertex_shader_handle,
+      fragment_shader_handle,
+      pipeline_layout,
+      variant,
+      None,
+      render_pass,
+      0,
+      1,
+      &MsaaConfig::default(),
+      primitive_state,
+      Some(base.pipeline),
+    )?;
+    self
+      .render_texture_pipelines
+      .write()
+      .unwrap()
+      .insert(key, pipeline);
+
+    Ok(pipeline)
+  }
+
+  /// Checks that the device supports the line width and point size and that
+  /// primitive restart is only used with strips and fans.
+  pub fn check_primitive_state(
+    instance: &Instance, physical_device: vk::PhysicalDevice, primitive_state: &PrimitiveState,
+  ) -> SarektResult<()> {
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+    let limits = unsafe {
+      instance
+        .get_physical_device_properties(physical_device)
+        .limits
+    };
+
+    if primitive_state.primitive_restart && !primitive_state.topology.is_strip() {
+      return Err(SarektError::UnsupportedPrimitiveState(format!(
+        "primitive restart can't be used with {:?}",
+        primitive_state.topology
+      )));
+    }
+
+    Self::check_primitive_size(
+      "line width",
+      primitive_state.line_width,
+      features.wide_lines,
+      limits.line_width_range,
+    )?;
+    Self::check_primitive_size(
+      "point size",
+      primitive_state.point_size,
+      features.large_points,
+      limits.point_size_range,
+    )
+  }
+
+  /// Returns the pipeline layout of the shader variant's pipelines.
+  pub fn get_pipeline_layout(&self, variant: ForwardShaderVariant) -> vk::PipelineLayout {
+    self.get_variant_shaders_and_layout(variant).2
   }
 
-  /// Returns the descriptor layouts of the current pipeline (just base for
-  /// now).
-  pub fn get_pipeline_descriptor_layouts(&self) -> Vec<vk::DescriptorSetLayout> {
-    vec![
-      self
-        .base_graphics_pipeline_bundle
-        .descriptor_set_layouts
-        .as_ref()
-        .unwrap()[0],
-    ]
+  /// Returns the descriptor layouts of the shader variant's pipelines.
+  pub fn get_pipeline_descriptor_layouts(
+    &self, variant: ForwardShaderVariant,
+  ) -> Vec<vk::DescriptorSetLayout> {
+    match variant {
+      ForwardShaderVariant::Plain => vec![
+        self
+          .base_graphics_pipeline_bundle
+          .descriptor_set_layouts
+          .as_ref()
+          .unwrap()[0],
+      ],
+      ForwardShaderVariant::ShadowReceiving => vec![self.shadowed_shaders.descriptor_set_layout],
+      ForwardShaderVariant::Lit => vec![self.lit_shaders.descriptor_set_layout],
+      ForwardShaderVariant::Pbr => vec![self.pbr_shaders.descriptor_set_layout],
+    }
+  }
+
+  fn get_variant_shaders_and_layout(
+    &self, variant: ForwardShaderVariant,
+  ) -> (&VulkanShaderHandle, &VulkanShaderHandle, vk::PipelineLayout) {
+    match variant {
+      ForwardShaderVariant::Plain => {
+        let base = &self.base_graphics_pipeline_bundle;
+        (
+          base.vertex_shader_handle.as_ref().unwrap(),
+          base.fragment_shader_handle.as_ref().unwrap(),
+          base.pipeline_layout,
+        )
+      }
+      ForwardShaderVariant::ShadowReceiving => (
+        &self.shadowed_shaders.vertex_shader_handle,
+        &self.shadowed_shaders.fragment_shader_handle,
+        self.shadowed_shaders.pipeline_layout,
+      ),
+      ForwardShaderVariant::Lit => (
+        &self.lit_shaders.vertex_shader_handle,
+        &self.lit_shaders.fragment_shader_handle,
+        self.lit_shaders.pipeline_layout,
+      ),
+      ForwardShaderVariant::Pbr => (
+        &self.pbr_shaders.vertex_shader_handle,
+        &self.pbr_shaders.fragment_shader_handle,
+        self.pbr_shaders.pipeline_layout,
+      ),
+    }
+  }
+
+  /// The vertex binding and attributes of the vertex type the shader
+  /// variant's vertex shader reads.
+  pub fn get_variant_vertex_input(
+    variant: ForwardShaderVariant,
+  ) -> (
+    vk::VertexInputBindingDescription,
+    Vec<vk::VertexInputAttributeDescription>,
+  ) {
+    match variant {
+      ForwardShaderVariant::Plain | ForwardShaderVariant::ShadowReceiving => (
+        DefaultForwardShaderVertex::get_binding_description(),
+        DefaultForwardShaderVertex::get_attribute_descriptions(),
+      ),
+      ForwardShaderVariant::Lit => (
+        DefaultLitShaderVertex::get_binding_description(),
+        DefaultLitShaderVertex::get_attribute_descriptions(),
+      ),
+      ForwardShaderVariant::Pbr => (
+        DefaultPbrShaderVertex::get_binding_description(),
+        DefaultPbrShaderVertex::get_attribute_descriptions(),
+      ),
+    }
   }
 
   /// Returns the framebuffer of the given (swapchain) index.
@@ -140,35 +525,75 @@ impl Pipelines {
     self.framebuffers[image_index]
   }
 
+  /// Returns the G-buffer, only there with the deferred rendering path.
+  pub fn get_g_buffer(&self) -> Option<&GBufferAttachments> {
+    self.g_buffer.as_ref()
+  }
+
+  /// The subpass of the forward render pass everything but the G-buffer is
+  /// drawn in, the last one of the deferred render pass.
+  pub fn forward_subpass(&self) -> u32 {
+    Self::forward_subpass_of(self.g_buffer.as_ref())
+  }
+
+  /// Returns the depth buffer the forward pass draws with.
+  pub fn get_depth_attachment(&self) -> &DepthAttachment {
+    self
+      .base_graphics_pipeline_bundle
+      .depth_resources
+      .as_ref()
+      .unwrap()
+  }
+
   /// Recreates all render passes associated with the pipeline for swapchain
-  /// recreation.
+  /// recreation.  `new_format` is the scene target's when `post_processed`.
   pub fn recreate_renderpasses(
     &mut self, logical_device: &Device, new_format: vk::Format, num_msaa_samples: NumSamples,
+    post_processed: bool, depth_sampled: bool,
   ) -> SarektResult<()> {
-    self.forward_render_pass = Self::create_forward_render_pass(
-      logical_device,
-      new_format,
-      self
-        .base_graphics_pipeline_bundle
-        .depth_resources
-        .as_ref()
-        .unwrap(),
-      num_msaa_samples,
-    )?;
+    let depth_buffer = self
+      .base_graphics_pipeline_bundle
+      .depth_resources
+      .as_ref()
+      .unwrap();
+    self.forward_render_pass = if self.g_buffer.is_some() {
+      Self::create_deferred_render_pass(
+        logical_device,
+        new_format,
+        depth_buffer,
+        post_processed,
+        depth_sampled,
+      )?
+    } else {
+      Self::create_forward_render_pass(
+        logical_device,
+        new_format,
+        depth_buffer,
+        num_msaa_samples,
+        post_processed,
+        depth_sampled,
+      )?
+    };
     Ok(())
   }
 
-  /// Same as above, recreates vulkan framebuffers
+  /// Same as above, recreates vulkan framebuffers.  The new G-buffer replaces
+  /// the old one, and must be there if the old one was.
+  #[allow(clippy::too_many_arguments)]
   pub fn recreate_framebuffers(
     &mut self, logical_device: &Device, resolve_attachment: Option<&ResolveAttachment>,
-    depth_buffer: &DepthAttachment, render_targets: &[ImageAndView], new_extent: vk::Extent2D,
+    depth_buffer: &DepthAttachment, g_buffer: Option<GBufferAttachments>,
+    render_targets: &[ImageAndView], scene_target: Option<&ImageAndMemory>,
+    new_extent: vk::Extent2D,
   ) -> SarektResult<()> {
+    self.g_buffer = g_buffer;
     self.framebuffers = Self::create_framebuffers(
       logical_device,
       self.forward_render_pass,
       resolve_attachment,
       depth_buffer,
-      render_targets,
+      self.g_buffer.as_ref(),
+      &Self::forward_render_targets(render_targets, scene_target),
       new_extent,
     )?;
     Ok(())
@@ -189,6 +614,7 @@ impl Pipelines {
       shader_store,
       new_extent,
       self.forward_render_pass,
+      self.forward_subpass(),
       resolve_attachment,
       depth_buffer,
       msaa_config,
@@ -199,6 +625,23 @@ impl Pipelines {
     Ok(())
   }
 
+  /// Same as above but for the skybox pipeline, which keeps its shaders and
+  /// layouts.
+  pub fn recreate_skybox_pipeline(
+    &mut self, logical_device: &Device,
+    shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>, new_extent: vk::Extent2D,
+    msaa_config: &MsaaConfig,
+  ) -> SarektResult<()> {
+    self.skybox_pipeline.recreate_pipeline(
+      logical_device,
+      shader_store,
+      new_extent,
+      self.forward_render_pass,
+      self.forward_subpass(),
+      msaa_config,
+    )
+  }
+
   // TODO(issue#2) PIPELINES handle when there is more than one pipeline.
   /// Save the handles to the base shaders so they don't have to be recreated
   /// for no reason during swapchain recreation.
@@ -240,6 +683,13 @@ impl Pipelines {
         logical_device.destroy_descriptor_set_layout(layout, None);
       }
     }
+
+    self
+      .skybox_pipeline
+      .cleanup_descriptor_set_layout(logical_device);
+    self.shadowed_shaders.cleanup(logical_device);
+    self.lit_shaders.cleanup(logical_device);
+    self.pbr_shaders.cleanup(logical_device);
   }
 
   /// Cleans up all vulkan resources, unsafe because it should only be called
@@ -250,6 +700,22 @@ impl Pipelines {
       logical_device.destroy_framebuffer(fb, None);
     }
 
+    info!("Destroying primitive state pipeline derivatives...");
+    for (_, pipeline) in self.primitive_pipelines.write().unwrap().drain() {
+      logical_device.destroy_pipeline(pipeline, None);
+    }
+    for (_, pipeline) in self.g_buffer_pipelines.write().unwrap().drain() {
+      logical_device.destroy_pipeline(pipeline, None);
+    }
+
+    info!("Destroying render texture pipelines and render passes...");
+    for (_, pipeline) in self.render_texture_pipelines.write().unwrap().drain() {
+      logical_device.destroy_pipeline(pipeline, None);
+    }
+    for (_, render_pass) in self.render_texture_passes.write().unwrap().drain() {
+      logical_device.destroy_render_pass(render_pass, None);
+    }
+
     info!("Destroying base graphics pipeline...");
     logical_device.destroy_pipeline(self.base_graphics_pipeline_bundle.pipeline, None);
 
@@ -257,6 +723,8 @@ impl Pipelines {
     logical_device
       .destroy_pipeline_layout(self.base_graphics_pipeline_bundle.pipeline_layout, None);
 
+    self.skybox_pipeline.cleanup_pipeline(logical_device);
+
     info!("Destroying render pass...");
     logical_device.destroy_render_pass(self.forward_render_pass, None);
   }
@@ -266,20 +734,28 @@ impl Pipelines {
   // ================================================================================
   // TODO(issue#35) make custom story related to issue 2 for custom render passes,
   // but not needed for basic single pass pipelines.
-  /// Creates a simple forward render pass with one subpass.
+  /// Creates a simple forward render pass with one subpass.  When
+  /// `post_processed` it leaves the scene ready for the post process chain to
+  /// sample instead of ready to present, and the depth buffer too when
+  /// `depth_sampled`.
   fn create_forward_render_pass(
     logical_device: &Device, format: vk::Format, depth_buffer: &DepthAttachment,
-    num_msaa_samples: NumSamples,
+    num_msaa_samples: NumSamples, post_processed: bool, depth_sampled: bool,
   ) -> SarektResult<vk::RenderPass> {
     // TODO(issue#9) OFFSCREEN only make swapchain optimal if this is going to
     // present. Otherwise TransferDST optimal would be good.
+    let output_final_layout = if post_processed {
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    } else {
+      vk::ImageLayout::PRESENT_SRC_KHR
+    };
 
     // Used to reference attachments in render passes.
     // This is the non MSAA sampled color attachment.
     let color_attachment_final_layout = if !matches!(num_msaa_samples, NumSamples::One) {
       vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
     } else {
-      vk::ImageLayout::PRESENT_SRC_KHR
+      output_final_layout
     };
     let color_attachment = vk::AttachmentDescription::builder()
       .format(format)
@@ -298,16 +774,27 @@ impl Pipelines {
       .build();
     let color_attachment_refs = [color_attachment_ref];
 
-    // This is the non sampled depth attachment
+    // This is the depth attachment, only kept for temporal anti-aliasing.
+    let (depth_store_op, depth_final_layout) = if depth_sampled {
+      (
+        vk::AttachmentStoreOp::STORE,
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+      )
+    } else {
+      (
+        vk::AttachmentStoreOp::DONT_CARE,
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+      )
+    };
     let depth_attachment = vk::AttachmentDescription::builder()
       .format(depth_buffer.format)
       .samples(num_msaa_samples.into())
       .load_op(vk::AttachmentLoadOp::CLEAR)
-      .store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .store_op(depth_store_op)
       .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
       .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
       .initial_layout(vk::ImageLayout::UNDEFINED)
-      .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+      .final_layout(depth_final_layout)
       .build();
     let depth_attachment_ref = vk::AttachmentReference::builder()
       .attachment(1)
@@ -324,7 +811,7 @@ impl Pipelines {
       .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
       .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
       .initial_layout(vk::ImageLayout::UNDEFINED)
-      .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+      .final_layout(output_final_layout)
       .build();
     let resolve_attachment_refs = [vk::AttachmentReference::builder()
       .attachment(2)
@@ -349,20 +836,336 @@ impl Pipelines {
     }
     let subpass_descriptions = [subpass_description.build()];
 
+    // The last frame's post processing may still be sampling the depth buffer.
+    let (depth_dst_stage_mask, depth_dst_access_mask) = if depth_sampled {
+      (
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      )
+    } else {
+      (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty())
+    };
     let dependency = vk::SubpassDependency::builder()
       .src_subpass(vk::SUBPASS_EXTERNAL)
       .dst_subpass(0u32)
-      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT) // We need to wait until the image is not in use (by the swapchain for example).
+      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER) // We need to wait until the image is not in use (by the swapchain or the last frame's post processing for example).
       .src_access_mask(vk::AccessFlags::empty()) // We're not going to access the swapchain as a source.
-      .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT) // Anyone waiting on this should wait in the color attachment stage.
-      .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE) // Dependents should wait if they read or write the color attachment.
+      .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_dst_stage_mask) // Anyone waiting on this should wait in the color attachment stage.
+      .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE | depth_dst_access_mask) // Dependents should wait if they read or write the color attachment.
+      .build();
+    // The post process chain samples the scene (and depth) afterwards.
+    let (depth_src_stage_mask, depth_src_access_mask) = if depth_sampled {
+      (
+        vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      )
+    } else {
+      (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty())
+    };
+    let post_process_dependency = vk::SubpassDependency::builder()
+      .src_subpass(0u32)
+      .dst_subpass(vk::SUBPASS_EXTERNAL)
+      .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_src_stage_mask)
+      .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | depth_src_access_mask)
+      .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+      .dst_access_mask(vk::AccessFlags::SHADER_READ)
       .build();
-    let dependencies = [dependency];
+    let dependencies = if post_processed {
+      vec![dependency, post_process_dependency]
+    } else {
+      vec![dependency]
+    };
 
     let render_pass_ci = vk::RenderPassCreateInfo::builder()
       .attachments(&attachments)
       .subpasses(&subpass_descriptions) // Only one subpass in this case.
-      .dependencies(&dependencies) // Only one dep.
+      .dependencies(&dependencies)
+      .build();
+
+    Ok(unsafe { logical_device.create_render_pass(&render_pass_ci, None)? })
+  }
+
+  /// Creates the deferred render pass, which draws opaque surfaces into the
+  /// G-buffer, then shades them with the lights in a subpass that reads the
+  /// G-buffer and depth as input attachments, and then draws everything else
+  /// forward, see `deferred_pass`.  Single sampled, its attachments are the
+  /// color, depth, and the G-buffer's albedo, normal and material.  The color
+  /// and depth end up like `create_forward_render_pass` leaves them.
+  fn create_deferred_render_pass(
+    logical_device: &Device, format: vk::Format, depth_buffer: &DepthAttachment,
+    post_processed: bool, depth_sampled: bool,
+  ) -> SarektResult<vk::RenderPass> {
+    let output_final_layout = if post_processed {
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    } else {
+      vk::ImageLayout::PRESENT_SRC_KHR
+    };
+    let (depth_store_op, depth_final_layout) = if depth_sampled {
+      (
+        vk::AttachmentStoreOp::STORE,
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+      )
+    } else {
+      (
+        vk::AttachmentStoreOp::DONT_CARE,
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+      )
+    };
+
+    // The G-buffer subpass starts the color attachment off with the emitted
+    // and environment light, and the lighting subpass adds onto it.
+    let color_attachment = vk::AttachmentDescription::builder()
+      .format(format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::CLEAR)
+      .store_op(vk::AttachmentStoreOp::STORE)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED)
+      .final_layout(output_final_layout)
+      .build();
+    let depth_attachment = vk::AttachmentDescription::builder()
+      .format(depth_buffer.format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::CLEAR)
+      .store_op(depth_store_op)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED)
+      .final_layout(depth_final_layout)
+      .build();
+    // The G-buffer is only needed within the pass, it's never stored.
+    let g_buffer_formats = GBufferAttachments::formats();
+    let g_buffer_attachments = g_buffer_formats.iter().map(|&format| {
+      vk::AttachmentDescription::builder()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .build()
+    });
+    let attachments: Vec<_> = [color_attachment, depth_attachment]
+      .iter()
+      .copied()
+      .chain(g_buffer_attachments)
+      .collect();
+
+    let attachment_ref = |attachment, layout| {
+      vk::AttachmentReference::builder()
+        .attachment(attachment)
+        .layout(layout)
+        .build()
+    };
+    let color_attachment_ref = attachment_ref(0, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    let depth_attachment_ref = attachment_ref(1, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+    // Output locations 1 to 3 of the G-buffer fragment shader.
+    let g_buffer_color_refs = [
+      color_attachment_ref,
+      attachment_ref(2, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+      attachment_ref(3, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+      attachment_ref(4, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+    ];
+    // Input attachment indices 0 to 3 of the lighting fragment shader.
+    let lighting_input_refs = [
+      attachment_ref(2, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+      attachment_ref(3, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+      attachment_ref(4, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+      attachment_ref(1, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+    ];
+    let color_attachment_refs = [color_attachment_ref];
+
+    let mut subpass_descriptions = [vk::SubpassDescription::default(); 3];
+    subpass_descriptions[G_BUFFER_SUBPASS as usize] = vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .color_attachments(&g_buffer_color_refs)
+      .depth_stencil_attachment(&depth_attachment_ref)
+      .build();
+    // No depth attachment, the depth buffer is read as an input instead.
+    subpass_descriptions[LIGHTING_SUBPASS as usize] = vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .input_attachments(&lighting_input_refs)
+      .color_attachments(&color_attachment_refs)
+      .build();
+    subpass_descriptions[FORWARD_SUBPASS as usize] = vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .color_attachments(&color_attachment_refs)
+      .depth_stencil_attachment(&depth_attachment_ref)
+      .build();
+
+    let depth_stages =
+      vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    let mut dependencies = vec![
+      // Wait until the previous frame's lighting and post processing are done
+      // reading, and the swapchain image is acquired.
+      vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(G_BUFFER_SUBPASS)
+        .src_stage_mask(
+          vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::FRAGMENT_SHADER
+            | depth_stages,
+        )
+        .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages)
+        .dst_access_mask(
+          vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .build(),
+      // The lights read the G-buffer and depth of their own pixel, and add onto
+      // the color.
+      vk::SubpassDependency::builder()
+        .src_subpass(G_BUFFER_SUBPASS)
+        .dst_subpass(LIGHTING_SUBPASS)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages)
+        .src_access_mask(
+          vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .dst_stage_mask(
+          vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        )
+        .dst_access_mask(
+          vk::AccessFlags::INPUT_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        )
+        .dependency_flags(vk::DependencyFlags::BY_REGION)
+        .build(),
+      // Forward objects are drawn over the lit color and depth tested against
+      // the G-buffer's depth, once the lights are done reading it.
+      vk::SubpassDependency::builder()
+        .src_subpass(LIGHTING_SUBPASS)
+        .dst_subpass(FORWARD_SUBPASS)
+        .src_stage_mask(
+          vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        )
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages)
+        .dst_access_mask(
+          vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .dependency_flags(vk::DependencyFlags::BY_REGION)
+        .build(),
+    ];
+    // The post process chain samples the scene (and depth) afterwards.
+    if post_processed {
+      let (depth_src_stage_mask, depth_src_access_mask) = if depth_sampled {
+        (
+          vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+          vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+      } else {
+        (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty())
+      };
+      dependencies.push(
+        vk::SubpassDependency::builder()
+          .src_subpass(FORWARD_SUBPASS)
+          .dst_subpass(vk::SUBPASS_EXTERNAL)
+          .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_src_stage_mask)
+          .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | depth_src_access_mask)
+          .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+          .dst_access_mask(vk::AccessFlags::SHADER_READ)
+          .build(),
+      );
+    }
+
+    let render_pass_ci = vk::RenderPassCreateInfo::builder()
+      .attachments(&attachments)
+      .subpasses(&subpass_descriptions)
+      .dependencies(&dependencies)
+      .build();
+
+    Ok(unsafe { logical_device.create_render_pass(&render_pass_ci, None)? })
+  }
+
+  /// Creates a single sampled render pass for drawing into a render texture.
+  /// The color attachment ends up ready to be sampled, and the dependencies
+  /// order it after the previous frame's sampling and before later sampling.
+  fn create_render_texture_pass(
+    logical_device: &Device, color_format: vk::Format, depth_format: vk::Format,
+  ) -> SarektResult<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription::builder()
+      .format(color_format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::CLEAR)
+      .store_op(vk::AttachmentStoreOp::STORE)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED) // Cleared anyway.
+      .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) // Sampled afterwards.
+      .build();
+    let color_attachment_refs = [vk::AttachmentReference::builder()
+      .attachment(0)
+      .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+      .build()];
+
+    let depth_attachment = vk::AttachmentDescription::builder()
+      .format(depth_format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::CLEAR)
+      .store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED)
+      .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+      .build();
+    let depth_attachment_ref = vk::AttachmentReference::builder()
+      .attachment(1)
+      .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+      .build();
+
+    let attachments = [color_attachment, depth_attachment];
+    let subpass_descriptions = [vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .color_attachments(&color_attachment_refs)
+      .depth_stencil_attachment(&depth_attachment_ref)
+      .build()];
+
+    let dependencies = [
+      // Don't overwrite the texture or depth buffer while an earlier pass still
+      // reads them.
+      vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0u32)
+        .src_stage_mask(
+          vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        )
+        .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst_stage_mask(
+          vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        )
+        .dst_access_mask(
+          vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .build(),
+      // Later draws that sample the texture wait for it to be written.
+      vk::SubpassDependency::builder()
+        .src_subpass(0u32)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .build(),
+    ];
+
+    let render_pass_ci = vk::RenderPassCreateInfo::builder()
+      .attachments(&attachments)
+      .subpasses(&subpass_descriptions)
+      .dependencies(&dependencies)
       .build();
 
     Ok(unsafe { logical_device.create_render_pass(&render_pass_ci, None)? })
@@ -377,9 +1180,10 @@ impl Pipelines {
   /// pipeline creation via config if it wont be used to save resources.
   ///
   /// TODO(issue#17) RENDERING_CAPABILITIES enable pipeline cache.
+  #[allow(clippy::too_many_arguments)]
   fn create_base_graphics_pipeline_and_shaders(
     logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
-    extent: vk::Extent2D, render_pass: vk::RenderPass,
+    extent: vk::Extent2D, render_pass: vk::RenderPass, subpass: u32,
     resolve_attachment: Option<ResolveAttachment>, depth_buffer: DepthAttachment,
     msaa_config: &MsaaConfig,
   ) -> SarektResult<BasePipelineBundle> {
@@ -394,6 +1198,7 @@ impl Pipelines {
       shader_store,
       extent,
       render_pass,
+      subpass,
       resolve_attachment,
       depth_buffer,
       msaa_config,
@@ -441,29 +1246,87 @@ impl Pipelines {
 
   fn create_base_graphics_pipeline(
     logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
-    extent: vk::Extent2D, render_pass: vk::RenderPass,
+    extent: vk::Extent2D, render_pass: vk::RenderPass, subpass: u32,
     resolve_attachment: Option<ResolveAttachment>, depth_buffer: DepthAttachment,
     msaa_config: &MsaaConfig, descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     vertex_shader_handle: VulkanShaderHandle, fragment_shader_handle: VulkanShaderHandle,
   ) -> SarektResult<BasePipelineBundle> {
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(&descriptor_set_layouts)
+      .build();
+    let pipeline_layout =
+      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? };
+
+    let pipeline = Self::create_graphics_pipeline(
+      logical_device,
+      shader_store,
+      &vertex_shader_handle,
+      &fragment_shader_handle,
+      pipeline_layout,
+      ForwardShaderVariant::Plain,
+      Some(extent),
+      render_pass,
+      subpass,
+      1,
+      msaa_config,
+      &PrimitiveState::default(),
+      None,
+    )?;
+
+    Ok(BasePipelineBundle::new(
+      pipeline,
+      pipeline_layout,
+      descriptor_set_layouts,
+      resolve_attachment,
+      depth_buffer,
+      vertex_shader_handle,
+      fragment_shader_handle,
+    ))
+  }
+
+  /// Creates the forward pipeline with the given primitive state, as a
+  /// derivative of `base_pipeline` if there is one.  Vertices are read as the
+  /// shader variant's vertex type.  Without an extent the viewport and scissor
+  /// are dynamic state.  `color_attachment_count` is how many color
+  /// attachments its subpass has, only the G-buffer subpass has more than one.
+  fn create_graphics_pipeline(
+    logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    vertex_shader_handle: &VulkanShaderHandle, fragment_shader_handle: &VulkanShaderHandle,
+    pipeline_layout: vk::PipelineLayout, variant: ForwardShaderVariant,
+    extent: Option<vk::Extent2D>, render_pass: vk::RenderPass, subpass: u32,
+    color_attachment_count: usize, msaa_config: &MsaaConfig, primitive_state: &PrimitiveState,
+    base_pipeline: Option<vk::Pipeline>,
+  ) -> SarektResult<vk::Pipeline> {
     let shader_store = shader_store.read().unwrap();
 
     let entry_point_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    // The point size is a specialization constant of the vertex shader.
+    let point_size_map_entries = [vk::SpecializationMapEntry::builder()
+      .constant_id(0)
+      .offset(0)
+      .size(std::mem::size_of::<f32>())
+      .build()];
+    let point_size_data = primitive_state.point_size.to_ne_bytes();
+    let vert_specialization_info = vk::SpecializationInfo::builder()
+      .map_entries(&point_size_map_entries)
+      .data(&point_size_data)
+      .build();
     let vert_shader_stage_ci = vk::PipelineShaderStageCreateInfo::builder()
       .stage(vk::ShaderStageFlags::VERTEX)
       .module(
         shader_store
-          .get_shader(&vertex_shader_handle)
+          .get_shader(vertex_shader_handle)
           .unwrap()
           .shader_handle,
       )
       .name(entry_point_name)
+      .specialization_info(&vert_specialization_info)
       .build();
     let frag_shader_stage_ci = vk::PipelineShaderStageCreateInfo::builder()
       .stage(vk::ShaderStageFlags::FRAGMENT)
       .module(
         shader_store
-          .get_shader(&fragment_shader_handle)
+          .get_shader(fragment_shader_handle)
           .unwrap()
           .shader_handle,
       )
@@ -472,42 +1335,54 @@ impl Pipelines {
 
     let shader_stage_cis = [vert_shader_stage_ci, frag_shader_stage_ci];
 
-    let binding_descs = [DefaultForwardShaderVertex::get_binding_description()];
-    let attr_descs = DefaultForwardShaderVertex::get_attribute_descriptions();
+    let (binding_desc, attr_descs) = Self::get_variant_vertex_input(variant);
+    let binding_descs = [binding_desc];
     let vertex_input_ci = vk::PipelineVertexInputStateCreateInfo::builder()
       .vertex_binding_descriptions(&binding_descs)
       .vertex_attribute_descriptions(&attr_descs)
       .build();
 
     let input_assembly_ci = vk::PipelineInputAssemblyStateCreateInfo::builder()
-      .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-      .primitive_restart_enable(false)
+      .topology(primitive_state.topology.into())
+      .primitive_restart_enable(primitive_state.primitive_restart)
       .build();
 
+    let viewport_extent = extent.unwrap_or_default();
     let viewport = vk::Viewport::builder()
       .x(0f32)
       .y(0f32)
-      .width(extent.width as f32)
-      .height(extent.height as f32)
+      .width(viewport_extent.width as f32)
+      .height(viewport_extent.height as f32)
       .min_depth(0f32)
       .max_depth(1.0f32)
       .build();
     let viewports = [viewport];
     let scissor = vk::Rect2D::builder()
       .offset(vk::Offset2D::default())
-      .extent(extent)
+      .extent(viewport_extent)
       .build();
     let scissors = [scissor];
-    let viewport_state_ci = vk::PipelineViewportStateCreateInfo::builder()
-      .viewports(&viewports)
-      .scissors(&scissors)
+    let viewport_state_ci = if extent.is_some() {
+      vk::PipelineViewportStateCreateInfo::builder()
+        .viewports(&viewports)
+        .scissors(&scissors)
+        .build()
+    } else {
+      vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1)
+        .build()
+    };
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_ci = vk::PipelineDynamicStateCreateInfo::builder()
+      .dynamic_states(&dynamic_states)
       .build();
 
     let raster_state_ci = vk::PipelineRasterizationStateCreateInfo::builder()
       .depth_clamp_enable(false) // Don't clamp things to the edge, cull them.
       .rasterizer_discard_enable(false) // Don't discard geometry.
       .polygon_mode(vk::PolygonMode::FILL) // Fill stuff in. Could also be point or line.
-      .line_width(1.0f32)
+      .line_width(primitive_state.line_width)
       .cull_mode(vk::CullModeFlags::BACK) // Back face culling.
       .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
       // Dont turn on depth bias, not adding constants to depth, same with depth_bias_clamp, bias_constant_factor, bias_slope_factor.
@@ -527,9 +1402,10 @@ impl Pipelines {
       .build();
 
     // TODO(issue#18) CONFIG enable stencil.
+    // Transparent objects don't hide what is drawn behind them afterwards.
     let depth_stencil_ci = vk::PipelineDepthStencilStateCreateInfo::builder()
       .depth_test_enable(true)
-      .depth_write_enable(true)
+      .depth_write_enable(!primitive_state.alpha_blending)
       .depth_compare_op(vk::CompareOp::LESS) // Lower depth closer.
       .depth_bounds_test_enable(false) // Not using bounds test.
       .min_depth_bounds(0.0f32)
@@ -539,25 +1415,33 @@ impl Pipelines {
       // .back(vk::StencilOpState)
       .build();
 
-    let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::builder()
-      .color_write_mask(vk::ColorComponentFlags::all()) // RGBA
-      .blend_enable(false)
-      // everything else optional because its not enabled.
-      .build();
-    let attachments = [color_blend_attachment_state];
+    let color_blend_attachment_state = if primitive_state.alpha_blending {
+      // Over operator, keeping the alpha of what is behind.
+      vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all())
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build()
+    } else {
+      vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all()) // RGBA
+        .blend_enable(false)
+        // everything else optional because its not enabled.
+        .build()
+    };
+    let attachments = vec![color_blend_attachment_state; color_attachment_count];
     let color_blend_ci = vk::PipelineColorBlendStateCreateInfo::builder()
       .logic_op_enable(false)
       .logic_op(vk::LogicOp::COPY)
       .attachments(&attachments)
       .build();
 
-    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
-      .set_layouts(&descriptor_set_layouts)
-      .build();
-    let pipeline_layout =
-      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? };
-
-    let base_graphics_pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
+    let mut graphics_pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
       .flags(vk::PipelineCreateFlags::ALLOW_DERIVATIVES)
       .stages(&shader_stage_cis)
       .vertex_input_state(&vertex_input_ci)
@@ -569,13 +1453,19 @@ impl Pipelines {
       .color_blend_state(&color_blend_ci)
       .layout(pipeline_layout)
       .render_pass(render_pass)
-      .subpass(0) // The subpass where the pipeline will be used.
-      // .base_pipeline_handle() // No basepipeline handle, this is the base pipeline!
-      // .base_pipeline_index(-1)
-      .build();
+      .subpass(subpass); // The subpass where the pipeline will be used.
+    if extent.is_none() {
+      graphics_pipeline_ci = graphics_pipeline_ci.dynamic_state(&dynamic_state_ci);
+    }
+    if let Some(base_pipeline) = base_pipeline {
+      graphics_pipeline_ci = graphics_pipeline_ci
+        .flags(vk::PipelineCreateFlags::DERIVATIVE)
+        .base_pipeline_handle(base_pipeline)
+        .base_pipeline_index(-1);
+    }
 
     // TODO(issue#17) RENDERING_CAPABILITIES use pipeline cache.
-    let pipeline_create_infos = [base_graphics_pipeline_ci];
+    let pipeline_create_infos = [graphics_pipeline_ci.build()];
     let pipeline = unsafe {
       logical_device.create_graphics_pipelines(
         vk::PipelineCache::null(),
@@ -587,22 +1477,58 @@ impl Pipelines {
       return Err(err.1.into());
     }
 
-    Ok(BasePipelineBundle::new(
-      pipeline.unwrap()[0],
-      pipeline_layout,
-      base_graphics_pipeline_ci,
-      descriptor_set_layouts,
-      resolve_attachment,
-      depth_buffer,
-      vertex_shader_handle,
-      fragment_shader_handle,
-    ))
+    Ok(pipeline.unwrap()[0])
+  }
+
+  /// Line widths and point sizes other than 1 need their feature and must be
+  /// in the device's range.
+  fn check_primitive_size(
+    name: &str, size: f32, feature: vk::Bool32, range: [f32; 2],
+  ) -> SarektResult<()> {
+    if size == 1.0 {
+      return Ok(());
+    }
+    if feature != vk::TRUE {
+      return Err(SarektError::UnsupportedPrimitiveState(format!(
+        "{} {} needs a device feature this device lacks, only 1 is supported",
+        name, size
+      )));
+    }
+    if size < range[0] || size > range[1] {
+      return Err(SarektError::UnsupportedPrimitiveState(format!(
+        "{} {} is outside the device's range of {} to {}",
+        name, size, range[0], range[1]
+      )));
+    }
+    Ok(())
+  }
+
+  /// The subpass forward pipelines draw in, depending on whether there is a
+  /// G-buffer (and so a deferred render pass).
+  fn forward_subpass_of(g_buffer: Option<&GBufferAttachments>) -> u32 {
+    if g_buffer.is_some() {
+      FORWARD_SUBPASS
+    } else {
+      0
+    }
+  }
+
+  /// The images the forward pass draws into (or resolves into) for each
+  /// swapchain image, the scene target for all of them when post processing.
+  fn forward_render_targets(
+    render_targets: &[ImageAndView], scene_target: Option<&ImageAndMemory>,
+  ) -> Vec<ImageAndView> {
+    match scene_target {
+      Some(scene_target) => vec![scene_target.image_and_view; render_targets.len()],
+      None => render_targets.to_vec(),
+    }
   }
 
   fn create_framebuffers(
     logical_device: &Device, render_pass: vk::RenderPass,
     resolve_attachment: Option<&ResolveAttachment>, depth_buffer: &DepthAttachment,
-    render_target_images: &[ImageAndView], extent: vk::Extent2D,
+    g_buffer: Option<&GBufferAttachments>, render_target_images: &[ImageAndView],
+    extent: vk::Extent2D,
   ) -> SarektResult<Vec<vk::Framebuffer>> {
     let mut framebuffers = Vec::with_capacity(render_target_images.len());
 
@@ -614,7 +1540,7 @@ impl Pipelines {
       // With the resolve, we draw to the color buffer and the swapchain becomes the
       // resolve attachment, without it there is no resolve step so we draw directly
       // to color buffer.
-      let attachments = if let Some(resolve_attachment) = resolve_attachment {
+      let mut attachments = if let Some(resolve_attachment) = resolve_attachment {
         vec![
           // Resolve attachment is swapchain itself if the resolve attachment is not present (AA
           // off).
@@ -628,6 +1554,11 @@ impl Pipelines {
           depth_buffer.image_and_memory.image_and_view.view,
         ]
       };
+      // The deferred render pass has the G-buffer after those, see
+      // create_deferred_render_pass.
+      if let Some(g_buffer) = g_buffer {
+        attachments.extend_from_slice(&g_buffer.views());
+      }
 
       let framebuffer_ci = vk::FramebufferCreateInfo::builder()
         .render_pass(render_pass)
@@ -643,3 +1574,58 @@ impl Pipelines {
     Ok(framebuffers)
   }
 }
+
+/// The shaders of a variant of the forward shaders, and its layouts.  Unlike
+/// the base pipeline's they don't depend on the swapchain, so they are kept
+/// until the renderer is dropped.
+struct VariantShaders {
+  vertex_shader_handle: VulkanShaderHandle,
+  fragment_shader_handle: VulkanShaderHandle,
+  descriptor_set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+}
+impl VariantShaders {
+  fn new(
+    logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    vertex_shader: &[u32], fragment_shader: &[u32],
+    descriptor_set_layout_bindings: &[vk::DescriptorSetLayoutBinding],
+  ) -> SarektResult<Self> {
+    let vertex_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(vertex_shader),
+      ShaderType::Vertex,
+    )?;
+    let fragment_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(fragment_shader),
+      ShaderType::Fragment,
+    )?;
+
+    let descriptor_set_layout_ci = vk::DescriptorSetLayoutCreateInfo::builder()
+      .bindings(descriptor_set_layout_bindings)
+      .build();
+    let descriptor_set_layout =
+      unsafe { logical_device.create_descriptor_set_layout(&descriptor_set_layout_ci, None)? };
+
+    let set_layouts = [descriptor_set_layout];
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(&set_layouts)
+      .build();
+    let pipeline_layout =
+      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? };
+
+    Ok(Self {
+      vertex_shader_handle,
+      fragment_shader_handle,
+      descriptor_set_layout,
+      pipeline_layout,
+    })
+  }
+
+  /// Must be called during renderer's drop.
+  unsafe fn cleanup(&self, logical_device: &Device) {
+    info!("Destroying forward shader variant pipeline layout...");
+    logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+    logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+  }
+}
@@ -0,0 +1,1206 @@
+use crate::{
+  error::{SarektError, SarektResult},
+  image_data::ImageDataFormat,
+  renderer::{
+    buffers_and_images::{
+      BufferImageHandle, BufferImageStore, MagnificationMinificationFilter, TextureAddressMode,
+    },
+    config::{AntiAliasingMode, NumSamples},
+    post_process::{PostProcessPass, TonemapOperator},
+    shaders::ShaderStore,
+    vulkan::{
+      images::ImageAndView, vulkan_buffer_image_functions::ImageAndMemory,
+      vulkan_shader_functions::VulkanShaderFunctions, VulkanShaderHandle,
+    },
+    ShaderCode, ShaderType, VulkanBufferImageFunctions,
+  },
+};
+use ash::{
+  version::{DeviceV1_0, InstanceV1_0},
+  vk, Device, Instance,
+};
+use log::{info, warn};
+use std::{
+  cell::Cell,
+  collections::HashMap,
+  ffi::CStr,
+  sync::{Arc, RwLock},
+};
+use ultraviolet as uv;
+use vk_shader_macros::include_glsl;
+
+/// Vertex shader of every post process pass, draws a full screen triangle and
+/// passes its texture coordinates on.
+pub const POST_PROCESS_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_process.vert");
+/// Copies the scene as is, the whole chain when it has no passes.
+pub const POST_COPY_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_copy.frag");
+pub const POST_TONEMAP_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_tonemap.frag");
+/// First step of bloom, keeps what is brighter than the threshold.
+pub const POST_BLOOM_EXTRACT_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_bloom_extract.frag");
+/// Separable gaussian blur along the direction in the parameters.
+pub const POST_BLUR_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_blur.frag");
+/// Last step of bloom, adds the blurred highlights to the source.
+pub const POST_BLOOM_COMPOSITE_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_bloom_composite.frag");
+pub const POST_FXAA_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_fxaa.frag");
+pub const POST_COLOR_GRADING_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_color_grading.frag");
+pub const POST_VIGNETTE_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_vignette.frag");
+/// Encodes the output of the chain for HDR10 swapchains.
+pub const POST_HDR10_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_hdr10.frag");
+/// Filters the supersampled scene down to the swapchain's size.
+pub const POST_DOWNSAMPLE_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_downsample.frag");
+/// Computes how far each pixel moved since the previous frame from the depth
+/// buffer, for temporal anti-aliasing.
+pub const POST_VELOCITY_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_velocity.frag");
+pub const POST_VELOCITY_MSAA_FRAGMENT_SHADER: &[u32] =
+  include_glsl!("shaders/sarekt_post_velocity_msaa.frag");
+/// Blends the frame into the reprojected history.
+pub const POST_TAA_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_post_taa.frag");
+
+/// Scene formats tried after the one in the config, the last one isn't HDR.
+const SCENE_FORMAT_FALLBACKS: [ImageDataFormat; 3] = [
+  ImageDataFormat::R16G16B16A16Sfloat,
+  ImageDataFormat::R32G32B32A32Sfloat,
+  ImageDataFormat::R8G8B8A8Unorm,
+];
+/// Nits 1.0 is displayed at on HDR10 swapchains, the reference white of ITU-R
+/// BT.2408.
+const HDR10_PAPER_WHITE_NITS: f32 = 203f32;
+/// Weight of the current frame when blending it into the history.
+const TAA_CURRENT_FRAME_WEIGHT: f32 = 0.1f32;
+/// Number of jitter offsets cycled through.
+const TAA_JITTER_SEQUENCE_LENGTH: u64 = 8;
+const VELOCITY_FORMAT: ImageDataFormat = ImageDataFormat::R16G16Sfloat;
+
+pub type VulkanPostProcessPass = PostProcessPass<VulkanBufferImageFunctions, VulkanShaderFunctions>;
+
+/// Matches PostProcessConstants in the post process shaders.  Only the
+/// velocity pass declares the reprojection, the rest stop before it.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct PostProcessConstants {
+  parameters: [f32; 8],
+  texel_size: [f32; 2],
+  // mat4 is 16 byte aligned.
+  _padding: [f32; 2],
+  reprojection: [f32; 16],
+}
+impl PostProcessConstants {
+  fn new(parameters: [f32; 8], source_extent: vk::Extent3D) -> Self {
+    Self {
+      parameters,
+      texel_size: [
+        1f32 / source_extent.width as f32,
+        1f32 / source_extent.height as f32,
+      ],
+      _padding: [0f32; 2],
+      reprojection: [0f32; 16],
+    }
+  }
+}
+
+/// An image a pass samples.
+#[derive(Copy, Clone, Debug)]
+struct PassInput {
+  view: vk::ImageView,
+  sampler: vk::Sampler,
+  layout: vk::ImageLayout,
+}
+impl From<&ImageAndMemory> for PassInput {
+  fn from(image: &ImageAndMemory) -> PassInput {
+    PassInput {
+      view: image.image_and_view.view,
+      sampler: image.sampler.unwrap(),
+      layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }
+  }
+}
+
+/// An intermediate color target of the chain and the framebuffer that draws
+/// into it.
+struct PostProcessTarget {
+  // Keeps the image alive.
+  _handle: BufferImageHandle<VulkanBufferImageFunctions>,
+  image: ImageAndMemory,
+  framebuffer: vk::Framebuffer,
+}
+
+/// The images the chain draws in, recreated with the swapchain.
+struct PostProcessTargets {
+  // The forward pass draws into it with its own framebuffer, at the scene
+  // extent.  The rest are at the swapchain's.
+  scene: (
+    BufferImageHandle<VulkanBufferImageFunctions>,
+    ImageAndMemory,
+  ),
+  full_resolution: [PostProcessTarget; 2],
+  half_resolution: [PostProcessTarget; 2],
+  // Only for temporal anti-aliasing, the history is written on alternate
+  // frames.
+  velocity: Option<PostProcessTarget>,
+  history: Option<[PostProcessTarget; 2]>,
+}
+impl PostProcessTargets {
+  fn new(
+    logical_device: &Device,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    intermediate_pass: vk::RenderPass, temporal: Option<&Temporal>, format: ImageDataFormat,
+    scene_extent: vk::Extent2D, extent: vk::Extent2D,
+  ) -> SarektResult<Self> {
+    let create_image = |extent: vk::Extent2D, format: ImageDataFormat| -> SarektResult<_> {
+      let (handle, buffer_or_image) = BufferImageStore::create_render_texture(
+        buffer_image_store,
+        (extent.width, extent.height),
+        format,
+        MagnificationMinificationFilter::Linear,
+        MagnificationMinificationFilter::Linear,
+        TextureAddressMode::ClampToEdge,
+        TextureAddressMode::ClampToEdge,
+      )?;
+      Ok((handle, buffer_or_image.handle.image()?))
+    };
+    let create_target_in = |extent: vk::Extent2D,
+                            format: ImageDataFormat,
+                            render_pass: vk::RenderPass|
+     -> SarektResult<PostProcessTarget> {
+      let (handle, image) = create_image(extent, format)?;
+      let attachments = [image.image_and_view.view];
+      let framebuffer_ci = vk::FramebufferCreateInfo::builder()
+        .render_pass(render_pass)
+        .attachments(&attachments)
+        .width(extent.width)
+        .height(extent.height)
+        .layers(1)
+        .build();
+      let framebuffer = unsafe { logical_device.create_framebuffer(&framebuffer_ci, None)? };
+
+      Ok(PostProcessTarget {
+        _handle: handle,
+        image,
+        framebuffer,
+      })
+    };
+
+    let create_target = |extent: vk::Extent2D| create_target_in(extent, format, intermediate_pass);
+
+    let half_extent = PostProcessChain::half_extent(extent);
+    let (velocity, history) = match temporal {
+      Some(temporal) => (
+        Some(create_target_in(
+          extent,
+          VELOCITY_FORMAT,
+          temporal.velocity_pass,
+        )?),
+        Some([create_target(extent)?, create_target(extent)?]),
+      ),
+      None => (None, None),
+    };
+    Ok(Self {
+      scene: create_image(scene_extent, format)?,
+      full_resolution: [create_target(extent)?, create_target(extent)?],
+      half_resolution: [create_target(half_extent)?, create_target(half_extent)?],
+      velocity,
+      history,
+    })
+  }
+
+  fn framebuffers(&self) -> impl Iterator<Item = vk::Framebuffer> + '_ {
+    self
+      .full_resolution
+      .iter()
+      .chain(self.half_resolution.iter())
+      .chain(self.velocity.iter())
+      .chain(self.history.iter().flatten())
+      .map(|target| target.framebuffer)
+  }
+}
+
+/// Where a pass draws.
+#[derive(Copy, Clone, Debug)]
+struct PassTarget {
+  render_pass: vk::RenderPass,
+  framebuffer: vk::Framebuffer,
+  extent: vk::Extent2D,
+}
+
+/// The fragment shaders of the built-in passes.
+struct BuiltInShaders {
+  copy: VulkanShaderHandle,
+  tonemap: VulkanShaderHandle,
+  bloom_extract: VulkanShaderHandle,
+  blur: VulkanShaderHandle,
+  bloom_composite: VulkanShaderHandle,
+  fxaa: VulkanShaderHandle,
+  color_grading: VulkanShaderHandle,
+  vignette: VulkanShaderHandle,
+  hdr10: VulkanShaderHandle,
+  downsample: VulkanShaderHandle,
+  velocity: VulkanShaderHandle,
+  velocity_msaa: VulkanShaderHandle,
+  taa: VulkanShaderHandle,
+}
+
+/// Temporal anti-aliasing resources and the camera and history state carried
+/// from frame to frame.
+struct Temporal {
+  // Draws into the velocity target, which has a format of its own.
+  velocity_pass: vk::RenderPass,
+  // Point samples the depth buffer, which has no sampler of its own.
+  depth_sampler: vk::Sampler,
+  multisampled_depth: bool,
+  view_projection: uv::Mat4,
+  // None until the first frame is drawn after the history was (re)created.
+  previous_view_projection: Cell<Option<uv::Mat4>>,
+  history_index: Cell<usize>,
+}
+
+/// Runs the post process chain after the forward pass, see
+/// [post_process](../../../post_process/index.html).
+///
+/// The forward pass draws into the scene target.  The passes then read the
+/// output of the one before and draw into the two full resolution targets in
+/// turn, or into the swapchain image for the last one.  Bloom blurs in the two
+/// half resolution targets.  The render passes order each pass after the
+/// sampling of the one before, including the previous frame's, so the targets
+/// are shared by all frames in flight.
+///
+/// When presenting to an HDR10 swapchain the last pass draws into a full
+/// resolution target too, and an extra pass encodes it into the swapchain
+/// image.
+///
+/// Anti-aliasing runs before the passes.  With supersampling the scene target
+/// is larger than the swapchain and is filtered down into a full resolution
+/// target first.  Temporal anti-aliasing computes the velocities from the depth
+/// buffer and blends the frame into the history target written the frame
+/// before, which the passes then read.
+pub struct PostProcessChain {
+  logical_device: Arc<Device>,
+  passes: Vec<VulkanPostProcessPass>,
+  scene_format: ImageDataFormat,
+
+  vertex_shader_handle: VulkanShaderHandle,
+  built_in_shaders: BuiltInShaders,
+  descriptor_set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+  // Draws into the intermediate targets, leaving them ready to be sampled.
+  intermediate_pass: vk::RenderPass,
+  // Draws into the swapchain image, leaving it ready to present.
+  present_pass: vk::RenderPass,
+  // By fragment shader and render pass, created on first use.
+  pipelines: RwLock<HashMap<(vk::ShaderModule, vk::RenderPass), vk::Pipeline>>,
+
+  targets: PostProcessTargets,
+  present_framebuffers: Vec<vk::Framebuffer>,
+  present_color_space: vk::ColorSpaceKHR,
+  temporal: Option<Temporal>,
+  scene_extent: vk::Extent2D,
+  extent: vk::Extent2D,
+}
+impl PostProcessChain {
+  /// `scene_format` must be one returned by `find_scene_format`.
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(
+    logical_device: Arc<Device>, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    scene_format: ImageDataFormat, present_format: vk::Format,
+    present_color_space: vk::ColorSpaceKHR, anti_aliasing: AntiAliasingMode,
+    num_msaa_samples: NumSamples, render_targets: &[ImageAndView], scene_extent: vk::Extent2D,
+    extent: vk::Extent2D,
+  ) -> SarektResult<Self> {
+    let load_fragment_shader =
+      |code| ShaderStore::load_shader(shader_store, &ShaderCode::Spirv(code), ShaderType::Fragment);
+    let vertex_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(POST_PROCESS_VERTEX_SHADER),
+      ShaderType::Vertex,
+    )?;
+    let built_in_shaders = BuiltInShaders {
+      copy: load_fragment_shader(POST_COPY_FRAGMENT_SHADER)?,
+      tonemap: load_fragment_shader(POST_TONEMAP_FRAGMENT_SHADER)?,
+      bloom_extract: load_fragment_shader(POST_BLOOM_EXTRACT_FRAGMENT_SHADER)?,
+      blur: load_fragment_shader(POST_BLUR_FRAGMENT_SHADER)?,
+      bloom_composite: load_fragment_shader(POST_BLOOM_COMPOSITE_FRAGMENT_SHADER)?,
+      fxaa: load_fragment_shader(POST_FXAA_FRAGMENT_SHADER)?,
+      color_grading: load_fragment_shader(POST_COLOR_GRADING_FRAGMENT_SHADER)?,
+      vignette: load_fragment_shader(POST_VIGNETTE_FRAGMENT_SHADER)?,
+      hdr10: load_fragment_shader(POST_HDR10_FRAGMENT_SHADER)?,
+      downsample: load_fragment_shader(POST_DOWNSAMPLE_FRAGMENT_SHADER)?,
+      velocity: load_fragment_shader(POST_VELOCITY_FRAGMENT_SHADER)?,
+      velocity_msaa: load_fragment_shader(POST_VELOCITY_MSAA_FRAGMENT_SHADER)?,
+      taa: load_fragment_shader(POST_TAA_FRAGMENT_SHADER)?,
+    };
+
+    // The source and the scene (or the bloom or color grading lookup table,
+    // or the history), and the velocities for temporal anti-aliasing.  The
+    // parameters come in through push constants.
+    let bindings: Vec<_> = (0..3)
+      .map(|binding| {
+        vk::DescriptorSetLayoutBinding::builder()
+          .binding(binding)
+          .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+          .descriptor_count(1)
+          .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+          .build()
+      })
+      .collect();
+    let descriptor_set_layout_ci = vk::DescriptorSetLayoutCreateInfo::builder()
+      .bindings(&bindings)
+      .build();
+    let descriptor_set_layout =
+      unsafe { logical_device.create_descriptor_set_layout(&descriptor_set_layout_ci, None)? };
+
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+      .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+      .offset(0)
+      .size(std::mem::size_of::<PostProcessConstants>() as u32)
+      .build()];
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(&set_layouts)
+      .push_constant_ranges(&push_constant_ranges)
+      .build();
+    let pipeline_layout =
+      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? };
+
+    let intermediate_pass = Self::create_render_pass(
+      &logical_device,
+      scene_format.into(),
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    )?;
+    let present_pass = Self::create_render_pass(
+      &logical_device,
+      present_format,
+      vk::ImageLayout::PRESENT_SRC_KHR,
+    )?;
+
+    let temporal = if anti_aliasing == AntiAliasingMode::Temporal {
+      let velocity_pass = Self::create_render_pass(
+        &logical_device,
+        VELOCITY_FORMAT.into(),
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      )?;
+      let depth_sampler_ci = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::NEAREST)
+        .min_filter(vk::Filter::NEAREST)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .anisotropy_enable(false)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .unnormalized_coordinates(false)
+        .compare_enable(false)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .build();
+      let depth_sampler = unsafe { logical_device.create_sampler(&depth_sampler_ci, None)? };
+
+      Some(Temporal {
+        velocity_pass,
+        depth_sampler,
+        multisampled_depth: !matches!(num_msaa_samples, NumSamples::One),
+        view_projection: uv::Mat4::identity(),
+        previous_view_projection: Cell::new(None),
+        history_index: Cell::new(0),
+      })
+    } else {
+      None
+    };
+
+    let targets = PostProcessTargets::new(
+      &logical_device,
+      buffer_image_store,
+      intermediate_pass,
+      temporal.as_ref(),
+      scene_format,
+      scene_extent,
+      extent,
+    )?;
+    let present_framebuffers =
+      Self::create_present_framebuffers(&logical_device, present_pass, render_targets, extent)?;
+
+    Ok(Self {
+      logical_device,
+      passes: Vec::new(),
+      scene_format,
+      vertex_shader_handle,
+      built_in_shaders,
+      descriptor_set_layout,
+      pipeline_layout,
+      intermediate_pass,
+      present_pass,
+      pipelines: RwLock::new(HashMap::new()),
+      targets,
+      present_framebuffers,
+      present_color_space,
+      temporal,
+      scene_extent,
+      extent,
+    })
+  }
+
+  /// The first of the requested format and the fallbacks that the device can
+  /// render to with blending and sample with linear filtering.
+  pub fn find_scene_format(
+    instance: &Instance, physical_device: vk::PhysicalDevice, requested_format: ImageDataFormat,
+  ) -> SarektResult<ImageDataFormat> {
+    let features = vk::FormatFeatureFlags::COLOR_ATTACHMENT
+      | vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND
+      | vk::FormatFeatureFlags::SAMPLED_IMAGE
+      | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+
+    let candidates =
+      std::iter::once(requested_format).chain(SCENE_FORMAT_FALLBACKS.iter().copied());
+    for format in candidates {
+      let supported_features = unsafe {
+        instance
+          .get_physical_device_format_properties(physical_device, format.into())
+          .optimal_tiling_features
+      };
+      if supported_features.contains(features) {
+        if format != requested_format {
+          warn!(
+            "Scene color format {:?} is not supported, drawing the scene in {:?} instead",
+            requested_format, format
+          );
+        }
+        return Ok(format);
+      }
+    }
+
+    Err(SarektError::NoSuitableSceneColorFormat)
+  }
+
+  /// The target the forward pass draws the scene into.
+  pub fn scene_target(&self) -> &ImageAndMemory {
+    &self.targets.scene.1
+  }
+
+  /// Offsets `projection` by a different subpixel amount each frame, for
+  /// temporal anti-aliasing.  Returned as is otherwise.
+  pub fn jitter_projection(&self, projection: uv::Mat4, frame_count: u64) -> uv::Mat4 {
+    if self.temporal.is_none() {
+      return projection;
+    }
+
+    // Halton (2, 3), centered on the pixel and converted to NDC.
+    let index = frame_count % TAA_JITTER_SEQUENCE_LENGTH + 1;
+    let jitter_x = (Self::halton(index, 2) - 0.5f32) * 2f32 / self.scene_extent.width as f32;
+    let jitter_y = (Self::halton(index, 3) - 0.5f32) * 2f32 / self.scene_extent.height as f32;
+
+    // Adds the offset after the perspective divide.
+    let mut jittered = projection;
+    for column in jittered.cols.iter_mut() {
+      column.x += jitter_x * column.w;
+      column.y += jitter_y * column.w;
+    }
+    jittered
+  }
+
+  /// The unjittered view projection of the frame about to be drawn, used to
+  /// reproject the history.
+  pub fn set_view_projection(&mut self, view_projection: uv::Mat4) {
+    if let Some(temporal) = &mut self.temporal {
+      temporal.view_projection = view_projection;
+    }
+  }
+
+  /// Replaces the passes, returning the old ones.  The frames in flight must
+  /// be done with them.
+  pub fn set_passes(
+    &mut self, passes: Vec<VulkanPostProcessPass>,
+    shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+  ) -> SarektResult<Vec<VulkanPostProcessPass>> {
+    for pass in passes.iter() {
+      match pass {
+        PostProcessPass::ColorGrading { lut } => {
+          let lut = buffer_image_store
+            .read()
+            .unwrap()
+            .get_image(lut)?
+            .handle
+            .image()?;
+          let extent = lut.extent();
+          if extent.width != extent.height * extent.height {
+            return Err(SarektError::InvalidPostProcessPass(format!(
+              "color grading lookup table of {}x{} is not N slices of NxN side by side",
+              extent.width, extent.height
+            )));
+          }
+        }
+        PostProcessPass::Custom {
+          fragment_shader, ..
+        } => {
+          let shader_type = shader_store
+            .read()
+            .unwrap()
+            .get_shader(fragment_shader)?
+            .shader_type;
+          if !matches!(shader_type, ShaderType::Fragment) {
+            return Err(SarektError::InvalidPostProcessPass(format!(
+              "custom pass shader is a {:?} shader, not a fragment shader",
+              shader_type
+            )));
+          }
+        }
+        _ => {}
+      }
+    }
+
+    // The old passes' shaders may be destroyed once they are handed back, and
+    // a new shader could get the same module handle as one of them.
+    for (_, pipeline) in self.pipelines.write().unwrap().drain() {
+      unsafe { self.logical_device.destroy_pipeline(pipeline, None) };
+    }
+
+    Ok(std::mem::replace(&mut self.passes, passes))
+  }
+
+  /// Recreates the targets, framebuffers and present render pass for a new
+  /// swapchain.  The old ones must already have been cleaned up with
+  /// `cleanup_swapchain_resources`.
+  #[allow(clippy::too_many_arguments)]
+  pub fn recreate_swapchain_resources(
+    &mut self, buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    present_format: vk::Format, present_color_space: vk::ColorSpaceKHR,
+    render_targets: &[ImageAndView], scene_extent: vk::Extent2D, extent: vk::Extent2D,
+  ) -> SarektResult<()> {
+    self.present_pass = Self::create_render_pass(
+      &self.logical_device,
+      present_format,
+      vk::ImageLayout::PRESENT_SRC_KHR,
+    )?;
+    self.targets = PostProcessTargets::new(
+      &self.logical_device,
+      buffer_image_store,
+      self.intermediate_pass,
+      self.temporal.as_ref(),
+      self.scene_format,
+      scene_extent,
+      extent,
+    )?;
+    self.present_framebuffers = Self::create_present_framebuffers(
+      &self.logical_device,
+      self.present_pass,
+      render_targets,
+      extent,
+    )?;
+    if let Some(temporal) = &self.temporal {
+      // The new history targets hold nothing yet.
+      temporal.previous_view_projection.set(None);
+    }
+    self.present_color_space = present_color_space;
+    self.scene_extent = scene_extent;
+    self.extent = extent;
+    Ok(())
+  }
+
+  /// Records every pass of the chain into the command buffer, after the
+  /// forward render pass has ended.  The last one draws into the swapchain
+  /// image at `image_index`.  `depth` is the forward pass's depth buffer, only
+  /// sampled for temporal anti-aliasing.
+  #[allow(clippy::too_many_arguments)]
+  pub fn draw_cmd(
+    &self, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    command_buffer: vk::CommandBuffer, descriptor_pool: vk::DescriptorPool, image_index: usize,
+    depth: &ImageAndMemory,
+  ) -> SarektResult<()> {
+    let shaders = &self.built_in_shaders;
+    let present_target = PassTarget {
+      render_pass: self.present_pass,
+      framebuffer: self.present_framebuffers[image_index],
+      extent: self.extent,
+    };
+    let intermediate_target = |target: &PostProcessTarget| PassTarget {
+      render_pass: self.intermediate_pass,
+      framebuffer: target.framebuffer,
+      extent: self.extent,
+    };
+    let scene = &self.targets.scene.1;
+    let draw_inputs = |fragment_shader: &VulkanShaderHandle,
+                       target: PassTarget,
+                       inputs: [PassInput; 3],
+                       constants: PostProcessConstants| {
+      let fragment_shader = shader_store
+        .read()
+        .unwrap()
+        .get_shader(fragment_shader)?
+        .shader_handle;
+      self.draw_pass_cmd(
+        shader_store,
+        command_buffer,
+        descriptor_pool,
+        fragment_shader,
+        target,
+        inputs,
+        constants,
+      )
+    };
+    let draw = |fragment_shader: &VulkanShaderHandle,
+                target: PassTarget,
+                source: &ImageAndMemory,
+                auxiliary: &ImageAndMemory,
+                parameters: [f32; 8]| {
+      draw_inputs(
+        fragment_shader,
+        target,
+        [source.into(), auxiliary.into(), auxiliary.into()],
+        PostProcessConstants::new(parameters, source.extent()),
+      )
+    };
+
+    // Anti-aliasing leaves its output in a full resolution target, which the
+    // passes continue from.  Starting the ping pong on the other one keeps it
+    // from being drawn over by the first pass.
+    let mut source = scene;
+    let mut next_target = 0;
+    if self.scene_extent != self.extent {
+      let output = &self.targets.full_resolution[0];
+      let scale = self.scene_extent.width as f32 / self.extent.width as f32;
+      draw(
+        &shaders.downsample,
+        intermediate_target(output),
+        source,
+        scene,
+        [scale, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+      )?;
+      source = &output.image;
+      next_target = 1;
+    }
+    if let (Some(temporal), Some(velocity), Some(history)) = (
+      &self.temporal,
+      &self.targets.velocity,
+      &self.targets.history,
+    ) {
+      let depth_input = PassInput {
+        view: depth.image_and_view.view,
+        sampler: temporal.depth_sampler,
+        layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+      };
+      let previous_view_projection = temporal.previous_view_projection.get();
+      let reprojection = previous_view_projection.unwrap_or(temporal.view_projection)
+        * temporal.view_projection.inversed();
+      let velocity_shader = if temporal.multisampled_depth {
+        &shaders.velocity_msaa
+      } else {
+        &shaders.velocity
+      };
+      let mut velocity_constants = PostProcessConstants::new([0f32; 8], depth.extent());
+      velocity_constants
+        .reprojection
+        .copy_from_slice(reprojection.as_array());
+      draw_inputs(
+        velocity_shader,
+        PassTarget {
+          render_pass: temporal.velocity_pass,
+          framebuffer: velocity.framebuffer,
+          extent: self.extent,
+        },
+        [depth_input, scene.into(), scene.into()],
+        velocity_constants,
+      )?;
+
+      let history_index = temporal.history_index.get();
+      let output = &history[history_index];
+      let history_valid = if previous_view_projection.is_some() {
+        1f32
+      } else {
+        0f32
+      };
+      draw_inputs(
+        &shaders.taa,
+        intermediate_target(output),
+        [
+          source.into(),
+          (&history[1 - history_index].image).into(),
+          (&velocity.image).into(),
+        ],
+        PostProcessConstants::new(
+          [
+            history_valid,
+            TAA_CURRENT_FRAME_WEIGHT,
+            0f32,
+            0f32,
+            0f32,
+            0f32,
+            0f32,
+            0f32,
+          ],
+          source.extent(),
+        ),
+      )?;
+      source = &output.image;
+
+      temporal.history_index.set(1 - history_index);
+      temporal
+        .previous_view_projection
+        .set(Some(temporal.view_projection));
+    }
+
+    // HDR10 needs an encoding pass at the end, which also stands in for the
+    // copy.
+    let hdr10 = self.present_color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT;
+    let hdr10_parameters = [
+      HDR10_PAPER_WHITE_NITS,
+      0f32,
+      0f32,
+      0f32,
+      0f32,
+      0f32,
+      0f32,
+      0f32,
+    ];
+    if self.passes.is_empty() {
+      return if hdr10 {
+        draw(
+          &shaders.hdr10,
+          present_target,
+          source,
+          scene,
+          hdr10_parameters,
+        )
+      } else {
+        draw(&shaders.copy, present_target, source, scene, [0f32; 8])
+      };
+    }
+
+    for (i, pass) in self.passes.iter().enumerate() {
+      let output = &self.targets.full_resolution[(next_target + i) % 2];
+      let target = if i + 1 == self.passes.len() && !hdr10 {
+        present_target
+      } else {
+        intermediate_target(output)
+      };
+
+      match pass {
+        &PostProcessPass::Tonemap { operator, exposure } => {
+          let operator = match operator {
+            TonemapOperator::Reinhard => 0f32,
+            TonemapOperator::Aces => 1f32,
+          };
+          draw(
+            &shaders.tonemap,
+            target,
+            source,
+            scene,
+            [exposure, operator, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+          )?;
+        }
+        &PostProcessPass::Bloom {
+          threshold,
+          intensity,
+        } => {
+          let [half_a, half_b] = &self.targets.half_resolution;
+          let half_extent = Self::half_extent(self.extent);
+          let half_target = |half: &PostProcessTarget| PassTarget {
+            render_pass: self.intermediate_pass,
+            framebuffer: half.framebuffer,
+            extent: half_extent,
+          };
+          draw(
+            &shaders.bloom_extract,
+            half_target(half_a),
+            source,
+            scene,
+            [threshold, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+          )?;
+          draw(
+            &shaders.blur,
+            half_target(half_b),
+            &half_a.image,
+            scene,
+            [1f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+          )?;
+          draw(
+            &shaders.blur,
+            half_target(half_a),
+            &half_b.image,
+            scene,
+            [0f32, 1f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+          )?;
+          draw(
+            &shaders.bloom_composite,
+            target,
+            source,
+            &half_a.image,
+            [intensity, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+          )?;
+        }
+        PostProcessPass::Fxaa => draw(&shaders.fxaa, target, source, scene, [0f32; 8])?,
+        PostProcessPass::ColorGrading { lut } => {
+          // Looked up each frame since defragmentation can move it.
+          let lut = buffer_image_store
+            .read()
+            .unwrap()
+            .get_image(lut)?
+            .handle
+            .image()?;
+          draw(&shaders.color_grading, target, source, &lut, [0f32; 8])?;
+        }
+        &PostProcessPass::Vignette { intensity, radius } => draw(
+          &shaders.vignette,
+          target,
+          source,
+          scene,
+          [intensity, radius, 0f32, 0f32, 0f32, 0f32, 0f32, 0f32],
+        )?,
+        PostProcessPass::Custom {
+          fragment_shader,
+          parameters,
+        } => draw(fragment_shader, target, source, scene, *parameters)?,
+      }
+
+      source = &output.image;
+    }
+
+    if hdr10 {
+      draw(
+        &shaders.hdr10,
+        present_target,
+        source,
+        scene,
+        hdr10_parameters,
+      )?;
+    }
+
+    Ok(())
+  }
+
+  /// Destroys everything that depends on the swapchain, for swapchain
+  /// recreation or cleanup.
+  pub unsafe fn cleanup_swapchain_resources(&self) {
+    info!("Destroying post process pipelines, framebuffers and present render pass...");
+    for (_, pipeline) in self.pipelines.write().unwrap().drain() {
+      self.logical_device.destroy_pipeline(pipeline, None);
+    }
+    let intermediate_framebuffers = self.targets.framebuffers();
+    for framebuffer in intermediate_framebuffers.chain(self.present_framebuffers.iter().copied()) {
+      self.logical_device.destroy_framebuffer(framebuffer, None);
+    }
+    self
+      .logical_device
+      .destroy_render_pass(self.present_pass, None);
+  }
+
+  /// Must be called during renderer's drop, after
+  /// `cleanup_swapchain_resources`.
+  pub unsafe fn cleanup(&self) {
+    info!("Destroying post process render pass and layouts...");
+    if let Some(temporal) = &self.temporal {
+      self
+        .logical_device
+        .destroy_render_pass(temporal.velocity_pass, None);
+      self
+        .logical_device
+        .destroy_sampler(temporal.depth_sampler, None);
+    }
+    self
+      .logical_device
+      .destroy_render_pass(self.intermediate_pass, None);
+    self
+      .logical_device
+      .destroy_pipeline_layout(self.pipeline_layout, None);
+    self
+      .logical_device
+      .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+  }
+
+  // ================================================================================
+  //  Post Process Helper Methods
+  // ================================================================================
+  /// Records one full screen pass, in a render pass of its own.
+  #[allow(clippy::too_many_arguments)]
+  fn draw_pass_cmd(
+    &self, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    command_buffer: vk::CommandBuffer, descriptor_pool: vk::DescriptorPool,
+    fragment_shader: vk::ShaderModule, target: PassTarget, inputs: [PassInput; 3],
+    constants: PostProcessConstants,
+  ) -> SarektResult<()> {
+    let logical_device = &self.logical_device;
+    let pipeline = self.get_pipeline(shader_store, fragment_shader, target.render_pass)?;
+
+    let set_layouts = [self.descriptor_set_layout];
+    let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+      .descriptor_pool(descriptor_pool)
+      .set_layouts(&set_layouts)
+      .build();
+    let descriptor_sets = unsafe { logical_device.allocate_descriptor_sets(&alloc_info)? };
+
+    let image_infos: Vec<_> = inputs
+      .iter()
+      .map(|input| {
+        vk::DescriptorImageInfo::builder()
+          .image_layout(input.layout)
+          .image_view(input.view)
+          .sampler(input.sampler)
+          .build()
+      })
+      .collect();
+    let descriptor_writes: Vec<_> = image_infos
+      .iter()
+      .enumerate()
+      .map(|(binding, image_info)| {
+        vk::WriteDescriptorSet::builder()
+          .dst_set(descriptor_sets[0])
+          .dst_binding(binding as u32)
+          .dst_array_element(0)
+          .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+          .image_info(std::slice::from_ref(image_info))
+          .build()
+      })
+      .collect();
+
+    let render_area = vk::Rect2D::builder()
+      .offset(vk::Offset2D::default())
+      .extent(target.extent)
+      .build();
+    let viewports = [vk::Viewport::builder()
+      .x(0f32)
+      .y(0f32)
+      .width(target.extent.width as f32)
+      .height(target.extent.height as f32)
+      .min_depth(0f32)
+      .max_depth(1.0f32)
+      .build()];
+
+    unsafe {
+      logical_device.update_descriptor_sets(&descriptor_writes, &[]);
+
+      // Every pixel is drawn over, so nothing is cleared.
+      let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+        .render_pass(target.render_pass)
+        .framebuffer(target.framebuffer)
+        .render_area(render_area)
+        .build();
+      logical_device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_begin_info,
+        vk::SubpassContents::INLINE,
+      );
+      logical_device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+      logical_device.cmd_set_viewport(command_buffer, 0, &viewports);
+      logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+      logical_device.cmd_bind_descriptor_sets(
+        command_buffer,
+        vk::PipelineBindPoint::GRAPHICS,
+        self.pipeline_layout,
+        0,
+        &descriptor_sets,
+        &[],
+      );
+      logical_device.cmd_push_constants(
+        command_buffer,
+        self.pipeline_layout,
+        vk::ShaderStageFlags::FRAGMENT,
+        0,
+        std::slice::from_raw_parts(
+          &constants as *const PostProcessConstants as *const u8,
+          std::mem::size_of::<PostProcessConstants>(),
+        ),
+      );
+      logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+      logical_device.cmd_end_render_pass(command_buffer);
+    }
+
+    Ok(())
+  }
+
+  /// Returns the pipeline drawing `fragment_shader` in `render_pass`, creating
+  /// it if this is the first time it is used since the swapchain was
+  /// (re)created.  The viewport is dynamic so the targets of any size share
+  /// it.
+  fn get_pipeline(
+    &self, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    fragment_shader: vk::ShaderModule, render_pass: vk::RenderPass,
+  ) -> SarektResult<vk::Pipeline> {
+    let key = (fragment_shader, render_pass);
+    if let Some(&pipeline) = self.pipelines.read().unwrap().get(&key) {
+      return Ok(pipeline);
+    }
+
+    info!("Creating post process pipeline for {:?}", fragment_shader);
+    let vertex_shader = shader_store
+      .read()
+      .unwrap()
+      .get_shader(&self.vertex_shader_handle)?
+      .shader_handle;
+    let entry_point_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let shader_stage_cis = [
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(vertex_shader)
+        .name(entry_point_name)
+        .build(),
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(fragment_shader)
+        .name(entry_point_name)
+        .build(),
+    ];
+
+    // Vertices are generated from gl_VertexIndex.
+    let vertex_input_ci = vk::PipelineVertexInputStateCreateInfo::builder().build();
+    let input_assembly_ci = vk::PipelineInputAssemblyStateCreateInfo::builder()
+      .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .primitive_restart_enable(false)
+      .build();
+    let viewport_state_ci = vk::PipelineViewportStateCreateInfo::builder()
+      .viewport_count(1)
+      .scissor_count(1)
+      .build();
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_ci = vk::PipelineDynamicStateCreateInfo::builder()
+      .dynamic_states(&dynamic_states)
+      .build();
+
+    let raster_state_ci = vk::PipelineRasterizationStateCreateInfo::builder()
+      .depth_clamp_enable(false)
+      .rasterizer_discard_enable(false)
+      .polygon_mode(vk::PolygonMode::FILL)
+      .line_width(1.0f32)
+      .cull_mode(vk::CullModeFlags::NONE) // The full screen triangle faces whichever way.
+      .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+      .depth_bias_enable(false)
+      .build();
+    let multisample_state_ci = vk::PipelineMultisampleStateCreateInfo::builder()
+      .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+      .sample_shading_enable(false)
+      .build();
+
+    let attachments = [vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(false)
+      .build()];
+    let color_blend_ci = vk::PipelineColorBlendStateCreateInfo::builder()
+      .logic_op_enable(false)
+      .logic_op(vk::LogicOp::COPY)
+      .attachments(&attachments)
+      .build();
+
+    // No depth attachment, so no depth stencil state.
+    let pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
+      .stages(&shader_stage_cis)
+      .vertex_input_state(&vertex_input_ci)
+      .input_assembly_state(&input_assembly_ci)
+      .viewport_state(&viewport_state_ci)
+      .rasterization_state(&raster_state_ci)
+      .multisample_state(&multisample_state_ci)
+      .color_blend_state(&color_blend_ci)
+      .dynamic_state(&dynamic_state_ci)
+      .layout(self.pipeline_layout)
+      .render_pass(render_pass)
+      .subpass(0)
+      .build();
+    let pipeline = unsafe {
+      self
+        .logical_device
+        .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_ci], None)
+        .map_err(|err| err.1)?[0]
+    };
+    self.pipelines.write().unwrap().insert(key, pipeline);
+
+    Ok(pipeline)
+  }
+
+  /// Creates a render pass drawing every pixel of a single color attachment,
+  /// leaving it in `final_layout`.
+  fn create_render_pass(
+    logical_device: &Device, format: vk::Format, final_layout: vk::ImageLayout,
+  ) -> SarektResult<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription::builder()
+      .format(format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::DONT_CARE) // Drawn over completely.
+      .store_op(vk::AttachmentStoreOp::STORE)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED)
+      .final_layout(final_layout)
+      .build();
+    let color_attachment_refs = [vk::AttachmentReference::builder()
+      .attachment(0)
+      .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+      .build()];
+
+    let attachments = [color_attachment];
+    let subpass_descriptions = [vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .color_attachments(&color_attachment_refs)
+      .build()];
+
+    let dependencies = [
+      // Don't overwrite the target while an earlier pass (or frame) still
+      // samples it, or before the swapchain image is acquired.
+      vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0u32)
+        .src_stage_mask(
+          vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        )
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .build(),
+      // The next pass samples what this one drew.
+      vk::SubpassDependency::builder()
+        .src_subpass(0u32)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .build(),
+    ];
+
+    let render_pass_ci = vk::RenderPassCreateInfo::builder()
+      .attachments(&attachments)
+      .subpasses(&subpass_descriptions)
+      .dependencies(&dependencies)
+      .build();
+
+    Ok(unsafe { logical_device.create_render_pass(&render_pass_ci, None)? })
+  }
+
+  fn create_present_framebuffers(
+    logical_device: &Device, present_pass: vk::RenderPass, render_targets: &[ImageAndView],
+    extent: vk::Extent2D,
+  ) -> SarektResult<Vec<vk::Framebuffer>> {
+    let mut framebuffers = Vec::with_capacity(render_targets.len());
+    for render_target in render_targets.iter() {
+      let attachments = [render_target.view];
+      let framebuffer_ci = vk::FramebufferCreateInfo::builder()
+        .render_pass(present_pass)
+        .attachments(&attachments)
+        .width(extent.width)
+        .height(extent.height)
+        .layers(1)
+        .build();
+      framebuffers.push(unsafe { logical_device.create_framebuffer(&framebuffer_ci, None)? });
+    }
+    Ok(framebuffers)
+  }
+
+  /// Element `index` of the Halton sequence in `base`, in [0, 1).
+  fn halton(mut index: u64, base: u64) -> f32 {
+    let mut fraction = 1f32;
+    let mut result = 0f32;
+    while index > 0 {
+      fraction /= base as f32;
+      result += fraction * (index % base) as f32;
+      index /= base;
+    }
+    result
+  }
+
+  fn half_extent(extent: vk::Extent2D) -> vk::Extent2D {
+    vk::Extent2D {
+      width: (extent.width / 2).max(1),
+      height: (extent.height / 2).max(1),
+    }
+  }
+}
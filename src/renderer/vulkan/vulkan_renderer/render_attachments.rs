@@ -9,6 +9,7 @@ use crate::{
   },
 };
 use ash::{version::InstanceV1_0, vk, Instance};
+use log::warn;
 use std::{
   convert::TryInto,
   sync::{Arc, RwLock},
@@ -25,9 +26,9 @@ impl DepthAttachment {
   pub fn new(
     instance: &Instance, physical_device: vk::PhysicalDevice,
     buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
-    extent: (u32, u32), num_msaa_samples: NumSamples,
+    extent: (u32, u32), num_msaa_samples: NumSamples, requested_format: Option<ImageDataFormat>,
   ) -> SarektResult<DepthAttachment> {
-    let format = Self::find_depth_format(instance, physical_device)?;
+    let format = Self::find_depth_format(instance, physical_device, requested_format)?;
     let (depth_buffer_image_handle, buffer_or_image) =
       BufferImageStore::create_uninitialized_image_msaa(
         buffer_image_store,
@@ -45,6 +46,28 @@ impl DepthAttachment {
     })
   }
 
+  /// Finds a depth format that can also be sampled, for shadow maps.
+  pub fn find_shadow_map_format(
+    instance: &Instance, physical_device: vk::PhysicalDevice,
+  ) -> SarektResult<vk::Format> {
+    let format_candidates = [
+      vk::Format::D32_SFLOAT,
+      vk::Format::D32_SFLOAT_S8_UINT,
+      vk::Format::D24_UNORM_S8_UINT,
+    ];
+    let tiling = vk::ImageTiling::OPTIMAL;
+    let features =
+      vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+
+    Self::find_supported_format(
+      instance,
+      physical_device,
+      &format_candidates,
+      tiling,
+      features,
+    )
+  }
+
   fn find_supported_format(
     instance: &Instance, physical_device: vk::PhysicalDevice, format_candidates: &[vk::Format],
     tiling: vk::ImageTiling, features: vk::FormatFeatureFlags,
@@ -65,17 +88,39 @@ impl DepthAttachment {
     Err(SarektError::NoSuitableDepthBufferFormat)
   }
 
+  /// Finds a depth format for the depth buffer, the requested one if it can
+  /// be used.
   fn find_depth_format(
     instance: &Instance, physical_device: vk::PhysicalDevice,
+    requested_format: Option<ImageDataFormat>,
   ) -> SarektResult<vk::Format> {
+    let tiling = vk::ImageTiling::OPTIMAL;
+    let features = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT;
+
+    if let Some(requested_format) = requested_format {
+      let supported = requested_format.is_depth()
+        && Self::find_supported_format(
+          instance,
+          physical_device,
+          &[requested_format.into()],
+          tiling,
+          features,
+        )
+        .is_ok();
+      if supported {
+        return Ok(requested_format.into());
+      }
+      warn!(
+        "Depth format {:?} is not supported as a depth attachment, falling back",
+        requested_format
+      );
+    }
+
     let format_candidates = [
       vk::Format::D32_SFLOAT,
       vk::Format::D32_SFLOAT_S8_UINT,
       vk::Format::D24_UNORM_S8_UINT,
     ];
-    let tiling = vk::ImageTiling::OPTIMAL;
-    let features = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT;
-
     Self::find_supported_format(
       instance,
       physical_device,
@@ -90,6 +135,31 @@ impl DepthAttachment {
   }
 }
 
+/// The depth image shadow casters are rendered into from the light, sampled
+/// with a comparison sampler by shadow receivers.
+pub struct ShadowMapAttachment {
+  pub shadow_map_image_handle: BufferImageHandle<VulkanBufferImageFunctions>,
+  pub image_and_memory: ImageAndMemory,
+  pub format: vk::Format,
+}
+impl ShadowMapAttachment {
+  pub fn new(
+    instance: &Instance, physical_device: vk::PhysicalDevice,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    dimensions: (u32, u32),
+  ) -> SarektResult<ShadowMapAttachment> {
+    let format = DepthAttachment::find_shadow_map_format(instance, physical_device)?;
+    let (shadow_map_image_handle, buffer_or_image) =
+      BufferImageStore::create_shadow_map(buffer_image_store, dimensions, format.try_into()?)?;
+
+    Ok(ShadowMapAttachment {
+      shadow_map_image_handle,
+      image_and_memory: buffer_or_image.handle.image()?,
+      format,
+    })
+  }
+}
+
 /// Used for resolving MSAA, see https://www.khronos.org/registry/vulkan/specs/1.2-khr-extensions/html/chap7.html#VkSubpassDescription
 pub struct ResolveAttachment {
   pub resolve_image_handle: BufferImageHandle<VulkanBufferImageFunctions>,
@@ -117,3 +187,64 @@ impl ResolveAttachment {
     })
   }
 }
+
+/// The G-buffer the deferred render pass draws surfaces into and shades in
+/// its lighting subpass, which reads them as input attachments.  Along with
+/// the depth buffer it is everything the lights need to know about a pixel.
+pub struct GBufferAttachments {
+  pub albedo: ImageAndMemory,
+  pub normal: ImageAndMemory,
+  pub material: ImageAndMemory,
+  // Keep the images alive.
+  _image_handles: Vec<BufferImageHandle<VulkanBufferImageFunctions>>,
+}
+impl GBufferAttachments {
+  /// Base color.
+  pub const ALBEDO_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+  /// Metalness and roughness.
+  pub const MATERIAL_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+  /// World space normals, which need more precision than 8 bits.
+  pub const NORMAL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+  pub fn new(
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    dimensions: (u32, u32),
+  ) -> SarektResult<GBufferAttachments> {
+    let mut image_handles = Vec::with_capacity(3);
+    let mut images = Vec::with_capacity(3);
+    for &format in Self::formats().iter() {
+      let (handle, image) = BufferImageStore::create_uninitialized_image(
+        buffer_image_store,
+        dimensions,
+        format.try_into()?,
+      )?;
+      image_handles.push(handle);
+      images.push(image.handle.image()?);
+    }
+
+    Ok(GBufferAttachments {
+      albedo: images[0],
+      normal: images[1],
+      material: images[2],
+      _image_handles: image_handles,
+    })
+  }
+
+  /// Formats of the albedo, normal and material attachments, in that order.
+  pub fn formats() -> [vk::Format; 3] {
+    [
+      Self::ALBEDO_FORMAT,
+      Self::NORMAL_FORMAT,
+      Self::MATERIAL_FORMAT,
+    ]
+  }
+
+  /// Views of the albedo, normal and material attachments, in that order.
+  pub fn views(&self) -> [vk::ImageView; 3] {
+    [
+      self.albedo.image_and_view.view,
+      self.normal.image_and_view.view,
+      self.material.image_and_view.view,
+    ]
+  }
+}
@@ -1,7 +1,7 @@
 use crate::{
   error::SarektResult,
   renderer::{
-    config::PresentMode,
+    config::{PresentMode, SurfaceFormatPreference},
     vulkan::{
       images::ImageAndView,
       queues::QueueFamilyIndices,
@@ -28,6 +28,7 @@ impl RenderTargetBundle {
   pub fn new(
     vulkan_core: &VulkanCoreStructures, device_bundle: &VulkanDeviceStructures,
     requested_width: u32, requested_height: u32, requested_present_mode: PresentMode,
+    requested_surface_format: SurfaceFormatPreference,
   ) -> SarektResult<RenderTargetBundle> {
     let swapchain_extension = ash::extensions::khr::Swapchain::new(
       vulkan_core.instance.as_ref(),
@@ -41,6 +42,7 @@ impl RenderTargetBundle {
       requested_width,
       requested_height,
       requested_present_mode,
+      requested_surface_format,
       None,
     )?;
     let swapchain_and_extension =
@@ -111,6 +113,10 @@ impl RenderTargetBundle {
     self.swapchain_and_extension.format
   }
 
+  pub fn get_render_target_color_space(&self) -> vk::ColorSpaceKHR {
+    self.swapchain_and_extension.color_space
+  }
+
   /// Checks if the width and height given differ from the render target extent.
   pub fn extent_is_equal_to(&self, width: u32, height: u32) -> bool {
     self.extent.width == width && self.extent.height == height
@@ -123,6 +129,7 @@ impl RenderTargetBundle {
   pub unsafe fn recreate_swapchain(
     &mut self, vulkan_core: &VulkanCoreStructures, device_bundle: &VulkanDeviceStructures,
     requested_width: u32, requested_height: u32, requested_present_mode: PresentMode,
+    requested_surface_format: SurfaceFormatPreference,
   ) -> SarektResult<(vk::SwapchainKHR, Vec<ImageAndView>)> {
     let old_swapchain = self.swapchain_and_extension.swapchain;
 
@@ -134,11 +141,13 @@ impl RenderTargetBundle {
       requested_width,
       requested_height,
       requested_present_mode,
+      requested_surface_format,
       Some(old_swapchain),
     )?;
 
     self.swapchain_and_extension.swapchain = new_swapchain;
-    self.swapchain_and_extension.format = new_format;
+    self.swapchain_and_extension.format = new_format.format;
+    self.swapchain_and_extension.color_space = new_format.color_space;
     self.extent = new_extent;
 
     // TODO(issue#9) OFFSCREEN if not swapchain create images that im rendering to.
@@ -150,7 +159,7 @@ impl RenderTargetBundle {
     let mut render_targets = Self::create_render_target_image_views(
       &device_bundle.logical_device,
       render_target_images,
-      new_format,
+      new_format.format,
     )?;
     std::mem::swap(&mut self.render_targets, &mut render_targets);
 
@@ -185,16 +194,19 @@ impl RenderTargetBundle {
   /// Based on the capabilities of the surface, the physical device, and the
   /// configuration of sarekt, creates a swapchain with the appropriate
   /// configuration (format, color space, present mode, and extent).
+  #[allow(clippy::too_many_arguments)]
   fn create_swapchain(
     surface_and_extension: &SurfaceAndExtension,
     swapchain_extension: &ash::extensions::khr::Swapchain, physical_device: vk::PhysicalDevice,
     queue_family_indices: &QueueFamilyIndices, requested_width: u32, requested_height: u32,
-    requested_present_mode: PresentMode, old_swapchain: Option<vk::SwapchainKHR>,
-  ) -> SarektResult<(vk::SwapchainKHR, vk::Format, vk::Extent2D)> {
+    requested_present_mode: PresentMode, requested_surface_format: SurfaceFormatPreference,
+    old_swapchain: Option<vk::SwapchainKHR>,
+  ) -> SarektResult<(vk::SwapchainKHR, vk::SurfaceFormatKHR, vk::Extent2D)> {
     let swapchain_support =
       VulkanDeviceStructures::query_swap_chain_support(surface_and_extension, physical_device)?;
 
-    let format = Self::choose_swap_surface_format(&swapchain_support.formats);
+    let format =
+      Self::choose_swap_surface_format(&swapchain_support.formats, requested_surface_format);
     let present_mode =
       Self::choose_presentation_mode(&swapchain_support.present_modes, requested_present_mode);
     let extent = Self::choose_swap_extent(
@@ -247,24 +259,76 @@ impl RenderTargetBundle {
       .build();
 
     let swapchain = unsafe { swapchain_extension.create_swapchain(&swapchain_ci, None)? };
-    Ok((swapchain, format.format, extent))
+    Ok((swapchain, format, extent))
   }
 
   /// If drawing to a surface, chooses the best format from the ones available
-  /// for the surface.  Tries to use B8g8r8a8Srgb format with SRGB_NONLINEAR
-  /// colorspace.
+  /// for the surface for the requested preference, see
+  /// `SurfaceFormatPreference` for the fallbacks.
   ///
-  /// If that isn't available, for now we just use the 0th SurfaceFormatKHR.
+  /// If none of them are available, for now we just use the 0th
+  /// SurfaceFormatKHR.
   fn choose_swap_surface_format(
-    available_formats: &[vk::SurfaceFormatKHR],
+    available_formats: &[vk::SurfaceFormatKHR], requested_surface_format: SurfaceFormatPreference,
   ) -> vk::SurfaceFormatKHR {
-    *available_formats
-      .iter()
-      .find(|format| {
-        format.format == vk::Format::B8G8R8A8_UNORM
-          && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-      })
-      .unwrap_or(&available_formats[0])
+    let preferences: &[SurfaceFormatPreference] = match requested_surface_format {
+      SurfaceFormatPreference::Unorm => &[
+        SurfaceFormatPreference::Unorm,
+        SurfaceFormatPreference::Srgb,
+      ],
+      SurfaceFormatPreference::Srgb => &[
+        SurfaceFormatPreference::Srgb,
+        SurfaceFormatPreference::Unorm,
+      ],
+      SurfaceFormatPreference::Hdr10 => &[
+        SurfaceFormatPreference::Hdr10,
+        SurfaceFormatPreference::Srgb,
+        SurfaceFormatPreference::Unorm,
+      ],
+    };
+
+    for &preference in preferences.iter() {
+      let (candidate_formats, color_space): (&[vk::Format], _) = match preference {
+        SurfaceFormatPreference::Unorm => (
+          &[vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM],
+          vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        SurfaceFormatPreference::Srgb => (
+          &[vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB],
+          vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        // Only listed when VK_EXT_swapchain_colorspace is enabled.
+        SurfaceFormatPreference::Hdr10 => (
+          &[
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+          ],
+          vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        ),
+      };
+
+      let found = candidate_formats.iter().find_map(|&candidate_format| {
+        available_formats
+          .iter()
+          .find(|format| format.format == candidate_format && format.color_space == color_space)
+      });
+      if let Some(&format) = found {
+        if preference != requested_surface_format {
+          warn!(
+            "Surface format {:?} is not supported, presenting with {:?} instead",
+            requested_surface_format, preference
+          );
+        }
+        info!("Selecting surface format: {:?}", format);
+        return format;
+      }
+    }
+
+    warn!(
+      "None of the preferred surface formats are supported, presenting with {:?}",
+      available_formats[0]
+    );
+    available_formats[0]
   }
 
   /// Selects Mailbox if available, but if not tries to fallback to FIFO. See the [spec](https://renderdoc.org/vkspec_chunked/chap32.html#VkPresentModeKHR) for details on modes.
@@ -0,0 +1,216 @@
+use crate::{error::SarektResult, renderer::MAX_FRAMES_IN_FLIGHT};
+use ash::{version::DeviceV1_0, vk, Device};
+use log::info;
+use std::{
+  cell::{Cell, RefCell},
+  sync::Arc,
+};
+
+/// The render texture pass draws are currently recorded into.
+#[derive(Copy, Clone, Debug)]
+pub struct CurrentPass {
+  pub color_format: vk::Format,
+  pub bound_pipeline: vk::Pipeline,
+}
+
+/// Records the render passes into render textures selected with
+/// `Drawer::set_render_target`.
+///
+/// They go in their own command buffer per frame in flight, which is submitted
+/// before the frame's main command buffer so the screen can sample what they
+/// drew.  Framebuffers are made per pass and destroyed once their frame is done
+/// on the GPU, since the render texture could be dropped in the meantime.
+pub struct RenderTexturePasses {
+  logical_device: Arc<Device>,
+  command_pool: vk::CommandPool,
+  command_buffers: Vec<vk::CommandBuffer>,
+  framebuffers: Vec<RefCell<Vec<vk::Framebuffer>>>,
+  current_frame_num: Cell<usize>,
+  // Whether the current frame's command buffer has begun.
+  recording: Cell<bool>,
+  current_pass: Cell<Option<CurrentPass>>,
+}
+impl RenderTexturePasses {
+  pub fn new(logical_device: Arc<Device>, command_pool: vk::CommandPool) -> SarektResult<Self> {
+    let command_buffer_ci = vk::CommandBufferAllocateInfo::builder()
+      .command_pool(command_pool)
+      .level(vk::CommandBufferLevel::PRIMARY)
+      .command_buffer_count(MAX_FRAMES_IN_FLIGHT as u32)
+      .build();
+    let command_buffers = unsafe { logical_device.allocate_command_buffers(&command_buffer_ci)? };
+
+    Ok(Self {
+      logical_device,
+      command_pool,
+      command_buffers,
+      framebuffers: (0..MAX_FRAMES_IN_FLIGHT)
+        .map(|_| RefCell::new(Vec::new()))
+        .collect(),
+      current_frame_num: Cell::new(0),
+      recording: Cell::new(false),
+      current_pass: Cell::new(None),
+    })
+  }
+
+  /// Starts recording for `current_frame_num`, whose previous frame must be
+  /// done on the GPU.
+  pub fn start_frame(&self, current_frame_num: usize) {
+    if current_frame_num >= MAX_FRAMES_IN_FLIGHT {
+      panic!("Invalid input! current_frame_num {}", current_frame_num);
+    }
+
+    self.current_frame_num.set(current_frame_num);
+    self.recording.set(false);
+    self.current_pass.set(None);
+    for framebuffer in self.framebuffers[current_frame_num].borrow_mut().drain(..) {
+      unsafe { self.logical_device.destroy_framebuffer(framebuffer, None) };
+    }
+  }
+
+  pub fn current_pass(&self) -> Option<CurrentPass> {
+    self.current_pass.get()
+  }
+
+  pub fn set_bound_pipeline(&self, pipeline: vk::Pipeline) {
+    if let Some(mut pass) = self.current_pass.get() {
+      pass.bound_pipeline = pipeline;
+      self.current_pass.set(Some(pass));
+    }
+  }
+
+  pub fn command_buffer(&self) -> vk::CommandBuffer {
+    self.command_buffers[self.current_frame_num.get()]
+  }
+
+  /// Ends the current pass, if any, and begins one into the color and depth
+  /// views, clearing them.
+  pub fn begin_pass(
+    &self, render_pass: vk::RenderPass, color_format: vk::Format, color_view: vk::ImageView,
+    depth_view: vk::ImageView, dimensions: (u32, u32), clear_color: [f32; 4],
+  ) -> SarektResult<()> {
+    self.end_pass();
+
+    let logical_device = &self.logical_device;
+    let command_buffer = self.command_buffer();
+    if !self.recording.get() {
+      let begin_ci = vk::CommandBufferBeginInfo::builder()
+        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+        .build();
+      unsafe { logical_device.begin_command_buffer(command_buffer, &begin_ci)? };
+      self.recording.set(true);
+    }
+
+    let extent = vk::Extent2D {
+      width: dimensions.0,
+      height: dimensions.1,
+    };
+    let attachments = [color_view, depth_view];
+    let framebuffer_ci = vk::FramebufferCreateInfo::builder()
+      .render_pass(render_pass)
+      .attachments(&attachments)
+      .width(extent.width)
+      .height(extent.height)
+      .layers(1)
+      .build();
+    let framebuffer = unsafe { logical_device.create_framebuffer(&framebuffer_ci, None)? };
+    self.framebuffers[self.current_frame_num.get()]
+      .borrow_mut()
+      .push(framebuffer);
+
+    unsafe {
+      let render_area = vk::Rect2D::builder()
+        .offset(vk::Offset2D::default())
+        .extent(extent)
+        .build();
+      let clear_values = [
+        vk::ClearValue {
+          color: vk::ClearColorValue {
+            float32: clear_color,
+          },
+        },
+        vk::ClearValue {
+          depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0f32,
+            stencil: 0u32,
+          },
+        },
+      ];
+      let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+        .render_pass(render_pass)
+        .framebuffer(framebuffer)
+        .render_area(render_area)
+        .clear_values(&clear_values)
+        .build();
+      logical_device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_begin_info,
+        vk::SubpassContents::INLINE,
+      );
+
+      // Render texture pipelines take the viewport and scissor dynamically.
+      let viewports = [vk::Viewport::builder()
+        .x(0f32)
+        .y(0f32)
+        .width(extent.width as f32)
+        .height(extent.height as f32)
+        .min_depth(0f32)
+        .max_depth(1.0f32)
+        .build()];
+      logical_device.cmd_set_viewport(command_buffer, 0, &viewports);
+      logical_device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+    }
+
+    self.current_pass.set(Some(CurrentPass {
+      color_format,
+      bound_pipeline: vk::Pipeline::null(),
+    }));
+    Ok(())
+  }
+
+  /// Ends the current pass, after which draws go to the screen again.
+  pub fn end_pass(&self) {
+    if self.current_pass.take().is_some() {
+      unsafe {
+        self
+          .logical_device
+          .cmd_end_render_pass(self.command_buffer())
+      };
+    }
+  }
+
+  /// Ends recording for the frame, returning the command buffer to submit if
+  /// any pass was recorded.
+  pub fn finish(&self) -> SarektResult<Option<vk::CommandBuffer>> {
+    self.end_pass();
+    if !self.recording.replace(false) {
+      return Ok(None);
+    }
+
+    let command_buffer = self.command_buffer();
+    unsafe { self.logical_device.end_command_buffer(command_buffer)? };
+    Ok(Some(command_buffer))
+  }
+
+  /// Forgets whatever was being recorded and destroys every framebuffer, for
+  /// swapchain recreation after the command pool was reset.
+  ///
+  /// Unsafe because the device must be idle.
+  pub unsafe fn reset(&self) {
+    self.recording.set(false);
+    self.current_pass.set(None);
+    for framebuffers in self.framebuffers.iter() {
+      for framebuffer in framebuffers.borrow_mut().drain(..) {
+        self.logical_device.destroy_framebuffer(framebuffer, None);
+      }
+    }
+  }
+
+  /// Unsafe because the device must be idle.
+  pub unsafe fn destroy_all(&self) {
+    info!("Destroying render texture framebuffers and command buffers...");
+    self.reset();
+    self
+      .logical_device
+      .free_command_buffers(self.command_pool, &self.command_buffers);
+  }
+}
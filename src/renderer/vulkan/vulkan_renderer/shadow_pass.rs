@@ -0,0 +1,559 @@
+use crate::{
+  error::SarektResult,
+  renderer::{
+    buffers_and_images::BufferImageStore,
+    shaders::ShaderStore,
+    shadows::{ShadowCascades, MAX_SHADOW_CASCADES},
+    vertex_bindings::ForwardShaderVariant,
+    vulkan::{
+      vulkan_buffer_image_functions::ImageAndMemory,
+      vulkan_renderer::{pipelines::Pipelines, render_attachments::ShadowMapAttachment},
+      vulkan_shader_functions::VulkanShaderFunctions,
+      VulkanShaderHandle,
+    },
+    ShaderCode, ShaderType, VulkanBufferImageFunctions, MAX_FRAMES_IN_FLIGHT,
+  },
+};
+use ash::{version::DeviceV1_0, vk, Device, Instance};
+use log::info;
+use std::{
+  cell::Cell,
+  ffi::CStr,
+  sync::{Arc, RwLock},
+};
+use ultraviolet as uv;
+use vk_shader_macros::include_glsl;
+
+/// Vertex shader of the shadow pass, transforms positions into the light's
+/// clip space.  There is no fragment shader, only depth is written.
+pub const SHADOW_CASTER_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_shadow_caster.vert");
+
+/// ShadowUniform in sarekt_forward_shadowed.frag, bound for every shadow
+/// receiving draw.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowUniform {
+  pub light_view_projections: [uv::Mat4; MAX_SHADOW_CASCADES],
+  pub cascade_splits: [f32; MAX_SHADOW_CASCADES],
+  pub camera_view: uv::Mat4,
+  pub cascade_count: i32,
+  pub texel_size: f32,
+}
+
+/// Records the depth only pass that draws shadow casters from the light into
+/// the shadow map, a 2x2 atlas with a tile per cascade.
+///
+/// It goes in its own command buffer per frame in flight, submitted before the
+/// frame's main command buffer.  Nothing here depends on the swapchain, so it
+/// lives as long as the renderer.
+pub struct ShadowPass {
+  logical_device: Arc<Device>,
+  command_pool: vk::CommandPool,
+  command_buffers: Vec<vk::CommandBuffer>,
+  shadow_map: Option<ShadowMapAttachment>,
+  tile_size: u32,
+  render_pass: vk::RenderPass,
+  framebuffer: vk::Framebuffer,
+  // Caster pipelines for the default, lit and PBR vertex layouts, which differ
+  // only in stride.
+  pipeline: vk::Pipeline,
+  lit_pipeline: vk::Pipeline,
+  pbr_pipeline: vk::Pipeline,
+  bound_pipeline: Cell<vk::Pipeline>,
+  pipeline_layout: vk::PipelineLayout,
+  vertex_shader_handle: Option<VulkanShaderHandle>,
+  cascades: Option<ShadowCascades>,
+  // Camera view the directional cascades were fit to.
+  camera_view: uv::Mat4,
+  current_frame_num: Cell<usize>,
+  // Whether the current frame's command buffer has begun.
+  recording: Cell<bool>,
+}
+impl ShadowPass {
+  pub fn new(
+    instance: &Instance, physical_device: vk::PhysicalDevice, logical_device: Arc<Device>,
+    command_pool: vk::CommandPool,
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>, tile_size: u32,
+  ) -> SarektResult<Self> {
+    let atlas_size = 2 * tile_size;
+    info!("Creating {0}x{0} shadow map atlas...", atlas_size);
+    let shadow_map = ShadowMapAttachment::new(
+      instance,
+      physical_device,
+      buffer_image_store,
+      (atlas_size, atlas_size),
+    )?;
+
+    let render_pass = Self::create_render_pass(&logical_device, shadow_map.format)?;
+    let attachments = [shadow_map.image_and_memory.image_and_view.view];
+    let framebuffer_ci = vk::FramebufferCreateInfo::builder()
+      .render_pass(render_pass)
+      .attachments(&attachments)
+      .width(atlas_size)
+      .height(atlas_size)
+      .layers(1)
+      .build();
+    let framebuffer = unsafe { logical_device.create_framebuffer(&framebuffer_ci, None)? };
+
+    let vertex_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(SHADOW_CASTER_VERTEX_SHADER),
+      ShaderType::Vertex,
+    )?;
+    let pipeline_layout = Self::create_pipeline_layout(&logical_device)?;
+    let pipeline = Self::create_pipeline(
+      &logical_device,
+      shader_store,
+      &vertex_shader_handle,
+      render_pass,
+      pipeline_layout,
+      ForwardShaderVariant::Plain,
+    )?;
+    let lit_pipeline = Self::create_pipeline(
+      &logical_device,
+      shader_store,
+      &vertex_shader_handle,
+      render_pass,
+      pipeline_layout,
+      ForwardShaderVariant::Lit,
+    )?;
+    let pbr_pipeline = Self::create_pipeline(
+      &logical_device,
+      shader_store,
+      &vertex_shader_handle,
+      render_pass,
+      pipeline_layout,
+      ForwardShaderVariant::Pbr,
+    )?;
+
+    let command_buffer_ci = vk::CommandBufferAllocateInfo::builder()
+      .command_pool(command_pool)
+      .level(vk::CommandBufferLevel::PRIMARY)
+      .command_buffer_count(MAX_FRAMES_IN_FLIGHT as u32)
+      .build();
+    let command_buffers = unsafe { logical_device.allocate_command_buffers(&command_buffer_ci)? };
+
+    Ok(Self {
+      logical_device,
+      command_pool,
+      command_buffers,
+      shadow_map: Some(shadow_map),
+      tile_size,
+      render_pass,
+      framebuffer,
+      pipeline,
+      lit_pipeline,
+      pbr_pipeline,
+      bound_pipeline: Cell::new(vk::Pipeline::null()),
+      pipeline_layout,
+      vertex_shader_handle: Some(vertex_shader_handle),
+      cascades: None,
+      camera_view: uv::Mat4::identity(),
+      current_frame_num: Cell::new(0),
+      recording: Cell::new(false),
+    })
+  }
+
+  pub fn tile_size(&self) -> u32 {
+    self.tile_size
+  }
+
+  /// The cascades to draw casters into, None when no light casts shadows.
+  pub fn set_cascades(&mut self, cascades: Option<ShadowCascades>, camera_view: uv::Mat4) {
+    self.cascades = cascades;
+    self.camera_view = camera_view;
+  }
+
+  pub fn cascade_count(&self) -> usize {
+    self.cascades.map_or(0, |cascades| cascades.count)
+  }
+
+  pub fn shadow_map(&self) -> &ImageAndMemory {
+    &self
+      .shadow_map
+      .as_ref()
+      .expect("Shadow map is only taken when dropping the renderer")
+      .image_and_memory
+  }
+
+  /// What the shadow receiving shader needs to find fragments in the shadow
+  /// map.
+  pub fn shadow_uniform(&self) -> ShadowUniform {
+    let (light_view_projections, cascade_splits, cascade_count) = match self.cascades {
+      Some(cascades) => (
+        cascades.light_view_projections,
+        cascades.splits,
+        cascades.count as i32,
+      ),
+      None => (
+        [uv::Mat4::identity(); MAX_SHADOW_CASCADES],
+        [0f32; MAX_SHADOW_CASCADES],
+        0,
+      ),
+    };
+
+    ShadowUniform {
+      light_view_projections,
+      cascade_splits,
+      camera_view: self.camera_view,
+      cascade_count,
+      texel_size: 1f32 / (2 * self.tile_size) as f32,
+    }
+  }
+
+  /// Starts recording for `current_frame_num`, whose previous frame must be
+  /// done on the GPU.
+  pub fn start_frame(&self, current_frame_num: usize) {
+    if current_frame_num >= MAX_FRAMES_IN_FLIGHT {
+      panic!("Invalid input! current_frame_num {}", current_frame_num);
+    }
+
+    self.current_frame_num.set(current_frame_num);
+    self.recording.set(false);
+  }
+
+  /// Returns the command buffer to draw casters into, inside the shadow pass
+  /// with the caster pipeline for the shader variant's vertices bound,
+  /// beginning it on the first caster of the frame.  None when no light casts
+  /// shadows.
+  pub fn begin_casters(
+    &self, variant: ForwardShaderVariant,
+  ) -> SarektResult<Option<vk::CommandBuffer>> {
+    if self.cascades.is_none() {
+      return Ok(None);
+    }
+
+    let command_buffer = self.command_buffers[self.current_frame_num.get()];
+    if !self.recording.get() {
+      self.begin_pass(command_buffer)?;
+      self.bound_pipeline.set(vk::Pipeline::null());
+    }
+
+    let pipeline = match variant {
+      ForwardShaderVariant::Lit => self.lit_pipeline,
+      ForwardShaderVariant::Pbr => self.pbr_pipeline,
+      ForwardShaderVariant::Plain | ForwardShaderVariant::ShadowReceiving => self.pipeline,
+    };
+    if self.bound_pipeline.replace(pipeline) != pipeline {
+      unsafe {
+        self.logical_device.cmd_bind_pipeline(
+          command_buffer,
+          vk::PipelineBindPoint::GRAPHICS,
+          pipeline,
+        );
+      }
+    }
+
+    Ok(Some(command_buffer))
+  }
+
+  /// Records drawing into cascade `cascade`'s tile with the object's `model`
+  /// matrix, the vertices are drawn after.
+  pub fn set_cascade_cmd(
+    &self, command_buffer: vk::CommandBuffer, cascade: usize, model: uv::Mat4,
+  ) {
+    let cascades = self.cascades.as_ref().unwrap();
+    let light_mvp = cascades.light_view_projections[cascade] * model;
+
+    let tile_offset = vk::Offset2D {
+      x: ((cascade % 2) as u32 * self.tile_size) as i32,
+      y: ((cascade / 2) as u32 * self.tile_size) as i32,
+    };
+    let tile = vk::Rect2D::builder()
+      .offset(tile_offset)
+      .extent(vk::Extent2D {
+        width: self.tile_size,
+        height: self.tile_size,
+      })
+      .build();
+    let viewports = [vk::Viewport::builder()
+      .x(tile_offset.x as f32)
+      .y(tile_offset.y as f32)
+      .width(self.tile_size as f32)
+      .height(self.tile_size as f32)
+      .min_depth(0f32)
+      .max_depth(1.0f32)
+      .build()];
+
+    unsafe {
+      let logical_device = &self.logical_device;
+      logical_device.cmd_set_viewport(command_buffer, 0, &viewports);
+      logical_device.cmd_set_scissor(command_buffer, 0, &[tile]);
+      logical_device.cmd_push_constants(
+        command_buffer,
+        self.pipeline_layout,
+        vk::ShaderStageFlags::VERTEX,
+        0,
+        std::slice::from_raw_parts(
+          &light_mvp as *const uv::Mat4 as *const u8,
+          std::mem::size_of::<uv::Mat4>(),
+        ),
+      );
+    }
+  }
+
+  /// Ends recording for the frame, returning the command buffer to submit if
+  /// a light casts shadows.  The pass is recorded even without casters so the
+  /// previous frame's shadows are cleared.
+  pub fn finish(&self) -> SarektResult<Option<vk::CommandBuffer>> {
+    if self.cascades.is_none() && !self.recording.get() {
+      return Ok(None);
+    }
+
+    let command_buffer = self.command_buffers[self.current_frame_num.get()];
+    if !self.recording.get() {
+      self.begin_pass(command_buffer)?;
+    }
+    self.recording.set(false);
+
+    unsafe {
+      self.logical_device.cmd_end_render_pass(command_buffer);
+      self.logical_device.end_command_buffer(command_buffer)?;
+    }
+    Ok(Some(command_buffer))
+  }
+
+  /// Forgets whatever was being recorded, for swapchain recreation after the
+  /// command pool was reset.
+  pub fn reset(&self) {
+    self.recording.set(false);
+  }
+
+  /// Unsafe because the device must be idle.  The shadow map must be
+  /// destroyed before the buffer and image store is cleaned up.
+  pub unsafe fn destroy_shadow_map(&mut self) {
+    info!("Destroying shadow map...");
+    let shadow_map = self.shadow_map.take();
+    std::mem::drop(shadow_map);
+  }
+
+  /// Unsafe because the device must be idle.
+  pub unsafe fn destroy_all(&mut self) {
+    info!("Destroying shadow pass...");
+    self.reset();
+    let logical_device = &self.logical_device;
+    logical_device.free_command_buffers(self.command_pool, &self.command_buffers);
+    logical_device.destroy_pipeline(self.pipeline, None);
+    logical_device.destroy_pipeline(self.lit_pipeline, None);
+    logical_device.destroy_pipeline(self.pbr_pipeline, None);
+    logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+    logical_device.destroy_framebuffer(self.framebuffer, None);
+    logical_device.destroy_render_pass(self.render_pass, None);
+    self.vertex_shader_handle.take();
+  }
+
+  fn begin_pass(&self, command_buffer: vk::CommandBuffer) -> SarektResult<()> {
+    let logical_device = &self.logical_device;
+    let begin_ci = vk::CommandBufferBeginInfo::builder()
+      .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+      .build();
+    unsafe { logical_device.begin_command_buffer(command_buffer, &begin_ci)? };
+    self.recording.set(true);
+
+    let atlas_size = 2 * self.tile_size;
+    let render_area = vk::Rect2D::builder()
+      .offset(vk::Offset2D::default())
+      .extent(vk::Extent2D {
+        width: atlas_size,
+        height: atlas_size,
+      })
+      .build();
+    let clear_values = [vk::ClearValue {
+      depth_stencil: vk::ClearDepthStencilValue {
+        depth: 1.0f32,
+        stencil: 0u32,
+      },
+    }];
+    let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+      .render_pass(self.render_pass)
+      .framebuffer(self.framebuffer)
+      .render_area(render_area)
+      .clear_values(&clear_values)
+      .build();
+    unsafe {
+      logical_device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_begin_info,
+        vk::SubpassContents::INLINE,
+      );
+    }
+
+    Ok(())
+  }
+
+  /// A depth only render pass that leaves the shadow map ready to be sampled.
+  fn create_render_pass(
+    logical_device: &Device, format: vk::Format,
+  ) -> SarektResult<vk::RenderPass> {
+    let depth_attachment = vk::AttachmentDescription::builder()
+      .format(format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(vk::AttachmentLoadOp::CLEAR)
+      .store_op(vk::AttachmentStoreOp::STORE) // Sampled afterwards.
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(vk::ImageLayout::UNDEFINED) // Cleared anyway.
+      .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+      .build();
+    let depth_attachment_ref = vk::AttachmentReference::builder()
+      .attachment(0)
+      .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+      .build();
+
+    let attachments = [depth_attachment];
+    let subpass_descriptions = [vk::SubpassDescription::builder()
+      .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+      .depth_stencil_attachment(&depth_attachment_ref)
+      .build()];
+
+    let dependencies = [
+      // Don't overwrite the shadow map while the previous frame still samples it.
+      vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0u32)
+        .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .src_access_mask(vk::AccessFlags::SHADER_READ)
+        .dst_stage_mask(
+          vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        )
+        .dst_access_mask(
+          vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .build(),
+      // Shadow receivers wait for the casters to be drawn.
+      vk::SubpassDependency::builder()
+        .src_subpass(0u32)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+        .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .build(),
+    ];
+
+    let render_pass_ci = vk::RenderPassCreateInfo::builder()
+      .attachments(&attachments)
+      .subpasses(&subpass_descriptions)
+      .dependencies(&dependencies)
+      .build();
+
+    Ok(unsafe { logical_device.create_render_pass(&render_pass_ci, None)? })
+  }
+
+  /// The model matrix is pushed per cascade along with the tile's view
+  /// projection, see `set_cascade_cmd`.
+  fn create_pipeline_layout(logical_device: &Device) -> SarektResult<vk::PipelineLayout> {
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+      .stage_flags(vk::ShaderStageFlags::VERTEX)
+      .offset(0)
+      .size(std::mem::size_of::<uv::Mat4>() as u32)
+      .build()];
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .push_constant_ranges(&push_constant_ranges)
+      .build();
+    Ok(unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? })
+  }
+
+  /// The caster pipeline reads only positions from the shader variant's vertex
+  /// layout, and takes the tile as dynamic viewport and scissor.
+  fn create_pipeline(
+    logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    vertex_shader_handle: &VulkanShaderHandle, render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout, variant: ForwardShaderVariant,
+  ) -> SarektResult<vk::Pipeline> {
+    let shader_store = shader_store.read().unwrap();
+
+    let entry_point_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let shader_stage_cis = [vk::PipelineShaderStageCreateInfo::builder()
+      .stage(vk::ShaderStageFlags::VERTEX)
+      .module(shader_store.get_shader(vertex_shader_handle)?.shader_handle)
+      .name(entry_point_name)
+      .build()];
+
+    let (binding_desc, attr_descs) = Pipelines::get_variant_vertex_input(variant);
+    let binding_descs = [binding_desc];
+    let attr_descs: Vec<_> = attr_descs
+      .into_iter()
+      .filter(|attr_desc| attr_desc.location == 0)
+      .collect();
+    let vertex_input_ci = vk::PipelineVertexInputStateCreateInfo::builder()
+      .vertex_binding_descriptions(&binding_descs)
+      .vertex_attribute_descriptions(&attr_descs)
+      .build();
+    let input_assembly_ci = vk::PipelineInputAssemblyStateCreateInfo::builder()
+      .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .primitive_restart_enable(false)
+      .build();
+
+    let viewport_state_ci = vk::PipelineViewportStateCreateInfo::builder()
+      .viewport_count(1)
+      .scissor_count(1)
+      .build();
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state_ci = vk::PipelineDynamicStateCreateInfo::builder()
+      .dynamic_states(&dynamic_states)
+      .build();
+
+    // Both faces cast, and the bias keeps surfaces from shadowing themselves.
+    let raster_state_ci = vk::PipelineRasterizationStateCreateInfo::builder()
+      .depth_clamp_enable(false)
+      .rasterizer_discard_enable(false)
+      .polygon_mode(vk::PolygonMode::FILL)
+      .line_width(1.0f32)
+      .cull_mode(vk::CullModeFlags::NONE)
+      .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+      .depth_bias_enable(true)
+      .depth_bias_constant_factor(1.25f32)
+      .depth_bias_slope_factor(1.75f32)
+      .build();
+
+    let multisample_state_ci = vk::PipelineMultisampleStateCreateInfo::builder()
+      .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+      .sample_shading_enable(false)
+      .alpha_to_coverage_enable(false)
+      .alpha_to_one_enable(false)
+      .build();
+
+    let depth_stencil_ci = vk::PipelineDepthStencilStateCreateInfo::builder()
+      .depth_test_enable(true)
+      .depth_write_enable(true)
+      .depth_compare_op(vk::CompareOp::LESS)
+      .depth_bounds_test_enable(false)
+      .min_depth_bounds(0.0f32)
+      .max_depth_bounds(1.0f32)
+      .stencil_test_enable(false)
+      .build();
+
+    // No color attachments.
+    let color_blend_ci = vk::PipelineColorBlendStateCreateInfo::builder()
+      .logic_op_enable(false)
+      .logic_op(vk::LogicOp::COPY)
+      .build();
+
+    let pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
+      .stages(&shader_stage_cis)
+      .vertex_input_state(&vertex_input_ci)
+      .input_assembly_state(&input_assembly_ci)
+      .viewport_state(&viewport_state_ci)
+      .rasterization_state(&raster_state_ci)
+      .multisample_state(&multisample_state_ci)
+      .depth_stencil_state(&depth_stencil_ci)
+      .color_blend_state(&color_blend_ci)
+      .dynamic_state(&dynamic_state_ci)
+      .layout(pipeline_layout)
+      .render_pass(render_pass)
+      .subpass(0)
+      .build();
+
+    let pipelines = unsafe {
+      logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_ci], None)
+    };
+    match pipelines {
+      Ok(pipelines) => Ok(pipelines[0]),
+      Err(err) => Err(err.1.into()),
+    }
+  }
+}
@@ -0,0 +1,297 @@
+use crate::{
+  error::SarektResult,
+  renderer::{
+    config::MsaaConfig,
+    shaders::ShaderStore,
+    vulkan::{
+      vulkan_buffer_image_functions::ImageAndMemory,
+      vulkan_shader_functions::VulkanShaderFunctions, VulkanShaderHandle,
+    },
+    ShaderCode, ShaderType,
+  },
+};
+use ash::{version::DeviceV1_0, vk, Device};
+use log::info;
+use std::{
+  ffi::CStr,
+  sync::{Arc, RwLock},
+};
+use ultraviolet as uv;
+use vk_shader_macros::include_glsl;
+
+/// Vertex shader of the skybox pass, draws a full screen triangle on the far
+/// plane.
+pub const SKYBOX_VERTEX_SHADER: &[u32] = include_glsl!("shaders/sarekt_skybox.vert");
+/// Fragment shader of the skybox pass, samples the cubemap in the view
+/// direction.
+pub const SKYBOX_FRAGMENT_SHADER: &[u32] = include_glsl!("shaders/sarekt_skybox.frag");
+
+/// The built-in skybox pipeline.  It is drawn last in the forward render pass
+/// with depth writes off and a LESS_OR_EQUAL depth test against the far plane,
+/// so it only shows where nothing else was drawn.
+pub struct SkyboxPipeline {
+  pub pipeline: vk::Pipeline,
+  pub pipeline_layout: vk::PipelineLayout,
+  descriptor_set_layout: vk::DescriptorSetLayout,
+  vertex_shader_handle: VulkanShaderHandle,
+  fragment_shader_handle: VulkanShaderHandle,
+}
+impl SkyboxPipeline {
+  pub fn new(
+    logical_device: &Device, shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>,
+    extent: vk::Extent2D, render_pass: vk::RenderPass, subpass: u32, msaa_config: &MsaaConfig,
+  ) -> SarektResult<Self> {
+    let vertex_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(SKYBOX_VERTEX_SHADER),
+      ShaderType::Vertex,
+    )?;
+    let fragment_shader_handle = ShaderStore::load_shader(
+      shader_store,
+      &ShaderCode::Spirv(SKYBOX_FRAGMENT_SHADER),
+      ShaderType::Fragment,
+    )?;
+
+    // Just the cubemap, the camera comes in through push constants.
+    let bindings = [vk::DescriptorSetLayoutBinding::builder()
+      .binding(0)
+      .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+      .descriptor_count(1)
+      .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+      .build()];
+    let descriptor_set_layout_ci = vk::DescriptorSetLayoutCreateInfo::builder()
+      .bindings(&bindings)
+      .build();
+    let descriptor_set_layout =
+      unsafe { logical_device.create_descriptor_set_layout(&descriptor_set_layout_ci, None)? };
+
+    let mut skybox_pipeline = Self {
+      pipeline: vk::Pipeline::null(),
+      pipeline_layout: vk::PipelineLayout::null(),
+      descriptor_set_layout,
+      vertex_shader_handle,
+      fragment_shader_handle,
+    };
+    skybox_pipeline.recreate_pipeline(
+      logical_device,
+      shader_store,
+      extent,
+      render_pass,
+      subpass,
+      msaa_config,
+    )?;
+
+    Ok(skybox_pipeline)
+  }
+
+  /// Recreates the pipeline for a new render pass or extent, keeping the
+  /// shaders and descriptor set layout.  The old one must already have been
+  /// cleaned up with `cleanup_pipeline`.  `subpass` is the render pass's
+  /// forward subpass.
+  pub fn recreate_pipeline(
+    &mut self, logical_device: &Device,
+    shader_store: &Arc<RwLock<ShaderStore<VulkanShaderFunctions>>>, extent: vk::Extent2D,
+    render_pass: vk::RenderPass, subpass: u32, msaa_config: &MsaaConfig,
+  ) -> SarektResult<()> {
+    let shader_store = shader_store.read().unwrap();
+
+    let entry_point_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let shader_stage_cis = [
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(
+          shader_store
+            .get_shader(&self.vertex_shader_handle)?
+            .shader_handle,
+        )
+        .name(entry_point_name)
+        .build(),
+      vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(
+          shader_store
+            .get_shader(&self.fragment_shader_handle)?
+            .shader_handle,
+        )
+        .name(entry_point_name)
+        .build(),
+    ];
+
+    // Vertices are generated from gl_VertexIndex.
+    let vertex_input_ci = vk::PipelineVertexInputStateCreateInfo::builder().build();
+    let input_assembly_ci = vk::PipelineInputAssemblyStateCreateInfo::builder()
+      .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .primitive_restart_enable(false)
+      .build();
+
+    let viewports = [vk::Viewport::builder()
+      .x(0f32)
+      .y(0f32)
+      .width(extent.width as f32)
+      .height(extent.height as f32)
+      .min_depth(0f32)
+      .max_depth(1.0f32)
+      .build()];
+    let scissors = [vk::Rect2D::builder()
+      .offset(vk::Offset2D::default())
+      .extent(extent)
+      .build()];
+    let viewport_state_ci = vk::PipelineViewportStateCreateInfo::builder()
+      .viewports(&viewports)
+      .scissors(&scissors)
+      .build();
+
+    let raster_state_ci = vk::PipelineRasterizationStateCreateInfo::builder()
+      .depth_clamp_enable(false)
+      .rasterizer_discard_enable(false)
+      .polygon_mode(vk::PolygonMode::FILL)
+      .line_width(1.0f32)
+      .cull_mode(vk::CullModeFlags::NONE) // The full screen triangle faces whichever way.
+      .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+      .depth_bias_enable(false)
+      .build();
+
+    let multisample_state_ci = vk::PipelineMultisampleStateCreateInfo::builder()
+      .rasterization_samples(msaa_config.samples.into())
+      .sample_shading_enable(false)
+      .alpha_to_coverage_enable(false)
+      .alpha_to_one_enable(false)
+      .build();
+
+    // Test against what the scene drew, but never write, the sky is at infinity.
+    let depth_stencil_ci = vk::PipelineDepthStencilStateCreateInfo::builder()
+      .depth_test_enable(true)
+      .depth_write_enable(false)
+      .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+      .depth_bounds_test_enable(false)
+      .min_depth_bounds(0.0f32)
+      .max_depth_bounds(1.0f32)
+      .stencil_test_enable(false)
+      .build();
+
+    let attachments = [vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(false)
+      .build()];
+    let color_blend_ci = vk::PipelineColorBlendStateCreateInfo::builder()
+      .logic_op_enable(false)
+      .logic_op(vk::LogicOp::COPY)
+      .attachments(&attachments)
+      .build();
+
+    let set_layouts = [self.descriptor_set_layout];
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+      .stage_flags(vk::ShaderStageFlags::VERTEX)
+      .offset(0)
+      .size(std::mem::size_of::<uv::Mat4>() as u32)
+      .build()];
+    let pipeline_layout_ci = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(&set_layouts)
+      .push_constant_ranges(&push_constant_ranges)
+      .build();
+    let pipeline_layout =
+      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_ci, None)? };
+
+    let pipeline_ci = vk::GraphicsPipelineCreateInfo::builder()
+      .stages(&shader_stage_cis)
+      .vertex_input_state(&vertex_input_ci)
+      .input_assembly_state(&input_assembly_ci)
+      .viewport_state(&viewport_state_ci)
+      .rasterization_state(&raster_state_ci)
+      .multisample_state(&multisample_state_ci)
+      .depth_stencil_state(&depth_stencil_ci)
+      .color_blend_state(&color_blend_ci)
+      .layout(pipeline_layout)
+      .render_pass(render_pass)
+      .subpass(subpass)
+      .build();
+
+    let pipeline = unsafe {
+      logical_device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_ci], None)
+    };
+    let pipeline = match pipeline {
+      Ok(pipelines) => pipelines[0],
+      Err(err) => {
+        unsafe { logical_device.destroy_pipeline_layout(pipeline_layout, None) };
+        return Err(err.1.into());
+      }
+    };
+
+    self.pipeline = pipeline;
+    self.pipeline_layout = pipeline_layout;
+    Ok(())
+  }
+
+  /// Records drawing the skybox into the command buffer, which must be inside
+  /// the forward render pass.  Binds the skybox pipeline, so the base pipeline
+  /// must be rebound if anything else is drawn afterwards.
+  pub fn draw_cmd(
+    &self, logical_device: &Device, command_buffer: vk::CommandBuffer,
+    descriptor_pool: vk::DescriptorPool, cubemap: &ImageAndMemory, view_projection: &uv::Mat4,
+  ) -> SarektResult<()> {
+    let set_layouts = [self.descriptor_set_layout];
+    let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+      .descriptor_pool(descriptor_pool)
+      .set_layouts(&set_layouts)
+      .build();
+    let descriptor_sets = unsafe { logical_device.allocate_descriptor_sets(&alloc_info)? };
+
+    let image_infos = [vk::DescriptorImageInfo::builder()
+      .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+      .image_view(cubemap.image_and_view.view)
+      .sampler(cubemap.sampler.unwrap())
+      .build()];
+    let descriptor_writes = [vk::WriteDescriptorSet::builder()
+      .dst_set(descriptor_sets[0])
+      .dst_binding(0)
+      .dst_array_element(0)
+      .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+      .image_info(&image_infos)
+      .build()];
+
+    unsafe {
+      logical_device.update_descriptor_sets(&descriptor_writes, &[]);
+
+      logical_device.cmd_bind_pipeline(
+        command_buffer,
+        vk::PipelineBindPoint::GRAPHICS,
+        self.pipeline,
+      );
+      logical_device.cmd_bind_descriptor_sets(
+        command_buffer,
+        vk::PipelineBindPoint::GRAPHICS,
+        self.pipeline_layout,
+        0,
+        &descriptor_sets,
+        &[],
+      );
+      logical_device.cmd_push_constants(
+        command_buffer,
+        self.pipeline_layout,
+        vk::ShaderStageFlags::VERTEX,
+        0,
+        std::slice::from_raw_parts(
+          view_projection as *const uv::Mat4 as *const u8,
+          std::mem::size_of::<uv::Mat4>(),
+        ),
+      );
+      logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+
+    Ok(())
+  }
+
+  /// Destroys the pipeline and its layout, for swapchain recreation or
+  /// cleanup.
+  pub unsafe fn cleanup_pipeline(&self, logical_device: &Device) {
+    info!("Destroying skybox pipeline...");
+    logical_device.destroy_pipeline(self.pipeline, None);
+    logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+  }
+
+  /// Must be called during renderer's drop.
+  pub unsafe fn cleanup_descriptor_set_layout(&self, logical_device: &Device) {
+    info!("Destroying skybox descriptor set layout...");
+    logical_device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+  }
+}
@@ -1,19 +1,21 @@
 use ash::vk;
 
-/// Wrapper for the swapchain, its format, and various methods.
+/// Wrapper for the swapchain, its format and color space, and various methods.
 pub struct SwapchainAndExtension {
   pub swapchain: vk::SwapchainKHR,
   pub format: vk::Format,
+  pub color_space: vk::ColorSpaceKHR,
   pub swapchain_functions: ash::extensions::khr::Swapchain,
 }
 impl SwapchainAndExtension {
   pub fn new(
-    swapchain: vk::SwapchainKHR, format: vk::Format,
+    swapchain: vk::SwapchainKHR, surface_format: vk::SurfaceFormatKHR,
     swapchain_functions: ash::extensions::khr::Swapchain,
   ) -> Self {
     SwapchainAndExtension {
       swapchain,
-      format,
+      format: surface_format.format,
+      color_space: surface_format.color_space,
       swapchain_functions,
     }
   }
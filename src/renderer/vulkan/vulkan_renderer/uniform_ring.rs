@@ -0,0 +1,135 @@
+use crate::{
+  error::{SarektError, SarektResult},
+  renderer::{
+    buffers_and_images::{BufferImageHandle, BufferImageStore, BufferType},
+    vulkan::vulkan_buffer_image_functions::VulkanBufferImageFunctions,
+    MAX_FRAMES_IN_FLIGHT,
+  },
+};
+use ash::vk;
+use log::{error, info};
+use std::{
+  cell::Cell,
+  sync::{Arc, RwLock},
+};
+
+/// Linear allocator for uniform data that only lives for one frame, used by
+/// `Drawer::draw_with_uniform`.
+///
+/// A single persistently mapped buffer is split into one region per frame in
+/// flight.  Each draw bumps the offset into the region of the frame being
+/// recorded and binds the data with a dynamic uniform buffer offset, and the
+/// region is reset once its frame is done on the GPU.
+pub struct UniformRing {
+  allocator: Arc<vk_mem::Allocator>,
+  buffer: vk::Buffer,
+  allocation: vk_mem::Allocation,
+  ptr: *mut u8,
+  /// Size of each frame's region, a multiple of `alignment`.
+  frame_size: u64,
+  /// The device's `minUniformBufferOffsetAlignment`.
+  alignment: u64,
+  current_frame_num: Cell<usize>,
+  /// Bytes used in the current frame's region, a multiple of `alignment`.
+  used: Cell<u64>,
+
+  // Keeps the buffer alive until after `drop` unmaps it.
+  _handle: BufferImageHandle<VulkanBufferImageFunctions>,
+}
+impl UniformRing {
+  pub fn new(
+    buffer_image_store: &Arc<RwLock<BufferImageStore<VulkanBufferImageFunctions>>>,
+    allocator: Arc<vk_mem::Allocator>, frame_size: u64, alignment: u64,
+  ) -> SarektResult<Self> {
+    let alignment = alignment.max(1);
+    let frame_size = align_up(frame_size.max(1), alignment);
+    info!(
+      "Creating transient uniform buffer of {} bytes per frame in flight...",
+      frame_size
+    );
+
+    let (handle, buffer_or_image) = BufferImageStore::load_buffer_without_staging(
+      buffer_image_store,
+      BufferType::Uniform,
+      &vec![0u8; (frame_size * MAX_FRAMES_IN_FLIGHT as u64) as usize],
+    )?;
+    let buffer = buffer_or_image.handle.buffer()?;
+    let ptr = allocator.map_memory(&buffer.allocation)?;
+
+    Ok(Self {
+      allocator,
+      buffer: buffer.buffer,
+      allocation: buffer.allocation,
+      ptr,
+      frame_size,
+      alignment,
+      current_frame_num: Cell::new(0),
+      used: Cell::new(0),
+      _handle: handle,
+    })
+  }
+
+  pub fn buffer(&self) -> vk::Buffer {
+    self.buffer
+  }
+
+  /// Starts allocating from the region of `current_frame_num`, whose previous
+  /// frame must be done on the GPU.
+  pub fn start_frame(&self, current_frame_num: usize) {
+    if current_frame_num >= MAX_FRAMES_IN_FLIGHT {
+      panic!("Invalid input! current_frame_num {}", current_frame_num);
+    }
+    self.current_frame_num.set(current_frame_num);
+    self.used.set(0);
+  }
+
+  /// Copies `data` into the current frame's region and returns its offset in
+  /// the buffer, to be passed as the dynamic offset.
+  pub fn allocate<T: Sized + Copy>(&self, data: &T) -> SarektResult<u32> {
+    let size = std::mem::size_of::<T>() as u64;
+    let offset_in_frame = self.used.get();
+    if offset_in_frame + size > self.frame_size {
+      return Err(SarektError::TransientUniformMemoryExhausted(
+        self.frame_size,
+      ));
+    }
+
+    let offset = self.current_frame_num.get() as u64 * self.frame_size + offset_in_frame;
+    unsafe {
+      let ptr = self.ptr.add(offset as usize) as *mut T;
+      ptr.copy_from_nonoverlapping(data, 1);
+    }
+    self
+      .used
+      .set(align_up(offset_in_frame + size, self.alignment));
+
+    Ok(offset as u32)
+  }
+
+  /// Makes the current frame's writes visible to the device, memory isn't
+  /// necessarily host coherent.
+  pub fn flush(&self) -> SarektResult<()> {
+    let used = self.used.get();
+    if used == 0 {
+      return Ok(());
+    }
+
+    let offset = self.current_frame_num.get() as u64 * self.frame_size;
+    Ok(
+      self
+        .allocator
+        .flush_allocation(&self.allocation, offset as usize, used as usize)?,
+    )
+  }
+}
+impl Drop for UniformRing {
+  fn drop(&mut self) {
+    if let Err(e) = self.allocator.unmap_memory(&self.allocation) {
+      error!("Could not unmap transient uniform buffer: {}", e);
+    }
+  }
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+  (value + alignment - 1) / alignment * alignment
+}
@@ -127,7 +127,7 @@ impl VulkanCoreStructures {
       }
     }
 
-    let extension_names = Self::get_required_extensions(window)?;
+    let extension_names = Self::get_required_extensions(entry, window)?;
     unsafe {
       if IS_DEBUG_MODE {
         Self::log_extensions_dialog(entry, &extension_names);
@@ -159,8 +159,10 @@ impl VulkanCoreStructures {
   //  Instance Helper Methods
   // ================================================================================
   /// Returns all extension needed for this renderer, depending on windowing
-  /// system (or lack thereof) etc.
-  fn get_required_extensions<W: HasRawWindowHandle>(window: &W) -> SarektResult<Vec<&CStr>> {
+  /// system (or lack thereof) etc, and the optional ones that are available.
+  fn get_required_extensions<W: HasRawWindowHandle>(
+    entry: &Entry, window: &W,
+  ) -> SarektResult<Vec<&'static CStr>> {
     // Includes VK_KHR_Surface and
     // VK_KHR_Win32_Surface/VK_KHR_xcb_surface/
     // VK_GGP_stream_descriptor_surface(stadia)
@@ -170,6 +172,16 @@ impl VulkanCoreStructures {
       extensions.push(DebugUtils::name());
     }
 
+    // Lets surfaces list HDR color spaces, see SurfaceFormatPreference::Hdr10.
+    let swapchain_colorspace = vk::ExtSwapchainColorspaceFn::name();
+    let available_extensions = entry.enumerate_instance_extension_properties()?;
+    let has_swapchain_colorspace = available_extensions
+      .iter()
+      .any(|e| unsafe { CStr::from_ptr(e.extension_name.as_ptr()) } == swapchain_colorspace);
+    if has_swapchain_colorspace {
+      extensions.push(swapchain_colorspace);
+    }
+
     Ok(extensions)
   }
 
@@ -530,8 +542,13 @@ impl VulkanDeviceStructures {
       })
       .collect();
 
+    // Wide lines and large points are optional, so enable them whenever they
+    // are supported and check for them when creating pipelines that use them.
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let device_features = vk::PhysicalDeviceFeatures::builder()
       .sampler_anisotropy(true)
+      .wide_lines(supported_features.wide_lines == vk::TRUE)
+      .large_points(supported_features.large_points == vk::TRUE)
       .build();
 
     let enabled_extension_names = [ash::extensions::khr::Swapchain::name().as_ptr()];
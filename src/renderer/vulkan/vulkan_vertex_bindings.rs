@@ -1,16 +1,104 @@
 use crate::{
-  error::SarektResult,
-  renderer::vertex_bindings::{
-    BindTextureInfo, BindUniformInfo, DefaultForwardShaderLayout, DefaultForwardShaderVertex,
-    DescriptorLayoutInfo, VertexBindings,
+  error::{SarektError, SarektResult},
+  renderer::{
+    lighting::{MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS},
+    pbr::PbrTextureSlot,
+    uniform_layout::{GlslType, LayoutRules, UniformBlockLayout},
+    vertex_bindings::{
+      BindTextureInfo, BindUniformInfo, DefaultForwardShaderLayout, DefaultLitShaderLayout,
+      DefaultLitShaderVertex, DefaultPbrShaderLayout, DefaultPbrShaderVertex,
+      DefaultShadowedShaderLayout, DescriptorLayoutInfo, ForwardShaderVariant, VertexBindings,
+    },
+    vertex_formats::{
+      Half2, Half4, Snorm1010102, Snorm16x2, Snorm16x4, Snorm8x4, Unorm1010102, Unorm16x2,
+      Unorm16x4, Unorm8x2, Unorm8x4,
+    },
   },
+  uniform_field,
 };
-use ash::vk;
+use ash::{version::InstanceV1_0, vk, Instance};
+use ultraviolet as uv;
 
 // TODO(issue#21) SHADERS use reflection to generate these at compile time
 // (generically?).
 
-unsafe impl VertexBindings for DefaultForwardShaderVertex {
+/// The Vulkan format of a vertex attribute of this type, used by
+/// `#[derive(VertexBindings)]`.
+///
+/// Unsafe because the format must match the type's size and layout.
+pub unsafe trait VertexAttributeFormat {
+  const FORMAT: vk::Format;
+}
+macro_rules! vertex_attribute_formats {
+  ($($ty:ty => $format:ident,)*) => {
+    $(
+      unsafe impl VertexAttributeFormat for $ty {
+        const FORMAT: vk::Format = vk::Format::$format;
+      }
+    )*
+  };
+}
+vertex_attribute_formats! {
+  f32 => R32_SFLOAT,
+  [f32; 2] => R32G32_SFLOAT,
+  [f32; 3] => R32G32B32_SFLOAT,
+  [f32; 4] => R32G32B32A32_SFLOAT,
+  uv::Vec2 => R32G32_SFLOAT,
+  uv::Vec3 => R32G32B32_SFLOAT,
+  uv::Vec4 => R32G32B32A32_SFLOAT,
+  Unorm8x2 => R8G8_UNORM,
+  Unorm8x4 => R8G8B8A8_UNORM,
+  Snorm8x4 => R8G8B8A8_SNORM,
+  Unorm16x2 => R16G16_UNORM,
+  Unorm16x4 => R16G16B16A16_UNORM,
+  Snorm16x2 => R16G16_SNORM,
+  Snorm16x4 => R16G16B16A16_SNORM,
+  Half2 => R16G16_SFLOAT,
+  Half4 => R16G16B16A16_SFLOAT,
+  Unorm1010102 => A2B10G10R10_UNORM_PACK32,
+  Snorm1010102 => A2B10G10R10_SNORM_PACK32,
+  [u8; 4] => R8G8B8A8_UINT,
+  [u16; 2] => R16G16_UINT,
+  [u16; 4] => R16G16B16A16_UINT,
+  [i16; 2] => R16G16_SINT,
+  [i16; 4] => R16G16B16A16_SINT,
+  u32 => R32_UINT,
+  [u32; 2] => R32G32_UINT,
+  [u32; 3] => R32G32B32_UINT,
+  [u32; 4] => R32G32B32A32_UINT,
+  i32 => R32_SINT,
+  [i32; 2] => R32G32_SINT,
+  [i32; 3] => R32G32B32_SINT,
+  [i32; 4] => R32G32B32A32_SINT,
+}
+
+/// Checks the device can read every attribute from a vertex buffer, listing
+/// the formats it can't in the error.
+pub(crate) fn check_vertex_attribute_formats(
+  instance: &Instance, physical_device: vk::PhysicalDevice,
+  attributes: &[vk::VertexInputAttributeDescription],
+) -> SarektResult<()> {
+  let unsupported: Vec<String> = attributes
+    .iter()
+    .filter(|attribute| {
+      let features = unsafe {
+        instance
+          .get_physical_device_format_properties(physical_device, attribute.format)
+          .buffer_features
+      };
+      !features.contains(vk::FormatFeatureFlags::VERTEX_BUFFER)
+    })
+    .map(|attribute| format!("{:?} at location {}", attribute.format, attribute.location))
+    .collect();
+
+  if unsupported.is_empty() {
+    Ok(())
+  } else {
+    Err(SarektError::UnsupportedVertexFormat(unsupported.join(", ")))
+  }
+}
+
+unsafe impl VertexBindings for DefaultLitShaderVertex {
   type BVA = vk::VertexInputAttributeDescription;
   type BVB = vk::VertexInputBindingDescription;
 
@@ -23,51 +111,113 @@ unsafe impl VertexBindings for DefaultForwardShaderVertex {
   }
 
   fn get_attribute_descriptions() -> Vec<Self::BVA> {
-    // Position
     let position_attr = vk::VertexInputAttributeDescription::builder()
-      .binding(0) // Which binding in the shader.
-      .location(0) // The layout location in the shader.
-      .format(vk::Format::R32G32B32_SFLOAT) // RGB is unintuitive but the point is its three floats.
-      .offset(offset_of!(DefaultForwardShaderVertex, position) as u32)
+      .binding(0)
+      .location(0)
+      .format(vk::Format::R32G32B32_SFLOAT)
+      .offset(offset_of!(DefaultLitShaderVertex, position) as u32)
+      .build();
+    let normal_attr = vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(1)
+      .format(vk::Format::R32G32B32_SFLOAT)
+      .offset(offset_of!(DefaultLitShaderVertex, normal) as u32)
       .build();
     let color_attr = vk::VertexInputAttributeDescription::builder()
-      .binding(0) // Which binding in the shader.
-      .location(1) // The layout location in the shader.
-      .format(vk::Format::R32G32B32_SFLOAT) // RGB is unintuitive but the point is its two floats.
-      .offset(offset_of!(DefaultForwardShaderVertex, color) as u32)
+      .binding(0)
+      .location(2)
+      .format(vk::Format::R32G32B32_SFLOAT)
+      .offset(offset_of!(DefaultLitShaderVertex, color) as u32)
       .build();
     let texture_attr = vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(3)
+      .format(vk::Format::R32G32_SFLOAT)
+      .offset(offset_of!(DefaultLitShaderVertex, texture_coordinates) as u32)
+      .build();
+
+    vec![position_attr, normal_attr, color_attr, texture_attr]
+  }
+}
+
+unsafe impl VertexBindings for DefaultPbrShaderVertex {
+  type BVA = vk::VertexInputAttributeDescription;
+  type BVB = vk::VertexInputBindingDescription;
+
+  fn get_binding_description() -> Self::BVB {
+    vk::VertexInputBindingDescription::builder()
+      .binding(0)
+      .stride(std::mem::size_of::<Self>() as u32)
+      .input_rate(vk::VertexInputRate::VERTEX)
+      .build()
+  }
+
+  fn get_attribute_descriptions() -> Vec<Self::BVA> {
+    let position_attr = vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(0)
+      .format(vk::Format::R32G32B32_SFLOAT)
+      .offset(offset_of!(DefaultPbrShaderVertex, position) as u32)
+      .build();
+    let normal_attr = vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(1)
+      .format(vk::Format::R32G32B32_SFLOAT)
+      .offset(offset_of!(DefaultPbrShaderVertex, normal) as u32)
+      .build();
+    let tangent_attr = vk::VertexInputAttributeDescription::builder()
       .binding(0)
       .location(2)
+      .format(vk::Format::R32G32B32A32_SFLOAT)
+      .offset(offset_of!(DefaultPbrShaderVertex, tangent) as u32)
+      .build();
+    let texture_attr = vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(3)
       .format(vk::Format::R32G32_SFLOAT)
-      .offset(offset_of!(DefaultForwardShaderVertex, texture_coordinates) as u32)
+      .offset(offset_of!(DefaultPbrShaderVertex, texture_coordinates) as u32)
       .build();
 
-    vec![position_attr, color_attr, texture_attr]
+    vec![position_attr, normal_attr, tangent_attr, texture_attr]
   }
 }
 
 // TODO(issue#21) SHADERS use reflection to generate descriptor set layouts.
 
-unsafe impl DescriptorLayoutInfo for DefaultForwardShaderLayout {
-  type BackendDescriptorSetLayoutBindings = [vk::DescriptorSetLayoutBinding; 2];
+/// Bindings of the shadow map and the light's `ShadowUniform` in
+/// sarekt_forward_shadowed.frag, which the renderer binds itself.
+pub(crate) const SHADOW_MAP_BINDING: u32 = 2;
+pub(crate) const SHADOW_UNIFORM_BINDING: u32 = 3;
+
+unsafe impl DescriptorLayoutInfo for DefaultShadowedShaderLayout {
+  type BackendDescriptorSetLayoutBindings = [vk::DescriptorSetLayoutBinding; 4];
 
   fn get_descriptor_set_layout_bindings() -> Self::BackendDescriptorSetLayoutBindings {
-    // Create the bindings for each part of the uniform.
     [
       vk::DescriptorSetLayoutBinding::builder()
-      .binding(0)
-      .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-      .descriptor_count(1) // If this uniform contained an array (like of lights, or transforms for each bone for animation) this is how many.
-      .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT) // used in the vertex and fragment shader.
-      // .immutable_samplers() no samplers since there's no textures. 
-      .build(),
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+        .build(),
       vk::DescriptorSetLayoutBinding::builder()
         .binding(1)
         .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
         .descriptor_count(1)
         .stage_flags(vk::ShaderStageFlags::FRAGMENT)
         .build(),
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(SHADOW_MAP_BINDING)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build(),
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(SHADOW_UNIFORM_BINDING)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build(),
     ]
   }
 
@@ -75,11 +225,469 @@ unsafe impl DescriptorLayoutInfo for DefaultForwardShaderLayout {
     Ok(BindUniformInfo {
       bindings: vec![0],
       offset: 0u64,
-      range: std::mem::size_of::<DefaultForwardShaderLayout>() as u64,
+      range: std::mem::size_of::<DefaultShadowedShaderLayout>() as u64,
+    })
+  }
+
+  fn get_bind_texture_info() -> SarektResult<BindTextureInfo> {
+    Ok(BindTextureInfo { bindings: vec![1] })
+  }
+
+  fn get_uniform_block_layout() -> Option<UniformBlockLayout> {
+    // DefaultShadowedShaderUniform in sarekt_forward_shadowed.vert and .frag.
+    let mat4 = GlslType::Matrix {
+      columns: 4,
+      rows: 4,
+    };
+    Some(UniformBlockLayout::new::<DefaultShadowedShaderLayout>(
+      LayoutRules::Std140,
+      vec![
+        uniform_field!(DefaultShadowedShaderLayout, mvp, mat4.clone()),
+        uniform_field!(DefaultShadowedShaderLayout, model, mat4),
+        uniform_field!(
+          DefaultShadowedShaderLayout,
+          enable_color_mixing,
+          GlslType::Scalar
+        ),
+        uniform_field!(
+          DefaultShadowedShaderLayout,
+          enable_texture_mixing,
+          GlslType::Scalar
+        ),
+      ],
+    ))
+  }
+
+  fn forward_shader_variant() -> ForwardShaderVariant {
+    ForwardShaderVariant::ShadowReceiving
+  }
+}
+
+unsafe impl DescriptorLayoutInfo for DefaultLitShaderLayout {
+  type BackendDescriptorSetLayoutBindings = [vk::DescriptorSetLayoutBinding; 2];
+
+  fn get_descriptor_set_layout_bindings() -> Self::BackendDescriptorSetLayoutBindings {
+    // Same as the default forward shaders, the lights are in the uniform.
+    DefaultForwardShaderLayout::get_descriptor_set_layout_bindings()
+  }
+
+  fn get_bind_uniform_info() -> SarektResult<BindUniformInfo> {
+    Ok(BindUniformInfo {
+      bindings: vec![0],
+      offset: 0u64,
+      range: std::mem::size_of::<DefaultLitShaderLayout>() as u64,
     })
   }
 
   fn get_bind_texture_info() -> SarektResult<BindTextureInfo> {
     Ok(BindTextureInfo { bindings: vec![1] })
   }
+
+  fn get_uniform_block_layout() -> Option<UniformBlockLayout> {
+    // DefaultLitShaderUniform in sarekt_forward_lit.vert and .frag.
+    let mat4 = GlslType::Matrix {
+      columns: 4,
+      rows: 4,
+    };
+    let vec3 = GlslType::Vector(3);
+    let point_light = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+    ]);
+    let directional_light = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+    ]);
+    let spot_light = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+      GlslType::Scalar,
+    ]);
+    let material = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+    ]);
+
+    Some(UniformBlockLayout::new::<DefaultLitShaderLayout>(
+      LayoutRules::Std140,
+      vec![
+        uniform_field!(DefaultLitShaderLayout, model, mat4.clone()),
+        uniform_field!(DefaultLitShaderLayout, view, mat4.clone()),
+        uniform_field!(DefaultLitShaderLayout, projection, mat4),
+        uniform_field!(DefaultLitShaderLayout, camera_position, vec3),
+        uniform_field!(DefaultLitShaderLayout, shading_model, GlslType::Scalar),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          point_lights,
+          GlslType::array(point_light, MAX_POINT_LIGHTS)
+        ),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          directional_lights,
+          GlslType::array(directional_light, MAX_DIRECTIONAL_LIGHTS)
+        ),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          spot_lights,
+          GlslType::array(spot_light, MAX_SPOT_LIGHTS)
+        ),
+        uniform_field!(DefaultLitShaderLayout, material, material),
+        uniform_field!(DefaultLitShaderLayout, point_light_count, GlslType::Scalar),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          directional_light_count,
+          GlslType::Scalar
+        ),
+        uniform_field!(DefaultLitShaderLayout, spot_light_count, GlslType::Scalar),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          enable_color_mixing,
+          GlslType::Scalar
+        ),
+        uniform_field!(
+          DefaultLitShaderLayout,
+          enable_texture_mixing,
+          GlslType::Scalar
+        ),
+      ],
+    ))
+  }
+
+  fn forward_shader_variant() -> ForwardShaderVariant {
+    ForwardShaderVariant::Lit
+  }
+}
+
+/// Binding of the first PBR material texture, the rest follow in
+/// `PbrTextureSlot` order.
+const PBR_FIRST_TEXTURE_BINDING: u32 = 1;
+/// Bindings of the image based lighting maps, which the renderer binds itself.
+pub(crate) const PBR_IRRADIANCE_BINDING: u32 = 6;
+pub(crate) const PBR_PREFILTERED_BINDING: u32 = 7;
+pub(crate) const PBR_BRDF_LUT_BINDING: u32 = 8;
+
+unsafe impl DescriptorLayoutInfo for DefaultPbrShaderLayout {
+  type BackendDescriptorSetLayoutBindings = [vk::DescriptorSetLayoutBinding; 9];
+
+  fn get_descriptor_set_layout_bindings() -> Self::BackendDescriptorSetLayoutBindings {
+    let sampler = |binding| {
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build()
+    };
+    [
+      vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+        .build(),
+      sampler(PBR_FIRST_TEXTURE_BINDING),
+      sampler(PBR_FIRST_TEXTURE_BINDING + 1),
+      sampler(PBR_FIRST_TEXTURE_BINDING + 2),
+      sampler(PBR_FIRST_TEXTURE_BINDING + 3),
+      sampler(PBR_FIRST_TEXTURE_BINDING + 4),
+      sampler(PBR_IRRADIANCE_BINDING),
+      sampler(PBR_PREFILTERED_BINDING),
+      sampler(PBR_BRDF_LUT_BINDING),
+    ]
+  }
+
+  fn get_bind_uniform_info() -> SarektResult<BindUniformInfo> {
+    Ok(BindUniformInfo {
+      bindings: vec![0],
+      offset: 0u64,
+      range: std::mem::size_of::<DefaultPbrShaderLayout>() as u64,
+    })
+  }
+
+  fn get_bind_texture_info() -> SarektResult<BindTextureInfo> {
+    // The drawable object's texture slots in order.
+    Ok(BindTextureInfo {
+      bindings: PbrTextureSlot::ALL
+        .iter()
+        .map(|slot| PBR_FIRST_TEXTURE_BINDING + slot.index() as u32)
+        .collect(),
+    })
+  }
+
+  fn get_uniform_block_layout() -> Option<UniformBlockLayout> {
+    // DefaultPbrShaderUniform in sarekt_forward_pbr.vert and .frag.
+    let mat4 = GlslType::Matrix {
+      columns: 4,
+      rows: 4,
+    };
+    let vec3 = GlslType::Vector(3);
+    let point_light = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+    ]);
+    let directional_light = point_light.clone();
+    let spot_light = GlslType::Struct(vec![
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+      vec3.clone(),
+      GlslType::Scalar,
+      GlslType::Scalar,
+    ]);
+    let material = GlslType::Struct(vec![
+      GlslType::Vector(4),
+      vec3.clone(),
+      GlslType::Scalar,
+      GlslType::Scalar,
+      GlslType::Scalar,
+      GlslType::Scalar,
+      GlslType::Scalar,
+    ]);
+
+    Some(UniformBlockLayout::new::<DefaultPbrShaderLayout>(
+      LayoutRules::Std140,
+      vec![
+        uniform_field!(DefaultPbrShaderLayout, model, mat4.clone()),
+        uniform_field!(DefaultPbrShaderLayout, view, mat4.clone()),
+        uniform_field!(DefaultPbrShaderLayout, projection, mat4),
+        uniform_field!(DefaultPbrShaderLayout, camera_position, vec3),
+        uniform_field!(
+          DefaultPbrShaderLayout,
+          environment_intensity,
+          GlslType::Scalar
+        ),
+        uniform_field!(
+          DefaultPbrShaderLayout,
+          point_lights,
+          GlslType::array(point_light, MAX_POINT_LIGHTS)
+        ),
+        uniform_field!(
+          DefaultPbrShaderLayout,
+          directional_lights,
+          GlslType::array(directional_light, MAX_DIRECTIONAL_LIGHTS)
+        ),
+        uniform_field!(
+          DefaultPbrShaderLayout,
+          spot_lights,
+          GlslType::array(spot_light, MAX_SPOT_LIGHTS)
+        ),
+        uniform_field!(DefaultPbrShaderLayout, material, material),
+        uniform_field!(DefaultPbrShaderLayout, point_light_count, GlslType::Scalar),
+        uniform_field!(
+          DefaultPbrShaderLayout,
+          directional_light_count,
+          GlslType::Scalar
+        ),
+        uniform_field!(DefaultPbrShaderLayout, spot_light_count, GlslType::Scalar),
+      ],
+    ))
+  }
+
+  fn forward_shader_variant() -> ForwardShaderVariant {
+    ForwardShaderVariant::Pbr
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::vertex_bindings::{
+    DefaultForwardShaderVertex, DescriptorLayout, VertexBindings,
+  };
+
+  #[repr(C)]
+  #[derive(Copy, Clone, VertexBindings)]
+  struct PackedVertex {
+    position: uv::Vec3,
+    #[location(4)]
+    normal: Snorm1010102,
+    color: Unorm8x4,
+    texture_coordinates: Half2,
+  }
+
+  #[repr(C)]
+  #[derive(Copy, Clone, DescriptorLayout)]
+  #[binding(0, 2)]
+  #[texture_binding(1)]
+  #[layout(std430)]
+  struct ParticleUniform {
+    view_projection: uv::Mat4,
+    sizes: [f32; 16],
+    tint: uv::Vec3,
+    time: f32,
+  }
+
+  #[test]
+  fn derived_vertex_bindings_match_offset_of() {
+    let binding = PackedVertex::get_binding_description();
+    assert_eq!(binding.binding, 0);
+    assert_eq!(binding.stride as usize, std::mem::size_of::<PackedVertex>());
+    assert_eq!(binding.input_rate, vk::VertexInputRate::VERTEX);
+
+    let attributes: Vec<_> = PackedVertex::get_attribute_descriptions()
+      .iter()
+      .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+      .collect();
+    assert_eq!(
+      attributes,
+      [
+        (
+          0,
+          vk::Format::R32G32B32_SFLOAT,
+          offset_of!(PackedVertex, position) as u32
+        ),
+        (
+          4,
+          vk::Format::A2B10G10R10_SNORM_PACK32,
+          offset_of!(PackedVertex, normal) as u32
+        ),
+        (
+          2,
+          vk::Format::R8G8B8A8_UNORM,
+          offset_of!(PackedVertex, color) as u32
+        ),
+        (
+          3,
+          vk::Format::R16G16_SFLOAT,
+          offset_of!(PackedVertex, texture_coordinates) as u32
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn derived_default_forward_vertex_matches_shader() {
+    let attributes: Vec<_> = DefaultForwardShaderVertex::get_attribute_descriptions()
+      .iter()
+      .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+      .collect();
+    assert_eq!(
+      attributes,
+      [
+        (
+          0,
+          vk::Format::R32G32B32_SFLOAT,
+          offset_of!(DefaultForwardShaderVertex, position) as u32
+        ),
+        (
+          1,
+          vk::Format::R32G32B32_SFLOAT,
+          offset_of!(DefaultForwardShaderVertex, color) as u32
+        ),
+        (
+          2,
+          vk::Format::R32G32_SFLOAT,
+          offset_of!(DefaultForwardShaderVertex, texture_coordinates) as u32
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn derived_descriptor_layout_bindings_and_block() {
+    let bindings: Vec<_> = ParticleUniform::get_descriptor_set_layout_bindings()
+      .iter()
+      .map(|binding| {
+        (
+          binding.binding,
+          binding.descriptor_type,
+          binding.stage_flags,
+        )
+      })
+      .collect();
+    let uniform_stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+    assert_eq!(
+      bindings,
+      [
+        (
+          0,
+          vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+          uniform_stages
+        ),
+        (
+          2,
+          vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+          uniform_stages
+        ),
+        (
+          1,
+          vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+          vk::ShaderStageFlags::FRAGMENT
+        ),
+      ]
+    );
+    assert_eq!(
+      ParticleUniform::get_bind_uniform_info().unwrap().range as usize,
+      std::mem::size_of::<ParticleUniform>()
+    );
+
+    let block_layout = ParticleUniform::get_uniform_block_layout().unwrap();
+    assert_eq!(block_layout.rules, LayoutRules::Std430);
+    let fields: Vec<_> = block_layout
+      .fields
+      .iter()
+      .map(|field| (field.name, field.glsl_type.clone(), field.offset))
+      .collect();
+    assert_eq!(
+      fields,
+      [
+        (
+          "view_projection",
+          GlslType::Matrix {
+            columns: 4,
+            rows: 4
+          },
+          offset_of!(ParticleUniform, view_projection)
+        ),
+        (
+          "sizes",
+          GlslType::array(GlslType::Scalar, 16),
+          offset_of!(ParticleUniform, sizes)
+        ),
+        (
+          "tint",
+          GlslType::Vector(3),
+          offset_of!(ParticleUniform, tint)
+        ),
+        ("time", GlslType::Scalar, offset_of!(ParticleUniform, time)),
+      ]
+    );
+    assert!(block_layout.validate().is_ok());
+  }
+
+  #[test]
+  fn derived_default_forward_layout_matches_shader() {
+    let bindings: Vec<_> = DefaultForwardShaderLayout::get_descriptor_set_layout_bindings()
+      .iter()
+      .map(|binding| (binding.binding, binding.descriptor_type))
+      .collect();
+    assert_eq!(
+      bindings,
+      [
+        (0, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC),
+        (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+      ]
+    );
+    assert_eq!(
+      DefaultForwardShaderLayout::forward_shader_variant(),
+      ForwardShaderVariant::Plain
+    );
+    assert!(DefaultForwardShaderLayout::get_uniform_block_layout()
+      .unwrap()
+      .validate()
+      .is_ok());
+  }
 }